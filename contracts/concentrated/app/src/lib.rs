@@ -0,0 +1,53 @@
+#![no_std]
+
+pub mod services;
+use sails_rs::{cell::RefCell, prelude::*};
+use services::pool::{self, Config, Pool, PoolService};
+use token_ops::{TokenCustody, TransferTracker};
+
+pub struct ConcentratedProgram {
+    state: RefCell<pool::State>,
+    tracker: RefCell<TransferTracker>,
+}
+
+#[sails_rs::program]
+impl ConcentratedProgram {
+    // Program's constructor
+    pub fn new(
+        config: Config,
+        token0: ActorId,
+        token1: ActorId,
+        swap_fee_bps: u64,
+        tick_spacing: i32,
+        admin_id: ActorId,
+    ) -> Self {
+        let pool = Pool::new(tick_spacing, swap_fee_bps)
+            .unwrap_or_else(|err| panic!("Invalid pool parameters: {err:?}"));
+        let factory_id = sails_rs::gstd::msg::source();
+
+        let state = pool::State {
+            token0,
+            token1,
+            factory_id,
+            admin_id,
+            config,
+            pool,
+            ..Default::default()
+        };
+        sails_rs::gstd::msg::reply_bytes(b"", 0).expect("Error during msg reply");
+        Self {
+            state: RefCell::new(state),
+            tracker: RefCell::new(TransferTracker::default()),
+        }
+    }
+
+    pub fn pool(&self) -> PoolService<'_> {
+        PoolService::new(&self.state, &self.tracker)
+    }
+
+    #[allow(dead_code)]
+    #[handle_reply]
+    fn handle_reply(&self) {
+        self.pool().on_reply();
+    }
+}
@@ -0,0 +1,248 @@
+use crate::services::pool::{PoolError, PoolEvent, PoolService, engine::SwapOutcome};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+use token_ops::TokenCustody;
+
+impl<'a> PoolService<'a> {
+    fn ensure_gas(&self) -> Result<(), PoolError> {
+        if exec::gas_available() < self.with_state(|st| st.config.gas_for_full_tx) {
+            return Err(PoolError::NotEnoughAttachedGas);
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn mint_core(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount0_desired: U256,
+        amount1_desired: U256,
+        amount0_min: U256,
+        amount1_min: U256,
+        deadline: u64,
+    ) -> Result<PoolEvent, PoolError> {
+        self.ensure_gas()?;
+        ensure_deadline(deadline)?;
+        let owner = msg::source();
+
+        let (token0, token1, amount0, amount1) = self.with_state(|st| {
+            let (_, amount0, amount1) =
+                st.pool
+                    .mint_amounts(tick_lower, tick_upper, amount0_desired, amount1_desired)?;
+            check_minimums(amount0, amount1, amount0_min, amount1_min)?;
+            Ok::<_, PoolError>((st.token0, st.token1, amount0, amount1))
+        })?;
+
+        self.pull(token0, owner, amount0).await?;
+        if let Err(err) = self.pull(token1, owner, amount1).await {
+            self.push(token0, owner, amount0).await;
+            return Err(err.into());
+        }
+
+        // The price may have moved while the tokens were coming in
+        let minted = self.with_state_mut(|st| {
+            let (liquidity, used0, used1) = st
+                .pool
+                .mint_amounts(tick_lower, tick_upper, amount0, amount1)?;
+            check_minimums(used0, used1, amount0_min, amount1_min)?;
+            let liquidity_delta = i128::try_from(liquidity).map_err(|_| PoolError::Overflow)?;
+            st.pool
+                .modify_position(owner, tick_lower, tick_upper, liquidity_delta)?;
+            Ok::<_, PoolError>((liquidity, used0, used1))
+        });
+        let (liquidity, used0, used1) = match minted {
+            Ok(minted) => minted,
+            Err(err) => {
+                self.push(token0, owner, amount0).await;
+                self.push(token1, owner, amount1).await;
+                return Err(err);
+            }
+        };
+
+        self.push(token0, owner, amount0.saturating_sub(used0))
+            .await;
+        self.push(token1, owner, amount1.saturating_sub(used1))
+            .await;
+
+        Ok(PoolEvent::Mint {
+            owner,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            amount0: used0,
+            amount1: used1,
+        })
+    }
+
+    pub fn burn_core(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+        amount0_min: U256,
+        amount1_min: U256,
+        deadline: u64,
+    ) -> Result<PoolEvent, PoolError> {
+        ensure_deadline(deadline)?;
+        let owner = msg::source();
+
+        let (amount0, amount1) =
+            self.with_state_mut(|st| st.pool.burn(owner, tick_lower, tick_upper, liquidity))?;
+        // Nothing is sent, so failing here undoes the burn
+        check_minimums(amount0, amount1, amount0_min, amount1_min)?;
+
+        Ok(PoolEvent::Burn {
+            owner,
+            tick_lower,
+            tick_upper,
+            liquidity,
+            amount0,
+            amount1,
+        })
+    }
+
+    pub async fn collect_core(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount0_requested: U256,
+        amount1_requested: U256,
+    ) -> Result<PoolEvent, PoolError> {
+        self.ensure_gas()?;
+        let owner = msg::source();
+
+        let (token0, token1, amount0, amount1) = self.with_state_mut(|st| {
+            let (amount0, amount1) = st.pool.collect(
+                (owner, tick_lower, tick_upper),
+                amount0_requested,
+                amount1_requested,
+            )?;
+            Ok::<_, PoolError>((st.token0, st.token1, amount0, amount1))
+        })?;
+
+        self.push(token0, owner, amount0).await;
+        self.push(token1, owner, amount1).await;
+
+        Ok(PoolEvent::Collect {
+            owner,
+            tick_lower,
+            tick_upper,
+            amount0,
+            amount1,
+        })
+    }
+
+    /// Swaps `amount`, the input when `exact_in` and the output otherwise.
+    /// `limit` is the least output for an exact input and the most input for an exact output.
+    pub async fn swap_core(
+        &self,
+        amount: U256,
+        limit: U256,
+        is_token0_to_token1: bool,
+        exact_in: bool,
+        deadline: u64,
+    ) -> Result<PoolEvent, PoolError> {
+        self.ensure_gas()?;
+        ensure_deadline(deadline)?;
+        let user = msg::source();
+
+        let (token_in, token_out, amount_in) = self.with_state(|st| {
+            let quote = st.pool.quote_swap(is_token0_to_token1, amount, exact_in)?;
+            check_swap_limit(&quote, exact_in, limit)?;
+            let (token_in, token_out) = if is_token0_to_token1 {
+                (st.token0, st.token1)
+            } else {
+                (st.token1, st.token0)
+            };
+            Ok::<_, PoolError>((token_in, token_out, quote.amount_in))
+        })?;
+
+        self.pull(token_in, user, amount_in).await?;
+
+        // The price may have moved while the input was coming in
+        let swapped = self.with_state_mut(|st| {
+            let outcome = st.pool.quote_swap(is_token0_to_token1, amount, exact_in)?;
+            check_swap_limit(&outcome, exact_in, limit)?;
+            if outcome.amount_in > amount_in {
+                return Err(PoolError::ExcessiveInputAmount);
+            }
+            st.pool.apply_swap(&outcome);
+            Ok(outcome)
+        });
+        let outcome = match swapped {
+            Ok(outcome) => outcome,
+            Err(err) => {
+                self.push(token_in, user, amount_in).await;
+                return Err(err);
+            }
+        };
+
+        self.push(token_in, user, amount_in - outcome.amount_in)
+            .await;
+        self.push(token_out, user, outcome.amount_out).await;
+
+        Ok(PoolEvent::Swap {
+            user_id: user,
+            amount_in: outcome.amount_in,
+            amount_out: outcome.amount_out,
+            is_token0_to_token1,
+            sqrt_price_x96: outcome.slot.sqrt_price_x96,
+            tick: outcome.slot.tick,
+            liquidity: outcome.slot.liquidity,
+        })
+    }
+
+    pub async fn claim_credit_core(&self, token: ActorId) -> Result<PoolEvent, PoolError> {
+        self.ensure_gas()?;
+        let user = msg::source();
+        let amount = self
+            .with_state_mut(|st| st.credits.remove(&(user, token)))
+            .ok_or(PoolError::NoCredit)?;
+
+        // A failed transfer is credited back by the reply hook
+        if !self.push(token, user, amount).await {
+            return Err(PoolError::TokenTransferFailed);
+        }
+
+        Ok(PoolEvent::CreditClaimed {
+            user_id: user,
+            token,
+            amount,
+        })
+    }
+}
+
+fn ensure_deadline(deadline: u64) -> Result<(), PoolError> {
+    if exec::block_timestamp() > deadline {
+        return Err(PoolError::DeadlineExpired);
+    }
+    Ok(())
+}
+
+fn check_minimums(
+    amount0: U256,
+    amount1: U256,
+    amount0_min: U256,
+    amount1_min: U256,
+) -> Result<(), PoolError> {
+    if amount0 < amount0_min {
+        return Err(PoolError::InsufficientAmount0);
+    }
+    if amount1 < amount1_min {
+        return Err(PoolError::InsufficientAmount1);
+    }
+    Ok(())
+}
+
+fn check_swap_limit(outcome: &SwapOutcome, exact_in: bool, limit: U256) -> Result<(), PoolError> {
+    if exact_in && outcome.amount_out < limit {
+        return Err(PoolError::InsufficientAmount);
+    }
+    if !exact_in && outcome.amount_in > limit {
+        return Err(PoolError::ExcessiveInputAmount);
+    }
+    Ok(())
+}
@@ -0,0 +1,465 @@
+use crate::services::pool::PoolError;
+use sails_rs::U256;
+
+/// Swap fees are in basis points of the input.
+pub const FEE_DENOM_BPS: u64 = 10_000; // 100.00%
+/// Highest swap fee a pool accepts.
+pub const MAX_SWAP_FEE_BPS: u64 = 1_000; // 10.00%
+/// Prices are `sqrt(token1 / token0)` in Q64.96 fixed point.
+pub const RESOLUTION: usize = 96;
+
+pub fn q96() -> U256 {
+    U256::one() << RESOLUTION
+}
+
+/// Fee growth is tracked per unit of liquidity in Q128.128 fixed point.
+pub fn q128() -> U256 {
+    U256::one() << 128
+}
+
+/// `a * b / denominator` with a 512-bit intermediate product.
+pub fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256, PoolError> {
+    if denominator.is_zero() {
+        return Err(PoolError::Overflow);
+    }
+    U256::try_from(a.full_mul(b) / denominator.full_mul(U256::one()))
+        .map_err(|_| PoolError::Overflow)
+}
+
+/// Same as `mul_div`, rounding up.
+pub fn mul_div_ceil(a: U256, b: U256, denominator: U256) -> Result<U256, PoolError> {
+    let quotient = mul_div(a, b, denominator)?;
+    if (a.full_mul(b) % denominator.full_mul(U256::one())).is_zero() {
+        Ok(quotient)
+    } else {
+        quotient.checked_add(U256::one()).ok_or(PoolError::Overflow)
+    }
+}
+
+fn div_ceil(a: U256, b: U256) -> Result<U256, PoolError> {
+    if b.is_zero() {
+        return Err(PoolError::Overflow);
+    }
+    let (quotient, remainder) = a.div_mod(b);
+    if remainder.is_zero() {
+        Ok(quotient)
+    } else {
+        Ok(quotient + U256::one())
+    }
+}
+
+/// Adds a signed liquidity delta to `liquidity`.
+pub fn add_delta(liquidity: u128, delta: i128) -> Result<u128, PoolError> {
+    if delta < 0 {
+        liquidity
+            .checked_sub(delta.unsigned_abs())
+            .ok_or(PoolError::InsufficientLiquidity)
+    } else {
+        liquidity
+            .checked_add(delta as u128)
+            .ok_or(PoolError::Overflow)
+    }
+}
+
+/// Amount of token0 between two prices for `liquidity`:
+///   liquidity * (sqrt_b - sqrt_a) / (sqrt_a * sqrt_b)
+pub fn amount0_delta(
+    sqrt_a: U256,
+    sqrt_b: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256, PoolError> {
+    let (sqrt_a, sqrt_b) = if sqrt_a <= sqrt_b {
+        (sqrt_a, sqrt_b)
+    } else {
+        (sqrt_b, sqrt_a)
+    };
+    if sqrt_a.is_zero() {
+        return Err(PoolError::InvalidSqrtPrice);
+    }
+    let numerator1 = U256::from(liquidity) << RESOLUTION;
+    let numerator2 = sqrt_b - sqrt_a;
+    if round_up {
+        div_ceil(mul_div_ceil(numerator1, numerator2, sqrt_b)?, sqrt_a)
+    } else {
+        Ok(mul_div(numerator1, numerator2, sqrt_b)? / sqrt_a)
+    }
+}
+
+/// Amount of token1 between two prices for `liquidity`:
+///   liquidity * (sqrt_b - sqrt_a)
+pub fn amount1_delta(
+    sqrt_a: U256,
+    sqrt_b: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<U256, PoolError> {
+    let diff = if sqrt_a <= sqrt_b {
+        sqrt_b - sqrt_a
+    } else {
+        sqrt_a - sqrt_b
+    };
+    if round_up {
+        mul_div_ceil(U256::from(liquidity), diff, q96())
+    } else {
+        mul_div(U256::from(liquidity), diff, q96())
+    }
+}
+
+/// Price after adding (or removing) `amount` of token0, rounded up so that
+/// the pool never gives away more than the exact curve.
+fn next_sqrt_price_from_amount0(
+    sqrt_price: U256,
+    liquidity: u128,
+    amount: U256,
+    add: bool,
+) -> Result<U256, PoolError> {
+    if amount.is_zero() {
+        return Ok(sqrt_price);
+    }
+    let numerator1 = U256::from(liquidity) << RESOLUTION;
+    let product = amount.checked_mul(sqrt_price);
+    if add {
+        if let Some(denominator) = product.and_then(|p| numerator1.checked_add(p)) {
+            return mul_div_ceil(numerator1, sqrt_price, denominator);
+        }
+        // liquidity / (liquidity / sqrt_price + amount), when the product overflows
+        let denominator = (numerator1 / sqrt_price)
+            .checked_add(amount)
+            .ok_or(PoolError::Overflow)?;
+        div_ceil(numerator1, denominator)
+    } else {
+        let denominator = product
+            .and_then(|p| numerator1.checked_sub(p))
+            .filter(|d| !d.is_zero())
+            .ok_or(PoolError::InsufficientLiquidity)?;
+        mul_div_ceil(numerator1, sqrt_price, denominator)
+    }
+}
+
+/// Price after adding (or removing) `amount` of token1, rounded down.
+fn next_sqrt_price_from_amount1(
+    sqrt_price: U256,
+    liquidity: u128,
+    amount: U256,
+    add: bool,
+) -> Result<U256, PoolError> {
+    let liquidity = U256::from(liquidity);
+    if add {
+        let quotient = mul_div(amount, q96(), liquidity)?;
+        sqrt_price.checked_add(quotient).ok_or(PoolError::Overflow)
+    } else {
+        let quotient = mul_div_ceil(amount, q96(), liquidity)?;
+        sqrt_price
+            .checked_sub(quotient)
+            .filter(|p| !p.is_zero())
+            .ok_or(PoolError::InsufficientLiquidity)
+    }
+}
+
+/// Price after `amount_in` goes into the pool.
+pub fn next_sqrt_price_from_input(
+    sqrt_price: U256,
+    liquidity: u128,
+    amount_in: U256,
+    zero_for_one: bool,
+) -> Result<U256, PoolError> {
+    if sqrt_price.is_zero() || liquidity == 0 {
+        return Err(PoolError::InsufficientLiquidity);
+    }
+    if zero_for_one {
+        next_sqrt_price_from_amount0(sqrt_price, liquidity, amount_in, true)
+    } else {
+        next_sqrt_price_from_amount1(sqrt_price, liquidity, amount_in, true)
+    }
+}
+
+/// Price after `amount_out` leaves the pool.
+pub fn next_sqrt_price_from_output(
+    sqrt_price: U256,
+    liquidity: u128,
+    amount_out: U256,
+    zero_for_one: bool,
+) -> Result<U256, PoolError> {
+    if sqrt_price.is_zero() || liquidity == 0 {
+        return Err(PoolError::InsufficientLiquidity);
+    }
+    if zero_for_one {
+        next_sqrt_price_from_amount1(sqrt_price, liquidity, amount_out, false)
+    } else {
+        next_sqrt_price_from_amount0(sqrt_price, liquidity, amount_out, false)
+    }
+}
+
+/// One step of a swap within a range of constant liquidity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapStep {
+    pub sqrt_price_next: U256,
+    /// Input without the fee.
+    pub amount_in: U256,
+    pub amount_out: U256,
+    pub fee_amount: U256,
+}
+
+/// Swaps from `sqrt_price_current` towards `sqrt_price_target` with `liquidity`,
+/// stopping at the target or once `amount_remaining` is used up.
+/// `amount_remaining` is the input left (fee included) when `exact_in`, the output left otherwise.
+/// The direction follows from the prices: token0 in when the target is below the current price.
+pub fn compute_swap_step(
+    sqrt_price_current: U256,
+    sqrt_price_target: U256,
+    liquidity: u128,
+    amount_remaining: U256,
+    exact_in: bool,
+    swap_fee_bps: u64,
+) -> Result<SwapStep, PoolError> {
+    let zero_for_one = sqrt_price_current >= sqrt_price_target;
+    let fee_denom = U256::from(FEE_DENOM_BPS);
+    let fee = U256::from(swap_fee_bps);
+    let fee_multiplier = fee_denom
+        .checked_sub(fee)
+        .filter(|m| !m.is_zero())
+        .ok_or(PoolError::InvalidSwapFee)?;
+
+    // Amount to reach the target in the direction that is fixed by the caller
+    let (to_target, sqrt_price_next) = if exact_in {
+        let remaining_less_fee = mul_div(amount_remaining, fee_multiplier, fee_denom)?;
+        let amount_in = if zero_for_one {
+            amount0_delta(sqrt_price_target, sqrt_price_current, liquidity, true)?
+        } else {
+            amount1_delta(sqrt_price_current, sqrt_price_target, liquidity, true)?
+        };
+        let next = if remaining_less_fee >= amount_in {
+            sqrt_price_target
+        } else {
+            next_sqrt_price_from_input(
+                sqrt_price_current,
+                liquidity,
+                remaining_less_fee,
+                zero_for_one,
+            )?
+        };
+        (amount_in, next)
+    } else {
+        let amount_out = if zero_for_one {
+            amount1_delta(sqrt_price_target, sqrt_price_current, liquidity, false)?
+        } else {
+            amount0_delta(sqrt_price_current, sqrt_price_target, liquidity, false)?
+        };
+        let next = if amount_remaining >= amount_out {
+            sqrt_price_target
+        } else {
+            next_sqrt_price_from_output(
+                sqrt_price_current,
+                liquidity,
+                amount_remaining,
+                zero_for_one,
+            )?
+        };
+        (amount_out, next)
+    };
+
+    let reached = sqrt_price_next == sqrt_price_target;
+    let (amount_in, mut amount_out) = if zero_for_one {
+        (
+            if reached && exact_in {
+                to_target
+            } else {
+                amount0_delta(sqrt_price_next, sqrt_price_current, liquidity, true)?
+            },
+            if reached && !exact_in {
+                to_target
+            } else {
+                amount1_delta(sqrt_price_next, sqrt_price_current, liquidity, false)?
+            },
+        )
+    } else {
+        (
+            if reached && exact_in {
+                to_target
+            } else {
+                amount1_delta(sqrt_price_current, sqrt_price_next, liquidity, true)?
+            },
+            if reached && !exact_in {
+                to_target
+            } else {
+                amount0_delta(sqrt_price_current, sqrt_price_next, liquidity, false)?
+            },
+        )
+    };
+
+    if !exact_in && amount_out > amount_remaining {
+        amount_out = amount_remaining;
+    }
+    // Whatever is left of an exact input that stops short of the target is the fee
+    let fee_amount = if exact_in && !reached {
+        amount_remaining
+            .checked_sub(amount_in)
+            .ok_or(PoolError::Overflow)?
+    } else {
+        mul_div_ceil(amount_in, fee, fee_multiplier)?
+    };
+
+    Ok(SwapStep {
+        sqrt_price_next,
+        amount_in,
+        amount_out,
+        fee_amount,
+    })
+}
+
+/// Liquidity that `amount0` of token0 provides between two prices.
+fn liquidity_for_amount0(sqrt_a: U256, sqrt_b: U256, amount0: U256) -> Result<U256, PoolError> {
+    let intermediate = mul_div(sqrt_a, sqrt_b, q96())?;
+    mul_div(amount0, intermediate, sqrt_b - sqrt_a)
+}
+
+/// Liquidity that `amount1` of token1 provides between two prices.
+fn liquidity_for_amount1(sqrt_a: U256, sqrt_b: U256, amount1: U256) -> Result<U256, PoolError> {
+    mul_div(amount1, q96(), sqrt_b - sqrt_a)
+}
+
+/// Largest liquidity the amounts provide in `[sqrt_a, sqrt_b)` at `sqrt_price`.
+pub fn liquidity_for_amounts(
+    sqrt_price: U256,
+    sqrt_a: U256,
+    sqrt_b: U256,
+    amount0: U256,
+    amount1: U256,
+) -> Result<u128, PoolError> {
+    let liquidity = if sqrt_price <= sqrt_a {
+        liquidity_for_amount0(sqrt_a, sqrt_b, amount0)?
+    } else if sqrt_price < sqrt_b {
+        liquidity_for_amount0(sqrt_price, sqrt_b, amount0)?
+            .min(liquidity_for_amount1(sqrt_a, sqrt_price, amount1)?)
+    } else {
+        liquidity_for_amount1(sqrt_a, sqrt_b, amount1)?
+    };
+    u128::try_from(liquidity).map_err(|_| PoolError::Overflow)
+}
+
+/// Token amounts of `liquidity` in `[sqrt_a, sqrt_b)` at `sqrt_price`,
+/// rounded up when the liquidity is added and down when it is removed.
+pub fn amounts_for_liquidity(
+    sqrt_price: U256,
+    sqrt_a: U256,
+    sqrt_b: U256,
+    liquidity: u128,
+    round_up: bool,
+) -> Result<(U256, U256), PoolError> {
+    if sqrt_price <= sqrt_a {
+        Ok((
+            amount0_delta(sqrt_a, sqrt_b, liquidity, round_up)?,
+            U256::zero(),
+        ))
+    } else if sqrt_price < sqrt_b {
+        Ok((
+            amount0_delta(sqrt_price, sqrt_b, liquidity, round_up)?,
+            amount1_delta(sqrt_a, sqrt_price, liquidity, round_up)?,
+        ))
+    } else {
+        Ok((
+            U256::zero(),
+            amount1_delta(sqrt_a, sqrt_b, liquidity, round_up)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod prop_tests {
+    use crate::services::pool::math::{
+        amount0_delta, amount1_delta, amounts_for_liquidity, compute_swap_step,
+        liquidity_for_amounts,
+    };
+    use crate::services::pool::tick_math::{MAX_TICK, MIN_TICK, sqrt_ratio_at_tick};
+    use proptest::prelude::*;
+    use sails_rs::U256;
+
+    const TICKS: core::ops::Range<i32> = -200_000..200_000;
+
+    proptest! {
+        /// Rounding up never asks for less than rounding down gives.
+        #[test]
+        fn prop_delta_rounding(a in TICKS, b in TICKS, liquidity in 1u128..u64::MAX as u128) {
+            let (sqrt_a, sqrt_b) = (sqrt_ratio_at_tick(a).unwrap(), sqrt_ratio_at_tick(b).unwrap());
+            let up0 = amount0_delta(sqrt_a, sqrt_b, liquidity, true).unwrap();
+            let down0 = amount0_delta(sqrt_a, sqrt_b, liquidity, false).unwrap();
+            prop_assert!(up0 >= down0 && up0 - down0 <= U256::one());
+            let up1 = amount1_delta(sqrt_a, sqrt_b, liquidity, true).unwrap();
+            let down1 = amount1_delta(sqrt_a, sqrt_b, liquidity, false).unwrap();
+            prop_assert!(up1 >= down1 && up1 - down1 <= U256::one());
+        }
+
+        /// A step never uses more than the remaining input, pays the fee on what it
+        /// uses, and moves the price towards the target without passing it.
+        #[test]
+        fn prop_swap_step_exact_in(
+            current in TICKS,
+            offset in -5_000i32..5_000,
+            liquidity in 1_000u128..u64::MAX as u128,
+            remaining in 1u128..u64::MAX as u128,
+            fee in 0u64..=1_000,
+        ) {
+            let sqrt_current = sqrt_ratio_at_tick(current).unwrap();
+            let sqrt_target = sqrt_ratio_at_tick((current + offset).clamp(MIN_TICK, MAX_TICK)).unwrap();
+            let remaining = U256::from(remaining);
+            let step = compute_swap_step(sqrt_current, sqrt_target, liquidity, remaining, true, fee).unwrap();
+            prop_assert!(step.amount_in + step.fee_amount <= remaining);
+            if sqrt_target <= sqrt_current {
+                prop_assert!(step.sqrt_price_next <= sqrt_current && step.sqrt_price_next >= sqrt_target);
+            } else {
+                prop_assert!(step.sqrt_price_next >= sqrt_current && step.sqrt_price_next <= sqrt_target);
+            }
+            if step.sqrt_price_next != sqrt_target {
+                prop_assert_eq!(step.amount_in + step.fee_amount, remaining);
+            }
+        }
+
+        /// An exact output step never gives more than asked, and charges at least
+        /// what the same input would need on the exact input side.
+        #[test]
+        fn prop_swap_step_exact_out(
+            current in TICKS,
+            offset in -5_000i32..5_000,
+            liquidity in 1_000u128..u64::MAX as u128,
+            remaining in 1u128..u64::MAX as u128,
+            fee in 0u64..=1_000,
+        ) {
+            let sqrt_current = sqrt_ratio_at_tick(current).unwrap();
+            let sqrt_target = sqrt_ratio_at_tick((current + offset).clamp(MIN_TICK, MAX_TICK)).unwrap();
+            let remaining = U256::from(remaining);
+            let step = compute_swap_step(sqrt_current, sqrt_target, liquidity, remaining, false, fee).unwrap();
+            prop_assert!(step.amount_out <= remaining);
+            let back = compute_swap_step(
+                sqrt_current,
+                sqrt_target,
+                liquidity,
+                step.amount_in + step.fee_amount,
+                true,
+                fee,
+            )
+            .unwrap();
+            prop_assert!(back.amount_out >= step.amount_out);
+        }
+
+        /// The amounts a liquidity needs are enough to give it back, and no more than given.
+        #[test]
+        fn prop_liquidity_roundtrip(
+            price in TICKS,
+            lower in -100_000i32..0,
+            width in 1i32..100_000,
+            amount0 in 1_000u128..u64::MAX as u128,
+            amount1 in 1_000u128..u64::MAX as u128,
+        ) {
+            let sqrt_price = sqrt_ratio_at_tick(price).unwrap();
+            let sqrt_a = sqrt_ratio_at_tick(lower).unwrap();
+            let sqrt_b = sqrt_ratio_at_tick(lower + width).unwrap();
+            let (amount0, amount1) = (U256::from(amount0), U256::from(amount1));
+            let liquidity = liquidity_for_amounts(sqrt_price, sqrt_a, sqrt_b, amount0, amount1).unwrap();
+            let (need0, need1) = amounts_for_liquidity(sqrt_price, sqrt_a, sqrt_b, liquidity, true).unwrap();
+            prop_assert!(need0 <= amount0 && need1 <= amount1);
+            let (back0, back1) = amounts_for_liquidity(sqrt_price, sqrt_a, sqrt_b, liquidity, false).unwrap();
+            prop_assert!(back0 <= need0 && back1 <= need1);
+        }
+    }
+}
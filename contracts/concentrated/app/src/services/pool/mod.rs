@@ -0,0 +1,432 @@
+use sails_rs::{cell::RefCell, collections::HashMap, gstd::msg, prelude::*};
+
+mod engine;
+mod funcs;
+mod math;
+mod position;
+mod tick;
+mod tick_math;
+mod token_operations;
+pub use engine::{MAX_TICK_SPACING, Pool, Slot};
+pub use math::MAX_SWAP_FEE_BPS;
+pub use position::Position;
+pub use tick::TickInfo;
+pub use tick_math::{MAX_TICK, MIN_TICK};
+use token_ops::{PendingTransfer, TransferTracker};
+
+pub struct PoolService<'a> {
+    state: &'a RefCell<State>,
+    tracker: &'a RefCell<TransferTracker>,
+}
+
+#[derive(Debug, Default)]
+pub struct State {
+    pub token0: ActorId,
+    pub token1: ActorId,
+    pub factory_id: ActorId,
+    pub admin_id: ActorId,
+    pub config: Config,
+    pub pool: Pool,
+    /// Tokens owed to users after a transfer to them failed, by `(user, token)`.
+    pub credits: HashMap<(ActorId, ActorId), U256>,
+}
+
+#[event]
+#[derive(Debug, Encode, Decode, TypeInfo)]
+pub enum PoolEvent {
+    Initialized {
+        sqrt_price_x96: U256,
+        tick: i32,
+    },
+    Mint {
+        owner: ActorId,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+        amount0: U256,
+        amount1: U256,
+    },
+    Burn {
+        owner: ActorId,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+        amount0: U256,
+        amount1: U256,
+    },
+    Collect {
+        owner: ActorId,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount0: U256,
+        amount1: U256,
+    },
+    /// `sqrt_price_x96`, `tick` and `liquidity` are the pool's after the swap.
+    Swap {
+        user_id: ActorId,
+        amount_in: U256,
+        amount_out: U256,
+        is_token0_to_token1: bool,
+        sqrt_price_x96: U256,
+        tick: i32,
+        liquidity: u128,
+    },
+    CreditClaimed {
+        user_id: ActorId,
+        token: ActorId,
+        amount: U256,
+    },
+}
+
+#[derive(Debug)]
+pub enum PoolError {
+    NotEnoughAttachedGas,
+    DeadlineExpired,
+    Unauthorized,
+    Overflow,
+    InsufficientLiquidity,
+    InsufficientAmount,
+    InsufficientAmount0,
+    InsufficientAmount1,
+    ExcessiveInputAmount,
+    ZeroLiquidity,
+    InvalidSqrtPrice,
+    InvalidSwapFee,
+    InvalidTick,
+    InvalidTickRange,
+    InvalidTickSpacing,
+    TickLiquidityOverflow,
+    PositionNotFound,
+    NotInitialized,
+    AlreadyInitialized,
+    NoCredit,
+    SendFailure,
+    ReplyTimeout,
+    ReplyFailure,
+    UnableToDecode,
+    TokenTransferFailed,
+    EventError,
+}
+
+/// Config that will be used to send messages to the other programs.
+#[derive(Default, Debug, Decode, Encode, TypeInfo, Clone)]
+pub struct Config {
+    /// Gas limit for token operations (TransferFrom and Transfer).
+    gas_for_token_ops: u64,
+    /// Gas to reserve for reply processing.
+    gas_for_reply_deposit: u64,
+    /// Timeout in blocks that current program will wait for reply from
+    /// the other programs such as VFT
+    reply_timeout: u32,
+    gas_for_full_tx: u64,
+}
+
+impl<'a> PoolService<'a> {
+    pub fn new(state: &'a RefCell<State>, tracker: &'a RefCell<TransferTracker>) -> Self {
+        Self { state, tracker }
+    }
+
+    #[inline]
+    pub fn with_state<R>(&self, f: impl FnOnce(&State) -> R) -> R {
+        let st = self.state.borrow();
+        f(&st)
+    }
+
+    #[inline]
+    pub fn with_state_mut<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        let mut st = self.state.borrow_mut();
+        f(&mut st)
+    }
+
+    fn ensure_factory_or_admin(&self) -> Result<(), PoolError> {
+        let caller = msg::source();
+        self.with_state(|st| {
+            if caller == st.factory_id || caller == st.admin_id {
+                Ok(())
+            } else {
+                Err(PoolError::Unauthorized)
+            }
+        })
+    }
+}
+
+#[sails_rs::service(events = PoolEvent)]
+impl<'a> PoolService<'a> {
+    /// Sets the starting price of the pool as `sqrt(token1 / token0)` in Q64.96.
+    /// Anyone can do it, once; liquidity can only be added afterwards.
+    #[export(unwrap_result)]
+    pub fn initialize(&mut self, sqrt_price_x96: U256) -> Result<(), PoolError> {
+        let tick = self.with_state_mut(|st| {
+            st.pool.initialize(sqrt_price_x96)?;
+            Ok::<_, PoolError>(st.pool.slot.tick)
+        })?;
+        self.emit_pool_event(PoolEvent::Initialized {
+            sqrt_price_x96,
+            tick,
+        })
+    }
+
+    /// Adds liquidity to the caller's position in `[tick_lower, tick_upper)`.
+    ///
+    /// The liquidity is the most the desired amounts provide at the current price.
+    /// Both desired amounts are taken first and what the position does not need is
+    /// sent back. Ticks must be multiples of `tick_spacing`.
+    #[export(unwrap_result)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn mint(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount0_desired: U256,
+        amount1_desired: U256,
+        amount0_min: U256,
+        amount1_min: U256,
+        deadline: u64,
+    ) -> Result<(), PoolError> {
+        let event = self
+            .mint_core(
+                tick_lower,
+                tick_upper,
+                amount0_desired,
+                amount1_desired,
+                amount0_min,
+                amount1_min,
+                deadline,
+            )
+            .await?;
+        self.emit_pool_event(event)
+    }
+
+    /// Removes `liquidity` from the caller's position. The tokens it held, along with
+    /// the fees it earned, are owed to the position until taken with `collect`.
+    /// Burning zero liquidity only brings the position's fees up to date.
+    #[export(unwrap_result)]
+    pub fn burn(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        liquidity: u128,
+        amount0_min: U256,
+        amount1_min: U256,
+        deadline: u64,
+    ) -> Result<(), PoolError> {
+        let event = self.burn_core(
+            tick_lower,
+            tick_upper,
+            liquidity,
+            amount0_min,
+            amount1_min,
+            deadline,
+        )?;
+        self.emit_pool_event(event)
+    }
+
+    /// Sends the caller up to the requested amounts of what their position is owed.
+    /// A transfer that fails is credited to the caller, see `claim_credit`.
+    #[export(unwrap_result)]
+    pub async fn collect(
+        &mut self,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount0_requested: U256,
+        amount1_requested: U256,
+    ) -> Result<(), PoolError> {
+        let event = self
+            .collect_core(tick_lower, tick_upper, amount0_requested, amount1_requested)
+            .await?;
+        self.emit_pool_event(event)
+    }
+
+    /// Swaps exactly `amount_in` across as many price ranges as needed.
+    /// Fails if the pool does not have the liquidity to take all of it.
+    #[export(unwrap_result)]
+    pub async fn swap_exact_tokens_for_tokens(
+        &mut self,
+        amount_in: U256,
+        amount_out_min: U256,
+        is_token0_to_token1: bool,
+        deadline: u64,
+    ) -> Result<(), PoolError> {
+        let event = self
+            .swap_core(
+                amount_in,
+                amount_out_min,
+                is_token0_to_token1,
+                true,
+                deadline,
+            )
+            .await?;
+        self.emit_pool_event(event)
+    }
+
+    /// Swaps for exactly `amount_out`. The input quoted at the time of the call is taken,
+    /// and any part of it the swap no longer needs when it executes is sent back.
+    #[export(unwrap_result)]
+    pub async fn swap_tokens_for_exact_tokens(
+        &mut self,
+        amount_out: U256,
+        amount_in_max: U256,
+        is_token0_to_token1: bool,
+        deadline: u64,
+    ) -> Result<(), PoolError> {
+        let event = self
+            .swap_core(
+                amount_out,
+                amount_in_max,
+                is_token0_to_token1,
+                false,
+                deadline,
+            )
+            .await?;
+        self.emit_pool_event(event)
+    }
+
+    /// Sends the caller the tokens credited to them after a transfer failed.
+    #[export(unwrap_result)]
+    pub async fn claim_credit(&mut self, token: ActorId) -> Result<(), PoolError> {
+        let event = self.claim_credit_core(token).await?;
+        self.emit_pool_event(event)
+    }
+
+    #[export(unwrap_result)]
+    pub fn update_config(&mut self, config: Config) -> Result<(), PoolError> {
+        self.ensure_factory_or_admin()?;
+        self.with_state_mut(|st| st.config = config);
+        Ok(())
+    }
+
+    #[export(unwrap_result)]
+    pub fn get_amount_out(
+        &self,
+        amount_in: U256,
+        is_token0_to_token1: bool,
+    ) -> Result<U256, PoolError> {
+        self.with_state(|st| {
+            st.pool
+                .quote_swap(is_token0_to_token1, amount_in, true)
+                .map(|outcome| outcome.amount_out)
+        })
+    }
+
+    #[export(unwrap_result)]
+    pub fn get_amount_in(
+        &self,
+        amount_out: U256,
+        is_token0_to_token1: bool,
+    ) -> Result<U256, PoolError> {
+        self.with_state(|st| {
+            st.pool
+                .quote_swap(is_token0_to_token1, amount_out, false)
+                .map(|outcome| outcome.amount_in)
+        })
+    }
+
+    /// Liquidity the amounts provide in `[tick_lower, tick_upper)` at the current price,
+    /// and the amounts `mint` would take for it.
+    #[export(unwrap_result)]
+    pub fn calculate_mint(
+        &self,
+        tick_lower: i32,
+        tick_upper: i32,
+        amount0_desired: U256,
+        amount1_desired: U256,
+    ) -> Result<(u128, U256, U256), PoolError> {
+        self.with_state(|st| {
+            st.pool
+                .mint_amounts(tick_lower, tick_upper, amount0_desired, amount1_desired)
+        })
+    }
+
+    /// Price, active liquidity and global fee growth.
+    #[export]
+    pub fn slot(&self) -> Slot {
+        self.with_state(|st| st.pool.slot)
+    }
+
+    #[export]
+    pub fn position(&self, owner: ActorId, tick_lower: i32, tick_upper: i32) -> Option<Position> {
+        self.with_state(|st| {
+            st.pool
+                .positions
+                .get(&(owner, tick_lower, tick_upper))
+                .cloned()
+        })
+    }
+
+    /// Positions of `owner` as `((tick_lower, tick_upper), position)`.
+    #[export]
+    pub fn positions_of(&self, owner: ActorId) -> Vec<((i32, i32), Position)> {
+        self.with_state(|st| {
+            st.pool
+                .positions
+                .range((owner, i32::MIN, i32::MIN)..=(owner, i32::MAX, i32::MAX))
+                .map(|((_, lower, upper), position)| ((*lower, *upper), position.clone()))
+                .collect()
+        })
+    }
+
+    /// Tokens `collect` could send for the position after a zero `burn`.
+    #[export(unwrap_result)]
+    pub fn collectable(
+        &self,
+        owner: ActorId,
+        tick_lower: i32,
+        tick_upper: i32,
+    ) -> Result<(U256, U256), PoolError> {
+        self.with_state(|st| st.pool.collectable(&(owner, tick_lower, tick_upper)))
+    }
+
+    #[export]
+    pub fn tick(&self, tick: i32) -> Option<TickInfo> {
+        self.with_state(|st| st.pool.ticks.get(&tick).cloned())
+    }
+
+    /// Initialized ticks in `[from, to]` with their liquidity net.
+    #[export]
+    pub fn liquidity_net_between(&self, from: i32, to: i32) -> Vec<(i32, i128)> {
+        self.with_state(|st| {
+            if from > to {
+                return Vec::new();
+            }
+            st.pool
+                .ticks
+                .range(from..=to)
+                .map(|(tick, info)| (*tick, info.liquidity_net))
+                .collect()
+        })
+    }
+
+    #[export]
+    pub fn credit_of(&self, user: ActorId, token: ActorId) -> U256 {
+        self.with_state(|st| st.credits.get(&(user, token)).copied().unwrap_or_default())
+    }
+
+    #[export]
+    pub fn pending_transfers(&self) -> Vec<(MessageId, PendingTransfer)> {
+        self.tracker
+            .borrow()
+            .pending
+            .iter()
+            .map(|(id, transfer)| (*id, transfer.clone()))
+            .collect()
+    }
+
+    #[export]
+    pub fn get_tokens(&self) -> (ActorId, ActorId) {
+        self.with_state(|st| (st.token0, st.token1))
+    }
+
+    #[export]
+    pub fn swap_fee_bps(&self) -> u64 {
+        self.with_state(|st| st.pool.swap_fee_bps)
+    }
+
+    #[export]
+    pub fn tick_spacing(&self) -> i32 {
+        self.with_state(|st| st.pool.tick_spacing)
+    }
+
+    fn emit_pool_event(&self, event: PoolEvent) -> Result<(), PoolError> {
+        self.emit_event(event).map_err(|_| PoolError::EventError)
+    }
+}
@@ -0,0 +1,66 @@
+use crate::services::pool::{
+    PoolError,
+    math::{add_delta, mul_div, q128},
+    tick::wrapping_sub,
+};
+use sails_rs::{U256, prelude::*};
+
+/// A liquidity position is identified by its owner and tick range.
+pub type PositionKey = (ActorId, i32, i32);
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Position {
+    pub liquidity: u128,
+    /// Fee growth inside the range when the position was last updated.
+    pub fee_growth_inside0_last_x128: U256,
+    pub fee_growth_inside1_last_x128: U256,
+    /// Fees and burned liquidity the owner can collect.
+    pub tokens_owed0: U256,
+    pub tokens_owed1: U256,
+}
+
+impl Position {
+    /// Fees earned since the last update, given the fee growth inside the range now.
+    pub fn fees_earned(
+        &self,
+        fee_growth_inside0_x128: U256,
+        fee_growth_inside1_x128: U256,
+    ) -> Result<(U256, U256), PoolError> {
+        let liquidity = U256::from(self.liquidity);
+        Ok((
+            mul_div(
+                wrapping_sub(fee_growth_inside0_x128, self.fee_growth_inside0_last_x128),
+                liquidity,
+                q128(),
+            )?,
+            mul_div(
+                wrapping_sub(fee_growth_inside1_x128, self.fee_growth_inside1_last_x128),
+                liquidity,
+                q128(),
+            )?,
+        ))
+    }
+
+    /// Credits the fees earned so far and applies `liquidity_delta`.
+    pub fn update(
+        &mut self,
+        liquidity_delta: i128,
+        fee_growth_inside0_x128: U256,
+        fee_growth_inside1_x128: U256,
+    ) -> Result<(), PoolError> {
+        let liquidity = add_delta(self.liquidity, liquidity_delta)?;
+        let (fees0, fees1) = self.fees_earned(fee_growth_inside0_x128, fee_growth_inside1_x128)?;
+
+        self.liquidity = liquidity;
+        self.fee_growth_inside0_last_x128 = fee_growth_inside0_x128;
+        self.fee_growth_inside1_last_x128 = fee_growth_inside1_x128;
+        // Owed amounts are capped rather than lost; they are collected long before this matters
+        self.tokens_owed0 = self.tokens_owed0.saturating_add(fees0);
+        self.tokens_owed1 = self.tokens_owed1.saturating_add(fees1);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.liquidity == 0 && self.tokens_owed0.is_zero() && self.tokens_owed1.is_zero()
+    }
+}
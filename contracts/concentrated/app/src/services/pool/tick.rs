@@ -0,0 +1,156 @@
+use crate::services::pool::{
+    PoolError,
+    math::add_delta,
+    tick_math::{MAX_TICK, MIN_TICK},
+};
+use sails_rs::{U256, collections::BTreeMap, prelude::*};
+
+/// Liquidity and fee growth tracked at an initialized tick.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct TickInfo {
+    /// Liquidity of all the positions that start or end at the tick.
+    pub liquidity_gross: u128,
+    /// Liquidity added to the active one when the price crosses the tick upwards,
+    /// and removed when it crosses downwards.
+    pub liquidity_net: i128,
+    /// Fee growth per unit of liquidity on the other side of the tick from the
+    /// current price. Only differences between these values are meaningful.
+    pub fee_growth_outside0_x128: U256,
+    pub fee_growth_outside1_x128: U256,
+}
+
+/// Most liquidity a single tick can reference, so that the active liquidity
+/// cannot overflow even when every usable tick is initialized.
+pub fn max_liquidity_per_tick(tick_spacing: i32) -> u128 {
+    let min_tick = (MIN_TICK / tick_spacing) * tick_spacing;
+    let max_tick = (MAX_TICK / tick_spacing) * tick_spacing;
+    let num_ticks = ((max_tick - min_tick) / tick_spacing) as u128 + 1;
+    u128::MAX / num_ticks
+}
+
+/// Checks that `[tick_lower, tick_upper)` is a usable range for a position.
+pub fn check_ticks(tick_lower: i32, tick_upper: i32, tick_spacing: i32) -> Result<(), PoolError> {
+    if tick_lower >= tick_upper {
+        return Err(PoolError::InvalidTickRange);
+    }
+    if tick_lower < MIN_TICK
+        || tick_upper > MAX_TICK
+        || tick_lower % tick_spacing != 0
+        || tick_upper % tick_spacing != 0
+    {
+        return Err(PoolError::InvalidTick);
+    }
+    Ok(())
+}
+
+impl TickInfo {
+    /// Adds `liquidity_delta` to the tick at `tick` for the lower or upper end of a position.
+    /// A tick initialized at or below the current one starts with all the fee growth
+    /// so far on its outside, by convention.
+    /// Returns whether the tick went from initialized to not, or the other way.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        tick: i32,
+        tick_current: i32,
+        liquidity_delta: i128,
+        fee_growth_global0_x128: U256,
+        fee_growth_global1_x128: U256,
+        upper: bool,
+        max_liquidity: u128,
+    ) -> Result<bool, PoolError> {
+        let gross_before = self.liquidity_gross;
+        let gross_after = add_delta(gross_before, liquidity_delta)?;
+        if gross_after > max_liquidity {
+            return Err(PoolError::TickLiquidityOverflow);
+        }
+        let liquidity_net = if upper {
+            self.liquidity_net.checked_sub(liquidity_delta)
+        } else {
+            self.liquidity_net.checked_add(liquidity_delta)
+        }
+        .ok_or(PoolError::Overflow)?;
+
+        if gross_before == 0 && tick <= tick_current {
+            self.fee_growth_outside0_x128 = fee_growth_global0_x128;
+            self.fee_growth_outside1_x128 = fee_growth_global1_x128;
+        }
+        self.liquidity_gross = gross_after;
+        self.liquidity_net = liquidity_net;
+
+        Ok((gross_after == 0) != (gross_before == 0))
+    }
+
+    /// Moves the price across the tick, flipping its outside fee growth to the other side.
+    /// Returns the liquidity net of the tick.
+    pub fn cross(&mut self, fee_growth_global0_x128: U256, fee_growth_global1_x128: U256) -> i128 {
+        self.fee_growth_outside0_x128 =
+            wrapping_sub(fee_growth_global0_x128, self.fee_growth_outside0_x128);
+        self.fee_growth_outside1_x128 =
+            wrapping_sub(fee_growth_global1_x128, self.fee_growth_outside1_x128);
+        self.liquidity_net
+    }
+}
+
+/// Fee growth per unit of liquidity inside `[tick_lower, tick_upper)`,
+/// given the info of both ticks.
+pub fn fee_growth_inside(
+    (tick_lower, lower): (i32, &TickInfo),
+    (tick_upper, upper): (i32, &TickInfo),
+    tick_current: i32,
+    fee_growth_global0_x128: U256,
+    fee_growth_global1_x128: U256,
+) -> (U256, U256) {
+    let inside = |global: U256, lower_outside: U256, upper_outside: U256| {
+        let below = if tick_current >= tick_lower {
+            lower_outside
+        } else {
+            wrapping_sub(global, lower_outside)
+        };
+        let above = if tick_current < tick_upper {
+            upper_outside
+        } else {
+            wrapping_sub(global, upper_outside)
+        };
+        wrapping_sub(wrapping_sub(global, below), above)
+    };
+
+    (
+        inside(
+            fee_growth_global0_x128,
+            lower.fee_growth_outside0_x128,
+            upper.fee_growth_outside0_x128,
+        ),
+        inside(
+            fee_growth_global1_x128,
+            lower.fee_growth_outside1_x128,
+            upper.fee_growth_outside1_x128,
+        ),
+    )
+}
+
+/// Closest initialized tick at or below `tick` when the price goes down,
+/// or above `tick` when it goes up.
+pub fn next_initialized(
+    ticks: &BTreeMap<i32, TickInfo>,
+    tick: i32,
+    zero_for_one: bool,
+) -> Option<i32> {
+    if zero_for_one {
+        ticks.range(..=tick).next_back().map(|(tick, _)| *tick)
+    } else {
+        ticks
+            .range(tick.saturating_add(1)..)
+            .next()
+            .map(|(tick, _)| *tick)
+    }
+}
+
+/// Fee growth counters are allowed to wrap around; only their differences are used.
+pub fn wrapping_sub(a: U256, b: U256) -> U256 {
+    a.overflowing_sub(b).0
+}
+
+pub fn wrapping_add(a: U256, b: U256) -> U256 {
+    a.overflowing_add(b).0
+}
@@ -0,0 +1,142 @@
+use crate::services::pool::PoolError;
+use sails_rs::U256;
+
+/// Lowest tick, the one whose price `1.0001^tick` is just above `2^-128`.
+pub const MIN_TICK: i32 = -887_272;
+/// Highest tick, the one whose price is just below `2^128`.
+pub const MAX_TICK: i32 = -MIN_TICK;
+/// `sqrt_ratio_at_tick(MIN_TICK)`.
+pub const MIN_SQRT_RATIO: u64 = 4_295_128_739;
+
+/// `sqrt_ratio_at_tick(MAX_TICK)`.
+pub fn max_sqrt_ratio() -> U256 {
+    U256::from_dec_str("1461446703485210103287273052203988822378723970342").expect("Valid constant")
+}
+
+pub fn min_sqrt_ratio() -> U256 {
+    U256::from(MIN_SQRT_RATIO)
+}
+
+/// `2^128 / sqrt(1.0001)^(2^i)` for each bit `i` of the tick, rounded down.
+const RATIOS: [u128; 20] = [
+    0xfffcb933bd6fad37aa2d162d1a594001,
+    0xfff97272373d413259a46990580e213a,
+    0xfff2e50f5f656932ef12357cf3c7fdcc,
+    0xffe5caca7e10e4e61c3624eaa0941cd0,
+    0xffcb9843d60f6159c9db58835c926644,
+    0xff973b41fa98c081472e6896dfb254c0,
+    0xff2ea16466c96a3843ec78b326b52861,
+    0xfe5dee046a99a2a811c461f1969c3053,
+    0xfcbe86c7900a88aedcffc83b479aa3a4,
+    0xf987a7253ac413176f2b074cf7815e54,
+    0xf3392b0822b70005940c7a398e4b70f3,
+    0xe7159475a2c29b7443b29c7fa6e889d9,
+    0xd097f3bdfd2022b8845ad8f792aa5825,
+    0xa9f746462d870fdf8a65dc1f90e061e5,
+    0x70d869a156d2a1b890bb3df62baf32f7,
+    0x31be135f97d08fd981231505542fcfa6,
+    0x9aa508b5b7a84e1c677de54f3e99bc9,
+    0x5d6af8dedb81196699c329225ee604,
+    0x2216e584f5fa1ea926041bedfe98,
+    0x48a170391f7dc42444e8fa2,
+];
+
+/// Calculates `sqrt(1.0001^tick) * 2^96`, as in Uniswap V3's `TickMath`.
+/// The result is rounded up, so it is the same value whichever direction it is reached from.
+pub fn sqrt_ratio_at_tick(tick: i32) -> Result<U256, PoolError> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return Err(PoolError::InvalidTick);
+    }
+    let abs_tick = tick.unsigned_abs();
+
+    // Q128.128 ratio for the negative tick, built bit by bit
+    let mut ratio = if abs_tick & 1 != 0 {
+        U256::from(RATIOS[0])
+    } else {
+        U256::one() << 128
+    };
+    for (bit, factor) in RATIOS.iter().enumerate().skip(1) {
+        if abs_tick & (1 << bit) != 0 {
+            ratio = (ratio * U256::from(*factor)) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // Q128.128 to Q64.96, rounding up
+    let rounding = if (ratio & U256::from(u32::MAX)).is_zero() {
+        U256::zero()
+    } else {
+        U256::one()
+    };
+    Ok((ratio >> 32) + rounding)
+}
+
+/// Returns the greatest tick whose ratio is at most `sqrt_price_x96`.
+/// `sqrt_price_x96` must be in `[MIN_SQRT_RATIO, max_sqrt_ratio())`.
+pub fn tick_at_sqrt_ratio(sqrt_price_x96: U256) -> Result<i32, PoolError> {
+    if sqrt_price_x96 < min_sqrt_ratio() || sqrt_price_x96 >= max_sqrt_ratio() {
+        return Err(PoolError::InvalidSqrtPrice);
+    }
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        // Upper middle, so that `low = mid` always makes progress
+        let mid = low + (high - low + 1) / 2;
+        if sqrt_ratio_at_tick(mid)? <= sqrt_price_x96 {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Ok(low)
+}
+
+#[cfg(test)]
+mod prop_tests {
+    use crate::services::pool::tick_math::{
+        MAX_TICK, MIN_SQRT_RATIO, MIN_TICK, max_sqrt_ratio, sqrt_ratio_at_tick, tick_at_sqrt_ratio,
+    };
+    use proptest::prelude::*;
+    use sails_rs::U256;
+
+    #[test]
+    fn bounds_match_uniswap() {
+        assert_eq!(
+            sqrt_ratio_at_tick(MIN_TICK).unwrap(),
+            U256::from(MIN_SQRT_RATIO)
+        );
+        assert_eq!(sqrt_ratio_at_tick(MAX_TICK).unwrap(), max_sqrt_ratio());
+        assert_eq!(sqrt_ratio_at_tick(0).unwrap(), U256::one() << 96);
+        assert!(sqrt_ratio_at_tick(MIN_TICK - 1).is_err());
+        assert!(sqrt_ratio_at_tick(MAX_TICK + 1).is_err());
+        assert_eq!(
+            tick_at_sqrt_ratio(U256::from(MIN_SQRT_RATIO)).unwrap(),
+            MIN_TICK
+        );
+        assert_eq!(
+            tick_at_sqrt_ratio(max_sqrt_ratio() - U256::one()).unwrap(),
+            MAX_TICK - 1
+        );
+        assert!(tick_at_sqrt_ratio(max_sqrt_ratio()).is_err());
+    }
+
+    proptest! {
+        /// The ratio grows with the tick.
+        #[test]
+        fn prop_ratio_monotone(tick in MIN_TICK..MAX_TICK) {
+            prop_assert!(sqrt_ratio_at_tick(tick).unwrap() < sqrt_ratio_at_tick(tick + 1).unwrap());
+        }
+
+        /// `tick_at_sqrt_ratio` inverts `sqrt_ratio_at_tick`, and stays on the
+        /// tick for any price up to the next one.
+        #[test]
+        fn prop_tick_roundtrip(tick in MIN_TICK..MAX_TICK, frac in 0u64..=1_000) {
+            let ratio = sqrt_ratio_at_tick(tick).unwrap();
+            prop_assert_eq!(tick_at_sqrt_ratio(ratio).unwrap(), tick);
+            let next = sqrt_ratio_at_tick(tick + 1).unwrap();
+            let inside = ratio + (next - ratio - U256::one()) * U256::from(frac) / U256::from(1_000);
+            prop_assert_eq!(tick_at_sqrt_ratio(inside).unwrap(), tick);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use crate::services::pool::{Config, PoolError, PoolService};
+use sails_rs::{U256, prelude::*};
+use token_ops::{GasConfig, TokenCustody, TokenOpError, TransferTracker};
+
+impl From<TokenOpError> for PoolError {
+    fn from(err: TokenOpError) -> Self {
+        match err {
+            TokenOpError::SendFailure => PoolError::SendFailure,
+            TokenOpError::ReplyTimeout => PoolError::ReplyTimeout,
+            TokenOpError::ReplyFailure => PoolError::ReplyFailure,
+            TokenOpError::UnableToDecode => PoolError::UnableToDecode,
+            TokenOpError::TransferFailed => PoolError::TokenTransferFailed,
+        }
+    }
+}
+
+impl Config {
+    fn gas(&self) -> GasConfig {
+        GasConfig {
+            gas_for_token_ops: self.gas_for_token_ops,
+            gas_for_reply_deposit: self.gas_for_reply_deposit,
+            reply_timeout: self.reply_timeout,
+        }
+    }
+}
+
+impl<'a> TokenCustody for PoolService<'a> {
+    fn gas_config(&self) -> GasConfig {
+        self.with_state(|st| st.config.gas())
+    }
+
+    fn with_tracker_mut<R>(&self, f: impl FnOnce(&mut TransferTracker) -> R) -> R {
+        let mut tr = self.tracker.borrow_mut();
+        f(&mut tr)
+    }
+
+    fn credit(&self, user: ActorId, token: ActorId, amount: U256) {
+        self.with_state_mut(|st| {
+            let credit = st.credits.entry((user, token)).or_default();
+            *credit = credit.saturating_add(amount);
+        });
+    }
+}
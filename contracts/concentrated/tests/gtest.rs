@@ -0,0 +1,333 @@
+use concentrated_client::{
+    pool::{Pool, PoolImpl},
+    Concentrated, ConcentratedCtors, ConcentratedProgram, Config,
+};
+use extended_vft_client::vft::{Vft, VftImpl};
+use extended_vft_client::{ExtendedVftClient, ExtendedVftClientCtors, ExtendedVftClientProgram};
+use sails_rs::gtest::System;
+use sails_rs::{client::*, prelude::*};
+
+const ADMIN_ID: u64 = 1;
+const USER_ID: u64 = 2;
+const LP_ID: u64 = 3;
+const ONE_VARA: u128 = 1_000_000_000_000;
+
+const FEE_BPS: u64 = 30;
+const TICK_SPACING: i32 = 60;
+
+type Token = Service<VftImpl, GtestEnv>;
+
+struct Setup {
+    env: GtestEnv,
+    pool: Service<PoolImpl, GtestEnv>,
+    token0: Token,
+    token1: Token,
+}
+
+fn default_config() -> Config {
+    Config {
+        gas_for_token_ops: 10_000_000_000,
+        gas_for_reply_deposit: 10_000_000_000,
+        reply_timeout: 100,
+        gas_for_full_tx: 100_000_000_000,
+    }
+}
+
+fn deadline(env: &GtestEnv) -> u64 {
+    env.system().block_timestamp() + 100_000_000
+}
+
+/// Price of 1 as `sqrt(token1 / token0)` in Q64.96.
+fn price_one() -> U256 {
+    U256::one() << 96
+}
+
+/// Deploys two tokens and a pool for them initialized at a price of 1.
+/// The LP and the user get `balance` of both tokens and approve the pool.
+async fn setup(balance: U256) -> Setup {
+    let system = System::new();
+    let admin = ActorId::from(ADMIN_ID);
+    let user = ActorId::from(USER_ID);
+    let lp = ActorId::from(LP_ID);
+    system.mint_to(admin, 1000 * ONE_VARA);
+    system.mint_to(user, 1000 * ONE_VARA);
+    system.mint_to(lp, 1000 * ONE_VARA);
+    let env = GtestEnv::new(system, admin);
+
+    let pool_code_id = env.system().submit_code(concentrated::WASM_BINARY);
+    let release_path = "../target/wasm32-gear/release/extended_vft.opt.wasm";
+    let debug_path = "../target/wasm32-gear/debug/extended_vft.opt.wasm";
+    let wasm_path = if std::path::Path::new(release_path).exists() {
+        release_path
+    } else {
+        debug_path
+    };
+    let token_code_id = env.system().submit_code_file(wasm_path);
+
+    let mut tokens = Vec::new();
+    for name in ["Token0", "Token1"] {
+        let program = env
+            .deploy::<ExtendedVftClientProgram>(token_code_id, name.as_bytes().to_vec())
+            .new(name.to_string(), name.to_string(), 6)
+            .await
+            .unwrap();
+        let mut token = program.vft();
+        token.mint(lp, balance).await.unwrap();
+        token.mint(user, balance).await.unwrap();
+        tokens.push(token);
+    }
+    let mut token1 = tokens.pop().unwrap();
+    let mut token0 = tokens.pop().unwrap();
+
+    let pool_program = env
+        .deploy::<ConcentratedProgram>(pool_code_id, b"salt".to_vec())
+        .new(
+            default_config(),
+            token0.actor_id(),
+            token1.actor_id(),
+            FEE_BPS,
+            TICK_SPACING,
+            admin,
+        )
+        .await
+        .unwrap();
+    let mut pool = pool_program.pool();
+    pool.initialize(price_one()).await.unwrap();
+
+    for token in [&mut token0, &mut token1] {
+        for actor in [lp, user] {
+            token
+                .approve(pool.actor_id(), balance)
+                .with_params(|p| p.with_actor_id(actor))
+                .await
+                .unwrap();
+        }
+    }
+
+    Setup {
+        env,
+        pool,
+        token0,
+        token1,
+    }
+}
+
+async fn mint(setup: &mut Setup, lower: i32, upper: i32, amount0: U256, amount1: U256) -> u128 {
+    let lp = ActorId::from(LP_ID);
+    let (liquidity, _, _) = setup
+        .pool
+        .calculate_mint(lower, upper, amount0, amount1)
+        .await
+        .unwrap();
+    let deadline = deadline(&setup.env);
+    setup
+        .pool
+        .mint(
+            lower,
+            upper,
+            amount0,
+            amount1,
+            U256::zero(),
+            U256::zero(),
+            deadline,
+        )
+        .with_params(|p| p.with_actor_id(lp))
+        .await
+        .unwrap();
+    liquidity
+}
+
+#[tokio::test]
+async fn pool_mints_swaps_and_burns() {
+    let balance = U256::from(1_000_000_000u64);
+    let mut setup = setup(balance).await;
+    let lp = ActorId::from(LP_ID);
+    let user = ActorId::from(USER_ID);
+    let amount = U256::from(100_000_000u64);
+
+    let (_, used0, used1) = setup
+        .pool
+        .calculate_mint(-600, 600, amount, amount)
+        .await
+        .unwrap();
+    let liquidity = mint(&mut setup, -600, 600, amount, amount).await;
+    assert!(liquidity > 0);
+    let position = setup.pool.position(lp, -600, 600).await.unwrap().unwrap();
+    assert_eq!(position.liquidity, liquidity);
+    assert_eq!(setup.slot_liquidity().await, liquidity);
+    // Only what the position needs is kept
+    assert_eq!(setup.token0.balance_of(lp).await.unwrap(), balance - used0);
+    assert_eq!(setup.token1.balance_of(lp).await.unwrap(), balance - used1);
+
+    let amount_in = U256::from(1_000_000u64);
+    let expected_out = setup.pool.get_amount_out(amount_in, true).await.unwrap();
+    let deadline = deadline(&setup.env);
+    setup
+        .pool
+        .swap_exact_tokens_for_tokens(amount_in, expected_out, true, deadline)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_eq!(
+        setup.token0.balance_of(user).await.unwrap(),
+        balance - amount_in
+    );
+    assert_eq!(
+        setup.token1.balance_of(user).await.unwrap(),
+        balance + expected_out
+    );
+    assert!(setup.pool.slot().await.unwrap().tick < 0);
+
+    setup
+        .pool
+        .burn(-600, 600, liquidity, U256::zero(), U256::zero(), deadline)
+        .with_params(|p| p.with_actor_id(lp))
+        .await
+        .unwrap();
+    let (owed0, owed1) = setup.pool.collectable(lp, -600, 600).await.unwrap();
+    // The position got the whole input, fee included, and paid the output
+    assert!(owed0 > used0 + amount_in - 10);
+    assert!(owed1 < used1 - expected_out + 10);
+    setup
+        .pool
+        .collect(-600, 600, owed0, owed1)
+        .with_params(|p| p.with_actor_id(lp))
+        .await
+        .unwrap();
+    assert!(setup.pool.position(lp, -600, 600).await.unwrap().is_none());
+    assert_eq!(
+        setup.token0.balance_of(lp).await.unwrap(),
+        balance - used0 + owed0
+    );
+    assert_eq!(
+        setup.token1.balance_of(lp).await.unwrap(),
+        balance - used1 + owed1
+    );
+    // Only rounding dust stays in the pool
+    let pool_id = setup.pool.actor_id();
+    assert!(setup.token0.balance_of(pool_id).await.unwrap() < U256::from(10));
+    assert!(setup.token1.balance_of(pool_id).await.unwrap() < U256::from(10));
+}
+
+#[tokio::test]
+async fn fees_go_to_positions_in_range() {
+    let balance = U256::from(1_000_000_000u64);
+    let mut setup = setup(balance).await;
+    let lp = ActorId::from(LP_ID);
+    let user = ActorId::from(USER_ID);
+    let amount = U256::from(100_000_000u64);
+
+    mint(&mut setup, -600, 600, amount, amount).await;
+    // Above the current price the position only holds token0
+    mint(&mut setup, 600, 1200, amount, U256::zero()).await;
+    assert_eq!(
+        setup
+            .pool
+            .liquidity_net_between(-600, 1200)
+            .await
+            .unwrap()
+            .len(),
+        3
+    );
+
+    let deadline = deadline(&setup.env);
+    setup
+        .pool
+        .swap_exact_tokens_for_tokens(U256::from(1_000_000u64), U256::zero(), true, deadline)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+
+    let (fees0, fees1) = setup.pool.collectable(lp, -600, 600).await.unwrap();
+    // The whole 0.3% fee, less rounding, since the other range is not in use
+    assert!(fees0 >= U256::from(2_998u64) && fees0 <= U256::from(3_000u64));
+    assert!(fees1.is_zero());
+    assert_eq!(
+        setup.pool.collectable(lp, 600, 1200).await.unwrap(),
+        (U256::zero(), U256::zero())
+    );
+}
+
+#[tokio::test]
+async fn swap_for_exact_output_crosses_ranges() {
+    let balance = U256::from(1_000_000_000u64);
+    let mut setup = setup(balance).await;
+    let user = ActorId::from(USER_ID);
+    let amount = U256::from(10_000_000u64);
+
+    mint(&mut setup, -120, 120, amount, amount).await;
+    mint(&mut setup, -1200, 1200, amount, amount).await;
+    let inner = setup.slot_liquidity().await;
+
+    let amount_out = U256::from(12_000_000u64);
+    let expected_in = setup.pool.get_amount_in(amount_out, false).await.unwrap();
+    let deadline = deadline(&setup.env);
+    let res = setup
+        .pool
+        .swap_tokens_for_exact_tokens(amount_out, expected_in - 1, false, deadline)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    setup
+        .pool
+        .swap_tokens_for_exact_tokens(amount_out, expected_in, false, deadline)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_eq!(
+        setup.token0.balance_of(user).await.unwrap(),
+        balance + amount_out
+    );
+    assert_eq!(
+        setup.token1.balance_of(user).await.unwrap(),
+        balance - expected_in
+    );
+    // The price left the inner range, so only the wide position is active
+    let slot = setup.pool.slot().await.unwrap();
+    assert!(slot.tick >= 120);
+    assert!(setup.slot_liquidity().await < inner);
+
+    // More than the pool holds cannot be bought
+    let res = setup
+        .pool
+        .get_amount_in(U256::from(100_000_000u64), false)
+        .await;
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn pool_rejects_bad_ranges_and_repeated_initialize() {
+    let balance = U256::from(1_000_000_000u64);
+    let mut setup = setup(balance).await;
+    let lp = ActorId::from(LP_ID);
+    let amount = U256::from(1_000_000u64);
+    let deadline = deadline(&setup.env);
+
+    assert!(setup.pool.initialize(price_one()).await.is_err());
+    for (lower, upper) in [(600, -600), (-50, 600), (-600, 600 + TICK_SPACING / 2)] {
+        let res = setup
+            .pool
+            .mint(
+                lower,
+                upper,
+                amount,
+                amount,
+                U256::zero(),
+                U256::zero(),
+                deadline,
+            )
+            .with_params(|p| p.with_actor_id(lp))
+            .await;
+        assert!(res.is_err());
+    }
+    // Nothing was taken by the failed mints
+    assert_eq!(setup.token0.balance_of(lp).await.unwrap(), balance);
+    assert_eq!(setup.token1.balance_of(lp).await.unwrap(), balance);
+    assert!(setup.pool.positions_of(lp).await.unwrap().is_empty());
+}
+
+impl Setup {
+    async fn slot_liquidity(&self) -> u128 {
+        self.pool.slot().await.unwrap().liquidity
+    }
+}
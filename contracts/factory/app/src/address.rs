@@ -0,0 +1,33 @@
+use blake2::{Blake2b, Digest, digest::consts::U32};
+use sails_rs::prelude::*;
+
+/// Salt the pair for sorted `(token0, token1)` and `fee_tier` is created with.
+pub fn pair_salt(token0: ActorId, token1: ActorId, fee_tier: u64) -> Vec<u8> {
+    (token0, token1, fee_tier).encode()
+}
+
+/// Salt of the stable pair for sorted `(token0, token1)` and `fee_tier`,
+/// distinct from the constant-product pair of the same tokens.
+pub fn stable_pair_salt(token0: ActorId, token1: ActorId, fee_tier: u64) -> Vec<u8> {
+    (b"stable", token0, token1, fee_tier).encode()
+}
+
+/// Salt of the concentrated liquidity pool for sorted `(token0, token1)` and `fee_tier`.
+pub fn concentrated_pool_salt(token0: ActorId, token1: ActorId, fee_tier: u64) -> Vec<u8> {
+    (b"concentrated", token0, token1, fee_tier).encode()
+}
+
+/// Address of the program created from `code_id` with `salt` while the
+/// factory handles message `message_id`.
+///
+/// Gear derives the id of a program created by another program from the id of
+/// the creating message, so the address is only known once the `create_pair`
+/// message has been sent, not from the tokens alone.
+pub fn program_address(message_id: MessageId, code_id: CodeId, salt: &[u8]) -> ActorId {
+    let mut hasher = Blake2b::<U32>::new();
+    hasher.update(b"program_from_wasm");
+    hasher.update(message_id);
+    hasher.update(code_id);
+    hasher.update(salt);
+    ActorId::new(hasher.finalize().into())
+}
@@ -1,20 +1,113 @@
 #![no_std]
 #![allow(static_mut_refs)]
 
-use gstd::prog::ProgramGenerator;
-use sails_rs::{collections::HashMap, gstd::msg, prelude::*};
+use sails_rs::{
+    collections::HashSet,
+    gstd::{exec, msg},
+    prelude::*,
+};
+mod address;
+mod registry;
+mod sync;
+mod tokens;
+use registry::PairRegistry;
+pub use registry::{MAX_PAGE_SIZE, PairInfo, PairStatus};
+use sync::PairSync;
+pub use sync::SyncStatus;
+pub use tokens::CreationMode;
 pub const ONE_VARA: u128 = 1_000_000_000_000;
+/// Pair creation fee the factory starts with.
+pub const DEFAULT_CREATION_FEE: u128 = ONE_VARA;
+/// Swap fee, in basis points, of pairs created with `create_pair` and registered with `add_pair`.
+pub const DEFAULT_FEE_TIER: u64 = 30;
+/// Fee tiers enabled at deployment: 0.05%, 0.3% and 1%.
+pub const INITIAL_FEE_TIERS: [u64; 3] = [5, DEFAULT_FEE_TIER, 100];
+/// Highest swap fee a pair accepts: 10%.
+const MAX_FEE_TIER: u64 = 1_000;
+/// Pairs a changed `fee_to` or `treasury_id` is sent to by the message changing it, so the
+/// change fits within one message's gas limit; the other pairs are queued for `retry_*_sync`.
+pub const PUSH_BATCH_SIZE: u32 = 50;
 
 struct FactoryService(());
 
 #[derive(Debug, Default)]
 struct State {
     pair_id: CodeId,
-    pairs: HashMap<(ActorId, ActorId), ActorId>,
+    /// Every code id used for new pairs; version `v` is `pair_codes[v - 1]`.
+    pair_codes: Vec<CodeId>,
+    pairs: PairRegistry,
+    /// StableSwap pairs, kept apart so the same tokens and fee tier
+    /// can have both a constant-product and a stable pair.
+    stable_pairs: PairRegistry,
+    /// Concentrated liquidity pools. They take no protocol or treasury fee,
+    /// so `fee_to` and `treasury_id` are not pushed to them.
+    concentrated_pools: PairRegistry,
+    /// Every code id used for new concentrated pools; the last one is current.
+    concentrated_codes: Vec<CodeId>,
     fee_to: ActorId,
     admin: ActorId,
     config: Config,
     treasury_id: ActorId,
+    fee_to_sync: PairSync,
+    treasury_sync: PairSync,
+    /// Value that must be attached to `create_pair`.
+    creation_fee: u128,
+    /// Fees attached to `create_pair` calls still waiting for the pair program.
+    /// They may have to be refunded, so they can't be withdrawn.
+    fees_in_flight: u128,
+    creation_mode: CreationMode,
+    allowlist: HashSet<ActorId>,
+    denylist: HashSet<ActorId>,
+    /// Whether `create_pair` checks that both tokens answer VFT queries.
+    verify_tokens: bool,
+    /// Swap fees, in basis points, pairs can be created with. Kept sorted.
+    fee_tiers: Vec<u64>,
+}
+
+/// Kind of pool `create` deploys.
+#[derive(Debug, Clone, Copy)]
+enum PoolKind {
+    ConstantProduct,
+    Stable { amp: u64 },
+    Concentrated,
+}
+
+impl State {
+    fn registry(&self, kind: PoolKind) -> &PairRegistry {
+        match kind {
+            PoolKind::ConstantProduct => &self.pairs,
+            PoolKind::Stable { .. } => &self.stable_pairs,
+            PoolKind::Concentrated => &self.concentrated_pools,
+        }
+    }
+
+    fn registry_mut(&mut self, kind: PoolKind) -> &mut PairRegistry {
+        match kind {
+            PoolKind::ConstantProduct => &mut self.pairs,
+            PoolKind::Stable { .. } => &mut self.stable_pairs,
+            PoolKind::Concentrated => &mut self.concentrated_pools,
+        }
+    }
+
+    /// The registry `pair_address` is recorded in, if any.
+    fn registry_of(&mut self, pair_address: ActorId) -> Option<&mut PairRegistry> {
+        if self.pairs.by_address(pair_address).is_some() {
+            Some(&mut self.pairs)
+        } else if self.stable_pairs.by_address(pair_address).is_some() {
+            Some(&mut self.stable_pairs)
+        } else if self.concentrated_pools.by_address(pair_address).is_some() {
+            Some(&mut self.concentrated_pools)
+        } else {
+            None
+        }
+    }
+
+    /// Addresses of the pairs that may still hold liquidity and take the protocol fees.
+    fn live_addresses(&self) -> Vec<ActorId> {
+        let mut addresses = self.pairs.live_addresses();
+        addresses.extend(self.stable_pairs.live_addresses());
+        addresses
+    }
 }
 
 /// Config that will be used to send messages to the other programs or create programs.
@@ -32,6 +125,7 @@ pub struct Config {
     reply_timeout: u32,
     gas_for_full_tx: u64,
     gas_for_pair_creation: u64,
+    /// Gas limit for settings pushed to the pairs (`ChangeFeeTo`, `ChangeTreasuryId`).
     gas_to_change_fee_to: u64,
 }
 static mut STATE: Option<State> = None;
@@ -43,9 +137,73 @@ pub enum FactoryEvent {
         token0: ActorId,
         token1: ActorId,
         pair_address: ActorId,
+        fee_tier: u64,
+    },
+    PairCodeIdChanged {
+        code_id: CodeId,
+        version: u32,
+    },
+    PairCreationFailed {
+        token0: ActorId,
+        token1: ActorId,
+        refunded: u128,
+    },
+    CreationFeeChanged {
+        fee: u128,
+    },
+    ValueWithdrawn {
+        to: ActorId,
+        amount: u128,
+    },
+    CreationModeChanged {
+        mode: CreationMode,
+    },
+    TokenAllowlistUpdated {
+        token: ActorId,
+        allowed: bool,
+    },
+    TokenDenylistUpdated {
+        token: ActorId,
+        denied: bool,
+    },
+    TokenVerificationChanged {
+        enabled: bool,
+    },
+    PairStatusChanged {
+        pair_address: ActorId,
+        status: PairStatus,
+    },
+    FeeTierUpdated {
+        fee_tier: u64,
+        enabled: bool,
+    },
+    StablePairCreated {
+        token0: ActorId,
+        token1: ActorId,
+        fee_tier: u64,
+        amp: u64,
+        pair_address: ActorId,
+    },
+    ConcentratedPoolCreated {
+        token0: ActorId,
+        token1: ActorId,
+        fee_tier: u64,
+        tick_spacing: i32,
+        pool_address: ActorId,
+    },
+    ConcentratedCodeIdChanged {
+        code_id: CodeId,
+        version: u32,
     },
 }
 
+/// Tick spacing of the concentrated pools of a fee tier: 10 for 0.05%, 60 for 0.3%,
+/// 200 for 1%. Fee tiers never exceed `MAX_FEE_TIER`, so this stays well within
+/// the pool's limit.
+pub fn tick_spacing_for_fee_tier(fee_tier: u64) -> i32 {
+    (fee_tier * 2).max(1) as i32
+}
+
 impl FactoryService {
     pub fn init(
         pair_id: CodeId,
@@ -57,10 +215,13 @@ impl FactoryService {
         unsafe {
             STATE = Some(State {
                 pair_id,
+                pair_codes: vec![pair_id],
                 admin,
                 fee_to,
                 config,
                 treasury_id,
+                creation_fee: DEFAULT_CREATION_FEE,
+                fee_tiers: INITIAL_FEE_TIERS.to_vec(),
                 ..Default::default()
             })
         }
@@ -72,44 +233,166 @@ impl FactoryService {
     fn get(&self) -> &'static State {
         unsafe { STATE.as_ref().expect("State is not initialized") }
     }
-}
 
-impl FactoryService {
-    pub fn new() -> Self {
-        Self(())
+    fn pair_code_version(&self) -> u32 {
+        self.get().pair_codes.len() as u32
     }
-}
-#[sails_rs::service(events = FactoryEvent)]
-impl FactoryService {
-    #[export]
-    pub async fn create_pair(&mut self, token0: ActorId, token1: ActorId) {
+
+    /// Sends the current `fee_to` to `pair_id` and records the delivery.
+    /// The status is updated in `on_reply` once the pair replies.
+    fn push_fee_to(&mut self, pair_id: ActorId) {
+        let state = self.get_mut();
+        let payload =
+            pair_client::pair::io::ChangeFeeTo::encode_params_with_prefix("Pair", state.fee_to);
+        match send_to_pair(pair_id, payload, &state.config) {
+            Ok(msg_id) => state.fee_to_sync.sent(pair_id, msg_id),
+            Err(_) => state.fee_to_sync.send_failed(pair_id),
+        }
+    }
+
+    /// Sends the current `treasury_id` to `pair_id` and records the delivery.
+    /// The status is updated in `on_reply` once the pair replies.
+    fn push_treasury_id(&mut self, pair_id: ActorId) {
+        let state = self.get_mut();
+        let payload = pair_client::pair::io::ChangeTreasuryId::encode_params_with_prefix(
+            "Pair",
+            state.treasury_id,
+        );
+        match send_to_pair(pair_id, payload, &state.config) {
+            Ok(msg_id) => state.treasury_sync.sent(pair_id, msg_id),
+            Err(_) => state.treasury_sync.send_failed(pair_id),
+        }
+    }
+
+    /// Panics if the caller may not create a pair for `token0`/`token1`.
+    fn ensure_can_create(&self, token0: ActorId, token1: ActorId) {
+        let state = self.get();
+        if state.denylist.contains(&token0) || state.denylist.contains(&token1) {
+            panic!("Token is denylisted")
+        }
+        let is_admin = msg::source() == state.admin;
+        match state.creation_mode {
+            CreationMode::Permissionless => {}
+            CreationMode::Allowlisted => {
+                if !is_admin
+                    && !(state.allowlist.contains(&token0) && state.allowlist.contains(&token1))
+                {
+                    panic!("Token is not allowlisted")
+                }
+            }
+            CreationMode::AdminOnly => {
+                if !is_admin {
+                    panic!("Not admin")
+                }
+            }
+        }
+    }
+
+    /// Returns the creation fee to the caller after a failed `create_pair`.
+    fn refund_creation(&mut self, token0: ActorId, token1: ActorId, fee: u128) {
+        if fee != 0 {
+            msg::send_bytes(msg::source(), b"", fee)
+                .expect("Error during refund of the creation fee");
+        }
+        self.emit_event(FactoryEvent::PairCreationFailed {
+            token0,
+            token1,
+            refunded: fee,
+        })
+        .expect("Error during event emission");
+    }
+
+    /// Creates a pool of `kind` for the tokens and fee tier.
+    async fn create(&mut self, token0: ActorId, token1: ActorId, fee_tier: u64, kind: PoolKind) {
         let state = self.get_mut();
         let (token0, token1) = sort_tokens(token0, token1);
 
-        if state.pairs.contains_key(&(token0, token1)) {
+        if !state.fee_tiers.contains(&fee_tier) {
+            panic!("Fee tier is not enabled")
+        }
+        if state.registry(kind).contains(token0, token1, fee_tier) {
             panic!("Pair exists")
         }
-        if msg::value() != ONE_VARA {
-            panic!("Must attach 1 Vara to create pair contract");
+        let code_id = match kind {
+            PoolKind::Concentrated => *state
+                .concentrated_codes
+                .last()
+                .unwrap_or_else(|| panic!("Concentrated pool code is not set")),
+            _ => state.pair_id,
+        };
+        let fee = msg::value();
+        if fee != state.creation_fee {
+            panic!("Must attach the pair creation fee");
         }
+        self.ensure_can_create(token0, token1);
+
+        if state.verify_tokens {
+            state.fees_in_flight += fee;
+            let config = state.config.clone();
+            let verified =
+                tokens::is_vft(token0, &config).await && tokens::is_vft(token1, &config).await;
+            state.fees_in_flight -= fee;
+
+            // The pair may have been created while the tokens were being checked
+            if !verified || state.registry(kind).contains(token0, token1, fee_tier) {
+                self.refund_creation(token0, token1, fee);
+                return;
+            }
+        }
+
         let pair_config = pair_client::Config {
             gas_for_token_ops: state.config.gas_for_token_ops,
             gas_for_reply_deposit: state.config.gas_for_reply_deposit,
             reply_timeout: state.config.reply_timeout,
             gas_for_full_tx: state.config.gas_for_full_tx,
+            swap_fee_bps: fee_tier,
         };
 
-        let payload = pair_client::io::New::encode_params(
-            pair_config,
-            token0,
-            token1,
-            state.fee_to,
-            state.treasury_id,
-            state.admin,
-        );
+        let (payload, salt) = match kind {
+            PoolKind::Stable { amp } => (
+                pair_client::io::NewStable::encode_params(
+                    pair_config,
+                    token0,
+                    token1,
+                    state.fee_to,
+                    state.treasury_id,
+                    state.admin,
+                    amp,
+                ),
+                address::stable_pair_salt(token0, token1, fee_tier),
+            ),
+            PoolKind::Concentrated => (
+                concentrated_client::io::New::encode_params(
+                    concentrated_client::Config {
+                        gas_for_token_ops: state.config.gas_for_token_ops,
+                        gas_for_reply_deposit: state.config.gas_for_reply_deposit,
+                        reply_timeout: state.config.reply_timeout,
+                        gas_for_full_tx: state.config.gas_for_full_tx,
+                    },
+                    token0,
+                    token1,
+                    fee_tier,
+                    tick_spacing_for_fee_tier(fee_tier),
+                    state.admin,
+                ),
+                address::concentrated_pool_salt(token0, token1, fee_tier),
+            ),
+            PoolKind::ConstantProduct => (
+                pair_client::io::New::encode_params(
+                    pair_config,
+                    token0,
+                    token1,
+                    state.fee_to,
+                    state.treasury_id,
+                    state.admin,
+                ),
+                address::pair_salt(token0, token1, fee_tier),
+            ),
+        };
 
-        let create_program_future = ProgramGenerator::create_program_bytes_with_gas_for_reply(
-            state.pair_id,
+        let create_program_future = gstd::prog::create_program_bytes_with_gas_for_reply(
+            code_id,
+            salt,
             payload,
             state.config.gas_for_pair_creation,
             0,
@@ -117,34 +400,174 @@ impl FactoryService {
         )
         .unwrap_or_else(|e| panic!("{:?}", e));
 
-        let (pair_address, _) = create_program_future
+        let (fee_to, treasury_id) = (state.fee_to, state.treasury_id);
+        let version = match kind {
+            PoolKind::Concentrated => state.concentrated_codes.len() as u32,
+            _ => self.pair_code_version(),
+        };
+        state.fees_in_flight += fee;
+        let created = create_program_future.await;
+        state.fees_in_flight -= fee;
+
+        let (pair_address, _) = match created {
+            Ok(created) => created,
+            Err(_) => {
+                self.refund_creation(token0, token1, fee);
+                return;
+            }
+        };
+        // Another pair may have been created for the same key meanwhile
+        if state
+            .registry_mut(kind)
+            .insert(token0, token1, fee_tier, pair_address, version)
+            .is_err()
+        {
+            self.refund_creation(token0, token1, fee);
+            return;
+        }
+
+        // Settings changed while the pair was being created
+        if !matches!(kind, PoolKind::Concentrated) {
+            if state.fee_to != fee_to {
+                self.push_fee_to(pair_address);
+            }
+            if state.treasury_id != treasury_id {
+                self.push_treasury_id(pair_address);
+            }
+        }
+
+        let event = match kind {
+            PoolKind::Stable { amp } => FactoryEvent::StablePairCreated {
+                token0,
+                token1,
+                fee_tier,
+                amp,
+                pair_address,
+            },
+            PoolKind::Concentrated => FactoryEvent::ConcentratedPoolCreated {
+                token0,
+                token1,
+                fee_tier,
+                tick_spacing: tick_spacing_for_fee_tier(fee_tier),
+                pool_address: pair_address,
+            },
+            PoolKind::ConstantProduct => FactoryEvent::PairCreated {
+                token0,
+                token1,
+                fee_tier,
+                pair_address,
+            },
+        };
+        self.emit_event(event).expect("Error during event emission");
+    }
+
+    pub fn on_reply(&mut self) {
+        let reply_to = msg::reply_to().expect("reply_to only in reply context");
+        let success = msg::reply_code().is_ok_and(|code| code.is_success());
+        let state = self.get_mut();
+        if !state.fee_to_sync.on_reply(reply_to, success) {
+            state.treasury_sync.on_reply(reply_to, success);
+        }
+    }
+}
+
+impl FactoryService {
+    pub fn new() -> Self {
+        Self(())
+    }
+}
+#[sails_rs::service(events = FactoryEvent)]
+impl FactoryService {
+    /// Creates a pair program for `token0`/`token1` with the `DEFAULT_FEE_TIER` swap fee.
+    /// The attached value must equal the current creation fee. If the tokens fail
+    /// verification, the pair program can't be created, or another pair for the tokens
+    /// was created meanwhile, the fee is refunded and `PairCreationFailed` is emitted.
+    #[export]
+    pub async fn create_pair(&mut self, token0: ActorId, token1: ActorId) {
+        self.create_pair_with_fee_tier(token0, token1, DEFAULT_FEE_TIER)
             .await
-            .unwrap_or_else(|e| panic!("{:?}", e));
-        state.pairs.insert((token0, token1), pair_address);
+    }
 
-        self.emit_event(FactoryEvent::PairCreated {
-            token0,
-            token1,
-            pair_address,
-        })
-        .expect("Error during event emission");
+    /// Creates a pair program for `token0`/`token1` charging `fee_tier` basis points per swap.
+    /// The same tokens may have one active pair per enabled fee tier.
+    #[export]
+    pub async fn create_pair_with_fee_tier(
+        &mut self,
+        token0: ActorId,
+        token1: ActorId,
+        fee_tier: u64,
+    ) {
+        self.create(token0, token1, fee_tier, PoolKind::ConstantProduct)
+            .await
     }
 
+    /// Creates a StableSwap pair for `token0`/`token1`, meant for pegged assets,
+    /// with amplification coefficient `amp`. Stable pairs are registered apart from
+    /// constant-product ones and are listed by `stable_pairs_paginated`.
+    /// An `amp` the pair rejects makes the creation fail and the fee is refunded.
+    #[export]
+    pub async fn create_stable_pair(
+        &mut self,
+        token0: ActorId,
+        token1: ActorId,
+        fee_tier: u64,
+        amp: u64,
+    ) {
+        self.create(token0, token1, fee_tier, PoolKind::Stable { amp })
+            .await
+    }
+
+    /// Creates a concentrated liquidity pool for `token0`/`token1` charging `fee_tier`
+    /// basis points per swap, with the tick spacing of `tick_spacing_for_fee_tier`.
+    /// Pools are registered apart from the pairs and listed by `concentrated_pools_paginated`.
+    /// The pool starts without a price; the first liquidity provider calls `Pool::Initialize`.
+    #[export]
+    pub async fn create_concentrated_pool(
+        &mut self,
+        token0: ActorId,
+        token1: ActorId,
+        fee_tier: u64,
+    ) {
+        self.create(token0, token1, fee_tier, PoolKind::Concentrated)
+            .await
+    }
+
+    /// Changes `fee_to` and pushes it to the first `PUSH_BATCH_SIZE` registered pairs.
+    /// The other pairs are queued, and they and the pairs that fail to apply it are listed
+    /// by `fee_to_out_of_sync` and reached with `retry_fee_to_sync`.
     #[export]
     pub fn change_fee_to(&mut self, fee_to: ActorId) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+
+        state.fee_to = fee_to;
+        for pair_id in state.live_addresses() {
+            state.fee_to_sync.queue(pair_id);
+        }
+        for pair_id in state.fee_to_sync.unsent(PUSH_BATCH_SIZE as usize) {
+            self.push_fee_to(pair_id);
+        }
+    }
+
+    /// Sends the current `fee_to` to at most `limit` pairs that are queued or whose last
+    /// delivery failed.
+    /// `limit` keeps a retry within one message's gas limit when many pairs are out of sync.
+    #[export]
+    pub fn retry_fee_to_sync(&mut self, limit: u32) {
         let state = self.get();
         if msg::source() != state.admin {
             panic!("Not admin")
         }
 
-        self.get_mut().fee_to = fee_to;
-        for &pair_id in state.pairs.values() {
-            let payload = pair_client::pair::io::ChangeFeeTo::encode_params(fee_to);
-            msg::send_bytes_with_gas(pair_id, payload, state.config.gas_to_change_fee_to, 0)
-                .expect("Error during sending message");
+        for pair_id in state.fee_to_sync.unsent(limit as usize) {
+            self.push_fee_to(pair_id);
         }
     }
 
+    /// Registers an existing pair under `DEFAULT_FEE_TIER`,
+    /// the fee of pairs deployed before fee tiers were introduced.
     #[export]
     pub fn add_pair(&mut self, token0: ActorId, token1: ActorId, pair_address: ActorId) {
         let state = self.get_mut();
@@ -152,24 +575,275 @@ impl FactoryService {
             panic!("Not admin")
         }
         let (token0, token1) = sort_tokens(token0, token1);
-        state.pairs.insert((token0, token1), pair_address);
+        state
+            .pairs
+            .insert(token0, token1, DEFAULT_FEE_TIER, pair_address, 0)
+            .unwrap_or_else(|e| panic!("{}", e));
 
         self.emit_event(FactoryEvent::PairCreated {
             token0,
             token1,
+            fee_tier: DEFAULT_FEE_TIER,
+            pair_address,
+        })
+        .expect("Error during event emission");
+    }
+
+    /// Sets the status of a registered pair.
+    /// Once a pair is migrated or deprecated, a new pair can be created for its tokens.
+    #[export]
+    pub fn set_pair_status(&mut self, pair_address: ActorId, status: PairStatus) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+
+        state
+            .registry_of(pair_address)
+            .ok_or("Pair is not registered")
+            .and_then(|registry| registry.set_status(pair_address, status))
+            .unwrap_or_else(|e| panic!("{}", e));
+        self.emit_event(FactoryEvent::PairStatusChanged {
+            pair_address,
+            status,
+        })
+        .expect("Error during event emission");
+    }
+
+    /// Called by a pair after it migrated all its liquidity.
+    #[export]
+    pub fn mark_pair_migrated(&mut self) {
+        let state = self.get_mut();
+        let pair_address = msg::source();
+        let Some(registry) = state.registry_of(pair_address) else {
+            panic!("Not a registered pair")
+        };
+
+        registry
+            .set_status(pair_address, PairStatus::Migrated)
+            .unwrap_or_else(|e| panic!("{}", e));
+        self.emit_event(FactoryEvent::PairStatusChanged {
             pair_address,
+            status: PairStatus::Migrated,
         })
         .expect("Error during event emission");
     }
 
+    /// Sets the value that must be attached to `create_pair`. Can be zero.
+    #[export]
+    pub fn set_creation_fee(&mut self, fee: u128) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+
+        state.creation_fee = fee;
+        self.emit_event(FactoryEvent::CreationFeeChanged { fee })
+            .expect("Error during event emission");
+    }
+
+    /// Sends `amount` of the collected value to `to`.
+    /// Fees of pair creations still in progress can't be withdrawn.
+    #[export]
+    pub fn withdraw_value(&mut self, to: ActorId, amount: u128) {
+        let state = self.get();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+        let available = exec::value_available().saturating_sub(state.fees_in_flight);
+        if amount == 0 || amount > available {
+            panic!("Invalid amount")
+        }
+
+        msg::send_bytes(to, b"", amount).expect("Error during value transfer");
+        self.emit_event(FactoryEvent::ValueWithdrawn { to, amount })
+            .expect("Error during event emission");
+    }
+
+    #[export]
+    pub fn set_creation_mode(&mut self, mode: CreationMode) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+
+        state.creation_mode = mode;
+        self.emit_event(FactoryEvent::CreationModeChanged { mode })
+            .expect("Error during event emission");
+    }
+
+    /// Adds `token` to the allowlist used in `CreationMode::Allowlisted`, or removes it.
+    #[export]
+    pub fn set_token_allowed(&mut self, token: ActorId, allowed: bool) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+
+        if allowed {
+            state.allowlist.insert(token);
+        } else {
+            state.allowlist.remove(&token);
+        }
+        self.emit_event(FactoryEvent::TokenAllowlistUpdated { token, allowed })
+            .expect("Error during event emission");
+    }
+
+    /// Adds `token` to the denylist, or removes it. No new pairs can be created
+    /// with a denylisted token, whatever the creation mode.
+    #[export]
+    pub fn set_token_denied(&mut self, token: ActorId, denied: bool) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+
+        if denied {
+            state.denylist.insert(token);
+        } else {
+            state.denylist.remove(&token);
+        }
+        self.emit_event(FactoryEvent::TokenDenylistUpdated { token, denied })
+            .expect("Error during event emission");
+    }
+
+    /// Enables `fee_tier` (in basis points) for new pairs, or disables it.
+    /// Existing pairs keep their fee.
+    #[export]
+    pub fn set_fee_tier_enabled(&mut self, fee_tier: u64, enabled: bool) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+        if fee_tier > MAX_FEE_TIER {
+            panic!("Fee tier must not exceed 10%")
+        }
+
+        match (state.fee_tiers.binary_search(&fee_tier), enabled) {
+            (Err(pos), true) => state.fee_tiers.insert(pos, fee_tier),
+            (Ok(pos), false) => {
+                state.fee_tiers.remove(pos);
+            }
+            _ => {}
+        }
+        self.emit_event(FactoryEvent::FeeTierUpdated { fee_tier, enabled })
+            .expect("Error during event emission");
+    }
+
+    /// Enables or disables the check that both tokens answer the VFT
+    /// `Decimals` and `BalanceOf` queries before a pair is created.
+    #[export]
+    pub fn set_verify_tokens(&mut self, enabled: bool) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+
+        state.verify_tokens = enabled;
+        self.emit_event(FactoryEvent::TokenVerificationChanged { enabled })
+            .expect("Error during event emission");
+    }
+
+    /// Sets the code new pairs are created from and bumps the pair code version.
+    /// Existing pairs keep the version they were created with.
+    #[export]
+    pub fn set_pair_code_id(&mut self, code_id: CodeId) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+        if code_id == state.pair_id {
+            panic!("Code id is already in use")
+        }
+
+        state.pair_id = code_id;
+        state.pair_codes.push(code_id);
+        let version = self.pair_code_version();
+
+        self.emit_event(FactoryEvent::PairCodeIdChanged { code_id, version })
+            .expect("Error during event emission");
+    }
+
+    /// Sets the code new concentrated pools are created from.
+    #[export]
+    pub fn set_concentrated_code_id(&mut self, code_id: CodeId) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+        if state.concentrated_codes.last() == Some(&code_id) {
+            panic!("Code id is already in use")
+        }
+
+        state.concentrated_codes.push(code_id);
+        let version = state.concentrated_codes.len() as u32;
+
+        self.emit_event(FactoryEvent::ConcentratedCodeIdChanged { code_id, version })
+            .expect("Error during event emission");
+    }
+
+    /// Changes the treasury and pushes it to the first `PUSH_BATCH_SIZE` registered pairs.
+    /// The other pairs are queued, and they and the pairs that fail to apply it are listed
+    /// by `treasury_out_of_sync` and reached with `retry_treasury_sync`.
     #[export]
     pub fn change_treasury_id(&mut self, new_treasury_id: ActorId) {
+        let state = self.get_mut();
+        if msg::source() != state.admin {
+            panic!("Not admin")
+        }
+
+        state.treasury_id = new_treasury_id;
+        for pair_id in state.live_addresses() {
+            state.treasury_sync.queue(pair_id);
+        }
+        for pair_id in state.treasury_sync.unsent(PUSH_BATCH_SIZE as usize) {
+            self.push_treasury_id(pair_id);
+        }
+    }
+
+    /// Sends the current `treasury_id` to at most `limit` pairs that are queued or whose last
+    /// delivery failed.
+    #[export]
+    pub fn retry_treasury_sync(&mut self, limit: u32) {
         let state = self.get();
         if msg::source() != state.admin {
             panic!("Not admin")
         }
 
-        self.get_mut().treasury_id = new_treasury_id;
+        for pair_id in state.treasury_sync.unsent(limit as usize) {
+            self.push_treasury_id(pair_id);
+        }
+    }
+
+    #[export]
+    pub fn creation_fee(&self) -> u128 {
+        self.get().creation_fee
+    }
+
+    #[export]
+    pub fn creation_mode(&self) -> CreationMode {
+        self.get().creation_mode
+    }
+
+    #[export]
+    pub fn allowlist(&self) -> Vec<ActorId> {
+        self.get().allowlist.iter().copied().collect()
+    }
+
+    #[export]
+    pub fn denylist(&self) -> Vec<ActorId> {
+        self.get().denylist.iter().copied().collect()
+    }
+
+    #[export]
+    pub fn verify_tokens(&self) -> bool {
+        self.get().verify_tokens
+    }
+
+    /// Returns the fee tiers, in basis points, new pairs can be created with.
+    #[export]
+    pub fn fee_tiers(&self) -> Vec<u64> {
+        self.get().fee_tiers.clone()
     }
 
     #[export]
@@ -182,20 +856,198 @@ impl FactoryService {
         self.get().treasury_id
     }
 
+    /// Pairs that have not confirmed the current `fee_to`, with their delivery status.
+    #[export]
+    pub fn fee_to_out_of_sync(&self) -> Vec<(ActorId, SyncStatus)> {
+        self.get().fee_to_sync.out_of_sync()
+    }
+
+    /// Pairs that have not confirmed the current `treasury_id`, with their delivery status.
+    #[export]
+    pub fn treasury_out_of_sync(&self) -> Vec<(ActorId, SyncStatus)> {
+        self.get().treasury_sync.out_of_sync()
+    }
+
+    /// Returns every active pair in creation order.
+    /// Prefer `pairs_paginated` once the registry grows.
     #[export]
     pub fn pairs(&self) -> Vec<((ActorId, ActorId), ActorId)> {
-        self.get().pairs.iter().map(|(k, v)| (*k, *v)).collect()
+        self.get()
+            .pairs
+            .iter()
+            .filter(|p| p.status == PairStatus::Active)
+            .map(|p| ((p.token0, p.token1), p.pair_address))
+            .collect()
     }
 
+    /// Returns the registry record of `pair_address`, whatever its status.
+    /// Stable pairs and concentrated pools are looked up too.
+    #[export]
+    pub fn pair_info(&self, pair_address: ActorId) -> Option<PairInfo> {
+        let state = self.get();
+        state
+            .pairs
+            .by_address(pair_address)
+            .or_else(|| state.stable_pairs.by_address(pair_address))
+            .or_else(|| state.concentrated_pools.by_address(pair_address))
+            .cloned()
+    }
+
+    /// Returns up to `limit` pairs starting at `offset`, in creation order.
+    /// `limit` is capped at `MAX_PAGE_SIZE`.
+    #[export]
+    pub fn pairs_paginated(&self, offset: u32, limit: u32) -> Vec<PairInfo> {
+        self.get().pairs.page(offset, limit)
+    }
+
+    /// Returns every pair that contains `token`, in creation order.
+    #[export]
+    pub fn pairs_for_token(&self, token: ActorId) -> Vec<PairInfo> {
+        self.get().pairs.for_token(token)
+    }
+
+    #[export]
+    pub fn pairs_count(&self) -> u32 {
+        self.get().pairs.len()
+    }
+
+    /// Returns up to `limit` stable pairs starting at `offset`, in creation order.
+    #[export]
+    pub fn stable_pairs_paginated(&self, offset: u32, limit: u32) -> Vec<PairInfo> {
+        self.get().stable_pairs.page(offset, limit)
+    }
+
+    #[export]
+    pub fn stable_pairs_count(&self) -> u32 {
+        self.get().stable_pairs.len()
+    }
+
+    /// Returns up to `limit` concentrated pools starting at `offset`, in creation order.
+    #[export]
+    pub fn concentrated_pools_paginated(&self, offset: u32, limit: u32) -> Vec<PairInfo> {
+        self.get().concentrated_pools.page(offset, limit)
+    }
+
+    #[export]
+    pub fn concentrated_pools_count(&self) -> u32 {
+        self.get().concentrated_pools.len()
+    }
+
+    /// Returns the current concentrated pool code id and its version,
+    /// zero if it was never set.
+    #[export]
+    pub fn concentrated_code_id(&self) -> (CodeId, u32) {
+        let codes = &self.get().concentrated_codes;
+        (
+            codes.last().copied().unwrap_or_default(),
+            codes.len() as u32,
+        )
+    }
+
+    /// Returns the current pair code id and its version.
+    #[export]
+    pub fn pair_code_id(&self) -> (CodeId, u32) {
+        (self.get().pair_id, self.pair_code_version())
+    }
+
+    /// Returns the code id of pair code `version`, if there is one.
+    #[export]
+    pub fn pair_code_by_version(&self, version: u32) -> Option<CodeId> {
+        let index = version.checked_sub(1)?;
+        self.get().pair_codes.get(index as usize).copied()
+    }
+
+    /// Returns up to `limit` pairs created from code `version`, starting at `offset`.
+    /// Version `0` lists pairs registered with `add_pair`.
+    #[export]
+    pub fn pairs_by_version(&self, version: u32, offset: u32, limit: u32) -> Vec<PairInfo> {
+        self.get().pairs.for_version(version, offset, limit)
+    }
+
+    #[export]
+    pub fn pairs_count_by_version(&self, version: u32) -> u32 {
+        self.get().pairs.count_for_version(version)
+    }
+
+    /// Returns the active `DEFAULT_FEE_TIER` pair for the tokens, or zero address if there is none.
     #[export]
     pub fn get_pair(&self, token0: ActorId, token1: ActorId) -> ActorId {
+        self.get_pair_with_fee_tier(token0, token1, DEFAULT_FEE_TIER)
+    }
+
+    /// Returns the address of the pair for the tokens and fee tier that the `create_pair`
+    /// (or `create_pair_with_fee_tier`) message `message_id` creates with the current pair code.
+    ///
+    /// Gear derives the address of a program created by another program from the message
+    /// creating it, so there is no address for the tokens alone: the salt only keeps it unique
+    /// per token pair and fee tier. The address is known as soon as the creating message is
+    /// sent, so tokens can be approved to it before the pair exists.
+    #[export]
+    pub fn compute_pair_address(
+        &self,
+        token0: ActorId,
+        token1: ActorId,
+        fee_tier: u64,
+        message_id: MessageId,
+    ) -> ActorId {
         let (token0, token1) = sort_tokens(token0, token1);
-        *(self
-            .get()
+        let salt = address::pair_salt(token0, token1, fee_tier);
+        address::program_address(message_id, self.get().pair_id, &salt)
+    }
+
+    /// Returns the active pair for the tokens and fee tier, or zero address if there is none.
+    #[export]
+    pub fn get_pair_with_fee_tier(
+        &self,
+        token0: ActorId,
+        token1: ActorId,
+        fee_tier: u64,
+    ) -> ActorId {
+        let (token0, token1) = sort_tokens(token0, token1);
+        self.get()
             .pairs
-            .get(&(token0, token1))
-            .unwrap_or(&ActorId::zero()))
+            .get(token0, token1, fee_tier)
+            .map(|p| p.pair_address)
+            .unwrap_or_default()
+    }
+
+    /// Returns the active stable pair for the tokens and fee tier, or zero address if there is none.
+    #[export]
+    pub fn get_stable_pair(&self, token0: ActorId, token1: ActorId, fee_tier: u64) -> ActorId {
+        let (token0, token1) = sort_tokens(token0, token1);
+        self.get()
+            .stable_pairs
+            .get(token0, token1, fee_tier)
+            .map(|p| p.pair_address)
+            .unwrap_or_default()
     }
+
+    /// Returns the active concentrated pool for the tokens and fee tier, or zero address if there is none.
+    #[export]
+    pub fn get_concentrated_pool(
+        &self,
+        token0: ActorId,
+        token1: ActorId,
+        fee_tier: u64,
+    ) -> ActorId {
+        let (token0, token1) = sort_tokens(token0, token1);
+        self.get()
+            .concentrated_pools
+            .get(token0, token1, fee_tier)
+            .map(|p| p.pair_address)
+            .unwrap_or_default()
+    }
+}
+
+/// Sends a setting to a pair with a reply deposit, so the reply reaches `on_reply`.
+fn send_to_pair(
+    pair_id: ActorId,
+    payload: Vec<u8>,
+    config: &Config,
+) -> gstd::errors::Result<MessageId> {
+    let msg_id = msg::send_bytes_with_gas(pair_id, payload, config.gas_to_change_fee_to, 0)?;
+    exec::reply_deposit(msg_id, config.gas_for_reply_deposit)?;
+    Ok(msg_id)
 }
 
 fn sort_tokens(token_a: ActorId, token_b: ActorId) -> (ActorId, ActorId) {
@@ -231,4 +1083,10 @@ impl FactoryProgram {
     pub fn factory(&self) -> FactoryService {
         FactoryService::new()
     }
+
+    #[allow(dead_code)]
+    #[handle_reply]
+    fn handle_reply(&self) {
+        FactoryService::new().on_reply();
+    }
 }
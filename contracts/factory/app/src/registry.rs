@@ -0,0 +1,187 @@
+use sails_rs::{collections::HashMap, prelude::*};
+
+/// Maximum number of records returned by a single paginated query.
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+/// Lifecycle of a registered pair.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Decode, Encode, TypeInfo)]
+pub enum PairStatus {
+    /// The pair `get_pair` resolves to for its tokens and fee tier.
+    #[default]
+    Active,
+    /// The pair moved its liquidity out; a new pair may be created for its tokens and fee tier.
+    Migrated,
+    /// Retired by the admin; a new pair may be created for its tokens and fee tier.
+    Deprecated,
+}
+
+/// A pair registered in the factory.
+#[derive(Debug, Clone, PartialEq, Eq, Decode, Encode, TypeInfo)]
+pub struct PairInfo {
+    /// Position in the registry, in creation order.
+    pub index: u32,
+    pub token0: ActorId,
+    pub token1: ActorId,
+    /// Swap fee of the pair in basis points.
+    pub fee_tier: u64,
+    pub pair_address: ActorId,
+    /// Version of the pair code the pair was created from.
+    /// `0` for pairs registered with `add_pair`, whose code is unknown to the factory.
+    pub version: u32,
+    pub status: PairStatus,
+}
+
+/// Pairs in creation order, indexed by their sorted tokens and fee tier, and by each token.
+/// Only active pairs are indexed by their tokens and fee tier; migrated and deprecated
+/// pairs stay in the other indices.
+#[derive(Debug, Default)]
+pub struct PairRegistry {
+    records: Vec<PairInfo>,
+    by_tokens: HashMap<(ActorId, ActorId, u64), u32>,
+    by_address: HashMap<ActorId, u32>,
+    by_token: HashMap<ActorId, Vec<u32>>,
+    by_version: HashMap<u32, Vec<u32>>,
+}
+
+impl PairRegistry {
+    pub fn contains(&self, token0: ActorId, token1: ActorId, fee_tier: u64) -> bool {
+        self.by_tokens.contains_key(&(token0, token1, fee_tier))
+    }
+
+    /// Registers an active pair for sorted `(token0, token1)` and `fee_tier`.
+    /// Fails if a pair is already active for them.
+    pub fn insert(
+        &mut self,
+        token0: ActorId,
+        token1: ActorId,
+        fee_tier: u64,
+        pair_address: ActorId,
+        version: u32,
+    ) -> Result<(), &'static str> {
+        if self.contains(token0, token1, fee_tier) {
+            return Err("Pair exists");
+        }
+
+        let index = self.records.len() as u32;
+        self.records.push(PairInfo {
+            index,
+            token0,
+            token1,
+            fee_tier,
+            pair_address,
+            version,
+            status: PairStatus::Active,
+        });
+        self.by_tokens.insert((token0, token1, fee_tier), index);
+        self.by_address.insert(pair_address, index);
+        self.by_token.entry(token0).or_default().push(index);
+        self.by_token.entry(token1).or_default().push(index);
+        self.by_version.entry(version).or_default().push(index);
+        Ok(())
+    }
+
+    pub fn get(&self, token0: ActorId, token1: ActorId, fee_tier: u64) -> Option<&PairInfo> {
+        self.by_tokens
+            .get(&(token0, token1, fee_tier))
+            .map(|&index| &self.records[index as usize])
+    }
+
+    pub fn by_address(&self, pair_address: ActorId) -> Option<&PairInfo> {
+        self.by_address
+            .get(&pair_address)
+            .map(|&index| &self.records[index as usize])
+    }
+
+    /// Changes the status of the pair at `pair_address`.
+    /// Fails if the pair is unknown, or if it is re-activated while another pair
+    /// is active for the same tokens and fee tier.
+    pub fn set_status(
+        &mut self,
+        pair_address: ActorId,
+        status: PairStatus,
+    ) -> Result<(), &'static str> {
+        let index = *self
+            .by_address
+            .get(&pair_address)
+            .ok_or("Pair is not registered")?;
+        let record = &mut self.records[index as usize];
+        let key = (record.token0, record.token1, record.fee_tier);
+        let active = self.by_tokens.get(&key).copied();
+
+        if status == PairStatus::Active {
+            match active {
+                Some(i) if i != index => {
+                    return Err("Another pair is active for these tokens and fee tier");
+                }
+                _ => {
+                    self.by_tokens.insert(key, index);
+                }
+            }
+        } else if active == Some(index) {
+            self.by_tokens.remove(&key);
+        }
+        record.status = status;
+        Ok(())
+    }
+
+    pub fn len(&self) -> u32 {
+        self.records.len() as u32
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PairInfo> {
+        self.records.iter()
+    }
+
+    /// Addresses of the pairs that may still hold liquidity, i.e. not migrated.
+    pub fn live_addresses(&self) -> Vec<ActorId> {
+        self.records
+            .iter()
+            .filter(|r| r.status != PairStatus::Migrated)
+            .map(|r| r.pair_address)
+            .collect()
+    }
+
+    /// Returns at most [`MAX_PAGE_SIZE`] records starting at `offset`.
+    pub fn page(&self, offset: u32, limit: u32) -> Vec<PairInfo> {
+        self.records
+            .iter()
+            .skip(offset as usize)
+            .take(limit.min(MAX_PAGE_SIZE) as usize)
+            .cloned()
+            .collect()
+    }
+
+    pub fn for_token(&self, token: ActorId) -> Vec<PairInfo> {
+        self.by_token
+            .get(&token)
+            .map(|indices| self.collect(indices.iter()))
+            .unwrap_or_default()
+    }
+
+    /// Returns at most [`MAX_PAGE_SIZE`] records created from code `version`, starting at `offset`.
+    pub fn for_version(&self, version: u32, offset: u32, limit: u32) -> Vec<PairInfo> {
+        self.by_version
+            .get(&version)
+            .map(|indices| {
+                self.collect(
+                    indices
+                        .iter()
+                        .skip(offset as usize)
+                        .take(limit.min(MAX_PAGE_SIZE) as usize),
+                )
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn count_for_version(&self, version: u32) -> u32 {
+        self.by_version
+            .get(&version)
+            .map_or(0, |indices| indices.len() as u32)
+    }
+
+    fn collect<'a>(&self, indices: impl Iterator<Item = &'a u32>) -> Vec<PairInfo> {
+        indices
+            .map(|&index| self.records[index as usize].clone())
+            .collect()
+    }
+}
@@ -0,0 +1,88 @@
+use sails_rs::{collections::HashMap, prelude::*};
+
+/// Delivery status of a setting pushed from the factory to a pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Decode, Encode, TypeInfo)]
+pub enum SyncStatus {
+    /// The message was sent and the reply has not arrived yet.
+    Pending,
+    /// The pair applied the setting.
+    Synced,
+    /// The message could not be sent, or the pair replied with an error.
+    Failed,
+    /// The setting changed and is waiting for a `retry_*_sync` batch to be sent.
+    Queued,
+}
+
+/// Tracks the delivery of one setting (e.g. `treasury_id`) to every pair.
+#[derive(Debug, Default)]
+pub struct PairSync {
+    /// Latest delivery per pair, together with the id of the message carrying it.
+    deliveries: HashMap<ActorId, (SyncStatus, MessageId)>,
+    /// Messages waiting for a reply, mapped to the pair they were sent to.
+    awaiting: HashMap<MessageId, ActorId>,
+}
+
+impl PairSync {
+    pub fn sent(&mut self, pair: ActorId, msg_id: MessageId) {
+        self.replace(pair, (SyncStatus::Pending, msg_id));
+        self.awaiting.insert(msg_id, pair);
+    }
+
+    pub fn send_failed(&mut self, pair: ActorId) {
+        self.replace(pair, (SyncStatus::Failed, MessageId::zero()));
+    }
+
+    /// Marks `pair` as waiting for the current setting; a reply to an earlier delivery
+    /// is dropped.
+    pub fn queue(&mut self, pair: ActorId) {
+        self.replace(pair, (SyncStatus::Queued, MessageId::zero()));
+    }
+
+    /// Applies the reply to `reply_to`. Returns `false` if the message is not tracked here.
+    /// Replies to a message superseded by a newer delivery are dropped.
+    pub fn on_reply(&mut self, reply_to: MessageId, success: bool) -> bool {
+        let Some(pair) = self.awaiting.remove(&reply_to) else {
+            return false;
+        };
+        if let Some((status, msg_id)) = self.deliveries.get_mut(&pair)
+            && *msg_id == reply_to
+        {
+            *status = if success {
+                SyncStatus::Synced
+            } else {
+                SyncStatus::Failed
+            };
+        }
+        true
+    }
+
+    /// Pairs whose last delivery has not been confirmed.
+    pub fn out_of_sync(&self) -> Vec<(ActorId, SyncStatus)> {
+        self.deliveries
+            .iter()
+            .filter(|(_, (status, _))| *status != SyncStatus::Synced)
+            .map(|(pair, (status, _))| (*pair, *status))
+            .collect()
+    }
+
+    /// At most `limit` pairs the setting has to be sent to again: queued ones first,
+    /// then the ones whose delivery failed.
+    pub fn unsent(&self, limit: usize) -> Vec<ActorId> {
+        let with_status = |wanted: SyncStatus| {
+            self.deliveries
+                .iter()
+                .filter(move |(_, (status, _))| *status == wanted)
+                .map(|(pair, _)| *pair)
+        };
+        with_status(SyncStatus::Queued)
+            .chain(with_status(SyncStatus::Failed))
+            .take(limit)
+            .collect()
+    }
+
+    fn replace(&mut self, pair: ActorId, delivery: (SyncStatus, MessageId)) {
+        if let Some((_, old_msg_id)) = self.deliveries.insert(pair, delivery) {
+            self.awaiting.remove(&old_msg_id);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use crate::Config;
+use extended_vft_client::vft::io::{BalanceOf, Decimals};
+use sails_rs::client::CallCodec;
+use sails_rs::{gstd::exec, prelude::*};
+
+/// Who may call `create_pair`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Decode, Encode, TypeInfo)]
+pub enum CreationMode {
+    /// Anyone, with any tokens that are not denylisted.
+    #[default]
+    Permissionless,
+    /// Anyone, but both tokens must be allowlisted. The admin may use any token.
+    Allowlisted,
+    /// Only the admin.
+    AdminOnly,
+}
+
+async fn query(token: ActorId, payload: Vec<u8>, config: &Config) -> Option<Vec<u8>> {
+    sails_rs::gstd::msg::send_bytes_with_gas_for_reply(
+        token,
+        payload,
+        config.gas_for_token_ops,
+        0,
+        config.gas_for_reply_deposit,
+    )
+    .ok()?
+    .up_to(Some(config.reply_timeout))
+    .ok()?
+    .await
+    .ok()
+}
+
+/// Checks that `token` answers the VFT `Decimals` and `BalanceOf` queries.
+pub async fn is_vft(token: ActorId, config: &Config) -> bool {
+    let payload = Decimals::encode_params_with_prefix("Vft");
+    let Some(reply) = query(token, payload, config).await else {
+        return false;
+    };
+    if Decimals::decode_reply_with_prefix("Vft", &reply).is_err() {
+        return false;
+    }
+
+    let payload = BalanceOf::encode_params_with_prefix("Vft", exec::program_id());
+    let Some(reply) = query(token, payload, config).await else {
+        return false;
+    };
+    BalanceOf::decode_reply_with_prefix("Vft", &reply).is_ok()
+}
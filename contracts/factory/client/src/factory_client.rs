@@ -67,19 +67,179 @@ pub mod factory {
             &mut self,
             new_treasury_id: ActorId,
         ) -> sails_rs::client::PendingCall<io::ChangeTreasuryId, Self::Env>;
+        fn create_concentrated_pool(
+            &mut self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::CreateConcentratedPool, Self::Env>;
         fn create_pair(
             &mut self,
             token0: ActorId,
             token1: ActorId,
         ) -> sails_rs::client::PendingCall<io::CreatePair, Self::Env>;
+        fn create_pair_with_fee_tier(
+            &mut self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::CreatePairWithFeeTier, Self::Env>;
+        fn create_stable_pair(
+            &mut self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+            amp: u64,
+        ) -> sails_rs::client::PendingCall<io::CreateStablePair, Self::Env>;
+        fn mark_pair_migrated(
+            &mut self,
+        ) -> sails_rs::client::PendingCall<io::MarkPairMigrated, Self::Env>;
+        fn retry_fee_to_sync(
+            &mut self,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::RetryFeeToSync, Self::Env>;
+        fn retry_treasury_sync(
+            &mut self,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::RetryTreasurySync, Self::Env>;
+        fn set_concentrated_code_id(
+            &mut self,
+            code_id: CodeId,
+        ) -> sails_rs::client::PendingCall<io::SetConcentratedCodeId, Self::Env>;
+        fn set_creation_fee(
+            &mut self,
+            fee: u128,
+        ) -> sails_rs::client::PendingCall<io::SetCreationFee, Self::Env>;
+        fn set_creation_mode(
+            &mut self,
+            mode: CreationMode,
+        ) -> sails_rs::client::PendingCall<io::SetCreationMode, Self::Env>;
+        fn set_fee_tier_enabled(
+            &mut self,
+            fee_tier: u64,
+            enabled: bool,
+        ) -> sails_rs::client::PendingCall<io::SetFeeTierEnabled, Self::Env>;
+        fn set_pair_code_id(
+            &mut self,
+            code_id: CodeId,
+        ) -> sails_rs::client::PendingCall<io::SetPairCodeId, Self::Env>;
+        fn set_pair_status(
+            &mut self,
+            pair_address: ActorId,
+            status: PairStatus,
+        ) -> sails_rs::client::PendingCall<io::SetPairStatus, Self::Env>;
+        fn set_token_allowed(
+            &mut self,
+            token: ActorId,
+            allowed: bool,
+        ) -> sails_rs::client::PendingCall<io::SetTokenAllowed, Self::Env>;
+        fn set_token_denied(
+            &mut self,
+            token: ActorId,
+            denied: bool,
+        ) -> sails_rs::client::PendingCall<io::SetTokenDenied, Self::Env>;
+        fn set_verify_tokens(
+            &mut self,
+            enabled: bool,
+        ) -> sails_rs::client::PendingCall<io::SetVerifyTokens, Self::Env>;
+        fn withdraw_value(
+            &mut self,
+            to: ActorId,
+            amount: u128,
+        ) -> sails_rs::client::PendingCall<io::WithdrawValue, Self::Env>;
+        fn allowlist(&self) -> sails_rs::client::PendingCall<io::Allowlist, Self::Env>;
+        fn compute_pair_address(
+            &self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+            message_id: MessageId,
+        ) -> sails_rs::client::PendingCall<io::ComputePairAddress, Self::Env>;
+        fn concentrated_code_id(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::ConcentratedCodeId, Self::Env>;
+        fn concentrated_pools_count(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::ConcentratedPoolsCount, Self::Env>;
+        fn concentrated_pools_paginated(
+            &self,
+            offset: u32,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::ConcentratedPoolsPaginated, Self::Env>;
+        fn creation_fee(&self) -> sails_rs::client::PendingCall<io::CreationFee, Self::Env>;
+        fn creation_mode(&self) -> sails_rs::client::PendingCall<io::CreationMode, Self::Env>;
+        fn denylist(&self) -> sails_rs::client::PendingCall<io::Denylist, Self::Env>;
+        fn fee_tiers(&self) -> sails_rs::client::PendingCall<io::FeeTiers, Self::Env>;
         fn fee_to(&self) -> sails_rs::client::PendingCall<io::FeeTo, Self::Env>;
+        fn fee_to_out_of_sync(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::FeeToOutOfSync, Self::Env>;
+        fn get_concentrated_pool(
+            &self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::GetConcentratedPool, Self::Env>;
         fn get_pair(
             &self,
             token0: ActorId,
             token1: ActorId,
         ) -> sails_rs::client::PendingCall<io::GetPair, Self::Env>;
+        fn get_pair_with_fee_tier(
+            &self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::GetPairWithFeeTier, Self::Env>;
+        fn get_stable_pair(
+            &self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::GetStablePair, Self::Env>;
+        fn pair_code_by_version(
+            &self,
+            version: u32,
+        ) -> sails_rs::client::PendingCall<io::PairCodeByVersion, Self::Env>;
+        fn pair_code_id(&self) -> sails_rs::client::PendingCall<io::PairCodeId, Self::Env>;
+        fn pair_info(
+            &self,
+            pair_address: ActorId,
+        ) -> sails_rs::client::PendingCall<io::PairInfo, Self::Env>;
         fn pairs(&self) -> sails_rs::client::PendingCall<io::Pairs, Self::Env>;
+        fn pairs_by_version(
+            &self,
+            version: u32,
+            offset: u32,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::PairsByVersion, Self::Env>;
+        fn pairs_count(&self) -> sails_rs::client::PendingCall<io::PairsCount, Self::Env>;
+        fn pairs_count_by_version(
+            &self,
+            version: u32,
+        ) -> sails_rs::client::PendingCall<io::PairsCountByVersion, Self::Env>;
+        fn pairs_for_token(
+            &self,
+            token: ActorId,
+        ) -> sails_rs::client::PendingCall<io::PairsForToken, Self::Env>;
+        fn pairs_paginated(
+            &self,
+            offset: u32,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::PairsPaginated, Self::Env>;
+        fn stable_pairs_count(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::StablePairsCount, Self::Env>;
+        fn stable_pairs_paginated(
+            &self,
+            offset: u32,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::StablePairsPaginated, Self::Env>;
         fn treasury_id(&self) -> sails_rs::client::PendingCall<io::TreasuryId, Self::Env>;
+        fn treasury_out_of_sync(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::TreasuryOutOfSync, Self::Env>;
+        fn verify_tokens(&self) -> sails_rs::client::PendingCall<io::VerifyTokens, Self::Env>;
     }
     pub struct FactoryImpl;
     impl<E: sails_rs::client::GearEnv> Factory for sails_rs::client::Service<FactoryImpl, E> {
@@ -104,6 +264,14 @@ pub mod factory {
         ) -> sails_rs::client::PendingCall<io::ChangeTreasuryId, Self::Env> {
             self.pending_call((new_treasury_id,))
         }
+        fn create_concentrated_pool(
+            &mut self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::CreateConcentratedPool, Self::Env> {
+            self.pending_call((token0, token1, fee_tier))
+        }
         fn create_pair(
             &mut self,
             token0: ActorId,
@@ -111,9 +279,162 @@ pub mod factory {
         ) -> sails_rs::client::PendingCall<io::CreatePair, Self::Env> {
             self.pending_call((token0, token1))
         }
+        fn create_pair_with_fee_tier(
+            &mut self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::CreatePairWithFeeTier, Self::Env> {
+            self.pending_call((token0, token1, fee_tier))
+        }
+        fn create_stable_pair(
+            &mut self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+            amp: u64,
+        ) -> sails_rs::client::PendingCall<io::CreateStablePair, Self::Env> {
+            self.pending_call((token0, token1, fee_tier, amp))
+        }
+        fn mark_pair_migrated(
+            &mut self,
+        ) -> sails_rs::client::PendingCall<io::MarkPairMigrated, Self::Env> {
+            self.pending_call(())
+        }
+        fn retry_fee_to_sync(
+            &mut self,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::RetryFeeToSync, Self::Env> {
+            self.pending_call((limit,))
+        }
+        fn retry_treasury_sync(
+            &mut self,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::RetryTreasurySync, Self::Env> {
+            self.pending_call((limit,))
+        }
+        fn set_concentrated_code_id(
+            &mut self,
+            code_id: CodeId,
+        ) -> sails_rs::client::PendingCall<io::SetConcentratedCodeId, Self::Env> {
+            self.pending_call((code_id,))
+        }
+        fn set_creation_fee(
+            &mut self,
+            fee: u128,
+        ) -> sails_rs::client::PendingCall<io::SetCreationFee, Self::Env> {
+            self.pending_call((fee,))
+        }
+        fn set_creation_mode(
+            &mut self,
+            mode: CreationMode,
+        ) -> sails_rs::client::PendingCall<io::SetCreationMode, Self::Env> {
+            self.pending_call((mode,))
+        }
+        fn set_fee_tier_enabled(
+            &mut self,
+            fee_tier: u64,
+            enabled: bool,
+        ) -> sails_rs::client::PendingCall<io::SetFeeTierEnabled, Self::Env> {
+            self.pending_call((fee_tier, enabled))
+        }
+        fn set_pair_code_id(
+            &mut self,
+            code_id: CodeId,
+        ) -> sails_rs::client::PendingCall<io::SetPairCodeId, Self::Env> {
+            self.pending_call((code_id,))
+        }
+        fn set_pair_status(
+            &mut self,
+            pair_address: ActorId,
+            status: PairStatus,
+        ) -> sails_rs::client::PendingCall<io::SetPairStatus, Self::Env> {
+            self.pending_call((pair_address, status))
+        }
+        fn set_token_allowed(
+            &mut self,
+            token: ActorId,
+            allowed: bool,
+        ) -> sails_rs::client::PendingCall<io::SetTokenAllowed, Self::Env> {
+            self.pending_call((token, allowed))
+        }
+        fn set_token_denied(
+            &mut self,
+            token: ActorId,
+            denied: bool,
+        ) -> sails_rs::client::PendingCall<io::SetTokenDenied, Self::Env> {
+            self.pending_call((token, denied))
+        }
+        fn set_verify_tokens(
+            &mut self,
+            enabled: bool,
+        ) -> sails_rs::client::PendingCall<io::SetVerifyTokens, Self::Env> {
+            self.pending_call((enabled,))
+        }
+        fn withdraw_value(
+            &mut self,
+            to: ActorId,
+            amount: u128,
+        ) -> sails_rs::client::PendingCall<io::WithdrawValue, Self::Env> {
+            self.pending_call((to, amount))
+        }
+        fn allowlist(&self) -> sails_rs::client::PendingCall<io::Allowlist, Self::Env> {
+            self.pending_call(())
+        }
+        fn compute_pair_address(
+            &self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+            message_id: MessageId,
+        ) -> sails_rs::client::PendingCall<io::ComputePairAddress, Self::Env> {
+            self.pending_call((token0, token1, fee_tier, message_id))
+        }
+        fn concentrated_code_id(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::ConcentratedCodeId, Self::Env> {
+            self.pending_call(())
+        }
+        fn concentrated_pools_count(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::ConcentratedPoolsCount, Self::Env> {
+            self.pending_call(())
+        }
+        fn concentrated_pools_paginated(
+            &self,
+            offset: u32,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::ConcentratedPoolsPaginated, Self::Env> {
+            self.pending_call((offset, limit))
+        }
+        fn creation_fee(&self) -> sails_rs::client::PendingCall<io::CreationFee, Self::Env> {
+            self.pending_call(())
+        }
+        fn creation_mode(&self) -> sails_rs::client::PendingCall<io::CreationMode, Self::Env> {
+            self.pending_call(())
+        }
+        fn denylist(&self) -> sails_rs::client::PendingCall<io::Denylist, Self::Env> {
+            self.pending_call(())
+        }
+        fn fee_tiers(&self) -> sails_rs::client::PendingCall<io::FeeTiers, Self::Env> {
+            self.pending_call(())
+        }
         fn fee_to(&self) -> sails_rs::client::PendingCall<io::FeeTo, Self::Env> {
             self.pending_call(())
         }
+        fn fee_to_out_of_sync(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::FeeToOutOfSync, Self::Env> {
+            self.pending_call(())
+        }
+        fn get_concentrated_pool(
+            &self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::GetConcentratedPool, Self::Env> {
+            self.pending_call((token0, token1, fee_tier))
+        }
         fn get_pair(
             &self,
             token0: ActorId,
@@ -121,12 +442,93 @@ pub mod factory {
         ) -> sails_rs::client::PendingCall<io::GetPair, Self::Env> {
             self.pending_call((token0, token1))
         }
+        fn get_pair_with_fee_tier(
+            &self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::GetPairWithFeeTier, Self::Env> {
+            self.pending_call((token0, token1, fee_tier))
+        }
+        fn get_stable_pair(
+            &self,
+            token0: ActorId,
+            token1: ActorId,
+            fee_tier: u64,
+        ) -> sails_rs::client::PendingCall<io::GetStablePair, Self::Env> {
+            self.pending_call((token0, token1, fee_tier))
+        }
+        fn pair_code_by_version(
+            &self,
+            version: u32,
+        ) -> sails_rs::client::PendingCall<io::PairCodeByVersion, Self::Env> {
+            self.pending_call((version,))
+        }
+        fn pair_code_id(&self) -> sails_rs::client::PendingCall<io::PairCodeId, Self::Env> {
+            self.pending_call(())
+        }
+        fn pair_info(
+            &self,
+            pair_address: ActorId,
+        ) -> sails_rs::client::PendingCall<io::PairInfo, Self::Env> {
+            self.pending_call((pair_address,))
+        }
         fn pairs(&self) -> sails_rs::client::PendingCall<io::Pairs, Self::Env> {
             self.pending_call(())
         }
+        fn pairs_by_version(
+            &self,
+            version: u32,
+            offset: u32,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::PairsByVersion, Self::Env> {
+            self.pending_call((version, offset, limit))
+        }
+        fn pairs_count(&self) -> sails_rs::client::PendingCall<io::PairsCount, Self::Env> {
+            self.pending_call(())
+        }
+        fn pairs_count_by_version(
+            &self,
+            version: u32,
+        ) -> sails_rs::client::PendingCall<io::PairsCountByVersion, Self::Env> {
+            self.pending_call((version,))
+        }
+        fn pairs_for_token(
+            &self,
+            token: ActorId,
+        ) -> sails_rs::client::PendingCall<io::PairsForToken, Self::Env> {
+            self.pending_call((token,))
+        }
+        fn pairs_paginated(
+            &self,
+            offset: u32,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::PairsPaginated, Self::Env> {
+            self.pending_call((offset, limit))
+        }
+        fn stable_pairs_count(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::StablePairsCount, Self::Env> {
+            self.pending_call(())
+        }
+        fn stable_pairs_paginated(
+            &self,
+            offset: u32,
+            limit: u32,
+        ) -> sails_rs::client::PendingCall<io::StablePairsPaginated, Self::Env> {
+            self.pending_call((offset, limit))
+        }
         fn treasury_id(&self) -> sails_rs::client::PendingCall<io::TreasuryId, Self::Env> {
             self.pending_call(())
         }
+        fn treasury_out_of_sync(
+            &self,
+        ) -> sails_rs::client::PendingCall<io::TreasuryOutOfSync, Self::Env> {
+            self.pending_call(())
+        }
+        fn verify_tokens(&self) -> sails_rs::client::PendingCall<io::VerifyTokens, Self::Env> {
+            self.pending_call(())
+        }
     }
 
     pub mod io {
@@ -134,11 +536,52 @@ pub mod factory {
         sails_rs::io_struct_impl!(AddPair (token0: ActorId, token1: ActorId, pair_address: ActorId) -> ());
         sails_rs::io_struct_impl!(ChangeFeeTo (fee_to: ActorId) -> ());
         sails_rs::io_struct_impl!(ChangeTreasuryId (new_treasury_id: ActorId) -> ());
+        sails_rs::io_struct_impl!(CreateConcentratedPool (token0: ActorId, token1: ActorId, fee_tier: u64) -> ());
         sails_rs::io_struct_impl!(CreatePair (token0: ActorId, token1: ActorId) -> ());
+        sails_rs::io_struct_impl!(CreatePairWithFeeTier (token0: ActorId, token1: ActorId, fee_tier: u64) -> ());
+        sails_rs::io_struct_impl!(CreateStablePair (token0: ActorId, token1: ActorId, fee_tier: u64, amp: u64) -> ());
+        sails_rs::io_struct_impl!(MarkPairMigrated () -> ());
+        sails_rs::io_struct_impl!(RetryFeeToSync (limit: u32) -> ());
+        sails_rs::io_struct_impl!(RetryTreasurySync (limit: u32) -> ());
+        sails_rs::io_struct_impl!(SetConcentratedCodeId (code_id: CodeId) -> ());
+        sails_rs::io_struct_impl!(SetCreationFee (fee: u128) -> ());
+        sails_rs::io_struct_impl!(SetCreationMode (mode: super::CreationMode) -> ());
+        sails_rs::io_struct_impl!(SetFeeTierEnabled (fee_tier: u64, enabled: bool) -> ());
+        sails_rs::io_struct_impl!(SetPairCodeId (code_id: CodeId) -> ());
+        sails_rs::io_struct_impl!(SetPairStatus (pair_address: ActorId, status: super::PairStatus) -> ());
+        sails_rs::io_struct_impl!(SetTokenAllowed (token: ActorId, allowed: bool) -> ());
+        sails_rs::io_struct_impl!(SetTokenDenied (token: ActorId, denied: bool) -> ());
+        sails_rs::io_struct_impl!(SetVerifyTokens (enabled: bool) -> ());
+        sails_rs::io_struct_impl!(WithdrawValue (to: ActorId, amount: u128) -> ());
+        sails_rs::io_struct_impl!(Allowlist () -> Vec<ActorId>);
+        sails_rs::io_struct_impl!(ComputePairAddress (token0: ActorId, token1: ActorId, fee_tier: u64, message_id: MessageId) -> ActorId);
+        sails_rs::io_struct_impl!(ConcentratedCodeId () -> (CodeId,u32,));
+        sails_rs::io_struct_impl!(ConcentratedPoolsCount () -> u32);
+        sails_rs::io_struct_impl!(ConcentratedPoolsPaginated (offset: u32, limit: u32) -> Vec<super::PairInfo>);
+        sails_rs::io_struct_impl!(CreationFee () -> u128);
+        sails_rs::io_struct_impl!(CreationMode () -> super::CreationMode);
+        sails_rs::io_struct_impl!(Denylist () -> Vec<ActorId>);
+        sails_rs::io_struct_impl!(FeeTiers () -> Vec<u64>);
         sails_rs::io_struct_impl!(FeeTo () -> ActorId);
+        sails_rs::io_struct_impl!(FeeToOutOfSync () -> Vec<(ActorId,super::SyncStatus,)>);
+        sails_rs::io_struct_impl!(GetConcentratedPool (token0: ActorId, token1: ActorId, fee_tier: u64) -> ActorId);
         sails_rs::io_struct_impl!(GetPair (token0: ActorId, token1: ActorId) -> ActorId);
+        sails_rs::io_struct_impl!(GetPairWithFeeTier (token0: ActorId, token1: ActorId, fee_tier: u64) -> ActorId);
+        sails_rs::io_struct_impl!(GetStablePair (token0: ActorId, token1: ActorId, fee_tier: u64) -> ActorId);
+        sails_rs::io_struct_impl!(PairCodeByVersion (version: u32) -> Option<CodeId>);
+        sails_rs::io_struct_impl!(PairCodeId () -> (CodeId,u32,));
+        sails_rs::io_struct_impl!(PairInfo (pair_address: ActorId) -> Option<super::PairInfo>);
         sails_rs::io_struct_impl!(Pairs () -> Vec<((ActorId,ActorId,),ActorId,)>);
+        sails_rs::io_struct_impl!(PairsByVersion (version: u32, offset: u32, limit: u32) -> Vec<super::PairInfo>);
+        sails_rs::io_struct_impl!(PairsCount () -> u32);
+        sails_rs::io_struct_impl!(PairsCountByVersion (version: u32) -> u32);
+        sails_rs::io_struct_impl!(PairsForToken (token: ActorId) -> Vec<super::PairInfo>);
+        sails_rs::io_struct_impl!(PairsPaginated (offset: u32, limit: u32) -> Vec<super::PairInfo>);
+        sails_rs::io_struct_impl!(StablePairsCount () -> u32);
+        sails_rs::io_struct_impl!(StablePairsPaginated (offset: u32, limit: u32) -> Vec<super::PairInfo>);
         sails_rs::io_struct_impl!(TreasuryId () -> ActorId);
+        sails_rs::io_struct_impl!(TreasuryOutOfSync () -> Vec<(ActorId,super::SyncStatus,)>);
+        sails_rs::io_struct_impl!(VerifyTokens () -> bool);
     }
 
     #[cfg(not(target_arch = "wasm32"))]
@@ -151,10 +594,82 @@ pub mod factory {
                 token0: ActorId,
                 token1: ActorId,
                 pair_address: ActorId,
+                fee_tier: u64,
+            },
+            PairCodeIdChanged {
+                code_id: CodeId,
+                version: u32,
+            },
+            PairCreationFailed {
+                token0: ActorId,
+                token1: ActorId,
+                refunded: u128,
+            },
+            CreationFeeChanged {
+                fee: u128,
+            },
+            ValueWithdrawn {
+                to: ActorId,
+                amount: u128,
+            },
+            CreationModeChanged {
+                mode: super::CreationMode,
+            },
+            TokenAllowlistUpdated {
+                token: ActorId,
+                allowed: bool,
+            },
+            TokenDenylistUpdated {
+                token: ActorId,
+                denied: bool,
+            },
+            TokenVerificationChanged {
+                enabled: bool,
+            },
+            PairStatusChanged {
+                pair_address: ActorId,
+                status: super::PairStatus,
+            },
+            FeeTierUpdated {
+                fee_tier: u64,
+                enabled: bool,
+            },
+            StablePairCreated {
+                token0: ActorId,
+                token1: ActorId,
+                fee_tier: u64,
+                amp: u64,
+                pair_address: ActorId,
+            },
+            ConcentratedPoolCreated {
+                token0: ActorId,
+                token1: ActorId,
+                fee_tier: u64,
+                tick_spacing: i32,
+                pool_address: ActorId,
+            },
+            ConcentratedCodeIdChanged {
+                code_id: CodeId,
+                version: u32,
             },
         }
         impl sails_rs::client::Event for FactoryEvents {
-            const EVENT_NAMES: &'static [Route] = &["PairCreated"];
+            const EVENT_NAMES: &'static [Route] = &[
+                "PairCreated",
+                "PairCodeIdChanged",
+                "PairCreationFailed",
+                "CreationFeeChanged",
+                "ValueWithdrawn",
+                "CreationModeChanged",
+                "TokenAllowlistUpdated",
+                "TokenDenylistUpdated",
+                "TokenVerificationChanged",
+                "PairStatusChanged",
+                "FeeTierUpdated",
+                "StablePairCreated",
+                "ConcentratedPoolCreated",
+                "ConcentratedCodeIdChanged",
+            ];
         }
         impl sails_rs::client::ServiceWithEvents for FactoryImpl {
             type Event = FactoryEvents;
@@ -178,5 +693,60 @@ pub struct Config {
     pub reply_timeout: u32,
     pub gas_for_full_tx: u64,
     pub gas_for_pair_creation: u64,
+    /// Gas limit for settings pushed to the pairs (`ChangeFeeTo`, `ChangeTreasuryId`).
     pub gas_to_change_fee_to: u64,
 }
+#[derive(PartialEq, Clone, Debug, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum SyncStatus {
+    /// The message was sent and the reply has not arrived yet.
+    Pending,
+    /// The pair applied the setting.
+    Synced,
+    /// The message could not be sent, or the pair replied with an error.
+    Failed,
+    /// The setting changed and is waiting for a `retry_*_sync` batch to be sent.
+    Queued,
+}
+/// A pair registered in the factory.
+#[derive(PartialEq, Clone, Debug, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub struct PairInfo {
+    /// Position in the registry, in creation order.
+    pub index: u32,
+    pub token0: ActorId,
+    pub token1: ActorId,
+    /// Swap fee of the pair in basis points.
+    pub fee_tier: u64,
+    pub pair_address: ActorId,
+    /// Version of the pair code the pair was created from.
+    /// `0` for pairs registered with `add_pair`, whose code is unknown to the factory.
+    pub version: u32,
+    pub status: PairStatus,
+}
+/// Lifecycle of a registered pair.
+#[derive(PartialEq, Clone, Debug, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum PairStatus {
+    /// The pair `get_pair` resolves to for its tokens and fee tier.
+    Active,
+    /// The pair moved its liquidity out; a new pair may be created for its tokens and fee tier.
+    Migrated,
+    /// Retired by the admin; a new pair may be created for its tokens and fee tier.
+    Deprecated,
+}
+/// Who may call `create_pair`.
+#[derive(PartialEq, Clone, Debug, Encode, Decode, TypeInfo)]
+#[codec(crate = sails_rs::scale_codec)]
+#[scale_info(crate = sails_rs::scale_info)]
+pub enum CreationMode {
+    /// Anyone, with any tokens that are not denylisted.
+    Permissionless,
+    /// Anyone, but both tokens must be allowlisted. The admin may use any token.
+    Allowlisted,
+    /// Only the admin.
+    AdminOnly,
+}
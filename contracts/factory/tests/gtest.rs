@@ -1,5 +1,9 @@
-use factory_app::ONE_VARA;
-use factory_client::{factory::*, FactoryClient, FactoryClientCtors};
+use concentrated_client::{pool::Pool, Concentrated as ConcentratedClient, ConcentratedProgram};
+use factory_app::{ONE_VARA, PUSH_BATCH_SIZE};
+use factory_client::{
+    factory::*, CreationMode, FactoryClient, FactoryClientCtors, PairInfo, PairStatus, SyncStatus,
+};
+use pair_client::{pair::Pair, Pair as PairClient, PairProgram};
 use sails_rs::gtest::System;
 use sails_rs::{client::*, prelude::*};
 
@@ -132,6 +136,32 @@ async fn factory_create_pair_rejects_duplicates() {
     assert_eq!(pairs.len(), 1);
 }
 
+#[tokio::test]
+async fn factory_registers_one_of_concurrent_creations() {
+    let (env, factory, _) = deploy_factory().await;
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(user, ONE_VARA * 1000);
+    let token0 = ActorId::from(10u64);
+    let token1 = ActorId::from(11u64);
+
+    // Both are checked before either pair is created
+    let program = env.system().get_program(factory.actor_id()).unwrap();
+    let payload = io::CreatePair::encode_params_with_prefix("Factory", token0, token1);
+    let factory_balance = env.system().balance_of(factory.actor_id());
+    program.send_bytes_with_value(user, payload.clone(), ONE_VARA);
+    program.send_bytes_with_value(user, payload, ONE_VARA);
+    env.system().run_next_block();
+
+    let pairs = factory.pairs().await.unwrap();
+    assert_eq!(pairs.len(), 1);
+    assert_eq!(factory.get_pair(token0, token1).await.unwrap(), pairs[0].1);
+    // The fee of the creation that came second is refunded
+    assert_eq!(
+        env.system().balance_of(factory.actor_id()),
+        factory_balance + ONE_VARA
+    );
+}
+
 #[tokio::test]
 async fn factory_add_pair_admin_only_and_sorts_key() {
     let (env, mut factory, _) = deploy_factory().await;
@@ -228,3 +258,832 @@ async fn factory_change_treasury_id_admin_only() {
 
     assert_eq!(factory.treasury_id().await.unwrap(), new_treasury);
 }
+
+#[tokio::test]
+async fn factory_change_treasury_id_propagates_to_pairs() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+
+    let mut pair_ids = Vec::new();
+    for (token0, token1) in [(10u64, 11u64), (12, 13)] {
+        let (token0, token1) = (ActorId::from(token0), ActorId::from(token1));
+        factory
+            .create_pair(token0, token1)
+            .with_params(|p| p.with_actor_id(admin).with_value(ONE_VARA))
+            .await
+            .unwrap();
+        pair_ids.push(factory.get_pair(token0, token1).await.unwrap());
+    }
+    // The factory itself does not serve `Pair` messages, so delivery to it fails
+    let broken_pair = factory.actor_id();
+    factory
+        .add_pair(ActorId::from(14u64), ActorId::from(15u64), broken_pair)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    let new_treasury = ActorId::from(888u64);
+    factory
+        .change_treasury_id(new_treasury)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    for pair_id in pair_ids {
+        let pair = Actor::<PairProgram, GtestEnv>::new(env.clone(), pair_id).pair();
+        let (treasury_id, _, _) = pair.get_treasury_info().await.unwrap();
+        assert_eq!(treasury_id, new_treasury);
+    }
+    assert_eq!(
+        factory.treasury_out_of_sync().await.unwrap(),
+        vec![(broken_pair, SyncStatus::Failed)]
+    );
+
+    // retry is admin only and keeps reporting the pair until it accepts the update
+    let res = factory
+        .retry_treasury_sync(10)
+        .with_params(|p| p.with_actor_id(ActorId::from(USER_ID)))
+        .await;
+    assert!(res.is_err());
+    factory
+        .retry_treasury_sync(10)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(
+        factory.treasury_out_of_sync().await.unwrap(),
+        vec![(broken_pair, SyncStatus::Failed)]
+    );
+}
+
+#[tokio::test]
+async fn factory_change_fee_to_tracks_delivery_per_pair() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+
+    let token0 = ActorId::from(10u64);
+    let token1 = ActorId::from(11u64);
+    factory
+        .create_pair(token0, token1)
+        .with_params(|p| p.with_actor_id(admin).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    let pair_id = factory.get_pair(token0, token1).await.unwrap();
+    // The factory itself does not serve `Pair` messages, so delivery to it fails
+    let broken_pair = factory.actor_id();
+    factory
+        .add_pair(ActorId::from(14u64), ActorId::from(15u64), broken_pair)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    let new_fee_to = ActorId::from(999u64);
+    factory
+        .change_fee_to(new_fee_to)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    let pair = Actor::<PairProgram, GtestEnv>::new(env.clone(), pair_id).pair();
+    assert_eq!(pair.fee_to().await.unwrap(), new_fee_to);
+    assert_eq!(
+        factory.fee_to_out_of_sync().await.unwrap(),
+        vec![(broken_pair, SyncStatus::Failed)]
+    );
+
+    let res = factory
+        .retry_fee_to_sync(10)
+        .with_params(|p| p.with_actor_id(ActorId::from(USER_ID)))
+        .await;
+    assert!(res.is_err());
+    factory
+        .retry_fee_to_sync(0)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(
+        factory.fee_to_out_of_sync().await.unwrap(),
+        vec![(broken_pair, SyncStatus::Failed)]
+    );
+    // treasury deliveries are tracked separately
+    assert!(factory.treasury_out_of_sync().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn factory_change_fee_to_pushes_in_batches() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+
+    let hub = ActorId::from(10u64);
+    let pairs = PUSH_BATCH_SIZE as u64 + 3;
+    for i in 0..pairs {
+        factory
+            .add_pair(ActorId::from(1_000 + i), hub, ActorId::from(2_000 + i))
+            .with_params(|p| p.with_actor_id(admin))
+            .await
+            .unwrap();
+    }
+    let queued = |out_of_sync: Vec<(ActorId, SyncStatus)>| {
+        out_of_sync
+            .into_iter()
+            .filter(|(_, status)| *status == SyncStatus::Queued)
+            .count()
+    };
+
+    factory
+        .change_fee_to(ActorId::from(999u64))
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    let out_of_sync = factory.fee_to_out_of_sync().await.unwrap();
+    assert_eq!(out_of_sync.len() as u64, pairs);
+    assert_eq!(queued(out_of_sync), 3);
+
+    // the queued pairs are sent with the retries
+    factory
+        .retry_fee_to_sync(2)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(queued(factory.fee_to_out_of_sync().await.unwrap()), 1);
+    factory
+        .retry_fee_to_sync(PUSH_BATCH_SIZE)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(queued(factory.fee_to_out_of_sync().await.unwrap()), 0);
+}
+
+#[tokio::test]
+async fn factory_registry_paginates_and_indexes_by_token() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+
+    let hub = ActorId::from(10u64);
+    let others: Vec<ActorId> = (20u64..25).map(ActorId::from).collect();
+    for (i, &token) in others.iter().enumerate() {
+        factory
+            .add_pair(token, hub, ActorId::from(700 + i as u64))
+            .with_params(|p| p.with_actor_id(admin))
+            .await
+            .unwrap();
+    }
+    factory
+        .add_pair(others[0], others[1], ActorId::from(800u64))
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    assert_eq!(factory.pairs_count().await.unwrap(), 6);
+
+    let page = factory.pairs_paginated(2, 2).await.unwrap();
+    assert_eq!(
+        page,
+        vec![
+            PairInfo {
+                index: 2,
+                token0: hub,
+                token1: others[2],
+                fee_tier: 30,
+                pair_address: ActorId::from(702u64),
+                version: 0,
+                status: PairStatus::Active,
+            },
+            PairInfo {
+                index: 3,
+                token0: hub,
+                token1: others[3],
+                fee_tier: 30,
+                pair_address: ActorId::from(703u64),
+                version: 0,
+                status: PairStatus::Active,
+            },
+        ]
+    );
+    assert!(factory.pairs_paginated(6, 10).await.unwrap().is_empty());
+
+    let hub_pairs = factory.pairs_for_token(hub).await.unwrap();
+    assert_eq!(hub_pairs.len(), 5);
+    assert!(hub_pairs.iter().all(|p| p.token0 == hub));
+
+    let indices: Vec<u32> = factory
+        .pairs_for_token(others[1])
+        .await
+        .unwrap()
+        .iter()
+        .map(|p| p.index)
+        .collect();
+    assert_eq!(indices, vec![1, 5]);
+    assert!(factory
+        .pairs_for_token(ActorId::from(99u64))
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn factory_set_pair_code_id_bumps_version() {
+    let (env, mut factory, pair_code_id) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+    env.system().mint_to(user, ONE_VARA * 1000);
+
+    assert_eq!(factory.pair_code_id().await.unwrap(), (pair_code_id, 1));
+
+    let token0 = ActorId::from(10u64);
+    let token1 = ActorId::from(11u64);
+    factory
+        .create_pair(token0, token1)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    let pair_v1 = factory.get_pair(token0, token1).await.unwrap();
+    factory
+        .add_pair(
+            ActorId::from(12u64),
+            ActorId::from(13u64),
+            ActorId::from(777u64),
+        )
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    // Any other code will do, the factory does not inspect it
+    let new_code_id = env.system().submit_code(factory::WASM_BINARY);
+    let res = factory
+        .set_pair_code_id(new_code_id)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    factory
+        .set_pair_code_id(new_code_id)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    assert_eq!(factory.pair_code_id().await.unwrap(), (new_code_id, 2));
+    assert_eq!(
+        factory.pair_code_by_version(1).await.unwrap(),
+        Some(pair_code_id)
+    );
+    assert_eq!(
+        factory.pair_code_by_version(2).await.unwrap(),
+        Some(new_code_id)
+    );
+    assert_eq!(factory.pair_code_by_version(0).await.unwrap(), None);
+
+    let v1 = factory.pairs_by_version(1, 0, 10).await.unwrap();
+    assert_eq!(v1.len(), 1);
+    assert_eq!(v1[0].pair_address, pair_v1);
+    assert_eq!(factory.pairs_count_by_version(0).await.unwrap(), 1);
+    assert_eq!(factory.pairs_count_by_version(2).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn factory_creation_fee_is_configurable() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+    env.system().mint_to(user, ONE_VARA * 1000);
+
+    assert_eq!(factory.creation_fee().await.unwrap(), ONE_VARA);
+
+    let res = factory
+        .set_creation_fee(0)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    factory
+        .set_creation_fee(0)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(factory.creation_fee().await.unwrap(), 0);
+
+    let token0 = ActorId::from(10u64);
+    let token1 = ActorId::from(11u64);
+    // the old fee is no longer accepted
+    let res = factory
+        .create_pair(token0, token1)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await;
+    assert!(res.is_err());
+
+    factory
+        .create_pair(token0, token1)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert!(!factory.get_pair(token0, token1).await.unwrap().is_zero());
+}
+
+#[tokio::test]
+async fn factory_withdraw_value_admin_only() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    let user: ActorId = ActorId::from(USER_ID);
+    let recipient: ActorId = ActorId::from(555u64);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+    env.system().mint_to(user, ONE_VARA * 1000);
+
+    factory
+        .create_pair(ActorId::from(10u64), ActorId::from(11u64))
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+
+    let res = factory
+        .withdraw_value(recipient, ONE_VARA)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    // more than the factory holds
+    let res = factory
+        .withdraw_value(recipient, ONE_VARA * 1000)
+        .with_params(|p| p.with_actor_id(admin))
+        .await;
+    assert!(res.is_err());
+
+    let before = env.system().balance_of(recipient);
+    factory
+        .withdraw_value(recipient, ONE_VARA)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(env.system().balance_of(recipient), before + ONE_VARA);
+}
+
+#[tokio::test]
+async fn factory_refunds_creation_fee_when_pair_init_fails() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+    env.system().mint_to(user, ONE_VARA * 1000);
+
+    // The factory code can't be initialized with the pair constructor payload
+    let broken_code_id = env.system().submit_code(factory::WASM_BINARY);
+    factory
+        .set_pair_code_id(broken_code_id)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    let factory_balance = env.system().balance_of(factory.actor_id());
+    factory
+        .create_pair(ActorId::from(10u64), ActorId::from(11u64))
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+
+    assert_eq!(env.system().balance_of(factory.actor_id()), factory_balance);
+    assert_eq!(factory.pairs_count().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn factory_creation_modes_and_token_lists() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+    env.system().mint_to(user, ONE_VARA * 1000);
+    let (token_a, token_b, token_c) = (
+        ActorId::from(10u64),
+        ActorId::from(11u64),
+        ActorId::from(12u64),
+    );
+
+    // denylisted tokens are rejected for everyone
+    factory
+        .set_token_denied(token_c, true)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(factory.denylist().await.unwrap(), vec![token_c]);
+    for caller in [user, admin] {
+        let res = factory
+            .create_pair(token_a, token_c)
+            .with_params(|p| p.with_actor_id(caller).with_value(ONE_VARA))
+            .await;
+        assert!(res.is_err());
+    }
+
+    // admin only
+    let res = factory
+        .set_creation_mode(CreationMode::AdminOnly)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    factory
+        .set_creation_mode(CreationMode::AdminOnly)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(
+        factory.creation_mode().await.unwrap(),
+        CreationMode::AdminOnly
+    );
+    let res = factory
+        .create_pair(token_a, token_b)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await;
+    assert!(res.is_err());
+
+    // allowlisted: both tokens must be on the list
+    factory
+        .set_creation_mode(CreationMode::Allowlisted)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    factory
+        .set_token_allowed(token_a, true)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    let res = factory
+        .create_pair(token_a, token_b)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await;
+    assert!(res.is_err());
+
+    factory
+        .set_token_allowed(token_b, true)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(factory.allowlist().await.unwrap().len(), 2);
+    factory
+        .create_pair(token_a, token_b)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    assert_eq!(factory.pairs_count().await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn factory_verifies_tokens_when_enabled() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+    env.system().mint_to(user, ONE_VARA * 1000);
+
+    // LP tokens of existing pairs are VFT programs
+    let mut lp_tokens = Vec::new();
+    for (token0, token1) in [(10u64, 11u64), (12, 13)] {
+        let (token0, token1) = (ActorId::from(token0), ActorId::from(token1));
+        factory
+            .create_pair(token0, token1)
+            .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+            .await
+            .unwrap();
+        lp_tokens.push(factory.get_pair(token0, token1).await.unwrap());
+    }
+
+    factory
+        .set_verify_tokens(true)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert!(factory.verify_tokens().await.unwrap());
+
+    // The factory does not answer VFT queries
+    let factory_balance = env.system().balance_of(factory.actor_id());
+    factory
+        .create_pair(lp_tokens[0], factory.actor_id())
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    assert_eq!(env.system().balance_of(factory.actor_id()), factory_balance);
+    assert!(factory
+        .get_pair(lp_tokens[0], factory.actor_id())
+        .await
+        .unwrap()
+        .is_zero());
+
+    factory
+        .create_pair(lp_tokens[0], lp_tokens[1])
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    assert!(!factory
+        .get_pair(lp_tokens[0], lp_tokens[1])
+        .await
+        .unwrap()
+        .is_zero());
+}
+
+#[tokio::test]
+async fn factory_replaces_retired_pair() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(admin, ONE_VARA * 1000);
+    env.system().mint_to(user, ONE_VARA * 1000);
+    let token0 = ActorId::from(10u64);
+    let token1 = ActorId::from(11u64);
+
+    factory
+        .create_pair(token0, token1)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    let old_pair = factory.get_pair(token0, token1).await.unwrap();
+
+    // only registered pairs can report their own migration
+    let res = factory
+        .mark_pair_migrated()
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    let res = factory
+        .set_pair_status(old_pair, PairStatus::Deprecated)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    factory
+        .set_pair_status(old_pair, PairStatus::Deprecated)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert!(factory.get_pair(token0, token1).await.unwrap().is_zero());
+    assert!(factory.pairs().await.unwrap().is_empty());
+
+    factory
+        .create_pair(token0, token1)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    let new_pair = factory.get_pair(token1, token0).await.unwrap();
+    assert!(!new_pair.is_zero());
+    assert_ne!(new_pair, old_pair);
+
+    let old_info = factory.pair_info(old_pair).await.unwrap().unwrap();
+    assert_eq!(old_info.status, PairStatus::Deprecated);
+    assert_eq!(factory.pairs_for_token(token0).await.unwrap().len(), 2);
+
+    // the old pair can't be re-activated while the new one is active
+    let res = factory
+        .set_pair_status(old_pair, PairStatus::Active)
+        .with_params(|p| p.with_actor_id(admin))
+        .await;
+    assert!(res.is_err());
+}
+
+#[tokio::test]
+async fn factory_creates_one_pair_per_fee_tier() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(user, ONE_VARA * 1000);
+    let token0 = ActorId::from(10u64);
+    let token1 = ActorId::from(11u64);
+
+    assert_eq!(factory.fee_tiers().await.unwrap(), vec![5, 30, 100]);
+
+    for fee_tier in [5, 100] {
+        factory
+            .create_pair_with_fee_tier(token1, token0, fee_tier)
+            .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+            .await
+            .unwrap();
+    }
+    let stable = factory
+        .get_pair_with_fee_tier(token0, token1, 5)
+        .await
+        .unwrap();
+    let exotic = factory
+        .get_pair_with_fee_tier(token0, token1, 100)
+        .await
+        .unwrap();
+    assert!(!stable.is_zero() && !exotic.is_zero());
+    assert_ne!(stable, exotic);
+    // `get_pair` resolves the default tier only
+    assert!(factory.get_pair(token0, token1).await.unwrap().is_zero());
+
+    let res = factory
+        .create_pair_with_fee_tier(token0, token1, 5)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await;
+    assert!(res.is_err());
+
+    for (pair_id, fee_tier) in [(stable, 5), (exotic, 100)] {
+        let pair = Actor::<PairProgram, GtestEnv>::new(env.clone(), pair_id).pair();
+        assert_eq!(pair.swap_fee_bps().await.unwrap(), fee_tier);
+        let info = factory.pair_info(pair_id).await.unwrap().unwrap();
+        assert_eq!(info.fee_tier, fee_tier);
+    }
+
+    // a tier must be enabled by the admin before it can be used
+    let res = factory
+        .create_pair_with_fee_tier(token0, token1, 50)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await;
+    assert!(res.is_err());
+    let res = factory
+        .set_fee_tier_enabled(50, true)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    let res = factory
+        .set_fee_tier_enabled(1_001, true)
+        .with_params(|p| p.with_actor_id(admin))
+        .await;
+    assert!(res.is_err());
+    factory
+        .set_fee_tier_enabled(50, true)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    factory
+        .set_fee_tier_enabled(100, false)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(factory.fee_tiers().await.unwrap(), vec![5, 30, 50]);
+
+    factory
+        .create_pair_with_fee_tier(token0, token1, 50)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    assert_eq!(factory.pairs_for_token(token0).await.unwrap().len(), 3);
+    // disabling a tier leaves its pairs in place
+    assert_eq!(
+        factory
+            .get_pair_with_fee_tier(token0, token1, 100)
+            .await
+            .unwrap(),
+        exotic
+    );
+}
+
+#[tokio::test]
+async fn factory_predicts_pair_address_from_create_message() {
+    let (env, factory, _) = deploy_factory().await;
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(user, ONE_VARA * 1000);
+    let token0 = ActorId::from(10u64);
+    let token1 = ActorId::from(11u64);
+
+    // The message id is known as soon as `create_pair` is sent
+    let program = env.system().get_program(factory.actor_id()).unwrap();
+    let payload =
+        io::CreatePairWithFeeTier::encode_params_with_prefix("Factory", token1, token0, 100);
+    let message_id = program.send_bytes_with_value(user, payload, ONE_VARA);
+    let predicted = factory
+        .compute_pair_address(token0, token1, 100, message_id)
+        .await
+        .unwrap();
+    env.system().run_next_block();
+
+    let pair = factory
+        .get_pair_with_fee_tier(token0, token1, 100)
+        .await
+        .unwrap();
+    assert!(!pair.is_zero());
+    assert_eq!(pair, predicted);
+
+    // The salt depends on the fee tier, not on the order of the tokens
+    assert_eq!(
+        factory
+            .compute_pair_address(token1, token0, 100, message_id)
+            .await
+            .unwrap(),
+        predicted
+    );
+    assert_ne!(
+        factory
+            .compute_pair_address(token0, token1, 30, message_id)
+            .await
+            .unwrap(),
+        predicted
+    );
+}
+
+#[tokio::test]
+async fn factory_creates_stable_pairs_apart() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(user, ONE_VARA * 1000);
+    let token0 = ActorId::from(10u64);
+    let token1 = ActorId::from(11u64);
+
+    factory
+        .create_pair_with_fee_tier(token0, token1, 5)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    // The same tokens and fee tier may also have a stable pair
+    factory
+        .create_stable_pair(token1, token0, 5, 200)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+
+    let volatile = factory
+        .get_pair_with_fee_tier(token0, token1, 5)
+        .await
+        .unwrap();
+    let stable = factory.get_stable_pair(token0, token1, 5).await.unwrap();
+    assert!(!stable.is_zero());
+    assert_ne!(stable, volatile);
+    assert_eq!(factory.pairs_count().await.unwrap(), 1);
+    assert_eq!(factory.stable_pairs_count().await.unwrap(), 1);
+    let page = factory.stable_pairs_paginated(0, 10).await.unwrap();
+    assert_eq!(page.len(), 1);
+    assert_eq!(page[0].pair_address, stable);
+    assert_eq!(factory.pair_info(stable).await.unwrap().unwrap(), page[0]);
+
+    let pair = Actor::<PairProgram, GtestEnv>::new(env.clone(), stable).pair();
+    assert_eq!(pair.amp().await.unwrap(), Some(200));
+    assert_eq!(pair.swap_fee_bps().await.unwrap(), 5);
+    let pair = Actor::<PairProgram, GtestEnv>::new(env.clone(), volatile).pair();
+    assert_eq!(pair.amp().await.unwrap(), None);
+
+    let res = factory
+        .create_stable_pair(token0, token1, 5, 100)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await;
+    assert!(res.is_err());
+
+    // An amplification the pair rejects refunds the fee
+    let factory_balance = env.system().balance_of(factory.actor_id());
+    factory
+        .create_stable_pair(token0, token1, 30, 0)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    assert_eq!(env.system().balance_of(factory.actor_id()), factory_balance);
+    assert_eq!(factory.stable_pairs_count().await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn factory_creates_concentrated_pools() {
+    let (env, mut factory, _) = deploy_factory().await;
+    let admin: ActorId = ActorId::from(ADMIN_ID);
+    let user: ActorId = ActorId::from(USER_ID);
+    env.system().mint_to(user, ONE_VARA * 1000);
+    let token0 = ActorId::from(10u64);
+    let token1 = ActorId::from(11u64);
+
+    // No code is set for concentrated pools at deployment
+    let res = factory
+        .create_concentrated_pool(token0, token1, 30)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await;
+    assert!(res.is_err());
+
+    let code_id = env.system().submit_code(concentrated::WASM_BINARY);
+    let res = factory
+        .set_concentrated_code_id(code_id)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    factory
+        .set_concentrated_code_id(code_id)
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(factory.concentrated_code_id().await.unwrap(), (code_id, 1));
+
+    factory
+        .create_concentrated_pool(token1, token0, 30)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await
+        .unwrap();
+    let pool_address = factory
+        .get_concentrated_pool(token0, token1, 30)
+        .await
+        .unwrap();
+    assert!(!pool_address.is_zero());
+    // Pools are kept apart from the pairs
+    assert!(factory.get_pair(token0, token1).await.unwrap().is_zero());
+    assert_eq!(factory.pairs_count().await.unwrap(), 0);
+    assert_eq!(factory.concentrated_pools_count().await.unwrap(), 1);
+    let page = factory.concentrated_pools_paginated(0, 10).await.unwrap();
+    assert_eq!(page[0].pair_address, pool_address);
+    assert_eq!(
+        factory.pair_info(pool_address).await.unwrap().unwrap(),
+        page[0]
+    );
+
+    let pool = Actor::<ConcentratedProgram, GtestEnv>::new(env.clone(), pool_address).pool();
+    assert_eq!(pool.get_tokens().await.unwrap(), (token0, token1));
+    assert_eq!(pool.swap_fee_bps().await.unwrap(), 30);
+    assert_eq!(pool.tick_spacing().await.unwrap(), 60);
+
+    let res = factory
+        .create_concentrated_pool(token0, token1, 30)
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await;
+    assert!(res.is_err());
+}
@@ -0,0 +1,26 @@
+#![no_std]
+
+pub mod services;
+use sails_rs::{cell::RefCell, prelude::*};
+use services::hook::{self, HookService};
+
+pub struct HookProgram {
+    state: RefCell<hook::State>,
+}
+
+#[sails_rs::program]
+impl HookProgram {
+    // Program's constructor
+    pub fn new(admin_id: ActorId) -> Self {
+        Self {
+            state: RefCell::new(hook::State {
+                admin_id,
+                ..Default::default()
+            }),
+        }
+    }
+
+    pub fn hook(&self) -> HookService<'_> {
+        HookService::new(&self.state)
+    }
+}
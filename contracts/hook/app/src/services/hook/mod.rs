@@ -0,0 +1,225 @@
+use sails_rs::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    gstd::msg,
+    prelude::*,
+};
+
+/// Highest swap fee a pair accepts from its hook, the pair's own limit.
+pub const MAX_SWAP_FEE_BPS: u64 = 1_000; // 10.00%
+
+/// What the pair does with a swap after `before_swap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum SwapVerdict {
+    /// Swap at the pair's own fee.
+    Allow,
+    /// Swap at this fee in basis points instead, at most `MAX_SWAP_FEE_BPS`.
+    AllowWithFee(u64),
+    Reject,
+}
+
+/// The reference hook: a blocklist, an optional swap fee for every pair using it
+/// and the swap volume of each account.
+#[derive(Debug, Default)]
+pub struct State {
+    pub admin_id: ActorId,
+    /// Accounts that may neither trade nor provide liquidity, as sender or recipient.
+    pub blocked: HashSet<ActorId>,
+    /// Fee charged on swaps instead of the pair's own, if set.
+    pub swap_fee_bps: Option<u64>,
+    /// Swap input reported by `after_swap`, by `(pair, sender)`.
+    pub volume: HashMap<(ActorId, ActorId), U256>,
+}
+
+#[event]
+#[derive(Debug, Encode, Decode, TypeInfo)]
+pub enum HookEvent {
+    BlockedSet { account: ActorId, blocked: bool },
+    SwapFeeSet { swap_fee_bps: Option<u64> },
+}
+
+#[derive(Debug)]
+pub enum HookError {
+    Unauthorized,
+    InvalidSwapFee,
+    EventError,
+}
+
+/// The hook interface, with a reference implementation.
+///
+/// A hook is any program with a `Hook` service exposing the `before_*` and `after_*`
+/// methods of this one with the same signatures. The pair calling them is `msg::source()`.
+/// The pair waits for the reply of a `before_*` call, which may reject the operation,
+/// and notifies `after_*` once the operation is done, without waiting for a reply.
+pub struct HookService<'a> {
+    state: &'a RefCell<State>,
+}
+
+impl<'a> HookService<'a> {
+    pub fn new(state: &'a RefCell<State>) -> Self {
+        Self { state }
+    }
+
+    #[inline]
+    pub fn with_state<R>(&self, f: impl FnOnce(&State) -> R) -> R {
+        let st = self.state.borrow();
+        f(&st)
+    }
+
+    #[inline]
+    pub fn with_state_mut<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        let mut st = self.state.borrow_mut();
+        f(&mut st)
+    }
+
+    fn ensure_admin(&self) -> Result<(), HookError> {
+        if msg::source() != self.with_state(|st| st.admin_id) {
+            return Err(HookError::Unauthorized);
+        }
+        Ok(())
+    }
+
+    fn allows(&self, sender: ActorId, to: ActorId) -> bool {
+        self.with_state(|st| !st.blocked.contains(&sender) && !st.blocked.contains(&to))
+    }
+}
+
+#[sails_rs::service(events = HookEvent)]
+impl<'a> HookService<'a> {
+    /// `amount` is the input when `exact_input` and the output otherwise,
+    /// `swap_fee_bps` the pair's own fee.
+    #[export]
+    #[allow(unused_variables)]
+    pub fn before_swap(
+        &mut self,
+        sender: ActorId,
+        to: ActorId,
+        is_token0_to_token1: bool,
+        exact_input: bool,
+        amount: U256,
+        swap_fee_bps: u64,
+    ) -> SwapVerdict {
+        if !self.allows(sender, to) {
+            return SwapVerdict::Reject;
+        }
+        match self.with_state(|st| st.swap_fee_bps) {
+            Some(fee_bps) => SwapVerdict::AllowWithFee(fee_bps),
+            None => SwapVerdict::Allow,
+        }
+    }
+
+    #[export]
+    #[allow(unused_variables)]
+    pub fn after_swap(
+        &mut self,
+        sender: ActorId,
+        to: ActorId,
+        is_token0_to_token1: bool,
+        amount_in: U256,
+        amount_out: U256,
+    ) {
+        let pair = msg::source();
+        self.with_state_mut(|st| {
+            let volume = st.volume.entry((pair, sender)).or_default();
+            *volume = volume.saturating_add(amount_in);
+        });
+    }
+
+    /// Returns whether the liquidity may be added.
+    #[export]
+    #[allow(unused_variables)]
+    pub fn before_add_liquidity(
+        &mut self,
+        sender: ActorId,
+        to: ActorId,
+        amount_a_desired: U256,
+        amount_b_desired: U256,
+    ) -> bool {
+        self.allows(sender, to)
+    }
+
+    #[export]
+    #[allow(unused_variables)]
+    pub fn after_add_liquidity(
+        &mut self,
+        sender: ActorId,
+        to: ActorId,
+        amount_a: U256,
+        amount_b: U256,
+        liquidity: U256,
+    ) {
+    }
+
+    /// Returns whether the liquidity may be removed.
+    #[export]
+    #[allow(unused_variables)]
+    pub fn before_remove_liquidity(
+        &mut self,
+        sender: ActorId,
+        to: ActorId,
+        liquidity: U256,
+    ) -> bool {
+        self.allows(sender, to)
+    }
+
+    #[export]
+    #[allow(unused_variables)]
+    pub fn after_remove_liquidity(
+        &mut self,
+        sender: ActorId,
+        to: ActorId,
+        amount_a: U256,
+        amount_b: U256,
+        liquidity: U256,
+    ) {
+    }
+
+    #[export(unwrap_result)]
+    pub fn set_blocked(&mut self, account: ActorId, blocked: bool) -> Result<(), HookError> {
+        self.ensure_admin()?;
+        self.with_state_mut(|st| {
+            if blocked {
+                st.blocked.insert(account);
+            } else {
+                st.blocked.remove(&account);
+            }
+        });
+        self.emit_hook_event(HookEvent::BlockedSet { account, blocked })
+    }
+
+    /// Sets the fee every pair using the hook charges on swaps, `None` for the pair's own.
+    #[export(unwrap_result)]
+    pub fn set_swap_fee(&mut self, swap_fee_bps: Option<u64>) -> Result<(), HookError> {
+        self.ensure_admin()?;
+        if swap_fee_bps.is_some_and(|fee_bps| fee_bps > MAX_SWAP_FEE_BPS) {
+            return Err(HookError::InvalidSwapFee);
+        }
+        self.with_state_mut(|st| st.swap_fee_bps = swap_fee_bps);
+        self.emit_hook_event(HookEvent::SwapFeeSet { swap_fee_bps })
+    }
+
+    #[export]
+    pub fn is_blocked(&self, account: ActorId) -> bool {
+        self.with_state(|st| st.blocked.contains(&account))
+    }
+
+    #[export]
+    pub fn swap_fee(&self) -> Option<u64> {
+        self.with_state(|st| st.swap_fee_bps)
+    }
+
+    /// Swap input of `account` through `pair`.
+    #[export]
+    pub fn volume_of(&self, pair: ActorId, account: ActorId) -> U256 {
+        self.with_state(|st| st.volume.get(&(pair, account)).copied().unwrap_or_default())
+    }
+
+    #[export]
+    pub fn admin(&self) -> ActorId {
+        self.with_state(|st| st.admin_id)
+    }
+
+    fn emit_hook_event(&self, event: HookEvent) -> Result<(), HookError> {
+        self.emit_event(event).map_err(|_| HookError::EventError)
+    }
+}
@@ -0,0 +1,89 @@
+use hook_client::{
+    hook::{Hook as _, HookImpl},
+    Hook as HookClient, HookCtors, HookProgram, SwapVerdict,
+};
+use sails_rs::gtest::System;
+use sails_rs::{client::*, prelude::*};
+
+const ADMIN_ID: u64 = 1;
+const PAIR_ID: u64 = 2;
+const USER_ID: u64 = 3;
+const ONE_VARA: u128 = 1_000_000_000_000;
+
+async fn setup() -> Service<HookImpl, GtestEnv> {
+    let system = System::new();
+    let admin = ActorId::from(ADMIN_ID);
+    for id in [ADMIN_ID, PAIR_ID, USER_ID] {
+        system.mint_to(id, 1000 * ONE_VARA);
+    }
+    let env = GtestEnv::new(system, admin);
+    let code_id = env.system().submit_code(hook::WASM_BINARY);
+    env.deploy::<HookProgram>(code_id, b"salt".to_vec())
+        .new(admin)
+        .await
+        .unwrap()
+        .hook()
+}
+
+#[tokio::test]
+async fn hook_screens_accounts_and_sets_fee() {
+    let mut hook = setup().await;
+    let pair = ActorId::from(PAIR_ID);
+    let user = ActorId::from(USER_ID);
+    let amount = U256::from(1_000u64);
+
+    let verdict = hook
+        .before_swap(user, user, true, true, amount, 30)
+        .with_params(|p| p.with_actor_id(pair))
+        .await
+        .unwrap();
+    assert_eq!(verdict, SwapVerdict::Allow);
+
+    // Only the admin configures the hook, within the pair's fee limit
+    assert!(hook
+        .set_blocked(user, true)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .is_err());
+    assert!(hook.set_swap_fee(Some(1_001)).await.is_err());
+
+    hook.set_swap_fee(Some(100)).await.unwrap();
+    let verdict = hook
+        .before_swap(user, user, true, true, amount, 30)
+        .with_params(|p| p.with_actor_id(pair))
+        .await
+        .unwrap();
+    assert_eq!(verdict, SwapVerdict::AllowWithFee(100));
+
+    hook.set_blocked(user, true).await.unwrap();
+    assert!(hook.is_blocked(user).await.unwrap());
+    let verdict = hook
+        .before_swap(pair, user, false, false, amount, 30)
+        .with_params(|p| p.with_actor_id(pair))
+        .await
+        .unwrap();
+    assert_eq!(verdict, SwapVerdict::Reject);
+    assert!(!hook
+        .before_add_liquidity(user, user, amount, amount)
+        .with_params(|p| p.with_actor_id(pair))
+        .await
+        .unwrap());
+}
+
+#[tokio::test]
+async fn hook_counts_volume_per_pair() {
+    let mut hook = setup().await;
+    let pair = ActorId::from(PAIR_ID);
+    let user = ActorId::from(USER_ID);
+    let amount = U256::from(1_000u64);
+
+    for _ in 0..2 {
+        hook.after_swap(user, user, true, amount, amount / 2)
+            .with_params(|p| p.with_actor_id(pair))
+            .await
+            .unwrap();
+    }
+    assert_eq!(hook.volume_of(pair, user).await.unwrap(), amount * 2);
+    // Volume is kept per calling pair
+    assert!(hook.volume_of(user, user).await.unwrap().is_zero());
+}
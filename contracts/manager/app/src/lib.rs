@@ -0,0 +1,38 @@
+#![no_std]
+
+pub mod services;
+use sails_rs::{cell::RefCell, prelude::*};
+use services::manager::{self, Config, ManagerService};
+use token_ops::{TokenCustody, TransferTracker};
+
+pub struct ManagerProgram {
+    state: RefCell<manager::State>,
+    tracker: RefCell<TransferTracker>,
+}
+
+#[sails_rs::program]
+impl ManagerProgram {
+    // Program's constructor
+    pub fn new(config: Config, admin_id: ActorId) -> Self {
+        let state = manager::State {
+            admin_id,
+            config,
+            ..Default::default()
+        };
+        sails_rs::gstd::msg::reply_bytes(b"", 0).expect("Error during msg reply");
+        Self {
+            state: RefCell::new(state),
+            tracker: RefCell::new(TransferTracker::default()),
+        }
+    }
+
+    pub fn manager(&self) -> ManagerService<'_> {
+        ManagerService::new(&self.state, &self.tracker)
+    }
+
+    #[allow(dead_code)]
+    #[handle_reply]
+    fn handle_reply(&self) {
+        self.manager().on_reply();
+    }
+}
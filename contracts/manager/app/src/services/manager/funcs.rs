@@ -0,0 +1,242 @@
+use crate::services::manager::{
+    ManagerError, ManagerEvent, ManagerService, math,
+    route::{apply_route, quote_route, sort_tokens},
+};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+use token_ops::TokenCustody;
+
+impl<'a> ManagerService<'a> {
+    fn ensure_gas(&self) -> Result<(), ManagerError> {
+        if exec::gas_available() < self.with_state(|st| st.config.gas_for_full_tx) {
+            return Err(ManagerError::NotEnoughAttachedGas);
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_liquidity_core(
+        &self,
+        token_a: ActorId,
+        token_b: ActorId,
+        amount_a_desired: U256,
+        amount_b_desired: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        deadline: u64,
+    ) -> Result<ManagerEvent, ManagerError> {
+        self.ensure_gas()?;
+        ensure_deadline(deadline)?;
+        if amount_a_desired.is_zero() || amount_b_desired.is_zero() {
+            return Err(ManagerError::ZeroLiquidity);
+        }
+        let user = msg::source();
+        let key = sort_tokens(token_a, token_b)?;
+        let a_is_token0 = token_a == key.0;
+
+        let optimal_amounts = |desired_a: U256, desired_b: U256| {
+            self.with_state(|st| {
+                let pool = st.pools.get(&key).ok_or(ManagerError::PoolNotFound)?;
+                let (reserve_a, reserve_b) = pool.reserves(a_is_token0);
+                let (amount_a, amount_b) =
+                    math::optimal_amounts(reserve_a, reserve_b, desired_a, desired_b)?;
+                check_minimums(amount_a, amount_b, amount_a_min, amount_b_min)?;
+                Ok::<_, ManagerError>((amount_a, amount_b))
+            })
+        };
+
+        let (amount_a, amount_b) = optimal_amounts(amount_a_desired, amount_b_desired)?;
+        self.pull(token_a, user, amount_a).await?;
+        if let Err(err) = self.pull(token_b, user, amount_b).await {
+            self.push(token_a, user, amount_a).await;
+            return Err(err.into());
+        }
+
+        // The price may have moved while the tokens were coming in
+        let minted = optimal_amounts(amount_a, amount_b).and_then(|(used_a, used_b)| {
+            let (amount0, amount1) = oriented(a_is_token0, used_a, used_b);
+            let liquidity = self.with_state_mut(|st| {
+                st.pools
+                    .get_mut(&key)
+                    .ok_or(ManagerError::PoolNotFound)?
+                    .mint(user, amount0, amount1)
+            })?;
+            Ok((used_a, used_b, liquidity))
+        });
+        let (used_a, used_b, liquidity) = match minted {
+            Ok(minted) => minted,
+            Err(err) => {
+                self.push(token_a, user, amount_a).await;
+                self.push(token_b, user, amount_b).await;
+                return Err(err);
+            }
+        };
+
+        self.push(token_a, user, amount_a - used_a).await;
+        self.push(token_b, user, amount_b - used_b).await;
+
+        let (amount0, amount1) = oriented(a_is_token0, used_a, used_b);
+        Ok(ManagerEvent::LiquidityAdded {
+            user_id: user,
+            token0: key.0,
+            token1: key.1,
+            amount0,
+            amount1,
+            liquidity,
+        })
+    }
+
+    pub async fn remove_liquidity_core(
+        &self,
+        token_a: ActorId,
+        token_b: ActorId,
+        liquidity: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        deadline: u64,
+    ) -> Result<ManagerEvent, ManagerError> {
+        self.ensure_gas()?;
+        ensure_deadline(deadline)?;
+        let user = msg::source();
+        let key = sort_tokens(token_a, token_b)?;
+        let a_is_token0 = token_a == key.0;
+
+        let (amount0, amount1) = self.with_state_mut(|st| {
+            st.pools
+                .get_mut(&key)
+                .ok_or(ManagerError::PoolNotFound)?
+                .burn(user, liquidity)
+        })?;
+        // Nothing is sent yet, so failing here undoes the burn
+        let (amount_a, amount_b) = oriented(a_is_token0, amount0, amount1);
+        check_minimums(amount_a, amount_b, amount_a_min, amount_b_min)?;
+
+        self.push(key.0, user, amount0).await;
+        self.push(key.1, user, amount1).await;
+
+        Ok(ManagerEvent::LiquidityRemoved {
+            user_id: user,
+            token0: key.0,
+            token1: key.1,
+            amount0,
+            amount1,
+            liquidity,
+        })
+    }
+
+    /// Swaps `amount` along `path`, the input when `exact_in` and the output otherwise.
+    /// `limit` is the least output for an exact input and the most input for an exact output.
+    ///
+    /// The first input is taken, then every hop is applied at once against the reserves
+    /// as they are when it arrives, and only the last output is sent.
+    pub async fn swap_core(
+        &self,
+        path: Vec<ActorId>,
+        amount: U256,
+        limit: U256,
+        exact_in: bool,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<ManagerEvent, ManagerError> {
+        self.ensure_gas()?;
+        ensure_deadline(deadline)?;
+        if to.is_zero() {
+            return Err(ManagerError::InvalidRecipient);
+        }
+        let user = msg::source();
+
+        let quote_checked = || {
+            self.with_state(|st| {
+                let hops = quote_route(st, &path, amount, exact_in)?;
+                let amount_in = hops[0].amount_in;
+                let amount_out = hops[hops.len() - 1].amount_out;
+                if exact_in && amount_out < limit {
+                    return Err(ManagerError::InsufficientOutputAmount);
+                }
+                if !exact_in && amount_in > limit {
+                    return Err(ManagerError::ExcessiveInputAmount);
+                }
+                Ok((hops, amount_in, amount_out))
+            })
+        };
+
+        let (_, amount_in, _) = quote_checked()?;
+        let (token_in, token_out) = (path[0], path[path.len() - 1]);
+        self.pull(token_in, user, amount_in).await?;
+
+        // The reserves may have moved while the input was coming in
+        let swapped = quote_checked().and_then(|(hops, used_in, amount_out)| {
+            if used_in > amount_in {
+                return Err(ManagerError::ExcessiveInputAmount);
+            }
+            self.with_state_mut(|st| apply_route(st, &hops));
+            Ok((used_in, amount_out))
+        });
+        let (used_in, amount_out) = match swapped {
+            Ok(swapped) => swapped,
+            Err(err) => {
+                self.push(token_in, user, amount_in).await;
+                return Err(err);
+            }
+        };
+
+        self.push(token_in, user, amount_in - used_in).await;
+        self.push(token_out, to, amount_out).await;
+
+        Ok(ManagerEvent::Swap {
+            user_id: user,
+            to,
+            path,
+            amount_in: used_in,
+            amount_out,
+        })
+    }
+
+    pub async fn claim_credit_core(&self, token: ActorId) -> Result<ManagerEvent, ManagerError> {
+        self.ensure_gas()?;
+        let user = msg::source();
+        let amount = self
+            .with_state_mut(|st| st.credits.remove(&(user, token)))
+            .ok_or(ManagerError::NoCredit)?;
+
+        // A failed transfer is credited back by the reply hook
+        if !self.push(token, user, amount).await {
+            return Err(ManagerError::TokenTransferFailed);
+        }
+
+        Ok(ManagerEvent::CreditClaimed {
+            user_id: user,
+            token,
+            amount,
+        })
+    }
+}
+
+/// Swaps a pair between the caller's `(a, b)` order and the pool's `(0, 1)`, both ways.
+fn oriented<T>(a_is_token0: bool, a: T, b: T) -> (T, T) {
+    if a_is_token0 { (a, b) } else { (b, a) }
+}
+
+fn ensure_deadline(deadline: u64) -> Result<(), ManagerError> {
+    if exec::block_timestamp() > deadline {
+        return Err(ManagerError::DeadlineExpired);
+    }
+    Ok(())
+}
+
+fn check_minimums(
+    amount_a: U256,
+    amount_b: U256,
+    amount_a_min: U256,
+    amount_b_min: U256,
+) -> Result<(), ManagerError> {
+    if amount_a < amount_a_min {
+        return Err(ManagerError::InsufficientAmountA);
+    }
+    if amount_b < amount_b_min {
+        return Err(ManagerError::InsufficientAmountB);
+    }
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use crate::services::manager::ManagerError;
+use sails_rs::U256;
+
+/// LP tokens locked forever by the first deposit into a pool, as in Uniswap V2.
+pub const MINIMUM_LIQUIDITY: u64 = 1000;
+/// Swap fees are in basis points of the input.
+pub const FEE_DENOM_BPS: u64 = 10_000; // 100.00%
+/// Highest swap fee a pool accepts.
+pub const MAX_SWAP_FEE_BPS: u64 = 1_000; // 10.00%
+
+/// Amount of token B worth `amount_a` of token A at the reserves' price, rounded down.
+pub fn quote(amount_a: U256, reserve_a: U256, reserve_b: U256) -> Result<U256, ManagerError> {
+    if reserve_a.is_zero() || reserve_b.is_zero() {
+        return Err(ManagerError::InsufficientLiquidity);
+    }
+    amount_a
+        .checked_mul(reserve_b)
+        .map(|numerator| numerator / reserve_a)
+        .ok_or(ManagerError::Overflow)
+}
+
+/// Largest amounts up to the desired ones that keep the reserves' price.
+/// An empty pool takes the desired amounts as they are.
+pub fn optimal_amounts(
+    reserve_a: U256,
+    reserve_b: U256,
+    amount_a_desired: U256,
+    amount_b_desired: U256,
+) -> Result<(U256, U256), ManagerError> {
+    if reserve_a.is_zero() && reserve_b.is_zero() {
+        return Ok((amount_a_desired, amount_b_desired));
+    }
+    let amount_b_optimal = quote(amount_a_desired, reserve_a, reserve_b)?;
+    if amount_b_optimal <= amount_b_desired {
+        return Ok((amount_a_desired, amount_b_optimal));
+    }
+    let amount_a_optimal = quote(amount_b_desired, reserve_b, reserve_a)?;
+    Ok((amount_a_optimal, amount_b_desired))
+}
+
+/// LP tokens minted for the amounts: `sqrt(amount0 * amount1) - MINIMUM_LIQUIDITY`
+/// on the first deposit, the smaller share of either reserve afterwards.
+pub fn liquidity_for(
+    reserve0: U256,
+    reserve1: U256,
+    amount0: U256,
+    amount1: U256,
+    total_supply: U256,
+) -> Result<U256, ManagerError> {
+    let liquidity = if total_supply.is_zero() {
+        amount0
+            .checked_mul(amount1)
+            .ok_or(ManagerError::Overflow)?
+            .integer_sqrt()
+            .checked_sub(U256::from(MINIMUM_LIQUIDITY))
+            .ok_or(ManagerError::InsufficientLiquidityMinted)?
+    } else {
+        let liquidity0 = amount0
+            .checked_mul(total_supply)
+            .ok_or(ManagerError::Overflow)?
+            / reserve0;
+        let liquidity1 = amount1
+            .checked_mul(total_supply)
+            .ok_or(ManagerError::Overflow)?
+            / reserve1;
+        liquidity0.min(liquidity1)
+    };
+    if liquidity.is_zero() {
+        return Err(ManagerError::InsufficientLiquidityMinted);
+    }
+    Ok(liquidity)
+}
+
+/// Output of a swap of `amount_in`, the fee taken from the input:
+/// `amount_in * (10_000 - fee) * reserve_out / (reserve_in * 10_000 + amount_in * (10_000 - fee))`.
+pub fn get_amount_out(
+    amount_in: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    swap_fee_bps: u64,
+) -> Result<U256, ManagerError> {
+    if amount_in.is_zero() {
+        return Err(ManagerError::InsufficientAmount);
+    }
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return Err(ManagerError::InsufficientLiquidity);
+    }
+    let (fee_multiplier, denom) = fee_factors(swap_fee_bps)?;
+    let amount_in_with_fee = amount_in
+        .checked_mul(fee_multiplier)
+        .ok_or(ManagerError::Overflow)?;
+    let numerator = amount_in_with_fee
+        .checked_mul(reserve_out)
+        .ok_or(ManagerError::Overflow)?;
+    let denominator = reserve_in
+        .checked_mul(denom)
+        .and_then(|r| r.checked_add(amount_in_with_fee))
+        .ok_or(ManagerError::Overflow)?;
+    Ok(numerator / denominator)
+}
+
+/// Input a swap needs for `amount_out`, rounded up:
+/// `reserve_in * amount_out * 10_000 / ((reserve_out - amount_out) * (10_000 - fee)) + 1`.
+pub fn get_amount_in(
+    amount_out: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    swap_fee_bps: u64,
+) -> Result<U256, ManagerError> {
+    if amount_out.is_zero() {
+        return Err(ManagerError::InsufficientAmount);
+    }
+    if reserve_in.is_zero() || amount_out >= reserve_out {
+        return Err(ManagerError::InsufficientLiquidity);
+    }
+    let (fee_multiplier, denom) = fee_factors(swap_fee_bps)?;
+    let numerator = reserve_in
+        .checked_mul(amount_out)
+        .and_then(|n| n.checked_mul(denom))
+        .ok_or(ManagerError::Overflow)?;
+    let denominator = (reserve_out - amount_out)
+        .checked_mul(fee_multiplier)
+        .ok_or(ManagerError::Overflow)?;
+    (numerator / denominator)
+        .checked_add(U256::one())
+        .ok_or(ManagerError::Overflow)
+}
+
+/// `(10_000 - swap_fee_bps, 10_000)`, the multiplier applied to the input and its denominator.
+fn fee_factors(swap_fee_bps: u64) -> Result<(U256, U256), ManagerError> {
+    if swap_fee_bps > MAX_SWAP_FEE_BPS {
+        return Err(ManagerError::InvalidSwapFee);
+    }
+    Ok((
+        U256::from(FEE_DENOM_BPS - swap_fee_bps),
+        U256::from(FEE_DENOM_BPS),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn reserve() -> impl Strategy<Value = u128> {
+        1_000u128..=u128::MAX >> 8
+    }
+
+    proptest! {
+        /// What `get_amount_in` asks for always buys at least the requested output.
+        #[test]
+        fn amount_in_buys_amount_out(
+            reserve_in in reserve(),
+            reserve_out in reserve(),
+            amount_out_fraction in 1u64..1_000,
+            swap_fee_bps in 0..=MAX_SWAP_FEE_BPS,
+        ) {
+            let (reserve_in, reserve_out) = (U256::from(reserve_in), U256::from(reserve_out));
+            let amount_out = reserve_out * amount_out_fraction / U256::from(1_000u64);
+            prop_assume!(!amount_out.is_zero());
+            let amount_in =
+                get_amount_in(amount_out, reserve_in, reserve_out, swap_fee_bps).unwrap();
+            let bought =
+                get_amount_out(amount_in, reserve_in, reserve_out, swap_fee_bps).unwrap();
+            prop_assert!(bought >= amount_out);
+        }
+
+        /// A swap never lowers `reserve0 * reserve1`.
+        #[test]
+        fn swap_keeps_the_product(
+            reserve_in in reserve(),
+            reserve_out in reserve(),
+            amount_in in 1u128..=u128::MAX >> 8,
+            swap_fee_bps in 0..=MAX_SWAP_FEE_BPS,
+        ) {
+            let (reserve_in, reserve_out) = (U256::from(reserve_in), U256::from(reserve_out));
+            let amount_in = U256::from(amount_in);
+            let amount_out =
+                get_amount_out(amount_in, reserve_in, reserve_out, swap_fee_bps).unwrap();
+            prop_assert!(
+                (reserve_in + amount_in) * (reserve_out - amount_out) >= reserve_in * reserve_out
+            );
+        }
+    }
+
+    #[test]
+    fn first_deposit_locks_minimum_liquidity() {
+        let amount = U256::from(1_000_000u64);
+        assert_eq!(
+            liquidity_for(U256::zero(), U256::zero(), amount, amount, U256::zero()).unwrap(),
+            amount - MINIMUM_LIQUIDITY
+        );
+        assert!(matches!(
+            liquidity_for(
+                U256::zero(),
+                U256::zero(),
+                U256::from(999u64),
+                U256::from(999u64),
+                U256::zero()
+            ),
+            Err(ManagerError::InsufficientLiquidityMinted)
+        ));
+    }
+}
@@ -0,0 +1,402 @@
+use sails_rs::{cell::RefCell, collections::HashMap, gstd::msg, prelude::*};
+
+mod funcs;
+mod math;
+mod pool;
+mod route;
+mod token_operations;
+pub use math::MAX_SWAP_FEE_BPS;
+pub use pool::{Pool, PoolInfo};
+use route::{quote_route, route_amounts, sort_tokens};
+use token_ops::{PendingTransfer, TransferTracker};
+
+pub struct ManagerService<'a> {
+    state: &'a RefCell<State>,
+    tracker: &'a RefCell<TransferTracker>,
+}
+
+#[derive(Debug, Default)]
+pub struct State {
+    pub admin_id: ActorId,
+    pub config: Config,
+    /// Pools by their sorted token pair. The manager holds the tokens of all of them.
+    pub pools: HashMap<(ActorId, ActorId), Pool>,
+    /// Tokens owed to users after a transfer to them failed, by `(user, token)`.
+    pub credits: HashMap<(ActorId, ActorId), U256>,
+}
+
+#[event]
+#[derive(Debug, Encode, Decode, TypeInfo)]
+pub enum ManagerEvent {
+    PoolCreated {
+        token0: ActorId,
+        token1: ActorId,
+        swap_fee_bps: u64,
+    },
+    SwapFeeSet {
+        token0: ActorId,
+        token1: ActorId,
+        swap_fee_bps: u64,
+    },
+    LiquidityAdded {
+        user_id: ActorId,
+        token0: ActorId,
+        token1: ActorId,
+        amount0: U256,
+        amount1: U256,
+        liquidity: U256,
+    },
+    LiquidityRemoved {
+        user_id: ActorId,
+        token0: ActorId,
+        token1: ActorId,
+        amount0: U256,
+        amount1: U256,
+        liquidity: U256,
+    },
+    LiquidityTransferred {
+        from: ActorId,
+        to: ActorId,
+        token0: ActorId,
+        token1: ActorId,
+        amount: U256,
+    },
+    /// Only `path[0]` and the last token of `path` were transferred.
+    Swap {
+        user_id: ActorId,
+        to: ActorId,
+        path: Vec<ActorId>,
+        amount_in: U256,
+        amount_out: U256,
+    },
+    CreditClaimed {
+        user_id: ActorId,
+        token: ActorId,
+        amount: U256,
+    },
+}
+
+#[derive(Debug)]
+pub enum ManagerError {
+    NotEnoughAttachedGas,
+    DeadlineExpired,
+    Unauthorized,
+    Overflow,
+    IdenticalTokens,
+    ZeroAddress,
+    PoolExists,
+    PoolNotFound,
+    InvalidPath,
+    InvalidRecipient,
+    InvalidSwapFee,
+    InsufficientLiquidity,
+    InsufficientLiquidityMinted,
+    InsufficientLiquidityBurned,
+    InsufficientLpBalance,
+    InsufficientAmount,
+    InsufficientAmountA,
+    InsufficientAmountB,
+    InsufficientOutputAmount,
+    ExcessiveInputAmount,
+    ZeroLiquidity,
+    NoCredit,
+    SendFailure,
+    ReplyTimeout,
+    ReplyFailure,
+    UnableToDecode,
+    TokenTransferFailed,
+    EventError,
+}
+
+/// Config that will be used to send messages to the other programs.
+#[derive(Default, Debug, Decode, Encode, TypeInfo, Clone)]
+pub struct Config {
+    /// Gas limit for token operations (TransferFrom and Transfer).
+    gas_for_token_ops: u64,
+    /// Gas to reserve for reply processing.
+    gas_for_reply_deposit: u64,
+    /// Timeout in blocks that current program will wait for reply from
+    /// the other programs such as VFT
+    reply_timeout: u32,
+    gas_for_full_tx: u64,
+}
+
+impl<'a> ManagerService<'a> {
+    pub fn new(state: &'a RefCell<State>, tracker: &'a RefCell<TransferTracker>) -> Self {
+        Self { state, tracker }
+    }
+
+    #[inline]
+    pub fn with_state<R>(&self, f: impl FnOnce(&State) -> R) -> R {
+        let st = self.state.borrow();
+        f(&st)
+    }
+
+    #[inline]
+    pub fn with_state_mut<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        let mut st = self.state.borrow_mut();
+        f(&mut st)
+    }
+
+    fn ensure_admin(&self) -> Result<(), ManagerError> {
+        if msg::source() != self.with_state(|st| st.admin_id) {
+            return Err(ManagerError::Unauthorized);
+        }
+        Ok(())
+    }
+}
+
+#[sails_rs::service(events = ManagerEvent)]
+impl<'a> ManagerService<'a> {
+    /// Creates the pool of `token_a` and `token_b`. Anyone can do it, once per pair.
+    #[export(unwrap_result)]
+    pub fn create_pool(
+        &mut self,
+        token_a: ActorId,
+        token_b: ActorId,
+        swap_fee_bps: u64,
+    ) -> Result<(), ManagerError> {
+        let (token0, token1) = sort_tokens(token_a, token_b)?;
+        self.with_state_mut(|st| {
+            if st.pools.contains_key(&(token0, token1)) {
+                return Err(ManagerError::PoolExists);
+            }
+            st.pools.insert((token0, token1), Pool::new(swap_fee_bps)?);
+            Ok(())
+        })?;
+        self.emit_manager_event(ManagerEvent::PoolCreated {
+            token0,
+            token1,
+            swap_fee_bps,
+        })
+    }
+
+    /// Adds liquidity to the pool of `token_a` and `token_b`, minting LP tokens
+    /// on the manager's ledger for the pool.
+    ///
+    /// The amounts are the most the desired ones provide at the pool's price. They are
+    /// taken first, and what the pool no longer needs once they arrive is sent back.
+    #[export(unwrap_result)]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_liquidity(
+        &mut self,
+        token_a: ActorId,
+        token_b: ActorId,
+        amount_a_desired: U256,
+        amount_b_desired: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        deadline: u64,
+    ) -> Result<(), ManagerError> {
+        let event = self
+            .add_liquidity_core(
+                token_a,
+                token_b,
+                amount_a_desired,
+                amount_b_desired,
+                amount_a_min,
+                amount_b_min,
+                deadline,
+            )
+            .await?;
+        self.emit_manager_event(event)
+    }
+
+    /// Burns `liquidity` of the caller's LP tokens and sends them their share of the pool.
+    /// A transfer that fails is credited to the caller, see `claim_credit`.
+    #[export(unwrap_result)]
+    pub async fn remove_liquidity(
+        &mut self,
+        token_a: ActorId,
+        token_b: ActorId,
+        liquidity: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        deadline: u64,
+    ) -> Result<(), ManagerError> {
+        let event = self
+            .remove_liquidity_core(
+                token_a,
+                token_b,
+                liquidity,
+                amount_a_min,
+                amount_b_min,
+                deadline,
+            )
+            .await?;
+        self.emit_manager_event(event)
+    }
+
+    /// Moves `amount` of the caller's LP tokens of a pool to `to`.
+    #[export(unwrap_result)]
+    pub fn transfer_liquidity(
+        &mut self,
+        token_a: ActorId,
+        token_b: ActorId,
+        to: ActorId,
+        amount: U256,
+    ) -> Result<(), ManagerError> {
+        let from = msg::source();
+        let (token0, token1) = sort_tokens(token_a, token_b)?;
+        if to.is_zero() {
+            return Err(ManagerError::InvalidRecipient);
+        }
+        self.with_state_mut(|st| {
+            st.pools
+                .get_mut(&(token0, token1))
+                .ok_or(ManagerError::PoolNotFound)?
+                .transfer(from, to, amount)
+        })?;
+        self.emit_manager_event(ManagerEvent::LiquidityTransferred {
+            from,
+            to,
+            token0,
+            token1,
+            amount,
+        })
+    }
+
+    /// Swaps exactly `amount_in` of `path[0]` along `path`, sending the output to `to`.
+    ///
+    /// Only the first input is taken and only the last output is sent, every hop between
+    /// is settled within the manager.
+    #[export(unwrap_result)]
+    pub async fn swap_exact_tokens_for_tokens(
+        &mut self,
+        amount_in: U256,
+        amount_out_min: U256,
+        path: Vec<ActorId>,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<(), ManagerError> {
+        let event = self
+            .swap_core(path, amount_in, amount_out_min, true, to, deadline)
+            .await?;
+        self.emit_manager_event(event)
+    }
+
+    /// Swaps along `path` for exactly `amount_out` of its last token. The input quoted
+    /// at the time of the call is taken, and any part of it the route no longer needs
+    /// when it executes is sent back.
+    #[export(unwrap_result)]
+    pub async fn swap_tokens_for_exact_tokens(
+        &mut self,
+        amount_out: U256,
+        amount_in_max: U256,
+        path: Vec<ActorId>,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<(), ManagerError> {
+        let event = self
+            .swap_core(path, amount_out, amount_in_max, false, to, deadline)
+            .await?;
+        self.emit_manager_event(event)
+    }
+
+    /// Sends the caller the tokens credited to them after a transfer failed.
+    #[export(unwrap_result)]
+    pub async fn claim_credit(&mut self, token: ActorId) -> Result<(), ManagerError> {
+        let event = self.claim_credit_core(token).await?;
+        self.emit_manager_event(event)
+    }
+
+    #[export(unwrap_result)]
+    pub fn set_swap_fee(
+        &mut self,
+        token_a: ActorId,
+        token_b: ActorId,
+        swap_fee_bps: u64,
+    ) -> Result<(), ManagerError> {
+        self.ensure_admin()?;
+        let (token0, token1) = sort_tokens(token_a, token_b)?;
+        self.with_state_mut(|st| {
+            st.pools
+                .get_mut(&(token0, token1))
+                .ok_or(ManagerError::PoolNotFound)?
+                .set_swap_fee(swap_fee_bps)
+        })?;
+        self.emit_manager_event(ManagerEvent::SwapFeeSet {
+            token0,
+            token1,
+            swap_fee_bps,
+        })
+    }
+
+    #[export(unwrap_result)]
+    pub fn update_config(&mut self, config: Config) -> Result<(), ManagerError> {
+        self.ensure_admin()?;
+        self.with_state_mut(|st| st.config = config);
+        Ok(())
+    }
+
+    /// `[amount_in, amount_out of each hop]` of a swap of exactly `amount_in` along `path`.
+    #[export(unwrap_result)]
+    pub fn get_amounts_out(
+        &self,
+        amount_in: U256,
+        path: Vec<ActorId>,
+    ) -> Result<Vec<U256>, ManagerError> {
+        self.with_state(|st| quote_route(st, &path, amount_in, true))
+            .map(|hops| route_amounts(&hops))
+    }
+
+    /// `[amount_in, amount_out of each hop]` of a swap for exactly `amount_out` along `path`.
+    #[export(unwrap_result)]
+    pub fn get_amounts_in(
+        &self,
+        amount_out: U256,
+        path: Vec<ActorId>,
+    ) -> Result<Vec<U256>, ManagerError> {
+        self.with_state(|st| quote_route(st, &path, amount_out, false))
+            .map(|hops| route_amounts(&hops))
+    }
+
+    #[export]
+    pub fn get_pool(&self, token_a: ActorId, token_b: ActorId) -> Option<PoolInfo> {
+        let key = sort_tokens(token_a, token_b).ok()?;
+        self.with_state(|st| st.pools.get(&key).map(|pool| pool.info(key)))
+    }
+
+    #[export]
+    pub fn pools(&self) -> Vec<PoolInfo> {
+        self.with_state(|st| st.pools.iter().map(|(key, pool)| pool.info(*key)).collect())
+    }
+
+    /// LP tokens of `account` in the pool of `token_a` and `token_b`.
+    #[export]
+    pub fn lp_balance_of(&self, token_a: ActorId, token_b: ActorId, account: ActorId) -> U256 {
+        let Ok(key) = sort_tokens(token_a, token_b) else {
+            return U256::zero();
+        };
+        self.with_state(|st| {
+            st.pools
+                .get(&key)
+                .map(|pool| pool.balance_of(&account))
+                .unwrap_or_default()
+        })
+    }
+
+    #[export]
+    pub fn credit_of(&self, user: ActorId, token: ActorId) -> U256 {
+        self.with_state(|st| st.credits.get(&(user, token)).copied().unwrap_or_default())
+    }
+
+    #[export]
+    pub fn pending_transfers(&self) -> Vec<(MessageId, PendingTransfer)> {
+        self.tracker
+            .borrow()
+            .pending
+            .iter()
+            .map(|(id, transfer)| (*id, transfer.clone()))
+            .collect()
+    }
+
+    #[export]
+    pub fn admin(&self) -> ActorId {
+        self.with_state(|st| st.admin_id)
+    }
+
+    fn emit_manager_event(&self, event: ManagerEvent) -> Result<(), ManagerError> {
+        self.emit_event(event).map_err(|_| ManagerError::EventError)
+    }
+}
@@ -0,0 +1,186 @@
+use crate::services::manager::{
+    ManagerError,
+    math::{self, MAX_SWAP_FEE_BPS, MINIMUM_LIQUIDITY},
+};
+use sails_rs::{collections::HashMap, prelude::*};
+
+/// A constant product pool kept by the manager, with its LP ledger.
+///
+/// The manager holds the tokens of every pool, so a pool is only its reserves:
+/// moving value between pools is a change of numbers, not a transfer.
+#[derive(Debug, Default)]
+pub struct Pool {
+    pub reserve0: U256,
+    pub reserve1: U256,
+    pub swap_fee_bps: u64,
+    pub total_supply: U256,
+    /// LP tokens by holder. `MINIMUM_LIQUIDITY` of the first deposit belongs to the zero address.
+    pub balances: HashMap<ActorId, U256>,
+}
+
+/// A pool as returned by the queries.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PoolInfo {
+    pub token0: ActorId,
+    pub token1: ActorId,
+    pub reserve0: U256,
+    pub reserve1: U256,
+    pub swap_fee_bps: u64,
+    pub total_supply: U256,
+}
+
+impl Pool {
+    pub fn new(swap_fee_bps: u64) -> Result<Self, ManagerError> {
+        check_swap_fee(swap_fee_bps)?;
+        Ok(Self {
+            swap_fee_bps,
+            ..Default::default()
+        })
+    }
+
+    pub fn info(&self, (token0, token1): (ActorId, ActorId)) -> PoolInfo {
+        PoolInfo {
+            token0,
+            token1,
+            reserve0: self.reserve0,
+            reserve1: self.reserve1,
+            swap_fee_bps: self.swap_fee_bps,
+            total_supply: self.total_supply,
+        }
+    }
+
+    pub fn set_swap_fee(&mut self, swap_fee_bps: u64) -> Result<(), ManagerError> {
+        check_swap_fee(swap_fee_bps)?;
+        self.swap_fee_bps = swap_fee_bps;
+        Ok(())
+    }
+
+    /// `(reserve_in, reserve_out)` of a swap in the given direction.
+    pub fn reserves(&self, zero_for_one: bool) -> (U256, U256) {
+        if zero_for_one {
+            (self.reserve0, self.reserve1)
+        } else {
+            (self.reserve1, self.reserve0)
+        }
+    }
+
+    pub fn balance_of(&self, account: &ActorId) -> U256 {
+        self.balances.get(account).copied().unwrap_or_default()
+    }
+
+    /// Adds the amounts to the reserves and mints the LP tokens for them to `to`.
+    pub fn mint(
+        &mut self,
+        to: ActorId,
+        amount0: U256,
+        amount1: U256,
+    ) -> Result<U256, ManagerError> {
+        let liquidity = math::liquidity_for(
+            self.reserve0,
+            self.reserve1,
+            amount0,
+            amount1,
+            self.total_supply,
+        )?;
+        let locked = if self.total_supply.is_zero() {
+            U256::from(MINIMUM_LIQUIDITY)
+        } else {
+            U256::zero()
+        };
+        // Everything is checked before the pool changes
+        let total_supply = self
+            .total_supply
+            .checked_add(liquidity)
+            .and_then(|supply| supply.checked_add(locked))
+            .ok_or(ManagerError::Overflow)?;
+        let reserve0 = self
+            .reserve0
+            .checked_add(amount0)
+            .ok_or(ManagerError::Overflow)?;
+        let reserve1 = self
+            .reserve1
+            .checked_add(amount1)
+            .ok_or(ManagerError::Overflow)?;
+        if !locked.is_zero() {
+            self.credit(ActorId::zero(), locked);
+        }
+        self.credit(to, liquidity);
+        self.total_supply = total_supply;
+        self.reserve0 = reserve0;
+        self.reserve1 = reserve1;
+        Ok(liquidity)
+    }
+
+    /// Burns `liquidity` of `owner` and takes its share of both reserves out of the pool.
+    pub fn burn(&mut self, owner: ActorId, liquidity: U256) -> Result<(U256, U256), ManagerError> {
+        if liquidity.is_zero() {
+            return Err(ManagerError::ZeroLiquidity);
+        }
+        if self.balance_of(&owner) < liquidity {
+            return Err(ManagerError::InsufficientLpBalance);
+        }
+        let amount0 = liquidity
+            .checked_mul(self.reserve0)
+            .ok_or(ManagerError::Overflow)?
+            / self.total_supply;
+        let amount1 = liquidity
+            .checked_mul(self.reserve1)
+            .ok_or(ManagerError::Overflow)?
+            / self.total_supply;
+        if amount0.is_zero() || amount1.is_zero() {
+            return Err(ManagerError::InsufficientLiquidityBurned);
+        }
+        self.debit(owner, liquidity);
+        self.total_supply -= liquidity;
+        self.reserve0 -= amount0;
+        self.reserve1 -= amount1;
+        Ok((amount0, amount1))
+    }
+
+    pub fn transfer(
+        &mut self,
+        from: ActorId,
+        to: ActorId,
+        amount: U256,
+    ) -> Result<(), ManagerError> {
+        if self.balance_of(&from) < amount {
+            return Err(ManagerError::InsufficientLpBalance);
+        }
+        self.debit(from, amount);
+        self.credit(to, amount);
+        Ok(())
+    }
+
+    /// Moves `amount_in` into and `amount_out` out of the reserves.
+    pub fn apply_swap(&mut self, zero_for_one: bool, amount_in: U256, amount_out: U256) {
+        if zero_for_one {
+            self.reserve0 += amount_in;
+            self.reserve1 -= amount_out;
+        } else {
+            self.reserve1 += amount_in;
+            self.reserve0 -= amount_out;
+        }
+    }
+
+    /// A balance never exceeds the total supply, so it can't overflow.
+    fn credit(&mut self, account: ActorId, amount: U256) {
+        *self.balances.entry(account).or_default() += amount;
+    }
+
+    /// The caller checks that `account` holds `amount`.
+    fn debit(&mut self, account: ActorId, amount: U256) {
+        let balance = self.balance_of(&account);
+        if balance == amount {
+            self.balances.remove(&account);
+        } else {
+            self.balances.insert(account, balance - amount);
+        }
+    }
+}
+
+fn check_swap_fee(swap_fee_bps: u64) -> Result<(), ManagerError> {
+    if swap_fee_bps > MAX_SWAP_FEE_BPS {
+        return Err(ManagerError::InvalidSwapFee);
+    }
+    Ok(())
+}
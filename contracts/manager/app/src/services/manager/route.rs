@@ -0,0 +1,110 @@
+use crate::services::manager::{ManagerError, State, math};
+use sails_rs::prelude::*;
+
+/// One pool of a route, with what it takes in and gives out.
+#[derive(Debug, Clone, Copy)]
+pub struct Hop {
+    pub key: (ActorId, ActorId),
+    pub zero_for_one: bool,
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+/// Orders the tokens of a pool, the pool's key.
+pub fn sort_tokens(token_a: ActorId, token_b: ActorId) -> Result<(ActorId, ActorId), ManagerError> {
+    if token_a == token_b {
+        return Err(ManagerError::IdenticalTokens);
+    }
+    if token_a.is_zero() || token_b.is_zero() {
+        return Err(ManagerError::ZeroAddress);
+    }
+    Ok(if token_a < token_b {
+        (token_a, token_b)
+    } else {
+        (token_b, token_a)
+    })
+}
+
+/// Quotes a swap along `path` against the current reserves. `amount` is the input
+/// when `exact_in` and the output otherwise.
+///
+/// A pool can appear only once in a route: every hop is quoted against the reserves
+/// before the swap, so all of them can be applied together with `apply_route`.
+pub fn quote_route(
+    st: &State,
+    path: &[ActorId],
+    amount: U256,
+    exact_in: bool,
+) -> Result<Vec<Hop>, ManagerError> {
+    if path.len() < 2 {
+        return Err(ManagerError::InvalidPath);
+    }
+    let mut keys: Vec<(ActorId, ActorId)> = Vec::with_capacity(path.len() - 1);
+    for tokens in path.windows(2) {
+        let key = sort_tokens(tokens[0], tokens[1])?;
+        if keys.contains(&key) {
+            return Err(ManagerError::InvalidPath);
+        }
+        keys.push(key);
+    }
+
+    let mut hops = Vec::with_capacity(keys.len());
+    let mut amount = amount;
+    let mut quote_hop = |i: usize| -> Result<(), ManagerError> {
+        let key = keys[i];
+        let pool = st.pools.get(&key).ok_or(ManagerError::PoolNotFound)?;
+        let zero_for_one = path[i] == key.0;
+        let (reserve_in, reserve_out) = pool.reserves(zero_for_one);
+        let (amount_in, amount_out) = if exact_in {
+            let amount_out =
+                math::get_amount_out(amount, reserve_in, reserve_out, pool.swap_fee_bps)?;
+            (amount, amount_out)
+        } else {
+            let amount_in =
+                math::get_amount_in(amount, reserve_in, reserve_out, pool.swap_fee_bps)?;
+            (amount_in, amount)
+        };
+        if amount_out.is_zero() {
+            return Err(ManagerError::InsufficientOutputAmount);
+        }
+        reserve_in
+            .checked_add(amount_in)
+            .ok_or(ManagerError::Overflow)?;
+        hops.push(Hop {
+            key,
+            zero_for_one,
+            amount_in,
+            amount_out,
+        });
+        amount = if exact_in { amount_out } else { amount_in };
+        Ok(())
+    };
+
+    if exact_in {
+        (0..keys.len()).try_for_each(&mut quote_hop)?;
+    } else {
+        // Backwards from the output, each hop's input is the output of the one before
+        (0..keys.len()).rev().try_for_each(&mut quote_hop)?;
+        hops.reverse();
+    }
+    Ok(hops)
+}
+
+/// Moves the reserves of every pool of a route quoted by `quote_route` in the same state.
+/// The tokens between the hops never leave the manager.
+pub fn apply_route(st: &mut State, hops: &[Hop]) {
+    for hop in hops {
+        if let Some(pool) = st.pools.get_mut(&hop.key) {
+            pool.apply_swap(hop.zero_for_one, hop.amount_in, hop.amount_out);
+        }
+    }
+}
+
+/// `[amount_in, amount_out of each hop]`, as the router returns them.
+pub fn route_amounts(hops: &[Hop]) -> Vec<U256> {
+    hops.first()
+        .map(|hop| hop.amount_in)
+        .into_iter()
+        .chain(hops.iter().map(|hop| hop.amount_out))
+        .collect()
+}
@@ -0,0 +1,8 @@
+fn main() {
+    if let Some((_, wasm_path)) = sails_rs::build_wasm() {
+        sails_rs::ClientBuilder::<manager_app::ManagerProgram>::from_wasm_path(
+            wasm_path.with_extension(""),
+        )
+        .build_idl();
+    }
+}
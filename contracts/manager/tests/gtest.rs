@@ -0,0 +1,312 @@
+use extended_vft_client::vft::{Vft, VftImpl};
+use extended_vft_client::{ExtendedVftClient, ExtendedVftClientCtors, ExtendedVftClientProgram};
+use manager_client::{
+    manager::{Manager as _, ManagerImpl},
+    Config, Manager as ManagerClient, ManagerCtors, ManagerProgram,
+};
+use sails_rs::gtest::System;
+use sails_rs::{client::*, prelude::*};
+
+const ADMIN_ID: u64 = 1;
+const USER_ID: u64 = 2;
+const LP_ID: u64 = 3;
+const ONE_VARA: u128 = 1_000_000_000_000;
+
+const FEE_BPS: u64 = 30;
+
+type Token = Service<VftImpl, GtestEnv>;
+
+struct Setup {
+    env: GtestEnv,
+    manager: Service<ManagerImpl, GtestEnv>,
+    tokens: Vec<Token>,
+}
+
+fn default_config() -> Config {
+    Config {
+        gas_for_token_ops: 10_000_000_000,
+        gas_for_reply_deposit: 10_000_000_000,
+        reply_timeout: 100,
+        gas_for_full_tx: 100_000_000_000,
+    }
+}
+
+fn deadline(env: &GtestEnv) -> u64 {
+    env.system().block_timestamp() + 100_000_000
+}
+
+/// Deploys `count` tokens and the manager. The LP and the user get `balance`
+/// of every token and approve the manager.
+async fn setup(count: usize, balance: U256) -> Setup {
+    let system = System::new();
+    let admin = ActorId::from(ADMIN_ID);
+    let user = ActorId::from(USER_ID);
+    let lp = ActorId::from(LP_ID);
+    system.mint_to(admin, 1000 * ONE_VARA);
+    system.mint_to(user, 1000 * ONE_VARA);
+    system.mint_to(lp, 1000 * ONE_VARA);
+    let env = GtestEnv::new(system, admin);
+
+    let manager_code_id = env.system().submit_code(manager::WASM_BINARY);
+    let release_path = "../target/wasm32-gear/release/extended_vft.opt.wasm";
+    let debug_path = "../target/wasm32-gear/debug/extended_vft.opt.wasm";
+    let wasm_path = if std::path::Path::new(release_path).exists() {
+        release_path
+    } else {
+        debug_path
+    };
+    let token_code_id = env.system().submit_code_file(wasm_path);
+
+    let manager_program = env
+        .deploy::<ManagerProgram>(manager_code_id, b"salt".to_vec())
+        .new(default_config(), admin)
+        .await
+        .unwrap();
+    let manager = manager_program.manager();
+
+    let mut tokens = Vec::new();
+    for i in 0..count {
+        let name = format!("Token{i}");
+        let program = env
+            .deploy::<ExtendedVftClientProgram>(token_code_id, name.as_bytes().to_vec())
+            .new(name.clone(), name, 6)
+            .await
+            .unwrap();
+        let mut token = program.vft();
+        for actor in [lp, user] {
+            token.mint(actor, balance).await.unwrap();
+            token
+                .approve(manager.actor_id(), balance)
+                .with_params(|p| p.with_actor_id(actor))
+                .await
+                .unwrap();
+        }
+        tokens.push(token);
+    }
+
+    Setup {
+        env,
+        manager,
+        tokens,
+    }
+}
+
+impl Setup {
+    fn token(&self, i: usize) -> ActorId {
+        self.tokens[i].actor_id()
+    }
+
+    async fn balance(&self, i: usize, account: ActorId) -> U256 {
+        self.tokens[i].balance_of(account).await.unwrap()
+    }
+
+    /// Creates the pool of tokens `a` and `b` and adds `amount` of both from the LP.
+    async fn pool_with_liquidity(&mut self, a: usize, b: usize, amount: U256) {
+        let (token_a, token_b) = (self.token(a), self.token(b));
+        self.manager
+            .create_pool(token_a, token_b, FEE_BPS)
+            .await
+            .unwrap();
+        let deadline = deadline(&self.env);
+        self.manager
+            .add_liquidity(
+                token_a,
+                token_b,
+                amount,
+                amount,
+                U256::zero(),
+                U256::zero(),
+                deadline,
+            )
+            .with_params(|p| p.with_actor_id(LP_ID.into()))
+            .await
+            .unwrap();
+    }
+}
+
+#[tokio::test]
+async fn manager_adds_and_removes_liquidity() {
+    let balance = U256::from(1_000_000_000u64);
+    let mut setup = setup(2, balance).await;
+    let lp = ActorId::from(LP_ID);
+    let amount = U256::from(100_000_000u64);
+    let (token0, token1) = (setup.token(0), setup.token(1));
+
+    setup.pool_with_liquidity(0, 1, amount).await;
+    let pool = setup
+        .manager
+        .get_pool(token1, token0)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        (pool.reserve0, pool.reserve1, pool.total_supply),
+        (amount, amount, amount)
+    );
+    let liquidity = setup
+        .manager
+        .lp_balance_of(token0, token1, lp)
+        .await
+        .unwrap();
+    // The first 1000 stay locked in the pool
+    assert_eq!(liquidity, amount - 1000);
+    assert!(setup
+        .manager
+        .create_pool(token1, token0, FEE_BPS)
+        .await
+        .is_err());
+
+    // Only what keeps the price is taken
+    let deadline = deadline(&setup.env);
+    setup
+        .manager
+        .add_liquidity(
+            token0,
+            token1,
+            amount,
+            amount * 2,
+            U256::zero(),
+            U256::zero(),
+            deadline,
+        )
+        .with_params(|p| p.with_actor_id(lp))
+        .await
+        .unwrap();
+    assert_eq!(setup.balance(0, lp).await, balance - amount * 2);
+    assert_eq!(setup.balance(1, lp).await, balance - amount * 2);
+
+    let liquidity = setup
+        .manager
+        .lp_balance_of(token0, token1, lp)
+        .await
+        .unwrap();
+    setup
+        .manager
+        .remove_liquidity(
+            token0,
+            token1,
+            liquidity,
+            U256::zero(),
+            U256::zero(),
+            deadline,
+        )
+        .with_params(|p| p.with_actor_id(lp))
+        .await
+        .unwrap();
+    assert!(setup
+        .manager
+        .lp_balance_of(token0, token1, lp)
+        .await
+        .unwrap()
+        .is_zero());
+    // Everything but the locked liquidity's share came back
+    assert_eq!(setup.balance(0, lp).await, balance - 1000);
+    assert_eq!(setup.balance(1, lp).await, balance - 1000);
+}
+
+#[tokio::test]
+async fn multi_hop_swap_moves_only_the_ends() {
+    let balance = U256::from(1_000_000_000u64);
+    let mut setup = setup(3, balance).await;
+    let user = ActorId::from(USER_ID);
+    let amount = U256::from(100_000_000u64);
+    setup.pool_with_liquidity(0, 1, amount).await;
+    setup.pool_with_liquidity(1, 2, amount).await;
+    let manager_id = setup.manager.actor_id();
+    let middle_held = setup.balance(1, manager_id).await;
+
+    let path = vec![setup.token(0), setup.token(1), setup.token(2)];
+    let amount_in = U256::from(1_000_000u64);
+    let amounts = setup
+        .manager
+        .get_amounts_out(amount_in, path.clone())
+        .await
+        .unwrap();
+    assert_eq!(amounts.len(), 3);
+    let deadline = deadline(&setup.env);
+    setup
+        .manager
+        .swap_exact_tokens_for_tokens(amount_in, amounts[2], path.clone(), user, deadline)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+
+    assert_eq!(setup.balance(0, user).await, balance - amount_in);
+    assert_eq!(setup.balance(1, user).await, balance);
+    assert_eq!(setup.balance(2, user).await, balance + amounts[2]);
+    // The middle token never moved, only the reserves of both pools did
+    assert_eq!(setup.balance(1, manager_id).await, middle_held);
+    let first = setup
+        .manager
+        .get_pool(path[0], path[1])
+        .await
+        .unwrap()
+        .unwrap();
+    let second = setup
+        .manager
+        .get_pool(path[1], path[2])
+        .await
+        .unwrap()
+        .unwrap();
+    let reserves_of = |pool: &manager_client::PoolInfo, token: ActorId| {
+        if pool.token0 == token {
+            pool.reserve0
+        } else {
+            pool.reserve1
+        }
+    };
+    assert_eq!(reserves_of(&first, path[0]), amount + amount_in);
+    assert_eq!(reserves_of(&first, path[1]), amount - amounts[1]);
+    assert_eq!(reserves_of(&second, path[1]), amount + amounts[1]);
+    assert_eq!(reserves_of(&second, path[2]), amount - amounts[2]);
+}
+
+#[tokio::test]
+async fn exact_output_route_and_bad_paths() {
+    let balance = U256::from(1_000_000_000u64);
+    let mut setup = setup(3, balance).await;
+    let user = ActorId::from(USER_ID);
+    let amount = U256::from(100_000_000u64);
+    setup.pool_with_liquidity(0, 1, amount).await;
+    setup.pool_with_liquidity(1, 2, amount).await;
+    let (token0, token1, token2) = (setup.token(0), setup.token(1), setup.token(2));
+    let deadline = deadline(&setup.env);
+
+    let amount_out = U256::from(1_000_000u64);
+    let path = vec![token2, token1, token0];
+    let amounts = setup
+        .manager
+        .get_amounts_in(amount_out, path.clone())
+        .await
+        .unwrap();
+    let res = setup
+        .manager
+        .swap_tokens_for_exact_tokens(amount_out, amounts[0] - 1, path.clone(), user, deadline)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    setup
+        .manager
+        .swap_tokens_for_exact_tokens(amount_out, amounts[0], path, user, deadline)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_eq!(setup.balance(2, user).await, balance - amounts[0]);
+    assert_eq!(setup.balance(0, user).await, balance + amount_out);
+
+    // A route can't use a pool twice nor a pool that doesn't exist
+    for path in [
+        vec![token0, token1, token0],
+        vec![token0, token2],
+        vec![token0],
+    ] {
+        let res = setup
+            .manager
+            .swap_exact_tokens_for_tokens(amount_out, U256::zero(), path, user, deadline)
+            .with_params(|p| p.with_actor_id(user))
+            .await;
+        assert!(res.is_err());
+    }
+    assert_eq!(setup.balance(0, user).await, balance + amount_out);
+    assert_eq!(setup.balance(1, user).await, balance);
+}
@@ -3,7 +3,7 @@
 pub mod services;
 use sails_rs::{cell::RefCell, prelude::*};
 use services::lp_token::{LpService, state::LpTokenState};
-use services::pair::{self, Config, PairService, msg_tracker::MessageTracker};
+use services::pair::{self, AmpRamp, Config, Curve, PairService, msg_tracker::MessageTracker};
 
 pub struct PairProgram {
     admins: RefCell<Vec<ActorId>>,
@@ -12,17 +12,19 @@ pub struct PairProgram {
     lp: LpTokenState,
 }
 
-#[sails_rs::program]
 impl PairProgram {
-    // Program's constructor
-    pub fn new(
+    fn init(
         config: Config,
         token0: ActorId,
         token1: ActorId,
         fee_to: ActorId,
         treasury_id: ActorId,
         admin_id: ActorId,
+        curve: Curve,
     ) -> Self {
+        if !config.has_valid_swap_fee() {
+            panic!("Swap fee must not exceed 10%")
+        }
         let lp = LpTokenState::new("LP".into(), "LP".into(), 18);
         let factory_id = sails_rs::gstd::msg::source();
 
@@ -32,7 +34,11 @@ impl PairProgram {
             fee_to,
             factory_id,
             treasury_id,
+            swap_fee_bps: config.swap_fee_bps(),
+            treasury_fee_bps: pair::TREASURY_FEE_BPS,
+            protocol_fee_divisor: pair::DEFAULT_PROTOCOL_FEE_DIVISOR,
             config,
+            curve,
             ..Default::default()
         };
         let admins = vec![admin_id];
@@ -44,6 +50,52 @@ impl PairProgram {
             admins: RefCell::new(admins),
         }
     }
+}
+
+#[sails_rs::program]
+impl PairProgram {
+    // Program's constructor
+    pub fn new(
+        config: Config,
+        token0: ActorId,
+        token1: ActorId,
+        fee_to: ActorId,
+        treasury_id: ActorId,
+        admin_id: ActorId,
+    ) -> Self {
+        Self::init(
+            config,
+            token0,
+            token1,
+            fee_to,
+            treasury_id,
+            admin_id,
+            Curve::ConstantProduct,
+        )
+    }
+
+    // Constructor of a StableSwap pair with amplification coefficient `amp`
+    pub fn new_stable(
+        config: Config,
+        token0: ActorId,
+        token1: ActorId,
+        fee_to: ActorId,
+        treasury_id: ActorId,
+        admin_id: ActorId,
+        amp: u64,
+    ) -> Self {
+        let ramp = AmpRamp::new(amp, sails_rs::gstd::exec::block_timestamp())
+            .unwrap_or_else(|_| panic!("Invalid amplification coefficient"));
+        Self::init(
+            config,
+            token0,
+            token1,
+            fee_to,
+            treasury_id,
+            admin_id,
+            Curve::Stable(ramp),
+        )
+    }
 
     pub fn pair(&self) -> PairService<'_> {
         PairService::new(&self.pair_state, &self.tracker, &self.lp, &self.admins)
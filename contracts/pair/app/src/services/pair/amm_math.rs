@@ -4,6 +4,13 @@ use sails_rs::{U256, prelude::*};
 pub const MINIMUM_LIQUIDITY: u64 = 1000;
 pub const FEE_DENOM_BPS: u64 = 10_000; // 100.00%
 pub const TREASURY_FEE_BPS: u64 = 5; // 0.05%
+pub const DEFAULT_SWAP_FEE_BPS: u64 = 30; // 0.30%
+pub const MAX_SWAP_FEE_BPS: u64 = 1_000; // 10.00%
+pub const MAX_TREASURY_FEE_BPS: u64 = 100; // 1.00%
+/// The protocol gets `1 / divisor` of the swap fee growth, as in Uniswap V2 (1/6).
+pub const DEFAULT_PROTOCOL_FEE_DIVISOR: u64 = 6;
+/// At most half of the swap fee growth goes to the protocol.
+pub const MIN_PROTOCOL_FEE_DIVISOR: u64 = 2;
 
 /// Calculates the amount of token B needed for a given amount of token A based on current reserves.
 /// Formula: amount_b = (amount_a * reserve_b) / reserve_a (floor division).
@@ -73,8 +80,8 @@ pub fn calculate_optimal_amounts(
 /// 2. We compute `treasury_fee = amount_in_total * treasury_fee_bps / 10_000`.
 /// 3. Remaining part goes into the pool:
 ///    amount_in_for_pool = amount_in_total - treasury_fee
-/// 4. `get_amount_out(amount_in_for_pool, ...)` is used with the standard
-///    Uniswap 0.3% fee math (997/1000).
+/// 4. `get_amount_out(amount_in_for_pool, ...)` is used with the pair's
+///    swap fee (`swap_fee_bps`, e.g. 30 = 0.3%, the 997/1000 multiplier).
 ///
 /// If `treasury_fee_bps == 0`, then:
 ///   amount_in_for_pool == amount_in_total and treasury_fee == 0.
@@ -87,8 +94,23 @@ pub fn get_amount_out_with_treasury(
     amount_in_total: U256,
     reserve_in: U256,
     reserve_out: U256,
+    swap_fee_bps: u64,
     treasury_fee_bps: u64,
 ) -> Result<(U256, U256, U256), PairError> {
+    let (amount_in_for_pool, treasury_fee) = split_treasury_fee(amount_in_total, treasury_fee_bps)?;
+
+    // Standard Uniswap V2 output calculation (internal swap fee)
+    let amount_out = get_amount_out(amount_in_for_pool, reserve_in, reserve_out, swap_fee_bps)?;
+
+    Ok((amount_in_for_pool, amount_out, treasury_fee))
+}
+
+/// Splits `amount_in_total` into the part entering the pool and the treasury fee,
+/// `amount_in_total * treasury_fee_bps / 10_000`. Step 2-3 of `get_amount_out_with_treasury`.
+pub fn split_treasury_fee(
+    amount_in_total: U256,
+    treasury_fee_bps: u64,
+) -> Result<(U256, U256), PairError> {
     if amount_in_total.is_zero() {
         return Err(PairError::InsufficientAmount);
     }
@@ -106,7 +128,7 @@ pub fn get_amount_out_with_treasury(
             .ok_or(PairError::Overflow)?
     };
 
-    // Portion that actually enters the pool and participates in x*y=k and 0.3% fee logic
+    // Portion that actually enters the pool and participates in x*y=k and swap fee logic
     let amount_in_for_pool = amount_in_total
         .checked_sub(treasury_fee)
         .ok_or(PairError::Overflow)?;
@@ -115,18 +137,15 @@ pub fn get_amount_out_with_treasury(
         return Err(PairError::InsufficientAmount);
     }
 
-    // Standard Uniswap V2 output calculation (internal 0.3% fee)
-    let amount_out = get_amount_out(amount_in_for_pool, reserve_in, reserve_out)?;
-
-    Ok((amount_in_for_pool, amount_out, treasury_fee))
+    Ok((amount_in_for_pool, treasury_fee))
 }
 
 /// Calculates required input amount for an ExactOutput swap, taking into account
 /// an additional treasury fee in the input token.
 ///
 /// 1. We first compute how much must enter the pool using standard Uniswap math:
-///    amount_in_for_pool = get_amount_in(amount_out, reserve_in, reserve_out)
-///    This uses the internal 0.3% swap fee (997/1000).
+///    amount_in_for_pool = get_amount_in(amount_out, reserve_in, reserve_out, swap_fee_bps)
+///    This uses the pair's internal swap fee.
 ///
 /// 2. If `treasury_fee_bps > 0`, we solve:
 ///    amount_in_for_pool = amount_in_total * (DENOM - treasury_fee_bps) / DENOM
@@ -147,18 +166,30 @@ pub fn get_amount_in_with_treasury(
     amount_out: U256,
     reserve_in: U256,
     reserve_out: U256,
+    swap_fee_bps: u64,
     treasury_fee_bps: u64,
 ) -> Result<(U256, U256, U256), PairError> {
-    // First, compute the pool-side requirement using Uniswap's 0.3% math
-    let amount_in_for_pool = get_amount_in(amount_out, reserve_in, reserve_out)?;
+    // First, compute the pool-side requirement using Uniswap's math with the pair's fee
+    let amount_in_for_pool = get_amount_in(amount_out, reserve_in, reserve_out, swap_fee_bps)?;
+
+    let (amount_in_total, treasury_fee) = add_treasury_fee(amount_in_for_pool, treasury_fee_bps)?;
 
+    Ok((amount_in_for_pool, amount_in_total, treasury_fee))
+}
+
+/// Returns `(amount_in_total, treasury_fee)` such that `amount_in_for_pool` is left
+/// once the treasury fee is taken. Step 2-3 of `get_amount_in_with_treasury`.
+pub fn add_treasury_fee(
+    amount_in_for_pool: U256,
+    treasury_fee_bps: u64,
+) -> Result<(U256, U256), PairError> {
     if amount_in_for_pool.is_zero() {
         return Err(PairError::InsufficientAmount);
     }
 
     if treasury_fee_bps == 0 {
         // Treasury disabled → user pays exactly what the pool needs
-        return Ok((amount_in_for_pool, amount_in_for_pool, U256::zero()));
+        return Ok((amount_in_for_pool, U256::zero()));
     }
 
     let denom = U256::from(FEE_DENOM_BPS);
@@ -188,7 +219,7 @@ pub fn get_amount_in_with_treasury(
         .checked_sub(amount_in_for_pool)
         .ok_or(PairError::Overflow)?;
 
-    Ok((amount_in_for_pool, amount_in_total, treasury_fee))
+    Ok((amount_in_total, treasury_fee))
 }
 
 /// Calculates the liquidity amount to mint based on added token amounts and current pool state.
@@ -250,14 +281,74 @@ pub fn calculate_liquidity(
     Ok(liquidity)
 }
 
+/// Calculates how much of `amount_in` to swap so that the rest and the swap output
+/// can be added as liquidity at the new reserve ratio, leaving (almost) nothing behind.
+///
+/// With `g = (1 - swap_fee) * (1 - treasury_fee)` and `t = 1 - treasury_fee`, the swapped
+/// amount `s` solves `(amount_in - s) * reserve_in = s * g * (reserve_in + s * t)`:
+///   s = (sqrt((1 + g)^2 * r^2 + 4 * g * t * amount_in * r) - (1 + g) * r) / (2 * g * t)
+/// For a 30 bps fee and no treasury fee this is the usual
+/// `(sqrt(r * (3988009 * r + 3988000 * amount_in)) - 1997 * r) / 1994`.
+/// `g` is rounded down to basis points, so a few units of dust may stay in the pool.
+/// # Arguments
+/// * `amount_in` - Amount of the single token being added
+/// * `reserve_in` - Reserve of that token in the pool
+/// * `swap_fee_bps` - Swap fee in basis points, below `FEE_DENOM_BPS`
+/// * `treasury_fee_bps` - Treasury fee in basis points charged on the swap input
+pub fn zap_swap_amount(
+    amount_in: U256,
+    reserve_in: U256,
+    swap_fee_bps: u64,
+    treasury_fee_bps: u64,
+) -> Result<U256, PairError> {
+    if amount_in.is_zero() {
+        return Err(PairError::InsufficientAmount);
+    }
+    if reserve_in.is_zero() {
+        return Err(PairError::InsufficientLiquidity);
+    }
+    if swap_fee_bps >= FEE_DENOM_BPS || treasury_fee_bps >= FEE_DENOM_BPS {
+        return Err(PairError::InvalidSwapFee);
+    }
+
+    let denom = FEE_DENOM_BPS;
+    let t = denom - treasury_fee_bps;
+    let g = (denom - swap_fee_bps) * t / denom;
+    if g == 0 {
+        return Err(PairError::InvalidSwapFee);
+    }
+
+    let one_plus_g = U256::from(denom + g);
+    let b = one_plus_g
+        .checked_mul(reserve_in)
+        .ok_or(PairError::Overflow)?;
+    let discriminant = b
+        .checked_mul(b)
+        .and_then(|b2| {
+            U256::from(4 * g * t)
+                .checked_mul(amount_in)?
+                .checked_mul(reserve_in)?
+                .checked_add(b2)
+        })
+        .ok_or(PairError::Overflow)?;
+
+    let numerator = (discriminant.integer_sqrt() - b)
+        .checked_mul(U256::from(denom))
+        .ok_or(PairError::Overflow)?;
+    let swap_amount = numerator / U256::from(2 * g * t);
+
+    Ok(swap_amount.min(amount_in))
+}
+
 /// Calculates the maximum output amount of the other asset given an input amount and pair reserves.
-/// This accounts for a 0.3% fee (997/1000 multiplier).
-/// Formula: amount_out = (amount_in * 997 * reserve_out) / (reserve_in * 1000 + amount_in * 997)
+/// This accounts for a `swap_fee_bps` fee (for 30 bps, the classic 997/1000 multiplier).
+/// Formula: amount_out = (amount_in * (10_000 - fee) * reserve_out) / (reserve_in * 10_000 + amount_in * (10_000 - fee))
 /// Uses floor division
 /// # Arguments
 /// * `amount_in` - Amount of input asset being swapped
 /// * `reserve_in` - Reserve of input asset in the pool
 /// * `reserve_out` - Reserve of output asset in the pool
+/// * `swap_fee_bps` - Swap fee in basis points, below `FEE_DENOM_BPS`
 /// # Returns
 /// * `Ok(U256)` - Calculated output amount
 /// * `Err(PairError)` - If input is zero, insufficient liquidity, or arithmetic overflows
@@ -269,6 +360,7 @@ pub fn get_amount_out(
     amount_in: U256,
     reserve_in: U256,
     reserve_out: U256,
+    swap_fee_bps: u64,
 ) -> Result<U256, PairError> {
     if amount_in.is_zero() {
         return Err(PairError::InsufficientAmount);
@@ -277,10 +369,9 @@ pub fn get_amount_out(
         return Err(PairError::InsufficientLiquidity);
     }
 
-    let fee_multiplier = U256::from(997u64);
-    let thousand = U256::from(1000u64);
+    let (fee_multiplier, denom) = fee_factors(swap_fee_bps)?;
 
-    // amount_in_with_fee = amount_in * 997
+    // amount_in_with_fee = amount_in * (10_000 - fee)
     let amount_in_with_fee = amount_in
         .checked_mul(fee_multiplier)
         .ok_or(PairError::Overflow)?;
@@ -290,10 +381,8 @@ pub fn get_amount_out(
         .checked_mul(reserve_out)
         .ok_or(PairError::Overflow)?;
 
-    // denominator = reserve_in * 1000 + amount_in_with_fee
-    let denominator_part1 = reserve_in
-        .checked_mul(thousand)
-        .ok_or(PairError::Overflow)?;
+    // denominator = reserve_in * 10_000 + amount_in_with_fee
+    let denominator_part1 = reserve_in.checked_mul(denom).ok_or(PairError::Overflow)?;
     let denominator = denominator_part1
         .checked_add(amount_in_with_fee)
         .ok_or(PairError::Overflow)?;
@@ -303,13 +392,14 @@ pub fn get_amount_out(
 }
 
 /// Calculates the required input amount of an asset given a desired output amount and pair reserves.
-/// This accounts for a 0.3% fee (997/1000 multiplier).
-/// Formula: amount_in = (reserve_in * amount_out * 1000) / (reserve_out - amount_out) * 997) + 1
+/// This accounts for a `swap_fee_bps` fee (for 30 bps, the classic 997/1000 multiplier).
+/// Formula: amount_in = (reserve_in * amount_out * 10_000) / ((reserve_out - amount_out) * (10_000 - fee)) + 1
 /// Uses floor division and adds 1 to ensure sufficient input (ceiling effect).
 /// # Arguments
 /// * `amount_out` - Desired amount of output asset
 /// * `reserve_in` - Reserve of input asset in the pool
 /// * `reserve_out` - Reserve of output asset in the pool
+/// * `swap_fee_bps` - Swap fee in basis points, below `FEE_DENOM_BPS`
 /// # Returns
 /// * `Ok(U256)` - Calculated input amount required
 /// * `Err(PairError)` - If output is zero, insufficient liquidity, output exceeds reserve, or arithmetic overflows
@@ -323,6 +413,7 @@ pub fn get_amount_in(
     amount_out: U256,
     reserve_in: U256,
     reserve_out: U256,
+    swap_fee_bps: u64,
 ) -> Result<U256, PairError> {
     if amount_out.is_zero() {
         return Err(PairError::InsufficientAmount);
@@ -334,18 +425,17 @@ pub fn get_amount_in(
         return Err(PairError::InsufficientLiquidity);
     }
 
-    let fee_multiplier = U256::from(997u64);
-    let thousand = U256::from(1000u64);
+    let (fee_multiplier, denom) = fee_factors(swap_fee_bps)?;
 
-    // numerator = reserve_in * amount_out * 1000
+    // numerator = reserve_in * amount_out * 10_000
     let numerator_part1 = reserve_in
         .checked_mul(amount_out)
         .ok_or(PairError::Overflow)?;
     let numerator = numerator_part1
-        .checked_mul(thousand)
+        .checked_mul(denom)
         .ok_or(PairError::Overflow)?;
 
-    // denominator = (reserve_out - amount_out) * 997
+    // denominator = (reserve_out - amount_out) * (10_000 - fee)
     let denominator_part1 = reserve_out
         .checked_sub(amount_out)
         .ok_or(PairError::Overflow)?;
@@ -361,11 +451,21 @@ pub fn get_amount_in(
     Ok(amount_in)
 }
 
+/// Returns `(10_000 - swap_fee_bps, 10_000)`, the multiplier applied to the input and its denominator.
+fn fee_factors(swap_fee_bps: u64) -> Result<(U256, U256), PairError> {
+    let multiplier = FEE_DENOM_BPS
+        .checked_sub(swap_fee_bps)
+        .filter(|m| *m != 0)
+        .ok_or(PairError::InvalidSwapFee)?;
+    Ok((U256::from(multiplier), U256::from(FEE_DENOM_BPS)))
+}
+
 #[cfg(test)]
 mod prop_tests {
     use crate::pair::amm_math::{
-        FEE_DENOM_BPS, calculate_liquidity, calculate_optimal_amounts, get_amount_in,
-        get_amount_in_with_treasury, get_amount_out, get_amount_out_with_treasury, quote,
+        DEFAULT_SWAP_FEE_BPS, FEE_DENOM_BPS, calculate_liquidity, calculate_optimal_amounts,
+        get_amount_in, get_amount_in_with_treasury, get_amount_out, get_amount_out_with_treasury,
+        quote, zap_swap_amount,
     };
     use proptest::prelude::*;
     use sails_rs::U256;
@@ -432,7 +532,7 @@ mod prop_tests {
             reserve_in in u256_small(),
             reserve_out in u256_small(),
         ) {
-            if let Ok(out) = get_amount_out(amount_in, reserve_in, reserve_out) {
+            if let Ok(out) = get_amount_out(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS) {
                 prop_assert!(out < reserve_out);
             }
         }
@@ -444,7 +544,7 @@ mod prop_tests {
             reserve_in in u256_small(),
             reserve_out in u256_small(),
         ) {
-            if let Ok(out) = get_amount_out(amount_in, reserve_in, reserve_out) {
+            if let Ok(out) = get_amount_out(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS) {
                 prop_assert!(out > U256::zero());
             }
         }
@@ -457,8 +557,8 @@ mod prop_tests {
             reserve_out in (1u64..=u32::MAX as u64).prop_map(U256::from),
         ) {
             if let (Ok(out1), Ok(out2)) = (
-                get_amount_out(amount_in, reserve_in, reserve_out),
-                get_amount_out(amount_in + delta, reserve_in, reserve_out),
+                get_amount_out(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS),
+                get_amount_out(amount_in + delta, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS),
             ) {
                 prop_assert!(out2 >= out1);
             }
@@ -470,7 +570,50 @@ mod prop_tests {
             reserve_in in u256_small(),
             reserve_out in u256_small(),
         ) {
-            prop_assert!(get_amount_out(U256::zero(), reserve_in, reserve_out).is_err());
+            prop_assert!(get_amount_out(U256::zero(), reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS).is_err());
+        }
+
+        /// The default 30 bps fee matches the classic 997/1000 Uniswap V2 formula.
+        #[test]
+        fn prop_get_amount_out_default_fee_matches_v2(
+            amount_in in (1u64..=u32::MAX as u64).prop_map(U256::from),
+            reserve_in in (1u64..=u32::MAX as u64).prop_map(U256::from),
+            reserve_out in (1u64..=u32::MAX as u64).prop_map(U256::from),
+        ) {
+            let with_fee = amount_in * U256::from(997u64);
+            let expected = with_fee * reserve_out / (reserve_in * U256::from(1000u64) + with_fee);
+            prop_assert_eq!(
+                get_amount_out(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS).unwrap(),
+                expected
+            );
+        }
+
+        /// A higher fee tier never yields more output.
+        #[test]
+        fn prop_get_amount_out_non_increasing_in_fee(
+            amount_in in u256_small(),
+            reserve_in in u256_small(),
+            reserve_out in u256_small(),
+            fee_low in 0u64..=30u64,
+            fee_high in 31u64..=1_000u64,
+        ) {
+            if let (Ok(out_low), Ok(out_high)) = (
+                get_amount_out(amount_in, reserve_in, reserve_out, fee_low),
+                get_amount_out(amount_in, reserve_in, reserve_out, fee_high),
+            ) {
+                prop_assert!(out_low >= out_high);
+            }
+        }
+
+        /// A fee of 100% or more is rejected.
+        #[test]
+        fn prop_get_amount_out_full_fee_is_err(
+            amount_in in u256_small(),
+            reserve_in in u256_small(),
+            reserve_out in u256_small(),
+            fee in FEE_DENOM_BPS..=u64::MAX,
+        ) {
+            prop_assert!(get_amount_out(amount_in, reserve_in, reserve_out, fee).is_err());
         }
     }
 
@@ -491,7 +634,7 @@ mod prop_tests {
             };
             if amount_out.is_zero() { return Ok(()); }
 
-            if let Ok(r_in) = get_amount_in(amount_out, reserve_in, reserve_out) {
+            if let Ok(r_in) = get_amount_in(amount_out, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS) {
                 prop_assert!(r_in > U256::zero());
             }
         }
@@ -504,7 +647,7 @@ mod prop_tests {
             reserve_in in u256_small(),
         ) {
             let bad_out = reserve_out + excess;
-            prop_assert!(get_amount_in(bad_out, reserve_in, reserve_out).is_err());
+            prop_assert!(get_amount_in(bad_out, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS).is_err());
         }
 
         /// Round-trip: get_amount_in then get_amount_out must yield ≥ amount_out.
@@ -523,8 +666,8 @@ mod prop_tests {
                 amount_out
             };
 
-            if let Ok(amount_in) = get_amount_in(amount_out, reserve_in, reserve_out)
-                && let Ok(out_check) = get_amount_out(amount_in, reserve_in, reserve_out) {
+            if let Ok(amount_in) = get_amount_in(amount_out, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS)
+                && let Ok(out_check) = get_amount_out(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS) {
                     // Due to ceiling in get_amount_in, out_check must be >= amount_out
                     prop_assert!(out_check >= amount_out,
                         "round-trip failed: desired={}, got={}", amount_out, out_check);
@@ -545,7 +688,7 @@ mod prop_tests {
             treasury_bps in 0u64..=FEE_DENOM_BPS,
         ) {
             if let Ok((amount_in_for_pool, _amount_out, treasury_fee)) =
-                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, treasury_bps)
+                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS, treasury_bps)
             {
                 let reconstructed = amount_in_for_pool
                     .checked_add(treasury_fee)
@@ -563,7 +706,7 @@ mod prop_tests {
             reserve_out in u256_small(),
         ) {
             if let Ok((_pool, _out, fee)) =
-                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, 0)
+                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS, 0)
             {
                 prop_assert_eq!(fee, U256::zero());
             }
@@ -578,7 +721,7 @@ mod prop_tests {
             treasury_bps in 0u64..FEE_DENOM_BPS,
         ) {
             if let Ok((_pool, out, _fee)) =
-                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, treasury_bps)
+                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS, treasury_bps)
             {
                 prop_assert!(out < reserve_out);
             }
@@ -594,8 +737,8 @@ mod prop_tests {
             bps_high in 51u64..=500u64,
         ) {
             if let (Ok((_, out_low, _)), Ok((_, out_high, _))) = (
-                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, bps_low),
-                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, bps_high),
+                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS, bps_low),
+                get_amount_out_with_treasury(amount_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS, bps_high),
             ) {
                 prop_assert!(out_low >= out_high,
                     "higher bps should yield <= output: low_bps={bps_low} out={out_low}, high_bps={bps_high} out={out_high}");
@@ -617,7 +760,7 @@ mod prop_tests {
             let amount_out = if amount_out >= reserve_out { return Ok(()); } else { amount_out };
 
             if let Ok((pool, total, fee)) =
-                get_amount_in_with_treasury(amount_out, reserve_in, reserve_out, treasury_bps)
+                get_amount_in_with_treasury(amount_out, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS, treasury_bps)
             {
                 let expected_fee = total.checked_sub(pool).unwrap();
                 prop_assert_eq!(fee, expected_fee);
@@ -635,7 +778,7 @@ mod prop_tests {
             let amount_out = if amount_out >= reserve_out { return Ok(()); } else { amount_out };
 
             if let Ok((pool, total, _fee)) =
-                get_amount_in_with_treasury(amount_out, reserve_in, reserve_out, treasury_bps)
+                get_amount_in_with_treasury(amount_out, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS, treasury_bps)
             {
                 prop_assert!(total >= pool);
             }
@@ -651,7 +794,7 @@ mod prop_tests {
             let amount_out = if amount_out >= reserve_out { return Ok(()); } else { amount_out };
 
             if let Ok((pool, total, fee)) =
-                get_amount_in_with_treasury(amount_out, reserve_in, reserve_out, 0)
+                get_amount_in_with_treasury(amount_out, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS, 0)
             {
                 prop_assert_eq!(fee, U256::zero());
                 prop_assert_eq!(pool, total);
@@ -669,8 +812,8 @@ mod prop_tests {
             let amount_out = if amount_out >= reserve_out { return Ok(()); } else { amount_out };
 
             if let Ok((pool_in, _total, _fee)) =
-                get_amount_in_with_treasury(amount_out, reserve_in, reserve_out, treasury_bps)
-                && let Ok(actual_out) = get_amount_out(pool_in, reserve_in, reserve_out) {
+                get_amount_in_with_treasury(amount_out, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS, treasury_bps)
+                && let Ok(actual_out) = get_amount_out(pool_in, reserve_in, reserve_out, DEFAULT_SWAP_FEE_BPS) {
                     prop_assert!(actual_out >= amount_out,
                         "round-trip: desired={amount_out}, got={actual_out}");
                 }
@@ -809,4 +952,46 @@ mod prop_tests {
                 "price ratio violated: a={a}, b={b}, ra={reserve_a}, rb={reserve_b}, diff={diff}");
         }
     }
+
+    // zap_swap_amount
+    proptest! {
+        /// The default fee without a treasury fee matches the classic Uniswap V2 zap formula.
+        #[test]
+        fn prop_zap_default_fee_matches_v2(
+            amount_in in (1u64..=u32::MAX as u64).prop_map(U256::from),
+            reserve_in in (1u64..=u32::MAX as u64).prop_map(U256::from),
+        ) {
+            let s = zap_swap_amount(amount_in, reserve_in, DEFAULT_SWAP_FEE_BPS, 0).unwrap();
+            let expected = ((reserve_in
+                * (reserve_in * U256::from(3_988_009u64) + amount_in * U256::from(3_988_000u64)))
+                .integer_sqrt()
+                - reserve_in * U256::from(1_997u64))
+                / U256::from(1_994u64);
+            // Both are floored, one from a scaled numerator
+            prop_assert!(s.max(expected) - s.min(expected) <= U256::one());
+        }
+
+        /// Swapping the zap amount leaves the rest in the new reserve ratio.
+        #[test]
+        fn prop_zap_leaves_little_dust(
+            amount_in in (1_000_000u64..=u32::MAX as u64).prop_map(U256::from),
+            reserve_in in (1_000_000u64..=u32::MAX as u64).prop_map(U256::from),
+            reserve_out in (1_000_000u64..=u32::MAX as u64).prop_map(U256::from),
+            treasury_fee_bps in 0u64..=100,
+        ) {
+            let s = zap_swap_amount(amount_in, reserve_in, DEFAULT_SWAP_FEE_BPS, treasury_fee_bps).unwrap();
+            prop_assert!(s < amount_in);
+            let (in_for_pool, out, _) = get_amount_out_with_treasury(
+                s,
+                reserve_in,
+                reserve_out,
+                DEFAULT_SWAP_FEE_BPS,
+                treasury_fee_bps,
+            ).unwrap();
+            let matching_in = quote(out, reserve_out - out, reserve_in + in_for_pool).unwrap();
+            let rest = amount_in - s;
+            let dust = rest.max(matching_in) - rest.min(matching_in);
+            prop_assert!(dust <= amount_in / U256::from(1_000u64) + U256::from(2u64));
+        }
+    }
 }
@@ -0,0 +1,200 @@
+use crate::services::pair::{
+    Config, LockState, PairError, PairEvent, PairService, State, amm_math,
+    lock::{LockCtx, SendTokenStage},
+    msg_tracker::MessageStatus,
+};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// Reserves and treasury fees once the input of a `swap` is in.
+struct CoreSwapSettlement {
+    reserve0: U256,
+    reserve1: U256,
+    treasury_fee0: U256,
+    treasury_fee1: U256,
+}
+
+impl<'a> PairService<'a> {
+    /// Uniswap V2 core's `swap`, with the input taken from the caller's internal balance
+    /// instead of the pair balances, so it can't be taken by another caller. The pool is
+    /// settled before the outputs are sent, a failed transfer pauses the pair with
+    /// `LockCtx::Swap` for `recover_paused`.
+    pub async fn swap_core(
+        &self,
+        amount0_out: U256,
+        amount1_out: U256,
+        to: ActorId,
+    ) -> Result<PairEvent, PairError> {
+        let sender = msg::source();
+
+        let (token0, token1, config, amount0_in, amount1_in) = self.with_state_mut(|st| {
+            if st.migrated {
+                return Err(PairError::PoolMigrated);
+            }
+            if !st.hook.is_zero() {
+                return Err(PairError::UnsupportedWithHook);
+            }
+            if exec::gas_available() < st.config.gas_for_full_tx {
+                return Err(PairError::NotEnoghAttachedGas);
+            }
+            if !st.is_idle() {
+                return Err(PairError::AnotherTxInProgress);
+            }
+            if amount0_out.is_zero() && amount1_out.is_zero() {
+                return Err(PairError::InsufficientAmount);
+            }
+            if amount0_out >= st.reserve0 || amount1_out >= st.reserve1 {
+                return Err(PairError::InsufficientLiquidity);
+            }
+            if to.is_zero() || to == st.token0 || to == st.token1 {
+                return Err(PairError::InvalidRecipient);
+            }
+
+            // Whatever the caller deposited pays for the swap
+            let (amount0_in, amount1_in) = st.vault.balance_of(&sender);
+            let settlement =
+                settle_core_swap(st, amount0_in, amount1_in, amount0_out, amount1_out)?;
+            st.vault.debit(sender, true, amount0_in)?;
+            st.vault.debit(sender, false, amount1_in)?;
+            st.set_reserves(settlement.reserve0, settlement.reserve1);
+            st.accrued_treasury_fee0 = st
+                .accrued_treasury_fee0
+                .checked_add(settlement.treasury_fee0)
+                .ok_or(PairError::Overflow)?;
+            st.accrued_treasury_fee1 = st
+                .accrued_treasury_fee1
+                .checked_add(settlement.treasury_fee1)
+                .ok_or(PairError::Overflow)?;
+
+            st.lock = LockState::Busy(LockCtx::Swap {
+                to,
+                amount0_out,
+                amount1_out,
+                stage: if amount0_out.is_zero() {
+                    SendTokenStage::SendToken1
+                } else {
+                    SendTokenStage::SendToken0
+                },
+            });
+            let _ = self.lp.pause.pause();
+            Ok((
+                st.token0,
+                st.token1,
+                st.config.clone(),
+                amount0_in,
+                amount1_in,
+            ))
+        })?;
+
+        // A failed transfer pauses the pair with the missing outputs (see `apply_reply`),
+        // so does a message that couldn't be sent
+        if let Err(err) = self
+            .send_core_swap_outputs(token0, token1, to, amount0_out, amount1_out, &config)
+            .await
+        {
+            self.with_state_mut(|st| st.lock.pause_keep_ctx());
+            return Err(err);
+        }
+
+        self.with_state_mut(|st| st.lock.set_free());
+        let _ = self.lp.pause.resume();
+        self.with_tracker_mut(|tr| tr.clear_all());
+
+        Ok(PairEvent::CoreSwap {
+            sender,
+            to,
+            amount0_in,
+            amount1_in,
+            amount0_out,
+            amount1_out,
+        })
+    }
+
+    /// Sends the non-zero outputs of a `swap`, also used to resume a paused one.
+    pub async fn send_core_swap_outputs(
+        &self,
+        token0: ActorId,
+        token1: ActorId,
+        to: ActorId,
+        amount0_out: U256,
+        amount1_out: U256,
+        config: &Config,
+    ) -> Result<(), PairError> {
+        let msg_id = msg::id();
+        if !amount0_out.is_zero() {
+            self.with_tracker_mut(|tr| {
+                tr.insert_msg_status(msg_id, MessageStatus::SendingMsgToUnlockTokenA);
+            });
+            self.transfer(token0, to, amount0_out, config, msg_id)
+                .await?;
+        }
+        if !amount1_out.is_zero() {
+            self.with_tracker_mut(|tr| {
+                tr.insert_msg_status(msg_id, MessageStatus::SendingMsgToUnlockTokenB);
+            });
+            self.transfer(token1, to, amount1_out, config, msg_id)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds the input of a `swap` to the reserves, as Uniswap V2 core does with the input it
+/// finds in the balances, takes the treasury fee from it and checks the invariant on what
+/// is left.
+fn settle_core_swap(
+    st: &State,
+    amount0_in: U256,
+    amount1_in: U256,
+    amount0_out: U256,
+    amount1_out: U256,
+) -> Result<CoreSwapSettlement, PairError> {
+    if amount0_in.is_zero() && amount1_in.is_zero() {
+        return Err(PairError::InsufficientAmount);
+    }
+    // Balances once the outputs are sent
+    let balance0 = st
+        .reserve0
+        .checked_add(amount0_in)
+        .ok_or(PairError::Overflow)?
+        - amount0_out;
+    let balance1 = st
+        .reserve1
+        .checked_add(amount1_in)
+        .ok_or(PairError::Overflow)?
+        - amount1_out;
+
+    let treasury_fee_bps = st.active_treasury_fee_bps();
+    let (amount0_in_for_pool, treasury_fee0) = split_input(amount0_in, treasury_fee_bps)?;
+    let (amount1_in_for_pool, treasury_fee1) = split_input(amount1_in, treasury_fee_bps)?;
+    let reserve0 = balance0 - treasury_fee0;
+    let reserve1 = balance1 - treasury_fee1;
+
+    st.curve.verify_invariant(
+        reserve0,
+        reserve1,
+        amount0_in_for_pool,
+        amount1_in_for_pool,
+        st.reserve0,
+        st.reserve1,
+        st.swap_fee_bps,
+        exec::block_timestamp(),
+    )?;
+
+    Ok(CoreSwapSettlement {
+        reserve0,
+        reserve1,
+        treasury_fee0,
+        treasury_fee1,
+    })
+}
+
+/// `amm_math::split_treasury_fee`, allowing no input on one side.
+fn split_input(amount_in: U256, treasury_fee_bps: u64) -> Result<(U256, U256), PairError> {
+    if amount_in.is_zero() {
+        return Ok((U256::zero(), U256::zero()));
+    }
+    amm_math::split_treasury_fee(amount_in, treasury_fee_bps)
+}
@@ -0,0 +1,140 @@
+use crate::services::pair::funcs::verify_constant_product_invariant;
+use crate::services::pair::stable_math::{self, AmpRamp};
+use crate::services::pair::{PairError, amm_math};
+use sails_rs::{U256, prelude::*};
+
+/// Invariant the pair prices swaps with, fixed when the pair is created.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Curve {
+    /// Uniswap V2 `x * y = k`.
+    #[default]
+    ConstantProduct,
+    /// Curve StableSwap invariant for pegged assets, with its amplification coefficient.
+    Stable(AmpRamp),
+}
+
+impl Curve {
+    /// Same as `amm_math::get_amount_out_with_treasury` on this curve at `now`.
+    pub fn get_amount_out_with_treasury(
+        &self,
+        amount_in_total: U256,
+        reserve_in: U256,
+        reserve_out: U256,
+        swap_fee_bps: u64,
+        treasury_fee_bps: u64,
+        now: u64,
+    ) -> Result<(U256, U256, U256), PairError> {
+        match self {
+            Curve::ConstantProduct => amm_math::get_amount_out_with_treasury(
+                amount_in_total,
+                reserve_in,
+                reserve_out,
+                swap_fee_bps,
+                treasury_fee_bps,
+            ),
+            Curve::Stable(ramp) => {
+                let (amount_in_for_pool, treasury_fee) =
+                    amm_math::split_treasury_fee(amount_in_total, treasury_fee_bps)?;
+                let amount_out = stable_math::get_amount_out(
+                    amount_in_for_pool,
+                    reserve_in,
+                    reserve_out,
+                    ramp.amp_at(now),
+                    swap_fee_bps,
+                )?;
+                Ok((amount_in_for_pool, amount_out, treasury_fee))
+            }
+        }
+    }
+
+    /// Same as `amm_math::get_amount_in_with_treasury` on this curve at `now`.
+    pub fn get_amount_in_with_treasury(
+        &self,
+        amount_out: U256,
+        reserve_in: U256,
+        reserve_out: U256,
+        swap_fee_bps: u64,
+        treasury_fee_bps: u64,
+        now: u64,
+    ) -> Result<(U256, U256, U256), PairError> {
+        match self {
+            Curve::ConstantProduct => amm_math::get_amount_in_with_treasury(
+                amount_out,
+                reserve_in,
+                reserve_out,
+                swap_fee_bps,
+                treasury_fee_bps,
+            ),
+            Curve::Stable(ramp) => {
+                let amount_in_for_pool = stable_math::get_amount_in(
+                    amount_out,
+                    reserve_in,
+                    reserve_out,
+                    ramp.amp_at(now),
+                    swap_fee_bps,
+                )?;
+                let (amount_in_total, treasury_fee) =
+                    amm_math::add_treasury_fee(amount_in_for_pool, treasury_fee_bps)?;
+                Ok((amount_in_for_pool, amount_in_total, treasury_fee))
+            }
+        }
+    }
+
+    /// Checks the balances after a swap keep the invariant of the reserves before it,
+    /// the swap fee taken off the inputs.
+    #[allow(clippy::too_many_arguments)]
+    pub fn verify_invariant(
+        &self,
+        balance0: U256,
+        balance1: U256,
+        amount0_in: U256,
+        amount1_in: U256,
+        reserve0: U256,
+        reserve1: U256,
+        swap_fee_bps: u64,
+        now: u64,
+    ) -> Result<(), PairError> {
+        match self {
+            Curve::ConstantProduct => verify_constant_product_invariant(
+                balance0,
+                balance1,
+                amount0_in,
+                amount1_in,
+                reserve0,
+                reserve1,
+                swap_fee_bps,
+            ),
+            Curve::Stable(ramp) => stable_math::verify_invariant(
+                balance0,
+                balance1,
+                amount0_in,
+                amount1_in,
+                reserve0,
+                reserve1,
+                ramp.amp_at(now),
+                swap_fee_bps,
+            ),
+        }
+    }
+
+    /// Value whose square root grows with the swap fees, used for the protocol fee and `k_last`:
+    /// `reserve0 * reserve1`, or `(D / 2)^2` on the stable curve.
+    pub fn k(&self, reserve0: U256, reserve1: U256, now: u64) -> Result<U256, PairError> {
+        match self {
+            Curve::ConstantProduct => reserve0.checked_mul(reserve1).ok_or(PairError::Overflow),
+            Curve::Stable(ramp) => {
+                let half_d =
+                    stable_math::compute_d(reserve0, reserve1, ramp.amp_at(now))? / U256::from(2);
+                half_d.checked_mul(half_d).ok_or(PairError::Overflow)
+            }
+        }
+    }
+
+    /// Amplification coefficient at `now`, without `A_PRECISION`; `None` for constant product.
+    pub fn amp(&self, now: u64) -> Option<u64> {
+        match self {
+            Curve::ConstantProduct => None,
+            Curve::Stable(ramp) => Some(ramp.amp_at(now) / stable_math::A_PRECISION),
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use crate::services::pair::{
+    LockState, PairError, PairEvent, PairService, State, lock::LockCtx, msg_tracker::MessageStatus,
+    token_operations,
+};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// Route of the callback the borrower program must expose:
+/// `FlashBorrower::OnFlashSwap(sender, amount0_out, amount1_out, data)`.
+/// Replying successfully means the borrower has sent the tokens back.
+const CALLBACK_SERVICE: &str = "FlashBorrower";
+const CALLBACK_METHOD: &str = "OnFlashSwap";
+
+/// Pool balances after a flash swap, net of accrued treasury fees and deposits.
+struct FlashSettlement {
+    balance0: U256,
+    balance1: U256,
+    amount0_in: U256,
+    amount1_in: U256,
+}
+
+impl<'a> PairService<'a> {
+    pub async fn flash_swap_core(
+        &self,
+        amount0_out: U256,
+        amount1_out: U256,
+        borrower: ActorId,
+        data: Vec<u8>,
+    ) -> Result<PairEvent, PairError> {
+        let sender = msg::source();
+        let msg_id = msg::id();
+
+        let (token0, token1, config) = self.with_state_mut(|st| {
+            if st.migrated {
+                return Err(PairError::PoolMigrated);
+            }
+            if !st.hook.is_zero() {
+                return Err(PairError::UnsupportedWithHook);
+            }
+            if exec::gas_available() < st.config.gas_for_full_tx {
+                return Err(PairError::NotEnoghAttachedGas);
+            }
+            if !st.is_idle() {
+                return Err(PairError::AnotherTxInProgress);
+            }
+            if amount0_out.is_zero() && amount1_out.is_zero() {
+                return Err(PairError::InsufficientAmount);
+            }
+            if amount0_out >= st.reserve0 || amount1_out >= st.reserve1 {
+                return Err(PairError::InsufficientLiquidity);
+            }
+            if borrower.is_zero() || borrower == st.token0 || borrower == st.token1 {
+                return Err(PairError::InvalidBorrower);
+            }
+
+            st.lock = LockState::Busy(LockCtx::FlashSwap {
+                borrower,
+                amount0_out,
+                amount1_out,
+            });
+            let _ = self.lp.pause.pause();
+
+            Ok((st.token0, st.token1, st.config.clone()))
+        })?;
+
+        // A failed first transfer frees the lock, a later failure pauses the pair (see `apply_reply`)
+        if !amount0_out.is_zero() {
+            self.with_tracker_mut(|tr| {
+                tr.insert_msg_status(msg_id, MessageStatus::SendingFlashToken0);
+            });
+            self.transfer(token0, borrower, amount0_out, &config, msg_id)
+                .await?;
+        }
+        if !amount1_out.is_zero() {
+            let status = if amount0_out.is_zero() {
+                MessageStatus::SendingFlashToken0
+            } else {
+                MessageStatus::SendingFlashToken1
+            };
+            self.with_tracker_mut(|tr| tr.insert_msg_status(msg_id, status));
+            self.transfer(token1, borrower, amount1_out, &config, msg_id)
+                .await?;
+        }
+
+        self.with_tracker_mut(|tr| {
+            tr.insert_msg_status(msg_id, MessageStatus::CallingFlashBorrower);
+        });
+        let payload = (
+            CALLBACK_SERVICE,
+            CALLBACK_METHOD,
+            sender,
+            amount0_out,
+            amount1_out,
+            data,
+        )
+            .encode();
+        let balances = async {
+            let fut = msg::send_bytes_for_reply(borrower, payload, 0, config.gas_for_reply_deposit)
+                .map_err(|_| PairError::SendFailure)?;
+            self.with_tracker_mut(|tr| tr.bind_reply(fut.waiting_reply_to, msg_id));
+            fut.up_to(Some(config.reply_timeout))
+                .map_err(|_| PairError::ReplyTimeout)?
+                .await
+                .map_err(|_| PairError::ReplyFailure)?;
+
+            let program_id = exec::program_id();
+            let token_balance0 = token_operations::balance_of(token0, program_id, &config).await?;
+            let token_balance1 = token_operations::balance_of(token1, program_id, &config).await?;
+            Ok::<_, PairError>((token_balance0, token_balance1))
+        }
+        .await;
+        // The tokens are out, so whether they came back is settled with `recover_paused`
+        let (token_balance0, token_balance1) = match balances {
+            Ok(balances) => balances,
+            Err(err) => {
+                self.with_state_mut(|st| st.lock.pause_keep_ctx());
+                return Err(err);
+            }
+        };
+
+        let event = self.with_state_mut(|st| {
+            match settle_flash_swap(st, token_balance0, token_balance1, amount0_out, amount1_out) {
+                Ok(settlement) => {
+                    st.set_reserves(settlement.balance0, settlement.balance1);
+                    st.lock.set_free();
+                    let _ = self.lp.pause.resume();
+                    PairEvent::FlashSwap {
+                        borrower,
+                        amount0_out,
+                        amount1_out,
+                        amount0_in: settlement.amount0_in,
+                        amount1_in: settlement.amount1_in,
+                    }
+                }
+                // The tokens are gone and can't be taken back: keep the pair paused
+                // until the admin settles it with `recover_paused`
+                Err(_) => {
+                    st.lock.pause_keep_ctx();
+                    PairEvent::FlashSwapDefaulted {
+                        borrower,
+                        amount0_out,
+                        amount1_out,
+                    }
+                }
+            }
+        });
+        self.with_tracker_mut(|tr| tr.clear_all());
+
+        Ok(event)
+    }
+
+    /// Settles a paused flash swap from the pool balances.
+    /// A late repayment completes the swap; otherwise the reserves are written
+    /// down to what the pool holds.
+    pub async fn recover_flash_swap(
+        &self,
+        borrower: ActorId,
+        amount0_out: U256,
+        amount1_out: U256,
+    ) -> Result<PairEvent, PairError> {
+        let (token0, token1, config) =
+            self.with_state(|st| (st.token0, st.token1, st.config.clone()));
+        let program_id = exec::program_id();
+        let token_balance0 = token_operations::balance_of(token0, program_id, &config).await?;
+        let token_balance1 = token_operations::balance_of(token1, program_id, &config).await?;
+
+        let event = self.with_state_mut(|st| {
+            let event = match settle_flash_swap(
+                st,
+                token_balance0,
+                token_balance1,
+                amount0_out,
+                amount1_out,
+            ) {
+                Ok(settlement) => {
+                    st.set_reserves(settlement.balance0, settlement.balance1);
+                    PairEvent::FlashSwap {
+                        borrower,
+                        amount0_out,
+                        amount1_out,
+                        amount0_in: settlement.amount0_in,
+                        amount1_in: settlement.amount1_in,
+                    }
+                }
+                Err(_) => {
+                    let (outside0, outside1) = st.outside_reserves();
+                    let balance0 = token_balance0.saturating_sub(outside0);
+                    let balance1 = token_balance1.saturating_sub(outside1);
+                    let event = PairEvent::FlashSwapWrittenOff {
+                        borrower,
+                        amount0_lost: st.reserve0.saturating_sub(balance0),
+                        amount1_lost: st.reserve1.saturating_sub(balance1),
+                    };
+                    st.set_reserves(balance0, balance1);
+                    if !st.fee_to.is_zero() {
+                        st.k_last = st.k(balance0, balance1).unwrap_or(U256::MAX);
+                    }
+                    event
+                }
+            };
+            st.lock.set_free();
+            event
+        });
+        let _ = self.lp.pause.resume();
+        self.with_tracker_mut(|tr| tr.clear_all());
+
+        Ok(event)
+    }
+}
+
+/// Checks the pool got back what was borrowed plus the swap fee, as in Uniswap V2's `swap`,
+/// on the pair's curve.
+fn settle_flash_swap(
+    st: &State,
+    token_balance0: U256,
+    token_balance1: U256,
+    amount0_out: U256,
+    amount1_out: U256,
+) -> Result<FlashSettlement, PairError> {
+    let (outside0, outside1) = st.outside_reserves();
+    let balance0 = token_balance0
+        .checked_sub(outside0)
+        .ok_or(PairError::InvariantViolation)?;
+    let balance1 = token_balance1
+        .checked_sub(outside1)
+        .ok_or(PairError::InvariantViolation)?;
+
+    // Anything above `reserve - amount_out` was paid in
+    let amount0_in = balance0.saturating_sub(st.reserve0 - amount0_out);
+    let amount1_in = balance1.saturating_sub(st.reserve1 - amount1_out);
+    if amount0_in.is_zero() && amount1_in.is_zero() {
+        return Err(PairError::InsufficientAmount);
+    }
+
+    st.curve.verify_invariant(
+        balance0,
+        balance1,
+        amount0_in,
+        amount1_in,
+        st.reserve0,
+        st.reserve1,
+        st.swap_fee_bps,
+        exec::block_timestamp(),
+    )?;
+
+    Ok(FlashSettlement {
+        balance0,
+        balance1,
+        amount0_in,
+        amount1_in,
+    })
+}
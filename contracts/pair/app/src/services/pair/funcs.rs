@@ -1,25 +1,28 @@
 use crate::services::pair::{Config, LpExposure};
 use crate::services::pair::{
     LockState, PairService,
-    lock::{LockCtx, SendTokenStage},
+    lock::{LockCtx, Reservation, SendTokenStage},
 };
 use crate::services::pair::{
-    PairError, PairEvent, State, amm_math, amm_math::TREASURY_FEE_BPS, msg_tracker::MessageStatus,
-    token_operations,
+    PairError, PairEvent, State, amm_math, msg_tracker::MessageStatus, token_operations,
 };
 
-const FEE_BPS: u64 = 3; // 0.3% fee (3/1000)
 pub const LP_DEAD: [u8; 32] = [1u8; 32];
 use sails_rs::{
     gstd::{exec, msg},
     prelude::*,
 };
 
-struct SwapDirection {
-    token_in: ActorId,
-    token_out: ActorId,
-    reserve_in: U256,
-    reserve_out: U256,
+/// Amounts of a swap priced by `quote_swap`.
+pub struct SwapQuote {
+    pub token_in: ActorId,
+    pub token_out: ActorId,
+    /// Part of the input that enters the reserves.
+    pub amount_in_for_pool: U256,
+    /// Input taken from the user, including the treasury fee.
+    pub amount_in_total: U256,
+    pub amount_out: U256,
+    pub treasury_fee: U256,
 }
 
 // Enum to define the type of swap operation
@@ -35,13 +38,6 @@ pub enum SwapType {
     },
 }
 
-struct SwapFinalize {
-    new_reserve0: U256,
-    new_reserve1: U256,
-    new_fee0: U256,
-    new_fee1: U256,
-}
-
 impl<'a> PairService<'a> {
     pub async fn add_liquidity_core(
         &self,
@@ -49,12 +45,19 @@ impl<'a> PairService<'a> {
         amount_b_desired: U256,
         amount_a_min: U256,
         amount_b_min: U256,
+        to: ActorId,
         deadline: u64,
     ) -> Result<PairEvent, PairError> {
+        // Every check below runs once the hook has replied
+        self.before_add_liquidity_hook(to, amount_a_desired, amount_b_desired)
+            .await?;
         let (token0, token1, reserve0, reserve1, config) = self.with_state_mut(|st| {
             if st.migrated {
                 return Err(PairError::PoolMigrated);
             }
+            if to.is_zero() {
+                return Err(PairError::InvalidRecipient);
+            }
             if exec::gas_available() < st.config.gas_for_full_tx {
                 return Err(PairError::NotEnoghAttachedGas);
             }
@@ -78,6 +81,7 @@ impl<'a> PairService<'a> {
         })?;
 
         let sender = msg::source();
+        let msg_id = msg::id();
         let (amount_a, amount_b) = amm_math::calculate_optimal_amounts(
             reserve0,
             reserve1,
@@ -86,78 +90,56 @@ impl<'a> PairService<'a> {
             amount_a_min,
             amount_b_min,
         )?;
+        // Nothing is reserved: the liquidity is computed from the reserves once both
+        // tokens are in, whatever other operations settled meanwhile
         self.with_state_mut(|st| {
-            st.lock = LockState::Busy(LockCtx::AddLiqRefund {
-                user: sender,
-                token: token0,
-                amount: amount_a,
-            });
+            st.ops.start(
+                msg_id,
+                LockCtx::AddLiqRefund {
+                    user: sender,
+                    token: token0,
+                    amount: amount_a,
+                },
+                Reservation::default(),
+            );
         });
-        let _ = self.lp.pause.pause();
         self.transfer_tokens_to_pool(sender, token0, token1, amount_a, amount_b, &config)
             .await?;
-        let _ = self.lp.pause.resume();
-        // mint protocol fee (if fee_on)
-        {
-            let mut lp = self.lp_service();
-            self.with_state_mut(|st| mint_fee_lp(st, &mut lp))
-                .map_err(|_| PairError::Overflow)?;
-        }
-        // compute liquidity with total_supply AFTER protocol fee mint
-        let mut lp = self.lp_service();
-        let total_supply = lp.total_supply().unwrap_or(U256::zero());
-        let (reserve0_now, reserve1_now) = self.with_state(|st| (st.reserve0, st.reserve1));
-        let liquidity = amm_math::calculate_liquidity(
-            reserve0_now,
-            reserve1_now,
-            amount_a,
-            amount_b,
-            total_supply,
-        )?;
-        // mint MINIMUM_LIQUIDITY once (to dead address), then mint user liquidity
-        {
-            if total_supply.is_zero() {
-                mint_liquidity(
-                    &mut lp,
-                    LP_DEAD.into(),
-                    U256::from(amm_math::MINIMUM_LIQUIDITY),
-                )?
-            }
-            mint_liquidity(&mut lp, sender, liquidity)?;
-        }
-
-        // update reserves + k_last + unlock
-        self.with_state_mut(|st| {
-            st.reserve0 = st
-                .reserve0
-                .checked_add(amount_a)
-                .ok_or(PairError::Overflow)?;
-            st.reserve1 = st
-                .reserve1
-                .checked_add(amount_b)
-                .ok_or(PairError::Overflow)?;
 
-            // fee_on is "fee_to != 0" as in Uniswap V2
-            if !st.fee_to.is_zero() {
-                st.k_last = st
-                    .reserve0
-                    .checked_mul(st.reserve1)
-                    .ok_or(PairError::Overflow)?;
-            }
-
-            st.lock.set_free();
-            Ok::<_, PairError>(())
-        })?;
-
-        // resume LP user ops when Free
-        let _ = self.lp.pause.resume();
+        // Swaps may have moved the price meanwhile: only what fits the current ratio is
+        // added, under the same minimums, and the rest goes back to the sender
+        let mut lp = self.lp_service();
+        let settled = self.with_state_mut(|st| {
+            st.ops.finish(&msg_id);
+            let (added_a, added_b) = amm_math::calculate_optimal_amounts(
+                st.reserve0,
+                st.reserve1,
+                amount_a,
+                amount_b,
+                amount_a_min,
+                amount_b_min,
+            )?;
+            let liquidity = settle_add_liquidity(st, &mut lp, added_a, added_b, to)?;
+            Ok::<_, PairError>((added_a, added_b, liquidity))
+        });
+        let (refund_a, refund_b) = match settled {
+            Ok((added_a, added_b, _)) => (amount_a - added_a, amount_b - added_b),
+            Err(_) => (amount_a, amount_b),
+        };
+        self.refund_as_operation(token0, sender, refund_a, &config)
+            .await;
+        self.refund_as_operation(token1, sender, refund_b, &config)
+            .await;
+        let (amount_a, amount_b, liquidity) = settled?;
 
-        Ok(PairEvent::LiquidityAdded {
+        let event = PairEvent::LiquidityAdded {
             user_id: sender,
             amount_a,
             amount_b,
             liquidity,
-        })
+        };
+        self.after_hook(to, &event);
+        Ok(event)
     }
 
     pub async fn remove_liquidity_core(
@@ -165,8 +147,11 @@ impl<'a> PairService<'a> {
         liquidity: U256,
         amount_a_min: U256,
         amount_b_min: U256,
+        to: ActorId,
         deadline: u64,
     ) -> Result<PairEvent, PairError> {
+        // Every check below runs once the hook has replied
+        self.before_remove_liquidity_hook(to, liquidity).await?;
         self.with_state(|st| {
             if exec::gas_available() < st.config.gas_for_full_tx {
                 return Err(PairError::NotEnoghAttachedGas);
@@ -176,6 +161,10 @@ impl<'a> PairService<'a> {
                 return Err(PairError::PoolMigrated);
             }
 
+            if to.is_zero() {
+                return Err(PairError::InvalidRecipient);
+            }
+
             if liquidity.is_zero() {
                 return Err(PairError::ZeroLiquidity);
             }
@@ -187,102 +176,60 @@ impl<'a> PairService<'a> {
             if !st.lock.is_free() {
                 return Err(PairError::AnotherTxInProgress);
             }
+            // A swap in flight was priced against the current pool, which must not shrink
+            // before it settles
+            if !st.ops.reserved().is_zero() {
+                return Err(PairError::AnotherTxInProgress);
+            }
             Ok::<_, PairError>(())
         })?;
 
         let sender = msg::source();
-        // Verify user has sufficient LP tokens
-        let user_balance = self.lp_service().balance_of(sender).unwrap_or(U256::zero());
-        if user_balance < liquidity {
-            return Err(PairError::InsufficientLiquidity);
-        }
-
-        let total_supply = self.lp_service().total_supply().unwrap_or(U256::zero());
-        if total_supply.is_zero() {
-            return Err(PairError::InsufficientLiquidity);
-        }
-
+        let msg_id = msg::id();
+        let mut lp = self.lp_service();
         let (token0, token1, config, amount_a, amount_b) = self.with_state_mut(
             |st| -> Result<(ActorId, ActorId, Config, U256, U256), PairError> {
-                // protocol fee (view) — не меняем state
-                let lp_protocol_fee = calculate_protocol_fee(st, total_supply)?;
-
-                // Calculate proportional amounts of underlying tokens to return
-                // Formula: user_amount = (liquidity_to_burn * reserve) / total_supply
-                let amount_a = liquidity
-                    .checked_mul(st.reserve0)
-                    .and_then(|result| result.checked_div(total_supply + lp_protocol_fee))
-                    .ok_or(PairError::Overflow)?;
-
-                let amount_b = liquidity
-                    .checked_mul(st.reserve1)
-                    .and_then(|result| result.checked_div(total_supply + lp_protocol_fee))
-                    .ok_or(PairError::Overflow)?;
-
-                // Slippage protection: ensure user receives at least minimum amounts
-                if amount_a < amount_a_min {
-                    return Err(PairError::InsufficientAmountA);
-                }
-                if amount_b < amount_b_min {
-                    return Err(PairError::InsufficientAmountB);
-                }
-
-                if amount_a.is_zero() || amount_b.is_zero() {
-                    return Err(PairError::InsufficientLiquidityBurned);
-                }
-                // Sanity check: ensure pool has sufficient reserves
-                if amount_a > st.reserve0 || amount_b > st.reserve1 {
-                    return Err(PairError::InsufficientLiquidity);
-                }
-                st.lock = LockState::Busy(LockCtx::RemLiq {
-                    user: sender,
+                // Settle before paying out, so the operations overlapping the payout already
+                // see the smaller pool
+                let (amount_a, amount_b) = settle_remove_liquidity(
+                    st,
+                    &mut lp,
+                    sender,
                     liquidity,
-                    amount_a,
-                    amount_b,
-                    stage: SendTokenStage::SendToken0,
-                });
+                    amount_a_min,
+                    amount_b_min,
+                )?;
+
+                st.ops.start(
+                    msg_id,
+                    LockCtx::RemLiq {
+                        user: sender,
+                        to,
+                        liquidity,
+                        amount_a,
+                        amount_b,
+                        stage: SendTokenStage::SendToken0,
+                    },
+                    Reservation::default(),
+                );
                 Ok((st.token0, st.token1, st.config.clone(), amount_a, amount_b))
             },
         )?;
 
-        // Transfer underlying tokens back to user
-        self.return_tokens_from_pool(token0, token1, sender, amount_a, amount_b, &config)
+        // Transfer underlying tokens to the recipient, a failure pauses the operation
+        // with the missing payout (see `apply_reply`)
+        self.return_tokens_from_pool(token0, token1, to, amount_a, amount_b, &config)
             .await?;
+        self.with_state_mut(|st| st.ops.finish(&msg_id));
 
-        self.with_state_mut(|st| -> Result<(), PairError> {
-            let mut lp = self.lp_service();
-
-            mint_fee_lp(st, &mut lp)?;
-
-            burn_liquidity(&mut lp, sender, liquidity)?;
-            st.reserve0 = st
-                .reserve0
-                .checked_sub(amount_a)
-                .ok_or(PairError::Overflow)?;
-            st.reserve1 = st
-                .reserve1
-                .checked_sub(amount_b)
-                .ok_or(PairError::Overflow)?;
-
-            if !st.fee_to.is_zero() {
-                st.k_last = st
-                    .reserve0
-                    .checked_mul(st.reserve1)
-                    .ok_or(PairError::Overflow)?;
-            } else if !st.k_last.is_zero() {
-                st.k_last = U256::zero();
-            }
-            // unlock + cleanup
-            st.lock.set_free();
-            Ok(())
-        })?;
-
-        Ok(PairEvent::LiquidityRemoved {
+        let event = PairEvent::LiquidityRemoved {
             user_id: sender,
             amount_a,
             amount_b,
             liquidity,
-        })
+        };
+        self.after_hook(to, &event);
+        Ok(event)
     }
 
     pub async fn migrate_all_liquidity_core(
@@ -302,7 +249,7 @@ impl<'a> PairService<'a> {
                 return Err(PairError::NotEnoghAttachedGas);
             }
 
-            if !st.lock.is_free() {
+            if !st.is_idle() {
                 return Err(PairError::AnotherTxInProgress);
             }
             Ok((st.token0, st.token1, st.config.clone()))
@@ -319,8 +266,14 @@ impl<'a> PairService<'a> {
             });
         });
 
-        let balance0 = token_operations::balance_of(token0, program_id, &config).await?;
-        let balance1 = token_operations::balance_of(token1, program_id, &config).await?;
+        // Deposits stay in the pair, to be withdrawn
+        let (deposits0, deposits1) = self.with_state(|st| st.vault.totals());
+        let balance0 = token_operations::balance_of(token0, program_id, &config)
+            .await?
+            .saturating_sub(deposits0);
+        let balance1 = token_operations::balance_of(token1, program_id, &config)
+            .await?
+            .saturating_sub(deposits1);
 
         if balance0.is_zero() && balance1.is_zero() {
             self.with_state_mut(|st| {
@@ -337,17 +290,20 @@ impl<'a> PairService<'a> {
                 stage: SendTokenStage::SendToken0,
             });
         });
+        let _ = self.lp.pause.pause();
         self.return_tokens_from_pool(token0, token1, target, balance0, balance1, &config)
             .await?;
+        let _ = self.lp.pause.resume();
 
         self.with_state_mut(|st| {
-            st.reserve0 = U256::zero();
-            st.reserve1 = U256::zero();
+            st.set_reserves(U256::zero(), U256::zero());
             st.k_last = U256::zero();
             st.accrued_treasury_fee0 = U256::zero();
             st.accrued_treasury_fee1 = U256::zero();
             st.migrated = true;
+            st.lock.set_free();
         });
+        token_operations::notify_factory_migrated(self.with_state(|st| st.factory_id));
 
         Ok(PairEvent::LiquidityMigrated {
             to: target,
@@ -361,6 +317,7 @@ impl<'a> PairService<'a> {
         amount_in: U256,
         amount_out_min: U256,
         is_token0_to_token1: bool,
+        to: ActorId,
         deadline: u64,
     ) -> Result<PairEvent, PairError> {
         self.swap_tokens(
@@ -369,6 +326,7 @@ impl<'a> PairService<'a> {
                 amount_out_min,
             },
             is_token0_to_token1,
+            to,
             deadline,
         )
         .await
@@ -379,6 +337,7 @@ impl<'a> PairService<'a> {
         amount_out: U256,
         amount_in_max: U256,
         is_token0_to_token1: bool,
+        to: ActorId,
         deadline: u64,
     ) -> Result<PairEvent, PairError> {
         self.swap_tokens(
@@ -387,6 +346,7 @@ impl<'a> PairService<'a> {
                 amount_in_max,
             },
             is_token0_to_token1,
+            to,
             deadline,
         )
         .await
@@ -396,224 +356,84 @@ impl<'a> PairService<'a> {
         &self,
         swap_type: SwapType,
         is_token0_to_token1: bool,
+        to: ActorId,
         deadline: u64,
     ) -> Result<PairEvent, PairError> {
-        // ---------- PREPARE: читаем state копиями и валидируем ----------
-        let (token_in, token_out, reserve_in, reserve_out, treasury_fee_bps) =
-            self.with_state(|st| {
-                if st.migrated {
-                    return Err(PairError::PoolMigrated);
-                }
-                if exec::gas_available() < st.config.gas_for_full_tx {
-                    return Err(PairError::NotEnoghAttachedGas);
-                }
-                if !st.lock.is_free() {
-                    return Err(PairError::AnotherTxInProgress);
-                }
-                if exec::block_timestamp() > deadline {
-                    return Err(PairError::DeadlineExpired);
-                }
-
-                let treasury_fee_bps = if st.treasury_id.is_zero() {
-                    0
-                } else {
-                    TREASURY_FEE_BPS
-                };
-
-                let (token_in, token_out, reserve_in, reserve_out) = if is_token0_to_token1 {
-                    (st.token0, st.token1, st.reserve0, st.reserve1)
-                } else {
-                    (st.token1, st.token0, st.reserve1, st.reserve0)
-                };
-
-                Ok((
-                    token_in,
-                    token_out,
-                    reserve_in,
-                    reserve_out,
-                    treasury_fee_bps,
-                ))
-            })?;
-
-        let swap_direction = SwapDirection {
-            token_in,
-            token_out,
-            reserve_in,
-            reserve_out,
-        };
-
-        let (amount_in_for_pool, amount_in_total, amount_out, treasury_fee) = match swap_type {
-            SwapType::ExactInput {
-                amount_in,
-                amount_out_min,
-            } => {
-                let (in_for_pool, out, t_fee) = amm_math::get_amount_out_with_treasury(
-                    amount_in,
-                    reserve_in,
-                    reserve_out,
-                    treasury_fee_bps,
-                )?;
-
-                if out < amount_out_min {
-                    return Err(PairError::InsufficientAmount);
-                }
+        let sender = msg::source();
+        let msg_id = msg::id();
+        // Every check below runs once the hook has replied
+        let hook_fee_bps = self
+            .before_swap_hook(to, swap_type, is_token0_to_token1)
+            .await?;
 
-                (in_for_pool, amount_in, out, t_fee)
+        // ---------- PREPARE: price, check invariant, reserve the amounts ----------
+        let (quote, config) = self.with_state_mut(|st| {
+            if st.migrated {
+                return Err(PairError::PoolMigrated);
             }
-            SwapType::ExactOutput {
-                amount_out,
-                amount_in_max,
-            } => {
-                let (in_for_pool, in_total, t_fee) = amm_math::get_amount_in_with_treasury(
-                    amount_out,
-                    swap_direction.reserve_in,
-                    swap_direction.reserve_out,
-                    treasury_fee_bps,
-                )?;
-
-                if in_total > amount_in_max {
-                    return Err(PairError::ExcessiveInputAmount);
-                }
-
-                (in_for_pool, in_total, amount_out, t_fee)
+            if exec::gas_available() < st.config.gas_for_full_tx {
+                return Err(PairError::NotEnoghAttachedGas);
+            }
+            if !st.lock.is_free() {
+                return Err(PairError::AnotherTxInProgress);
+            }
+            if exec::block_timestamp() > deadline {
+                return Err(PairError::DeadlineExpired);
+            }
+            if to.is_zero() {
+                return Err(PairError::InvalidRecipient);
             }
-        };
-
-        if amount_out > swap_direction.reserve_out {
-            return Err(PairError::InsufficientLiquidity);
-        }
-
-        self.execute_swap(
-            &swap_direction,
-            amount_in_for_pool,
-            amount_in_total,
-            amount_out,
-            treasury_fee,
-            is_token0_to_token1,
-        )
-        .await
-    }
-    async fn execute_swap(
-        &self,
-        swap_direction: &SwapDirection,
-        amount_in_for_pool: U256,
-        amount_in_total: U256,
-        amount_out: U256,
-        treasury_fee: U256,
-        is_token0_to_token1: bool,
-    ) -> Result<PairEvent, PairError> {
-        let sender = msg::source();
-
-        // PREPARE
-        // Calculate new reserves/fee, check invariant, set lock=Busy
-        let (finalize, token_in, token_out, config) = self.with_state_mut(
-            |st| -> Result<(SwapFinalize, ActorId, ActorId, Config), PairError> {
-                // new reserves
-                let (new_reserve0, new_reserve1) = if is_token0_to_token1 {
-                    (
-                        st.reserve0
-                            .checked_add(amount_in_for_pool)
-                            .ok_or(PairError::Overflow)?,
-                        st.reserve1
-                            .checked_sub(amount_out)
-                            .ok_or(PairError::Overflow)?,
-                    )
-                } else {
-                    (
-                        st.reserve0
-                            .checked_sub(amount_out)
-                            .ok_or(PairError::Overflow)?,
-                        st.reserve1
-                            .checked_add(amount_in_for_pool)
-                            .ok_or(PairError::Overflow)?,
-                    )
-                };
-
-                // new treasury accumulators (apply only on success later)
-                let mut new_fee0 = st.accrued_treasury_fee0;
-                let mut new_fee1 = st.accrued_treasury_fee1;
-
-                if !treasury_fee.is_zero() && !st.treasury_id.is_zero() {
-                    if is_token0_to_token1 {
-                        new_fee0 = new_fee0
-                            .checked_add(treasury_fee)
-                            .ok_or(PairError::Overflow)?;
-                    } else {
-                        new_fee1 = new_fee1
-                            .checked_add(treasury_fee)
-                            .ok_or(PairError::Overflow)?;
-                    }
-                }
-
-                // invariant inputs
-                let (amount0_in, amount1_in) = if is_token0_to_token1 {
-                    (amount_in_for_pool, U256::zero())
-                } else {
-                    (U256::zero(), amount_in_for_pool)
-                };
 
-                verify_constant_product_invariant(
-                    new_reserve0,
-                    new_reserve1,
-                    amount0_in,
-                    amount1_in,
-                    st.reserve0,
-                    st.reserve1,
-                )?;
+            let swap_fee_bps = hook_fee_bps.unwrap_or(st.swap_fee_bps);
+            let quote = quote_swap(st, swap_type, is_token0_to_token1, swap_fee_bps)?;
 
-                // lock context for refund path
-                st.lock = LockState::Busy(LockCtx::SwapRefund {
+            // operation context for the refund path
+            st.ops.start(
+                msg_id,
+                LockCtx::SwapRefund {
                     user: sender,
-                    token: swap_direction.token_in,
-                    amount: amount_in_total,
-                });
-
-                let _ = self.lp.pause.pause();
-
-                Ok((
-                    SwapFinalize {
-                        new_reserve0,
-                        new_reserve1,
-                        new_fee0,
-                        new_fee1,
-                    },
-                    swap_direction.token_in,
-                    swap_direction.token_out,
-                    st.config.clone(),
-                ))
-            },
-        )?;
+                    token: quote.token_in,
+                    amount: quote.amount_in_total,
+                },
+                Reservation::swap(
+                    is_token0_to_token1,
+                    quote.amount_in_for_pool,
+                    quote.amount_out,
+                ),
+            );
+            Ok((quote, st.config.clone()))
+        })?;
 
         // ---------- IO (await) — без borrow state ----------
         self.execute_swap_transfers(
             sender,
-            token_in,
-            amount_in_total,
-            token_out,
-            amount_out,
+            quote.token_in,
+            quote.amount_in_total,
+            quote.token_out,
+            quote.amount_out,
+            to,
             &config,
         )
         .await?;
 
         // ---------- FINALIZE (короткий borrow) ----------
-        self.with_state_mut(|st| {
-            st.reserve0 = finalize.new_reserve0;
-            st.reserve1 = finalize.new_reserve1;
-            st.accrued_treasury_fee0 = finalize.new_fee0;
-            st.accrued_treasury_fee1 = finalize.new_fee1;
-
-            st.lock.set_free();
+        self.with_state_mut(|st| -> Result<(), PairError> {
+            settle_swap(st, &quote, is_token0_to_token1)?;
+            st.ops.finish(&msg_id);
+            Ok(())
+        })?;
+        self.with_tracker_mut(|tr| {
+            tr.remove_msg_status(&msg_id);
         });
 
-        let _ = self.lp.pause.resume();
-        self.with_tracker_mut(|tr| tr.clear_all());
-
-        Ok(PairEvent::Swap {
+        let event = PairEvent::Swap {
             user_id: sender,
-            amount_in: amount_in_total,
-            amount_out,
+            amount_in: quote.amount_in_total,
+            amount_out: quote.amount_out,
             is_token0_to_token1,
-        })
+        };
+        self.after_hook(to, &event);
+        Ok(event)
     }
 
     pub async fn send_treasury_fees_from_pool(&self) -> Result<PairEvent, PairError> {
@@ -644,18 +464,23 @@ impl<'a> PairService<'a> {
 
                 let treasury_id = st.treasury_id;
 
-                st.lock = LockState::Busy(LockCtx::TreasuryPayout {
-                    treasury: treasury_id,
-                    amount0: amount_a,
-                    amount1: amount_b,
-                    stage: if amount_a.is_zero() {
-                        SendTokenStage::SendToken1
-                    } else {
-                        SendTokenStage::SendToken0
+                // Swaps settling during the payout accrue new fees from zero
+                st.accrued_treasury_fee0 = U256::zero();
+                st.accrued_treasury_fee1 = U256::zero();
+                st.ops.start(
+                    msg_id,
+                    LockCtx::TreasuryPayout {
+                        treasury: treasury_id,
+                        amount0: amount_a,
+                        amount1: amount_b,
+                        stage: if amount_a.is_zero() {
+                            SendTokenStage::SendToken1
+                        } else {
+                            SendTokenStage::SendToken0
+                        },
                     },
-                });
-
-                let _ = self.lp.pause.pause();
+                    Reservation::default(),
+                );
 
                 Ok((
                     st.token0,
@@ -688,15 +513,11 @@ impl<'a> PairService<'a> {
                 .map_err(|_| PairError::TokenTransferFailed)?;
         }
 
-        self.with_state_mut(|st| {
-            st.accrued_treasury_fee0 = U256::zero();
-            st.accrued_treasury_fee1 = U256::zero();
-            st.lock.set_free();
+        self.with_state_mut(|st| st.ops.finish(&msg_id));
+        self.with_tracker_mut(|tr| {
+            tr.remove_msg_status(&msg_id);
         });
 
-        let _ = self.lp.pause.resume();
-        self.with_tracker_mut(|tr| tr.clear_all());
-
         Ok(PairEvent::TreasuryFeesCollected {
             treasury_id,
             amount_a,
@@ -710,19 +531,157 @@ impl<'a> PairService<'a> {
         if !self.is_admin(&caller) {
             return Err(PairError::Unauthorized);
         }
-        let (ctx, token1, config) = self.with_state(|st| {
+        let (ctx, token0, token1, config) = self.with_state(|st| {
             let ctx = match st.lock.clone() {
                 LockState::Paused(ctx) => ctx,
                 _ => return Err(PairError::NotPaused),
             };
-            Ok((ctx, st.token1, st.config.clone()))
+            Ok((ctx, st.token0, st.token1, st.config.clone()))
         })?;
 
-        let clear_tracker = || {
-            self.with_tracker_mut(|tr| tr.clear_all());
-        };
-
+        let msg_id = msg::id();
         match ctx {
+            // -------------------------
+            // 1) Migrate liquidity recovery (only SendToken1)
+            // -------------------------
+            LockCtx::MigrateAllLiquidity {
+                target,
+                amount0,
+                amount1,
+                stage,
+            } => {
+                if stage != SendTokenStage::SendToken1 {
+                    return Err(PairError::InvalidRecoveryState);
+                }
+
+                self.with_tracker_mut(|tr| {
+                    tr.insert_msg_status(msg_id, MessageStatus::SendingMsgToUnlockTokenB);
+                });
+
+                self.transfer(token1, target, amount1, &config, msg_id)
+                    .await?;
+
+                let event = self.with_state_mut(|st| -> Result<PairEvent, PairError> {
+                    st.set_reserves(U256::zero(), U256::zero());
+                    st.k_last = U256::zero();
+                    st.accrued_treasury_fee0 = U256::zero();
+                    st.accrued_treasury_fee1 = U256::zero();
+                    st.migrated = true;
+                    st.lock.set_free();
+
+                    Ok(PairEvent::LiquidityMigrated {
+                        to: target,
+                        amount0,
+                        amount1,
+                    })
+                })?;
+
+                token_operations::notify_factory_migrated(self.with_state(|st| st.factory_id));
+                let _ = self.lp.pause.resume();
+                self.with_tracker_mut(|tr| {
+                    tr.remove_msg_status(&msg_id);
+                });
+                return Ok(Some(event));
+            }
+            // -------------------------
+            // 2) Admin pause - just unlock
+            // -------------------------
+            LockCtx::AdminPause => {
+                self.with_state_mut(|st| {
+                    st.lock.set_free();
+                });
+                let _ = self.lp.pause.resume();
+            }
+            // -------------------------
+            // 3) Flash swap - settle from the pool balances
+            // -------------------------
+            LockCtx::FlashSwap {
+                borrower,
+                amount0_out,
+                amount1_out,
+            } => {
+                let event = self
+                    .recover_flash_swap(borrower, amount0_out, amount1_out)
+                    .await?;
+                return Ok(Some(event));
+            }
+            // -------------------------
+            // 4) Sync / skim - nothing was accounted yet, just unlock
+            // -------------------------
+            LockCtx::Reconcile => {
+                self.with_state_mut(|st| {
+                    st.lock.set_free();
+                });
+                let _ = self.lp.pause.resume();
+            }
+            // -------------------------
+            // 5) Zap out - the LP is burnt only after the payout, just unlock
+            // -------------------------
+            LockCtx::ZapOut { .. } => {
+                self.with_state_mut(|st| {
+                    st.lock.set_free();
+                });
+                let _ = self.lp.pause.resume();
+            }
+            // -------------------------
+            // 6) Core swap - the pool is settled, send the missing outputs
+            // -------------------------
+            LockCtx::Swap {
+                to,
+                amount0_out,
+                amount1_out,
+                ref stage,
+            } => {
+                let amount0_out = if *stage == SendTokenStage::SendToken0 {
+                    amount0_out
+                } else {
+                    U256::zero()
+                };
+                // Busy again, so the replies advance or pause it as during the swap
+                self.with_state_mut(|st| st.lock = LockState::Busy(ctx.clone()));
+                if let Err(err) = self
+                    .send_core_swap_outputs(token0, token1, to, amount0_out, amount1_out, &config)
+                    .await
+                {
+                    self.with_state_mut(|st| st.lock.pause_keep_ctx());
+                    return Err(err);
+                }
+
+                self.with_state_mut(|st| st.lock.set_free());
+                let _ = self.lp.pause.resume();
+                self.with_tracker_mut(|tr| tr.clear_all());
+            }
+            // Swaps, liquidity changes, treasury payouts and deposits are recovered
+            // with `recover_operation`
+            LockCtx::AddLiqRefund { .. }
+            | LockCtx::SwapRefund { .. }
+            | LockCtx::RemLiq { .. }
+            | LockCtx::TreasuryPayout { .. }
+            | LockCtx::Deposit { .. }
+            | LockCtx::Withdraw { .. } => {
+                return Err(PairError::InvalidRecoveryState);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Retries the transfer a paused operation is missing. The operation is tracked under
+    /// the current message while the transfer is in flight, so a new failure pauses it
+    /// again under this message id.
+    pub async fn recover_operation_core(
+        &self,
+        op_id: MessageId,
+    ) -> Result<Option<PairEvent>, PairError> {
+        if !self.is_admin(&msg::source()) {
+            return Err(PairError::Unauthorized);
+        }
+        let msg_id = msg::id();
+        let (ctx, token0, token1, config) = self.with_state_mut(|st| -> Result<_, PairError> {
+            let ctx = st.ops.restart(op_id, msg_id).ok_or(PairError::NotPaused)?;
+            Ok((ctx, st.token0, st.token1, st.config.clone()))
+        })?;
+
+        let event = match ctx {
             // -------------------------
             // 1) Add liquidity refund retry
             // -------------------------
@@ -731,18 +690,12 @@ impl<'a> PairService<'a> {
                 token,
                 amount,
             } => {
-                let msg_id = msg::id();
                 self.with_tracker_mut(|tr| {
                     tr.insert_msg_status(msg_id, MessageStatus::SendingMessageToReturnTokensA);
                 });
 
                 self.transfer(token, user, amount, &config, msg_id).await?;
-
-                self.with_state_mut(|st| {
-                    st.lock.set_free();
-                });
-                let _ = self.lp.pause.resume();
-                clear_tracker();
+                None
             }
             // -------------------------
             // 2) Swap refund retry
@@ -752,164 +705,120 @@ impl<'a> PairService<'a> {
                 token,
                 amount,
             } => {
-                let msg_id = msg::id();
                 self.with_tracker_mut(|tr| {
                     tr.insert_msg_status(msg_id, MessageStatus::SendingMessageToReturnTokenIn);
                 });
 
                 self.transfer(token, user, amount, &config, msg_id).await?;
-
-                self.with_state_mut(|st| {
-                    st.lock.set_free();
-                });
-                let _ = self.lp.pause.resume();
-                clear_tracker();
+                None
             }
             // -------------------------
-            // 3) Remove liquidity recovery (only SendToken1)
+            // 3) Remove liquidity recovery - already settled, finish the missing payouts
             // -------------------------
             LockCtx::RemLiq {
                 user,
+                to,
                 liquidity,
                 amount_a,
                 amount_b,
                 stage,
             } => {
-                if stage != SendTokenStage::SendToken1 {
-                    return Err(PairError::InvalidRecoveryState);
+                if stage == SendTokenStage::SendToken0 {
+                    self.with_tracker_mut(|tr| {
+                        tr.insert_msg_status(msg_id, MessageStatus::SendingMsgToUnlockTokenA);
+                    });
+                    self.transfer(token0, to, amount_a, &config, msg_id).await?;
                 }
 
-                // finish missing payout (token1)
-                let msg_id = msg::id();
                 self.with_tracker_mut(|tr| {
                     tr.insert_msg_status(msg_id, MessageStatus::SendingMsgToUnlockTokenB);
                 });
+                self.transfer(token1, to, amount_b, &config, msg_id).await?;
 
-                self.transfer(token1, user, amount_b, &config, msg_id)
-                    .await?;
-
-                let _ = self.lp.pause.resume();
-                // finalize exactly-once: mint_fee -> burn -> reserves -> k_last
-                let event = self.with_state_mut(|st| -> Result<PairEvent, PairError> {
-                    let mut lp = self.lp_service();
-                    mint_fee_lp(st, &mut lp)?;
-                    burn_liquidity(&mut lp, user, liquidity)?;
-
-                    st.reserve0 = st
-                        .reserve0
-                        .checked_sub(amount_a)
-                        .ok_or(PairError::Overflow)?;
-                    st.reserve1 = st
-                        .reserve1
-                        .checked_sub(amount_b)
-                        .ok_or(PairError::Overflow)?;
-
-                    if !st.fee_to.is_zero() {
-                        set_new_k_last(st)?;
-                    }
-
-                    st.lock.set_free();
-                    Ok(PairEvent::LiquidityRemoved {
-                        user_id: user,
-                        amount_a,
-                        amount_b,
-                        liquidity,
-                    })
-                })?;
-                clear_tracker();
-                return Ok(Some(event));
+                Some(PairEvent::LiquidityRemoved {
+                    user_id: user,
+                    amount_a,
+                    amount_b,
+                    liquidity,
+                })
             }
             // -------------------------
-            // 4) Migrate liquidity recovery (only SendToken1)
+            // 4) Treasury payout recovery - fees were cleared up front, finish the payouts
             // -------------------------
-            LockCtx::MigrateAllLiquidity {
-                target,
+            LockCtx::TreasuryPayout {
+                treasury,
                 amount0,
                 amount1,
                 stage,
             } => {
-                if stage != SendTokenStage::SendToken1 {
-                    return Err(PairError::InvalidRecoveryState);
+                if stage == SendTokenStage::SendToken0 {
+                    self.with_tracker_mut(|tr| {
+                        tr.insert_msg_status(msg_id, MessageStatus::SendingTreasuryTokenA);
+                    });
+                    self.transfer(token0, treasury, amount0, &config, msg_id)
+                        .await?;
                 }
 
-                let msg_id = msg::id();
-                self.with_tracker_mut(|tr| {
-                    tr.insert_msg_status(msg_id, MessageStatus::SendingMsgToUnlockTokenB);
-                });
-
-                self.transfer(token1, target, amount1, &config, msg_id)
-                    .await?;
-
-                let event = self.with_state_mut(|st| -> Result<PairEvent, PairError> {
-                    st.reserve0 = U256::zero();
-                    st.reserve1 = U256::zero();
-                    st.k_last = U256::zero();
-                    st.accrued_treasury_fee0 = U256::zero();
-                    st.accrued_treasury_fee1 = U256::zero();
-                    st.migrated = true;
-                    st.lock.set_free();
-
-                    Ok(PairEvent::LiquidityMigrated {
-                        to: target,
-                        amount0,
-                        amount1,
-                    })
-                })?;
+                if !amount1.is_zero() {
+                    self.with_tracker_mut(|tr| {
+                        tr.insert_msg_status(msg_id, MessageStatus::SendingTreasuryTokenB);
+                    });
+                    self.transfer(token1, treasury, amount1, &config, msg_id)
+                        .await?;
+                }
 
-                let _ = self.lp.pause.resume();
-                clear_tracker();
-                return Ok(Some(event));
+                Some(PairEvent::TreasuryFeesCollected {
+                    treasury_id: treasury,
+                    amount_a: amount0,
+                    amount_b: amount1,
+                })
             }
             // -------------------------
-            // 5) Treasury payout recovery (only SendToken1)
+            // 5) Withdrawal recovery - the internal balance was debited up front, resend
             // -------------------------
-            LockCtx::TreasuryPayout {
-                treasury,
-                amount0,
-                amount1,
-                stage,
+            LockCtx::Withdraw {
+                user,
+                token,
+                amount,
             } => {
-                if stage != SendTokenStage::SendToken1 {
-                    return Err(PairError::InvalidRecoveryState);
-                }
-
-                let msg_id = msg::id();
                 self.with_tracker_mut(|tr| {
-                    tr.insert_msg_status(msg_id, MessageStatus::SendingTreasuryTokenB);
+                    tr.insert_msg_status(msg_id, MessageStatus::SendingWithdrawal);
                 });
+                self.transfer(token, user, amount, &config, msg_id).await?;
 
-                self.transfer(token1, treasury, amount1, &config, msg_id)
-                    .await?;
-
-                let event = self.with_state_mut(|st| -> Result<PairEvent, PairError> {
-                    st.accrued_treasury_fee0 = U256::zero();
-                    st.accrued_treasury_fee1 = U256::zero();
-                    st.lock.set_free();
+                Some(PairEvent::Withdrawn {
+                    user_id: user,
+                    token,
+                    amount,
+                })
+            }
+            // Pair-wide contexts are recovered with `recover_paused`
+            _ => return Err(PairError::InvalidRecoveryState),
+        };
 
-                    Ok(PairEvent::TreasuryFeesCollected {
-                        treasury_id: treasury,
-                        amount_a: amount0,
-                        amount_b: amount1,
-                    })
-                })?;
+        self.with_state_mut(|st| st.ops.finish(&msg_id));
+        self.with_tracker_mut(|tr| {
+            tr.remove_msg_status(&msg_id);
+        });
+        Ok(event)
+    }
 
-                let _ = self.lp.pause.resume();
-                clear_tracker();
-                return Ok(Some(event));
+    /// Mints the protocol fee accrued at the current share, then switches to `1 / divisor`.
+    pub fn change_protocol_fee_divisor(&self, divisor: u64) -> Result<(), PairError> {
+        let mut lp = self.lp_service();
+        self.with_state_mut(|st| {
+            if !st.lock.is_free() {
+                return Err(PairError::AnotherTxInProgress);
             }
-            // -------------------------
-            // 6) Admin pause - just unlock
-            // -------------------------
-            LockCtx::AdminPause => {
-                self.with_state_mut(|st| {
-                    st.lock.set_free();
-                });
-                let _ = self.lp.pause.resume();
-                clear_tracker();
+            mint_fee_lp(st, &mut lp)?;
+            if !st.fee_to.is_zero() {
+                set_new_k_last(st)?;
             }
-        }
-        Ok(None)
+            st.protocol_fee_divisor = divisor;
+            Ok(())
+        })
     }
+
     async fn transfer_tokens_to_pool(
         &self,
         sender: ActorId,
@@ -946,7 +855,9 @@ impl<'a> PairService<'a> {
                 .await?;
             return Err(PairError::TokenTransferFailed);
         }
-        self.with_tracker_mut(|tr| tr.clear_all());
+        self.with_tracker_mut(|tr| {
+            tr.remove_msg_status(&msg_id);
+        });
 
         Ok(())
     }
@@ -965,7 +876,6 @@ impl<'a> PairService<'a> {
         self.with_tracker_mut(|tr| {
             tr.insert_msg_status(msg_id, MessageStatus::SendingMsgToUnlockTokenA);
         });
-        let _ = self.lp.pause.pause();
 
         self.transfer(token0, sender, amount_a, config, msg_id)
             .await?;
@@ -977,8 +887,9 @@ impl<'a> PairService<'a> {
         self.transfer(token1, sender, amount_b, config, msg_id)
             .await?;
 
-        let _ = self.lp.pause.resume();
-        self.with_tracker_mut(|tr| tr.clear_all());
+        self.with_tracker_mut(|tr| {
+            tr.remove_msg_status(&msg_id);
+        });
         Ok(())
     }
 
@@ -989,6 +900,7 @@ impl<'a> PairService<'a> {
         amount_in: U256,
         token_out: ActorId,
         amount_out: U256,
+        to: ActorId,
         config: &Config,
     ) -> Result<(), PairError> {
         let program_id = exec::program_id();
@@ -1005,9 +917,9 @@ impl<'a> PairService<'a> {
         self.with_tracker_mut(|tr| {
             tr.insert_msg_status(msg_id, MessageStatus::SendingMsgToTransferTokenOut);
         });
-        // Send output tokens to user
+        // Send output tokens to the recipient
         let result = self
-            .transfer(token_out, sender, amount_out, config, msg_id)
+            .transfer(token_out, to, amount_out, config, msg_id)
             .await;
 
         // Very unlikely
@@ -1028,25 +940,22 @@ impl<'a> PairService<'a> {
 /// Calculates and mints protocol fees for the liquidity pool, similar to Uniswap V2.
 ///
 /// This function checks if protocol fees are enabled (via `fee_to` address) and calculates
-/// the growth in pool reserves due to accumulated swap fees (0.3% per swap, with 1/6 or
-/// 0.05% going to the protocol). If growth is detected, it mints new liquidity tokens (LP tokens)
+/// the growth in pool reserves due to accumulated swap fees (`swap_fee_bps` per swap, with
+/// `1 / protocol_fee_divisor` of it going to the protocol). If growth is detected, it mints new liquidity tokens (LP tokens)
 /// to the `fee_to` address, proportional to the increase in the square root of the constant
-/// product (`reserve0 * reserve1`). If protocol fees are disabled, it resets `k_last` to zero
+/// product (`reserve0 * reserve1`, `State::k` on the stable curve). If protocol fees are disabled, it resets `k_last` to zero
 /// to prevent future minting unless re-enabled.
 ///
 /// Called internally before adding (`mint`) or removing (`burn`) liquidity to
 /// ensure protocol fees from accumulated swaps are accounted for.
-fn mint_fee_lp(state: &mut State, lp: &mut LpExposure<'_>) -> Result<(), PairError> {
+pub fn mint_fee_lp(state: &mut State, lp: &mut LpExposure<'_>) -> Result<(), PairError> {
     let fee_to = state.fee_to;
     let k_last = state.k_last;
     let fee_on = !fee_to.is_zero();
 
     if fee_on {
         if !k_last.is_zero() {
-            let current_k = state
-                .reserve0
-                .checked_mul(state.reserve1)
-                .ok_or(PairError::Overflow)?;
+            let current_k = state.k(state.reserve0, state.reserve1)?;
 
             let root_k = current_k.integer_sqrt();
             let root_k_last = k_last.integer_sqrt();
@@ -1057,19 +966,12 @@ fn mint_fee_lp(state: &mut State, lp: &mut LpExposure<'_>) -> Result<(), PairErr
                 // total_supply from balances (bypass pause)
                 let total_supply = lp.total_supply().unwrap_or(U256::zero());
 
-                let numerator = total_supply
-                    .checked_mul(root_k_diff)
-                    .ok_or(PairError::Overflow)?;
-
-                let root_k_times_5 = root_k
-                    .checked_mul(U256::from(5))
-                    .ok_or(PairError::Overflow)?;
-
-                let denominator = root_k_times_5
-                    .checked_add(root_k_last)
-                    .ok_or(PairError::Overflow)?;
-
-                let liquidity = numerator / denominator;
+                let liquidity = protocol_fee_liquidity(
+                    total_supply,
+                    root_k,
+                    root_k_last,
+                    state.protocol_fee_divisor,
+                )?;
 
                 if !liquidity.is_zero() {
                     mint_liquidity(lp, fee_to, liquidity)?;
@@ -1083,8 +985,8 @@ fn mint_fee_lp(state: &mut State, lp: &mut LpExposure<'_>) -> Result<(), PairErr
     Ok(())
 }
 
-/// Verifies the constant product invariant (k) after a swap, accounting for a 0.3% fee.
-/// Ensures that (balance0 * 1000 - amount0_in * 3) * (balance1 * 1000 - amount1_in * 3) >= reserve0 * reserve1 * 1000^2.
+/// Verifies the constant product invariant (k) after a swap, accounting for the swap fee.
+/// Ensures that (balance0 * 10_000 - amount0_in * fee) * (balance1 * 10_000 - amount1_in * fee) >= reserve0 * reserve1 * 10_000^2.
 /// # Arguments
 /// * `balance0` - New balance of token0 after swap
 /// * `balance1` - New balance of token1 after swap
@@ -1092,6 +994,7 @@ fn mint_fee_lp(state: &mut State, lp: &mut LpExposure<'_>) -> Result<(), PairErr
 /// * `amount1_in` - Input amount of token1
 /// * `reserve0` - Reserve of token0 before swap
 /// * `reserve1` - Reserve of token1 before swap
+/// * `swap_fee_bps` - Swap fee of the pair in basis points
 pub fn verify_constant_product_invariant(
     balance0: U256,
     balance1: U256,
@@ -1099,19 +1002,20 @@ pub fn verify_constant_product_invariant(
     amount1_in: U256,
     reserve0: U256,
     reserve1: U256,
+    swap_fee_bps: u64,
 ) -> Result<(), PairError> {
-    let thousand = U256::from(1000);
-    let fee = U256::from(FEE_BPS);
+    let denom = U256::from(amm_math::FEE_DENOM_BPS);
+    let fee = U256::from(swap_fee_bps);
 
-    // Calculate adjusted balances: balance * 1000 - amount_in * 3
+    // Calculate adjusted balances: balance * 10_000 - amount_in * fee
     let balance0_adjusted = balance0
-        .checked_mul(thousand)
+        .checked_mul(denom)
         .ok_or(PairError::Overflow)?
         .checked_sub(amount0_in.checked_mul(fee).ok_or(PairError::Overflow)?)
         .ok_or(PairError::Overflow)?;
 
     let balance1_adjusted = balance1
-        .checked_mul(thousand)
+        .checked_mul(denom)
         .ok_or(PairError::Overflow)?
         .checked_sub(amount1_in.checked_mul(fee).ok_or(PairError::Overflow)?)
         .ok_or(PairError::Overflow)?;
@@ -1121,11 +1025,11 @@ pub fn verify_constant_product_invariant(
         .checked_mul(balance1_adjusted)
         .ok_or(PairError::Overflow)?;
 
-    // Calculate old constant product: reserve0 * reserve1 * 1000*1000
+    // Calculate old constant product: reserve0 * reserve1 * 10_000^2
     let k_old = reserve0
         .checked_mul(reserve1)
         .ok_or(PairError::Overflow)?
-        .checked_mul(thousand * thousand)
+        .checked_mul(denom * denom)
         .ok_or(PairError::Overflow)?;
 
     // Verify invariant
@@ -1136,7 +1040,7 @@ pub fn verify_constant_product_invariant(
     Ok(())
 }
 
-fn mint_liquidity(
+pub fn mint_liquidity(
     lp: &mut LpExposure<'_>,
     sender: ActorId,
     liquidity: U256,
@@ -1147,7 +1051,7 @@ fn mint_liquidity(
 }
 
 /// Burns LP tokens from user's balance
-fn burn_liquidity(
+pub fn burn_liquidity(
     lp: &mut LpExposure<'_>,
     from: ActorId,
     liquidity: U256,
@@ -1157,15 +1061,301 @@ fn burn_liquidity(
     Ok(())
 }
 
-fn set_new_k_last(state: &mut State) -> Result<(), PairError> {
-    state.k_last = state
+/// Mints the LP tokens for `amount_a` of token0 and `amount_b` of token1 already received
+/// and adds them to the reserves: mint_fee -> liquidity -> reserves -> k_last.
+/// Returns the liquidity minted to `to`.
+pub fn settle_add_liquidity(
+    st: &mut State,
+    lp: &mut LpExposure<'_>,
+    amount_a: U256,
+    amount_b: U256,
+    to: ActorId,
+) -> Result<U256, PairError> {
+    // mint protocol fee (if fee_on)
+    mint_fee_lp(st, lp).map_err(|_| PairError::Overflow)?;
+    // compute liquidity with total_supply AFTER protocol fee mint
+    let total_supply = lp.total_supply().unwrap_or(U256::zero());
+    let liquidity =
+        amm_math::calculate_liquidity(st.reserve0, st.reserve1, amount_a, amount_b, total_supply)?;
+    // mint MINIMUM_LIQUIDITY once (to dead address), then mint user liquidity
+    if total_supply.is_zero() {
+        mint_liquidity(lp, LP_DEAD.into(), U256::from(amm_math::MINIMUM_LIQUIDITY))?
+    }
+    mint_liquidity(lp, to, liquidity)?;
+
+    let reserve0 = st
         .reserve0
-        .checked_mul(state.reserve1)
+        .checked_add(amount_a)
+        .ok_or(PairError::Overflow)?;
+    let reserve1 = st
+        .reserve1
+        .checked_add(amount_b)
+        .ok_or(PairError::Overflow)?;
+    st.set_reserves(reserve0, reserve1);
+
+    // fee_on is "fee_to != 0" as in Uniswap V2
+    if !st.fee_to.is_zero() {
+        set_new_k_last(st)?;
+    }
+    Ok(liquidity)
+}
+
+/// Burns `liquidity` of `owner` and takes its share out of the reserves:
+/// mint_fee -> burn -> reserves -> k_last. Returns `(amount_a, amount_b)` to pay out.
+pub fn settle_remove_liquidity(
+    st: &mut State,
+    lp: &mut LpExposure<'_>,
+    owner: ActorId,
+    liquidity: U256,
+    amount_a_min: U256,
+    amount_b_min: U256,
+) -> Result<(U256, U256), PairError> {
+    // Verify user has sufficient LP tokens
+    let user_balance = lp.balance_of(owner).unwrap_or(U256::zero());
+    if user_balance < liquidity {
+        return Err(PairError::InsufficientLiquidity);
+    }
+    let total_supply = lp.total_supply().unwrap_or(U256::zero());
+    if total_supply.is_zero() {
+        return Err(PairError::InsufficientLiquidity);
+    }
+
+    // protocol fee (view) — не меняем state
+    let lp_protocol_fee = calculate_protocol_fee(st, total_supply)?;
+
+    // Calculate proportional amounts of underlying tokens to return
+    // Formula: user_amount = (liquidity_to_burn * reserve) / total_supply
+    let amount_a = liquidity
+        .checked_mul(st.reserve0)
+        .and_then(|result| result.checked_div(total_supply + lp_protocol_fee))
+        .ok_or(PairError::Overflow)?;
+
+    let amount_b = liquidity
+        .checked_mul(st.reserve1)
+        .and_then(|result| result.checked_div(total_supply + lp_protocol_fee))
         .ok_or(PairError::Overflow)?;
+
+    // Slippage protection: ensure user receives at least minimum amounts
+    if amount_a < amount_a_min {
+        return Err(PairError::InsufficientAmountA);
+    }
+    if amount_b < amount_b_min {
+        return Err(PairError::InsufficientAmountB);
+    }
+
+    if amount_a.is_zero() || amount_b.is_zero() {
+        return Err(PairError::InsufficientLiquidityBurned);
+    }
+    // Sanity check: ensure pool has sufficient reserves
+    if amount_a > st.reserve0 || amount_b > st.reserve1 {
+        return Err(PairError::InsufficientLiquidity);
+    }
+
+    mint_fee_lp(st, lp)?;
+    burn_liquidity(lp, owner, liquidity)?;
+    st.set_reserves(st.reserve0 - amount_a, st.reserve1 - amount_b);
+    if !st.fee_to.is_zero() {
+        set_new_k_last(st)?;
+    } else if !st.k_last.is_zero() {
+        st.k_last = U256::zero();
+    }
+    Ok((amount_a, amount_b))
+}
+
+/// Prices a swap against `swap_reserves` at `swap_fee_bps` and checks the slippage limit
+/// and the invariant.
+pub fn quote_swap(
+    st: &State,
+    swap_type: SwapType,
+    is_token0_to_token1: bool,
+    swap_fee_bps: u64,
+) -> Result<SwapQuote, PairError> {
+    let (token_in, token_out) = if is_token0_to_token1 {
+        (st.token0, st.token1)
+    } else {
+        (st.token1, st.token0)
+    };
+    let (reserve_in, reserve_out) = swap_reserves(st, is_token0_to_token1)?;
+    let treasury_fee_bps = st.active_treasury_fee_bps();
+    let now = exec::block_timestamp();
+
+    let (amount_in_for_pool, amount_in_total, amount_out, treasury_fee) = match swap_type {
+        SwapType::ExactInput {
+            amount_in,
+            amount_out_min,
+        } => {
+            let (in_for_pool, out, t_fee) = st.curve.get_amount_out_with_treasury(
+                amount_in,
+                reserve_in,
+                reserve_out,
+                swap_fee_bps,
+                treasury_fee_bps,
+                now,
+            )?;
+
+            if out < amount_out_min {
+                return Err(PairError::InsufficientAmount);
+            }
+
+            (in_for_pool, amount_in, out, t_fee)
+        }
+        SwapType::ExactOutput {
+            amount_out,
+            amount_in_max,
+        } => {
+            let (in_for_pool, in_total, t_fee) = st.curve.get_amount_in_with_treasury(
+                amount_out,
+                reserve_in,
+                reserve_out,
+                swap_fee_bps,
+                treasury_fee_bps,
+                now,
+            )?;
+
+            if in_total > amount_in_max {
+                return Err(PairError::ExcessiveInputAmount);
+            }
+
+            (in_for_pool, in_total, amount_out, t_fee)
+        }
+    };
+
+    if amount_out > reserve_out {
+        return Err(PairError::InsufficientLiquidity);
+    }
+
+    // invariant inputs, ordered as (token0, token1)
+    let new_reserve_in = reserve_in
+        .checked_add(amount_in_for_pool)
+        .ok_or(PairError::Overflow)?;
+    let new_reserve_out = reserve_out - amount_out;
+    let (new_reserve0, new_reserve1, reserve0, reserve1, amount0_in, amount1_in) =
+        if is_token0_to_token1 {
+            (
+                new_reserve_in,
+                new_reserve_out,
+                reserve_in,
+                reserve_out,
+                amount_in_for_pool,
+                U256::zero(),
+            )
+        } else {
+            (
+                new_reserve_out,
+                new_reserve_in,
+                reserve_out,
+                reserve_in,
+                U256::zero(),
+                amount_in_for_pool,
+            )
+        };
+    st.curve.verify_invariant(
+        new_reserve0,
+        new_reserve1,
+        amount0_in,
+        amount1_in,
+        reserve0,
+        reserve1,
+        swap_fee_bps,
+        now,
+    )?;
+
+    Ok(SwapQuote {
+        token_in,
+        token_out,
+        amount_in_for_pool,
+        amount_in_total,
+        amount_out,
+        treasury_fee,
+    })
+}
+
+/// Applies a quoted swap to the reserves as they are now, other swaps may have settled since
+/// it was priced, and accrues its treasury fee.
+pub fn settle_swap(
+    st: &mut State,
+    quote: &SwapQuote,
+    is_token0_to_token1: bool,
+) -> Result<(), PairError> {
+    let (amount0_in, amount1_in, amount0_out, amount1_out) = if is_token0_to_token1 {
+        (
+            quote.amount_in_for_pool,
+            U256::zero(),
+            U256::zero(),
+            quote.amount_out,
+        )
+    } else {
+        (
+            U256::zero(),
+            quote.amount_in_for_pool,
+            quote.amount_out,
+            U256::zero(),
+        )
+    };
+    let reserve0 = st
+        .reserve0
+        .checked_add(amount0_in)
+        .and_then(|reserve| reserve.checked_sub(amount0_out))
+        .ok_or(PairError::Overflow)?;
+    let reserve1 = st
+        .reserve1
+        .checked_add(amount1_in)
+        .and_then(|reserve| reserve.checked_sub(amount1_out))
+        .ok_or(PairError::Overflow)?;
+    st.set_reserves(reserve0, reserve1);
+
+    if !quote.treasury_fee.is_zero() && !st.treasury_id.is_zero() {
+        if is_token0_to_token1 {
+            st.accrued_treasury_fee0 = st
+                .accrued_treasury_fee0
+                .checked_add(quote.treasury_fee)
+                .ok_or(PairError::Overflow)?;
+        } else {
+            st.accrued_treasury_fee1 = st
+                .accrued_treasury_fee1
+                .checked_add(quote.treasury_fee)
+                .ok_or(PairError::Overflow)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reserves a new swap is priced against: what the swaps in flight may add is counted on the
+/// input side and what they may take is left out on the output side, so the new swap keeps
+/// the invariant whichever of them settle, in any order.
+fn swap_reserves(state: &State, is_token0_to_token1: bool) -> Result<(U256, U256), PairError> {
+    let reserved = state.ops.reserved();
+    let (reserve_in, reserved_in, reserve_out, reserved_out) = if is_token0_to_token1 {
+        (state.reserve0, reserved.in0, state.reserve1, reserved.out1)
+    } else {
+        (state.reserve1, reserved.in1, state.reserve0, reserved.out0)
+    };
+    Ok((
+        reserve_in
+            .checked_add(reserved_in)
+            .ok_or(PairError::Overflow)?,
+        reserve_out
+            .checked_sub(reserved_out)
+            .ok_or(PairError::InsufficientLiquidity)?,
+    ))
+}
+
+pub fn set_new_k_last(state: &mut State) -> Result<(), PairError> {
+    state.k_last = state.k(state.reserve0, state.reserve1)?;
     Ok(())
 }
 
 pub fn calculate_protocol_fee(state: &State, total_supply: U256) -> Result<U256, PairError> {
+    protocol_fee_at(state, state.reserve0, state.reserve1, total_supply)
+}
+
+/// Same as [`calculate_protocol_fee`], as if the reserves were `reserve0` and `reserve1`.
+pub fn protocol_fee_at(
+    state: &State,
+    reserve0: U256,
+    reserve1: U256,
+    total_supply: U256,
+) -> Result<U256, PairError> {
     let fee_to = state.fee_to;
     let fee_on = !fee_to.is_zero();
     let k_last = state.k_last;
@@ -1178,10 +1368,7 @@ pub fn calculate_protocol_fee(state: &State, total_supply: U256) -> Result<U256,
         return Ok(U256::zero());
     }
 
-    let current_k = state
-        .reserve0
-        .checked_mul(state.reserve1)
-        .ok_or(PairError::Overflow)?;
+    let current_k = state.k(reserve0, reserve1)?;
 
     let root_k = current_k.integer_sqrt();
     let root_k_last = k_last.integer_sqrt();
@@ -1190,29 +1377,48 @@ pub fn calculate_protocol_fee(state: &State, total_supply: U256) -> Result<U256,
         return Ok(U256::zero());
     }
 
+    protocol_fee_liquidity(
+        total_supply,
+        root_k,
+        root_k_last,
+        state.protocol_fee_divisor,
+    )
+}
+
+/// LP tokens minted to `fee_to` so that it owns `1 / divisor` of the growth of `sqrt(k)`:
+/// `total_supply * (root_k - root_k_last) / (root_k * (divisor - 1) + root_k_last)`.
+/// With the default divisor of 6 this is the Uniswap V2 `root_k * 5 + root_k_last` formula.
+/// Expects `root_k > root_k_last`.
+fn protocol_fee_liquidity(
+    total_supply: U256,
+    root_k: U256,
+    root_k_last: U256,
+    divisor: u64,
+) -> Result<U256, PairError> {
     let root_k_diff = root_k - root_k_last;
 
     let numerator = total_supply
         .checked_mul(root_k_diff)
         .ok_or(PairError::Overflow)?;
 
-    let root_k_times_5 = root_k
-        .checked_mul(U256::from(5))
+    let lp_share = divisor
+        .checked_sub(1)
+        .ok_or(PairError::InvalidProtocolFeeShare)?;
+    let root_k_times_lp_share = root_k
+        .checked_mul(U256::from(lp_share))
         .ok_or(PairError::Overflow)?;
 
-    let denominator = root_k_times_5
+    let denominator = root_k_times_lp_share
         .checked_add(root_k_last)
         .ok_or(PairError::Overflow)?;
 
-    let liquidity = numerator / denominator;
-
-    Ok(liquidity)
+    Ok(numerator / denominator)
 }
 
 /// Calculates accumulated swap fees for all LP providers, similar to Uniswap V2.
 ///
-/// This function calculates the total growth in pool reserves due to swap fees (0.3% per swap),
-/// subtracts the protocol share (1/6 or 0.05%), and returns the remaining fees (0.25%) as the
+/// This function calculates the total growth in pool reserves due to swap fees (`swap_fee_bps` per swap),
+/// subtracts the protocol share (`1 / protocol_fee_divisor`), and returns the rest of the fees as the
 /// equivalent LP token value for all providers combined. Returns 0 if no growth or fees disabled.
 ///
 /// Can be called for estimation. Does not modify state.
@@ -1223,9 +1429,11 @@ pub fn calculate_lp_fee(state: &State, total_supply: U256) -> Result<U256, PairE
         return Ok(U256::zero());
     }
 
-    // LP fees = total growth - protocol fee (5/6 of growth)
-    let total_growth = /* Calculate total growth, e.g., from root_k_diff * denominator / 6 or similar; for simplicity, assume protocol is 1/6 */
-        protocol_fee.checked_mul(U256::from(5)).ok_or(PairError::Overflow)?;
+    // LP fees = total growth - protocol fee ((divisor - 1) / divisor of growth)
+    let lp_share = state.protocol_fee_divisor.saturating_sub(1);
+    let total_growth = protocol_fee
+        .checked_mul(U256::from(lp_share))
+        .ok_or(PairError::Overflow)?;
 
     Ok(total_growth)
 }
@@ -0,0 +1,172 @@
+use crate::services::pair::{Config, PairError, PairEvent, PairService, amm_math, funcs::SwapType};
+use hook_client::SwapVerdict;
+use hook_client::hook::io::{
+    AfterAddLiquidity, AfterRemoveLiquidity, AfterSwap, BeforeAddLiquidity, BeforeRemoveLiquidity,
+    BeforeSwap,
+};
+use sails_rs::client::CallCodec;
+use sails_rs::{gstd::msg, prelude::*};
+
+/// Service of the hook program, see the `hook` program for the interface.
+const HOOK_SERVICE: &str = "Hook";
+
+impl<'a> PairService<'a> {
+    /// Hook of the pair and the config to call it with, `None` while no hook is set.
+    fn hook_with_config(&self) -> Option<(ActorId, Config)> {
+        self.with_state(|st| (!st.hook.is_zero()).then(|| (st.hook, st.config.clone())))
+    }
+
+    /// Asks the hook whether the swap may go ahead. Returns the swap fee the hook sets
+    /// for it, `None` for the pair's own.
+    pub async fn before_swap_hook(
+        &self,
+        to: ActorId,
+        swap_type: SwapType,
+        is_token0_to_token1: bool,
+    ) -> Result<Option<u64>, PairError> {
+        let Some((hook, config)) = self.hook_with_config() else {
+            return Ok(None);
+        };
+        let (exact_input, amount) = match swap_type {
+            SwapType::ExactInput { amount_in, .. } => (true, amount_in),
+            SwapType::ExactOutput { amount_out, .. } => (false, amount_out),
+        };
+        let swap_fee_bps = self.with_state(|st| st.swap_fee_bps);
+        let payload = BeforeSwap::encode_params_with_prefix(
+            HOOK_SERVICE,
+            msg::source(),
+            to,
+            is_token0_to_token1,
+            exact_input,
+            amount,
+            swap_fee_bps,
+        );
+        let reply = call_hook(hook, payload, &config).await?;
+        match BeforeSwap::decode_reply_with_prefix(HOOK_SERVICE, &reply)
+            .map_err(|_| PairError::HookFailure)?
+        {
+            SwapVerdict::Allow => Ok(None),
+            SwapVerdict::AllowWithFee(fee_bps) if fee_bps <= amm_math::MAX_SWAP_FEE_BPS => {
+                Ok(Some(fee_bps))
+            }
+            SwapVerdict::AllowWithFee(_) => Err(PairError::InvalidSwapFee),
+            SwapVerdict::Reject => Err(PairError::HookRejected),
+        }
+    }
+
+    pub async fn before_add_liquidity_hook(
+        &self,
+        to: ActorId,
+        amount_a_desired: U256,
+        amount_b_desired: U256,
+    ) -> Result<(), PairError> {
+        let Some((hook, config)) = self.hook_with_config() else {
+            return Ok(());
+        };
+        let payload = BeforeAddLiquidity::encode_params_with_prefix(
+            HOOK_SERVICE,
+            msg::source(),
+            to,
+            amount_a_desired,
+            amount_b_desired,
+        );
+        let reply = call_hook(hook, payload, &config).await?;
+        let allowed = BeforeAddLiquidity::decode_reply_with_prefix(HOOK_SERVICE, &reply)
+            .map_err(|_| PairError::HookFailure)?;
+        if !allowed {
+            return Err(PairError::HookRejected);
+        }
+        Ok(())
+    }
+
+    pub async fn before_remove_liquidity_hook(
+        &self,
+        to: ActorId,
+        liquidity: U256,
+    ) -> Result<(), PairError> {
+        let Some((hook, config)) = self.hook_with_config() else {
+            return Ok(());
+        };
+        let payload = BeforeRemoveLiquidity::encode_params_with_prefix(
+            HOOK_SERVICE,
+            msg::source(),
+            to,
+            liquidity,
+        );
+        let reply = call_hook(hook, payload, &config).await?;
+        let allowed = BeforeRemoveLiquidity::decode_reply_with_prefix(HOOK_SERVICE, &reply)
+            .map_err(|_| PairError::HookFailure)?;
+        if !allowed {
+            return Err(PairError::HookRejected);
+        }
+        Ok(())
+    }
+
+    /// Tells the hook about a completed swap, liquidity addition or removal.
+    /// The reply is not awaited, so the hook can't undo the operation.
+    pub fn after_hook(&self, to: ActorId, event: &PairEvent) {
+        let Some((hook, config)) = self.hook_with_config() else {
+            return;
+        };
+        let sender = msg::source();
+        let payload = match *event {
+            PairEvent::Swap {
+                amount_in,
+                amount_out,
+                is_token0_to_token1,
+                ..
+            } => AfterSwap::encode_params_with_prefix(
+                HOOK_SERVICE,
+                sender,
+                to,
+                is_token0_to_token1,
+                amount_in,
+                amount_out,
+            ),
+            PairEvent::LiquidityAdded {
+                amount_a,
+                amount_b,
+                liquidity,
+                ..
+            } => AfterAddLiquidity::encode_params_with_prefix(
+                HOOK_SERVICE,
+                sender,
+                to,
+                amount_a,
+                amount_b,
+                liquidity,
+            ),
+            PairEvent::LiquidityRemoved {
+                amount_a,
+                amount_b,
+                liquidity,
+                ..
+            } => AfterRemoveLiquidity::encode_params_with_prefix(
+                HOOK_SERVICE,
+                sender,
+                to,
+                amount_a,
+                amount_b,
+                liquidity,
+            ),
+            _ => return,
+        };
+        // The operation is done whatever happens to the notification
+        let _ = msg::send_bytes_with_gas(hook, payload, config.gas_for_token_ops, 0);
+    }
+}
+
+async fn call_hook(hook: ActorId, payload: Vec<u8>, config: &Config) -> Result<Vec<u8>, PairError> {
+    msg::send_bytes_with_gas_for_reply(
+        hook,
+        payload,
+        config.gas_for_token_ops,
+        0,
+        config.gas_for_reply_deposit,
+    )
+    .map_err(|_| PairError::HookFailure)?
+    .up_to(Some(config.reply_timeout))
+    .map_err(|_| PairError::HookFailure)?
+    .await
+    .map_err(|_| PairError::HookFailure)
+}
@@ -1,4 +1,6 @@
 use crate::*;
+use sails_rs::collections::HashMap;
+
 #[derive(Debug, Default, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
 pub enum LockState {
     #[default]
@@ -28,7 +30,8 @@ impl LockState {
         match self {
             LockState::Busy(LockCtx::RemLiq { stage, .. })
             | LockState::Busy(LockCtx::MigrateAllLiquidity { stage, .. })
-            | LockState::Busy(LockCtx::TreasuryPayout { stage, .. }) => {
+            | LockState::Busy(LockCtx::TreasuryPayout { stage, .. })
+            | LockState::Busy(LockCtx::Swap { stage, .. }) => {
                 *stage = SendTokenStage::SendToken1;
             }
             _ => {}
@@ -41,6 +44,8 @@ pub enum LockCtx {
     /// remove_liquidity: we are doing sequential payouts. Stage tells where we are.
     RemLiq {
         user: ActorId,
+        /// Receives the underlying tokens; `user` owns the burnt LP.
+        to: ActorId,
         liquidity: U256,
         amount_a: U256,
         amount_b: U256,
@@ -71,6 +76,40 @@ pub enum LockCtx {
         stage: SendTokenStage,
     },
     AdminPause,
+    /// flash_swap: the borrower holds `amount*_out` until it pays them back with the fee
+    FlashSwap {
+        borrower: ActorId,
+        amount0_out: U256,
+        amount1_out: U256,
+    },
+    /// sync / skim: token balances are being read or the excess sent out
+    Reconcile,
+    /// deposit: `amount` of `token` is being taken from `user`, credited once it arrives
+    Deposit {
+        user: ActorId,
+        token: ActorId,
+        amount: U256,
+    },
+    /// withdraw: `amount` of `token` was debited from `user` and is being sent
+    Withdraw {
+        user: ActorId,
+        token: ActorId,
+        amount: U256,
+    },
+    /// swap: the pool is settled from its balances, the outputs are being sent to `to`
+    Swap {
+        to: ActorId,
+        amount0_out: U256,
+        amount1_out: U256,
+        stage: SendTokenStage,
+    },
+    /// remove_liquidity_single: `amount` of `token` is being sent, `liquidity` is burnt after
+    ZapOut {
+        user: ActorId,
+        token: ActorId,
+        liquidity: U256,
+        amount: U256,
+    },
 }
 
 #[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
@@ -78,3 +117,127 @@ pub enum SendTokenStage {
     SendToken0, // about to send token0
     SendToken1, // token0 already done, about to send token1 (critical if fails)
 }
+
+/// Token amounts an operation in flight may still add to or take from the reserves.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Reservation {
+    pub in0: U256,
+    pub in1: U256,
+    pub out0: U256,
+    pub out1: U256,
+}
+
+impl Reservation {
+    /// A swap adding `amount_in` on its input side and taking `amount_out` on the other.
+    pub fn swap(is_token0_to_token1: bool, amount_in: U256, amount_out: U256) -> Self {
+        if is_token0_to_token1 {
+            Self {
+                in0: amount_in,
+                out1: amount_out,
+                ..Default::default()
+            }
+        } else {
+            Self {
+                in1: amount_in,
+                out0: amount_out,
+                ..Default::default()
+            }
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn add(&mut self, other: &Reservation) {
+        self.in0 = self.in0.saturating_add(other.in0);
+        self.in1 = self.in1.saturating_add(other.in1);
+        self.out0 = self.out0.saturating_add(other.out0);
+        self.out1 = self.out1.saturating_add(other.out1);
+    }
+}
+
+#[derive(Debug)]
+struct Operation {
+    state: LockState,
+    reserved: Reservation,
+}
+
+/// Swaps and liquidity operations in flight, keyed by the message that started them.
+///
+/// Unlike the pair-wide `LockState`, any number of them can overlap. A `Busy` operation holds
+/// its `Reservation` until it settles; a `Paused` one has released it and only keeps the
+/// context for `recover_operation`.
+#[derive(Debug, Default)]
+pub struct Operations {
+    ops: HashMap<MessageId, Operation>,
+}
+
+impl Operations {
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn contains(&self, op_id: &MessageId) -> bool {
+        self.ops.contains_key(op_id)
+    }
+
+    pub fn start(&mut self, op_id: MessageId, ctx: LockCtx, reserved: Reservation) {
+        self.ops.insert(
+            op_id,
+            Operation {
+                state: LockState::Busy(ctx),
+                reserved,
+            },
+        );
+    }
+
+    /// Removes a settled, refunded or failed operation.
+    pub fn finish(&mut self, op_id: &MessageId) {
+        self.ops.remove(op_id);
+    }
+
+    /// Keeps the context of an operation that can't complete, releasing its reservation.
+    pub fn pause(&mut self, op_id: &MessageId) {
+        if let Some(op) = self.ops.get_mut(op_id) {
+            op.state.pause_keep_ctx();
+            op.reserved = Reservation::default();
+        }
+    }
+
+    pub fn advance_after_token0_ok(&mut self, op_id: &MessageId) {
+        if let Some(op) = self.ops.get_mut(op_id) {
+            op.state.advance_after_token0_ok();
+        }
+    }
+
+    /// Moves the paused operation `op_id` under `msg_id`, busy again, and returns its context.
+    pub fn restart(&mut self, op_id: MessageId, msg_id: MessageId) -> Option<LockCtx> {
+        let ctx = match self.ops.get(&op_id) {
+            Some(Operation {
+                state: LockState::Paused(ctx),
+                ..
+            }) => ctx.clone(),
+            _ => return None,
+        };
+        self.ops.remove(&op_id);
+        self.start(msg_id, ctx.clone(), Reservation::default());
+        Some(ctx)
+    }
+
+    /// Sum of the reservations of the busy operations.
+    pub fn reserved(&self) -> Reservation {
+        let mut total = Reservation::default();
+        for op in self.ops.values() {
+            total.add(&op.reserved);
+        }
+        total
+    }
+
+    pub fn states(&self) -> Vec<(MessageId, LockState)> {
+        self.ops
+            .iter()
+            .map(|(op_id, op)| (*op_id, op.state.clone()))
+            .collect()
+    }
+}
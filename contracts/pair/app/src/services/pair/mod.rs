@@ -1,18 +1,39 @@
 #![allow(static_mut_refs)]
 
 use sails_rs::gstd::services::Service as Svc;
-use sails_rs::{gstd::msg, prelude::*};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
 
 mod amm_math;
+mod core_swap;
+mod curve;
+mod flash;
+pub use amm_math::{DEFAULT_PROTOCOL_FEE_DIVISOR, TREASURY_FEE_BPS};
+pub use curve::Curve;
 mod funcs;
+mod hooks;
 mod lock;
 pub mod msg_tracker;
+mod multicall;
+mod oracle;
+mod reconcile;
+mod stable_math;
+mod zap;
 use crate::LpTokenState;
-use crate::services::pair::lock::LockState;
+use crate::services::pair::lock::{LockState, Operations};
 use msg_tracker::{MessageStatus, MessageTracker};
+pub use multicall::{Call, CallResult};
+pub use oracle::Observation;
+use oracle::Oracle;
+pub use reconcile::TokenAudit;
 use sails_rs::cell::RefCell;
+pub use stable_math::AmpRamp;
 mod token_operations;
+mod vault;
 use crate::services::lp_token::LpService;
+use vault::Vault;
 
 type LpExposure<'a> = <LpService<'a> as Svc>::Exposure;
 pub struct PairService<'a> {
@@ -32,11 +53,69 @@ pub struct State {
     pub factory_id: ActorId,
     pub k_last: U256,
     pub config: Config,
+    /// Pair-wide lock of the operations that run alone (migration, flash swaps, zaps,
+    /// sync and skim) and of the admin pause.
     pub lock: LockState,
+    /// Swaps, liquidity changes, treasury payouts, deposits and withdrawals in flight,
+    /// which can overlap.
+    pub ops: Operations,
     pub treasury_id: ActorId,
     pub migrated: bool,
     pub accrued_treasury_fee0: U256,
     pub accrued_treasury_fee1: U256,
+    /// Swap fee in basis points kept in the pool.
+    pub swap_fee_bps: u64,
+    /// Fee in basis points of the swap input sent to the treasury.
+    pub treasury_fee_bps: u64,
+    /// The protocol share of the swap fee is `1 / protocol_fee_divisor`.
+    pub protocol_fee_divisor: u64,
+    /// Cumulative prices observed at past reserve changes.
+    pub oracle: Oracle,
+    pub curve: Curve,
+    /// Internal balances deposited with `deposit`, held by the pair outside the reserves.
+    pub vault: Vault,
+    /// Program called before and after swaps and liquidity changes, zero while none is set.
+    pub hook: ActorId,
+}
+
+impl State {
+    /// Treasury fee charged on swaps; zero while no treasury is set.
+    pub fn active_treasury_fee_bps(&self) -> u64 {
+        if self.treasury_id.is_zero() {
+            0
+        } else {
+            self.treasury_fee_bps
+        }
+    }
+
+    /// Sets the reserves, first accumulating the prices of the previous ones
+    /// for the time they were held.
+    pub fn set_reserves(&mut self, reserve0: U256, reserve1: U256) {
+        self.oracle
+            .update(self.reserve0, self.reserve1, exec::block_timestamp());
+        self.reserve0 = reserve0;
+        self.reserve1 = reserve1;
+    }
+
+    /// Whether an operation that runs alone can start: no lock is held and no operation
+    /// is in flight or paused.
+    pub fn is_idle(&self) -> bool {
+        self.lock.is_free() && self.ops.is_empty()
+    }
+
+    /// Tokens the pair holds outside the reserves: accrued treasury fees and deposits.
+    pub fn outside_reserves(&self) -> (U256, U256) {
+        let (deposits0, deposits1) = self.vault.totals();
+        (
+            self.accrued_treasury_fee0.saturating_add(deposits0),
+            self.accrued_treasury_fee1.saturating_add(deposits1),
+        )
+    }
+
+    /// `k` of the pair's curve for the given reserves, see `Curve::k`.
+    pub fn k(&self, reserve0: U256, reserve1: U256) -> Result<U256, PairError> {
+        self.curve.k(reserve0, reserve1, exec::block_timestamp())
+    }
 }
 
 #[event]
@@ -71,6 +150,125 @@ pub enum PairEvent {
         amount1: U256,
     },
     NoLiquidityToMigrate,
+    SwapFeeChanged {
+        fee_bps: u64,
+    },
+    TreasuryFeeChanged {
+        fee_bps: u64,
+    },
+    ProtocolFeeShareChanged {
+        divisor: u64,
+    },
+    ObservationCardinalityIncreased {
+        cardinality: u32,
+    },
+    FlashSwap {
+        borrower: ActorId,
+        amount0_out: U256,
+        amount1_out: U256,
+        amount0_in: U256,
+        amount1_in: U256,
+    },
+    /// The borrower did not pay the flash swap back; the pair is paused until `recover_paused`.
+    FlashSwapDefaulted {
+        borrower: ActorId,
+        amount0_out: U256,
+        amount1_out: U256,
+    },
+    /// `recover_paused` wrote the reserves down to the pool balances after a defaulted flash swap.
+    FlashSwapWrittenOff {
+        borrower: ActorId,
+        amount0_lost: U256,
+        amount1_lost: U256,
+    },
+    Sync {
+        reserve0: U256,
+        reserve1: U256,
+    },
+    Skim {
+        to: ActorId,
+        amount0: U256,
+        amount1: U256,
+    },
+    ZapIn {
+        user_id: ActorId,
+        token: ActorId,
+        amount_in: U256,
+        amount_swapped: U256,
+        liquidity: U256,
+    },
+    ZapOut {
+        user_id: ActorId,
+        token: ActorId,
+        liquidity: U256,
+        amount_out: U256,
+    },
+    /// Amplification coefficients without `A_PRECISION`.
+    AmpRampStarted {
+        initial_amp: u64,
+        future_amp: u64,
+        initial_time: u64,
+        future_time: u64,
+    },
+    AmpRampStopped {
+        amp: u64,
+    },
+    Deposited {
+        user_id: ActorId,
+        token: ActorId,
+        amount: U256,
+    },
+    Withdrawn {
+        user_id: ActorId,
+        token: ActorId,
+        amount: U256,
+    },
+    /// A `swap` settled from the pool balances, as Uniswap V2 core's `Swap`.
+    CoreSwap {
+        sender: ActorId,
+        to: ActorId,
+        amount0_in: U256,
+        amount1_in: U256,
+        amount0_out: U256,
+        amount1_out: U256,
+    },
+    HookSet {
+        hook: ActorId,
+    },
+}
+
+impl PairEvent {
+    /// Returns `(amount_in, amount_out)` of a `Swap` event, zeroes for any other event.
+    fn swap_amounts(&self) -> (U256, U256) {
+        match self {
+            PairEvent::Swap {
+                amount_in,
+                amount_out,
+                ..
+            } => (*amount_in, *amount_out),
+            _ => (U256::zero(), U256::zero()),
+        }
+    }
+
+    /// Returns `(amount_a, amount_b, liquidity)` of a `LiquidityAdded` or `LiquidityRemoved`
+    /// event, zeroes for any other event.
+    fn liquidity_amounts(&self) -> (U256, U256, U256) {
+        match self {
+            PairEvent::LiquidityAdded {
+                amount_a,
+                amount_b,
+                liquidity,
+                ..
+            }
+            | PairEvent::LiquidityRemoved {
+                amount_a,
+                amount_b,
+                liquidity,
+                ..
+            } => (*amount_a, *amount_b, *liquidity),
+            _ => (U256::zero(), U256::zero(), U256::zero()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -103,6 +301,26 @@ pub enum PairError {
     NotPaused,
     InvalidRecoveryState,
     EventError,
+    InvalidSwapFee,
+    InvalidTreasuryFee,
+    InvalidProtocolFeeShare,
+    InvalidObservationCardinality,
+    ObservationTooOld,
+    InvalidBorrower,
+    InvalidRecipient,
+    InvalidToken,
+    InvalidAmp,
+    InvalidRampTime,
+    UnsupportedCurve,
+    InsufficientInternalBalance,
+    /// The hook rejected the operation.
+    HookRejected,
+    /// The hook could not be called or its reply could not be decoded.
+    HookFailure,
+    /// The operation can't call the hook, so it is disabled while a hook is set.
+    UnsupportedWithHook,
+    /// A `multicall` without calls, or with a withdrawal before another call.
+    InvalidCalls,
 }
 
 /// Config that will be used to send messages to the other programs.
@@ -119,6 +337,20 @@ pub struct Config {
     /// the other programs such as VFT
     reply_timeout: u32,
     gas_for_full_tx: u64,
+    /// Swap fee in basis points the pair is created with (e.g. 30 = 0.3%).
+    /// The factory registers the pair under this fee tier; the fee charged
+    /// afterwards is changed with `set_swap_fee_bps`.
+    swap_fee_bps: u64,
+}
+
+impl Config {
+    pub fn has_valid_swap_fee(&self) -> bool {
+        self.swap_fee_bps <= amm_math::MAX_SWAP_FEE_BPS
+    }
+
+    pub fn swap_fee_bps(&self) -> u64 {
+        self.swap_fee_bps
+    }
 }
 
 impl<'a> PairService<'a> {
@@ -197,6 +429,11 @@ impl<'a> PairService<'a> {
 
 #[sails_rs::service(events = PairEvent)]
 impl<'a> PairService<'a> {
+    /// Returns `(amount_a, amount_b, liquidity)`: the tokens added and the LP tokens minted.
+    ///
+    /// The desired amounts are pulled at the current ratio, but added at the ratio of the
+    /// reserves once both tokens are in; what swaps settled meanwhile pushed out of it is
+    /// sent back. If the liquidity can't be added then, both tokens are sent back.
     #[export(unwrap_result)]
     pub async fn add_liquidity(
         &mut self,
@@ -205,18 +442,43 @@ impl<'a> PairService<'a> {
         amount_a_min: U256,
         amount_b_min: U256,
         deadline: u64,
-    ) -> Result<(), PairError> {
+    ) -> Result<(U256, U256, U256), PairError> {
+        self.add_liquidity_to(
+            amount_a_desired,
+            amount_b_desired,
+            amount_a_min,
+            amount_b_min,
+            msg::source(),
+            deadline,
+        )
+        .await
+    }
+
+    /// Same as `add_liquidity`, but the LP tokens are minted to `to`.
+    /// The tokens are still taken from the caller.
+    #[export(unwrap_result)]
+    pub async fn add_liquidity_to(
+        &mut self,
+        amount_a_desired: U256,
+        amount_b_desired: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<(U256, U256, U256), PairError> {
         let event = self
             .add_liquidity_core(
                 amount_a_desired,
                 amount_b_desired,
                 amount_a_min,
                 amount_b_min,
+                to,
                 deadline,
             )
             .await?;
+        let amounts = event.liquidity_amounts();
         self.emit_pair_event(event)?;
-        Ok(())
+        Ok(amounts)
     }
 
     /// Removes liquidity from the AMM pool
@@ -234,6 +496,8 @@ impl<'a> PairService<'a> {
     /// 4. Validates amounts against minimum thresholds
     /// 5. Burns user's LP tokens and transfers underlying tokens back
     /// 6. Updates pool reserves
+    ///
+    /// Returns `(amount_a, amount_b)` sent for the burnt LP tokens.
     #[export(unwrap_result)]
     pub async fn remove_liquidity(
         &mut self,
@@ -241,12 +505,96 @@ impl<'a> PairService<'a> {
         amount_a_min: U256,
         amount_b_min: U256,
         deadline: u64,
-    ) -> Result<(), PairError> {
+    ) -> Result<(U256, U256), PairError> {
+        self.remove_liquidity_to(
+            liquidity,
+            amount_a_min,
+            amount_b_min,
+            msg::source(),
+            deadline,
+        )
+        .await
+    }
+
+    /// Same as `remove_liquidity`, but the underlying tokens are sent to `to`.
+    /// The LP tokens are still burnt from the caller.
+    #[export(unwrap_result)]
+    pub async fn remove_liquidity_to(
+        &mut self,
+        liquidity: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<(U256, U256), PairError> {
         let event = self
-            .remove_liquidity_core(liquidity, amount_a_min, amount_b_min, deadline)
+            .remove_liquidity_core(liquidity, amount_a_min, amount_b_min, to, deadline)
             .await?;
+        let (amount_a, amount_b, _) = event.liquidity_amounts();
         self.emit_event(event).expect("Event emission error");
-        Ok(())
+        Ok((amount_a, amount_b))
+    }
+
+    /// Adds liquidity from a single token.
+    ///
+    /// Part of `amount` is swapped to the other token inside the pool, so that the rest
+    /// and the swap output match the reserve ratio after the swap. The swap pays the usual
+    /// swap and treasury fees; rounding dust stays in the pool.
+    /// Not available on stable pairs.
+    ///
+    /// # Parameters
+    /// * `token` - token0 or token1 of the pair
+    /// * `amount` - Amount of `token` to deposit
+    /// * `min_liquidity` - Minimum LP tokens to mint (slippage protection)
+    /// * `deadline` - Timestamp after which the transaction is considered invalid
+    ///
+    /// Returns the LP tokens minted to the caller.
+    #[export(unwrap_result)]
+    pub async fn add_liquidity_single(
+        &mut self,
+        token: ActorId,
+        amount: U256,
+        min_liquidity: U256,
+        deadline: u64,
+    ) -> Result<U256, PairError> {
+        let event = self
+            .add_liquidity_single_core(token, amount, min_liquidity, deadline)
+            .await?;
+        let PairEvent::ZapIn { liquidity, .. } = event else {
+            return Err(PairError::EventError);
+        };
+        self.emit_pair_event(event)?;
+        Ok(liquidity)
+    }
+
+    /// Removes liquidity into a single token.
+    ///
+    /// Burns `liquidity` and swaps the other token of the withdrawn share to `token`
+    /// inside the pool, paying the usual swap and treasury fees.
+    ///
+    /// # Parameters
+    /// * `liquidity` - Amount of LP tokens to burn
+    /// * `token` - token0 or token1 of the pair, the only token received
+    /// * `amount_out_min` - Minimum amount of `token` to receive (slippage protection)
+    /// * `deadline` - Timestamp after which the transaction is considered invalid
+    ///
+    /// Returns the amount of `token` sent to the caller.
+    #[export(unwrap_result)]
+    pub async fn remove_liquidity_single(
+        &mut self,
+        liquidity: U256,
+        token: ActorId,
+        amount_out_min: U256,
+        deadline: u64,
+    ) -> Result<U256, PairError> {
+        let event = self
+            .remove_liquidity_single_core(liquidity, token, amount_out_min, deadline)
+            .await?;
+        let PairEvent::ZapOut { amount_out, .. } = event else {
+            return Err(PairError::EventError);
+        };
+        self.emit_pair_event(event)?;
+        Ok(amount_out)
     }
 
     /// Migrates all pool liquidity and accrued treasury fees to a target address.
@@ -273,6 +621,8 @@ impl<'a> PairService<'a> {
     /// * `amount_out_min` - Minimum amount of output token expected (slippage protection)
     /// * `is_token0_to_token1` - Direction of swap (true: token0 to token1, false: token1 to token0)
     /// * `deadline` - Unix timestamp after which the transaction will revert
+    ///
+    /// Returns `(amount_in, amount_out)` actually moved by the swap.
     #[export(unwrap_result)]
     pub async fn swap_exact_tokens_for_tokens(
         &mut self,
@@ -280,17 +630,40 @@ impl<'a> PairService<'a> {
         amount_out_min: U256,
         is_token0_to_token1: bool,
         deadline: u64,
-    ) -> Result<(), PairError> {
+    ) -> Result<(U256, U256), PairError> {
+        self.swap_exact_tokens_for_tokens_to(
+            amount_in,
+            amount_out_min,
+            is_token0_to_token1,
+            msg::source(),
+            deadline,
+        )
+        .await
+    }
+
+    /// Same as `swap_exact_tokens_for_tokens`, but the output tokens are sent to `to`.
+    /// The input tokens are still taken from the caller, and refunded to it if the swap fails.
+    #[export(unwrap_result)]
+    pub async fn swap_exact_tokens_for_tokens_to(
+        &mut self,
+        amount_in: U256,
+        amount_out_min: U256,
+        is_token0_to_token1: bool,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<(U256, U256), PairError> {
         let event = self
             .swap_exact_tokens_for_tokens_core(
                 amount_in,
                 amount_out_min,
                 is_token0_to_token1,
+                to,
                 deadline,
             )
             .await?;
+        let amounts = event.swap_amounts();
         self.emit_pair_event(event)?;
-        Ok(())
+        Ok(amounts)
     }
 
     /// Swaps as few input tokens as possible for an exact amount of output tokens in a single pair.
@@ -301,6 +674,8 @@ impl<'a> PairService<'a> {
     /// * `amount_in_max` - Maximum amount of input token willing to pay (slippage protection)
     /// * `is_token0_to_token1` - Direction of swap (true: token0 to token1, false: token1 to token0)
     /// * `deadline` - Unix timestamp after which the transaction will revert
+    ///
+    /// Returns `(amount_in, amount_out)` actually moved by the swap.
     #[export(unwrap_result)]
     pub async fn swap_tokens_for_exact_tokens(
         &mut self,
@@ -308,17 +683,257 @@ impl<'a> PairService<'a> {
         amount_in_max: U256,
         is_token0_to_token1: bool,
         deadline: u64,
-    ) -> Result<(), PairError> {
+    ) -> Result<(U256, U256), PairError> {
+        self.swap_tokens_for_exact_tokens_to(
+            amount_out,
+            amount_in_max,
+            is_token0_to_token1,
+            msg::source(),
+            deadline,
+        )
+        .await
+    }
+
+    /// Same as `swap_tokens_for_exact_tokens`, but the output tokens are sent to `to`.
+    /// The input tokens are still taken from the caller, and refunded to it if the swap fails.
+    #[export(unwrap_result)]
+    pub async fn swap_tokens_for_exact_tokens_to(
+        &mut self,
+        amount_out: U256,
+        amount_in_max: U256,
+        is_token0_to_token1: bool,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<(U256, U256), PairError> {
         let event = self
             .swap_tokens_for_exact_tokens_core(
                 amount_out,
                 amount_in_max,
                 is_token0_to_token1,
+                to,
                 deadline,
             )
             .await?;
+        let amounts = event.swap_amounts();
         self.emit_pair_event(event)?;
-        Ok(())
+        Ok(amounts)
+    }
+
+    /// Takes `amount` of `token` (token0 or token1) from the caller into its internal
+    /// balance in the pair, see `balance_of_internal`.
+    ///
+    /// Swaps and liquidity changes on internal balances (`*_internal`) settle within
+    /// the message, without token transfers, so they can't fail halfway or pause the pair.
+    #[export(unwrap_result)]
+    pub async fn deposit(&mut self, token: ActorId, amount: U256) -> Result<(), PairError> {
+        let event = self.deposit_core(token, amount).await?;
+        self.emit_pair_event(event)
+    }
+
+    /// Sends `amount` of `token` from the caller's internal balance to the caller.
+    /// Still available after migration. A failed transfer pauses the operation
+    /// until `recover_operation`; the internal balance is not restored.
+    #[export(unwrap_result)]
+    pub async fn withdraw(&mut self, token: ActorId, amount: U256) -> Result<(), PairError> {
+        let event = self.withdraw_core(token, amount).await?;
+        self.emit_pair_event(event)
+    }
+
+    /// Same as `swap_exact_tokens_for_tokens`, but the input is taken from the caller's
+    /// internal balance and the output is credited to it.
+    ///
+    /// Returns `(amount_in, amount_out)` actually moved by the swap.
+    #[export(unwrap_result)]
+    pub fn swap_exact_tokens_for_tokens_internal(
+        &mut self,
+        amount_in: U256,
+        amount_out_min: U256,
+        is_token0_to_token1: bool,
+        deadline: u64,
+    ) -> Result<(U256, U256), PairError> {
+        let event = self.swap_internal_core(
+            funcs::SwapType::ExactInput {
+                amount_in,
+                amount_out_min,
+            },
+            is_token0_to_token1,
+            deadline,
+        )?;
+        let amounts = event.swap_amounts();
+        self.emit_pair_event(event)?;
+        Ok(amounts)
+    }
+
+    /// Same as `swap_tokens_for_exact_tokens`, but the input is taken from the caller's
+    /// internal balance and the output is credited to it.
+    ///
+    /// Returns `(amount_in, amount_out)` actually moved by the swap.
+    #[export(unwrap_result)]
+    pub fn swap_tokens_for_exact_tokens_internal(
+        &mut self,
+        amount_out: U256,
+        amount_in_max: U256,
+        is_token0_to_token1: bool,
+        deadline: u64,
+    ) -> Result<(U256, U256), PairError> {
+        let event = self.swap_internal_core(
+            funcs::SwapType::ExactOutput {
+                amount_out,
+                amount_in_max,
+            },
+            is_token0_to_token1,
+            deadline,
+        )?;
+        let amounts = event.swap_amounts();
+        self.emit_pair_event(event)?;
+        Ok(amounts)
+    }
+
+    /// Same as `add_liquidity`, but the tokens are taken from the caller's internal balance.
+    ///
+    /// Returns `(amount_a, amount_b, liquidity)`: the tokens taken and the LP tokens minted.
+    #[export(unwrap_result)]
+    pub fn add_liquidity_internal(
+        &mut self,
+        amount_a_desired: U256,
+        amount_b_desired: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        deadline: u64,
+    ) -> Result<(U256, U256, U256), PairError> {
+        let event = self.add_liquidity_internal_core(
+            amount_a_desired,
+            amount_b_desired,
+            amount_a_min,
+            amount_b_min,
+            deadline,
+        )?;
+        let amounts = event.liquidity_amounts();
+        self.emit_pair_event(event)?;
+        Ok(amounts)
+    }
+
+    /// Same as `remove_liquidity`, but the underlying tokens are credited to the caller's
+    /// internal balance.
+    ///
+    /// Returns `(amount_a, amount_b)` credited for the burnt LP tokens.
+    #[export(unwrap_result)]
+    pub fn remove_liquidity_internal(
+        &mut self,
+        liquidity: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        deadline: u64,
+    ) -> Result<(U256, U256), PairError> {
+        let event =
+            self.remove_liquidity_internal_core(liquidity, amount_a_min, amount_b_min, deadline)?;
+        let (amount_a, amount_b, _) = event.liquidity_amounts();
+        self.emit_pair_event(event)?;
+        Ok((amount_a, amount_b))
+    }
+
+    /// Runs several swaps and liquidity changes on the caller's internal balance in one
+    /// message, then the withdrawals. The LP tokens are minted to and burnt from the caller,
+    /// and every call is checked against its own limits and `deadline`.
+    ///
+    /// Only the internal-balance operations can be batched: the tokens are brought in with
+    /// `deposit` beforehand, as the regular swaps and liquidity changes await their
+    /// transfers and could not be undone together. For the same reason every `Withdraw`
+    /// must come after all the other calls; a batch that is empty or has a withdrawal
+    /// before another call fails with `InvalidCalls` and nothing is applied.
+    ///
+    /// Nothing is awaited before the withdrawals, so if any swap or liquidity change fails,
+    /// none of them is applied. A failed withdrawal pauses its operation as `withdraw` does,
+    /// the calls before it stay applied.
+    ///
+    /// Returns what every call moved, in order.
+    #[export(unwrap_result)]
+    pub async fn multicall(
+        &mut self,
+        calls: Vec<Call>,
+        deadline: u64,
+    ) -> Result<Vec<CallResult>, PairError> {
+        self.multicall_core(calls, deadline).await
+    }
+
+    /// Low-level swap of Uniswap V2 core: sends `amount0_out` of token0 and `amount1_out`
+    /// of token1 to `to`, taking as input the caller's whole internal balance. The input is
+    /// deposited first, e.g. by a router, so it pays for the caller's swap only; tokens
+    /// transferred to the pair directly don't count, as nothing tells who sent them.
+    /// The treasury fee is taken from the input, and what is left must keep the invariant
+    /// less the swap fee.
+    ///
+    /// A failed output transfer pauses the pair with `LockCtx::Swap` until `recover_paused`.
+    ///
+    /// Returns `(amount0_in, amount1_in)` taken from the internal balance.
+    #[export(unwrap_result)]
+    pub async fn swap(
+        &mut self,
+        amount0_out: U256,
+        amount1_out: U256,
+        to: ActorId,
+    ) -> Result<(U256, U256), PairError> {
+        let event = self.swap_core(amount0_out, amount1_out, to).await?;
+        let PairEvent::CoreSwap {
+            amount0_in,
+            amount1_in,
+            ..
+        } = event
+        else {
+            return Err(PairError::EventError);
+        };
+        self.emit_pair_event(event)?;
+        Ok((amount0_in, amount1_in))
+    }
+
+    /// Lends `amount0_out` of token0 and `amount1_out` of token1 to `borrower`, then calls
+    /// `FlashBorrower::OnFlashSwap(sender, amount0_out, amount1_out, data)` on it.
+    /// Before replying, the borrower must pay the pool back in either token so that the
+    /// constant product, less the swap fee on what was paid in, is not below the one before,
+    /// e.g. `amount_out * 10_000 / (10_000 - swap_fee_bps) + 1` of the borrowed token.
+    /// No treasury fee is charged.
+    ///
+    /// A missing repayment pauses the pair with `LockCtx::FlashSwap` and emits
+    /// `FlashSwapDefaulted`. A failed callback or balance check pauses it the same way
+    /// and fails the call. The admin settles both with `recover_paused`.
+    #[export(unwrap_result)]
+    pub async fn flash_swap(
+        &mut self,
+        amount0_out: U256,
+        amount1_out: U256,
+        borrower: ActorId,
+        data: Vec<u8>,
+    ) -> Result<(), PairError> {
+        let event = self
+            .flash_swap_core(amount0_out, amount1_out, borrower, data)
+            .await?;
+        self.emit_pair_event(event)
+    }
+
+    /// Sets the reserves to the token balances of the pair less the accrued treasury fees
+    /// and deposits, so tokens sent to the pair directly go to the liquidity providers.
+    #[export(unwrap_result)]
+    pub async fn sync(&mut self) -> Result<(), PairError> {
+        let event = self.sync_core().await?;
+        self.emit_pair_event(event)
+    }
+
+    /// Sends the tokens the pair holds above its reserves, accrued treasury fees and deposits
+    /// to `to`.
+    /// Admin only, as the excess is usually a transfer to the pair made by mistake.
+    #[export(unwrap_result)]
+    pub async fn skim(&mut self, to: ActorId) -> Result<(), PairError> {
+        self.ensure_admin()?;
+        let event = self.skim_core(to).await?;
+        self.emit_pair_event(event)
+    }
+
+    /// Compares the token balances of the pair with its reserves, accrued treasury fees and
+    /// deposits, for token0 and token1. Changes nothing, but it is a message rather than a query as
+    /// the balances are requested from the token programs.
+    #[export(unwrap_result)]
+    pub async fn audit(&mut self) -> Result<(TokenAudit, TokenAudit), PairError> {
+        self.audit_core().await
     }
 
     #[export(unwrap_result)]
@@ -330,6 +945,16 @@ impl<'a> PairService<'a> {
         Ok(())
     }
 
+    /// Retries the missing transfer of the paused operation `op_id`, see `operations`.
+    #[export(unwrap_result)]
+    pub async fn recover_operation(&mut self, op_id: MessageId) -> Result<(), PairError> {
+        let res = self.recover_operation_core(op_id).await?;
+        if let Some(event) = res {
+            self.emit_pair_event(event)?;
+        }
+        Ok(())
+    }
+
     #[export(unwrap_result)]
     pub async fn send_treasury_fees(&mut self) -> Result<(), PairError> {
         let event = self.send_treasury_fees_from_pool().await?;
@@ -348,8 +973,8 @@ impl<'a> PairService<'a> {
     /// Calculates protocol fees for the liquidity pool, similar to Uniswap V2, without minting.
     ///
     /// This function checks if protocol fees are enabled (via `fee_to` address) and calculates
-    /// the growth in pool reserves due to accumulated swap fees (0.3% per swap, with 1/6 or
-    /// 0.05% going to the protocol). Returns the amount of new liquidity tokens (LP tokens)
+    /// the growth in pool reserves due to accumulated swap fees (`swap_fee_bps` per swap, with 1/6 of it
+    /// going to the protocol). Returns the amount of new liquidity tokens (LP tokens)
     /// that would be minted to the `fee_to` address, proportional to the increase in the square root
     /// of the constant product (`reserve0 * reserve1`). If protocol fees are disabled or no growth,
     /// returns 0.
@@ -389,16 +1014,17 @@ impl<'a> PairService<'a> {
     }
 
     /// Calculates the expected output amount for a swap, given the input amount and
-    /// current reserves, including both the internal 0.3% swap fee (Uniswap-style)
+    /// current reserves, including both the pair's swap fee (Uniswap-style)
     /// and the optional treasury fee in the input token.
     /// Uses floor division
     ///
     /// - If `treasury` is configured (non-zero address), the input is split into:
     ///     * a small part reserved as treasury fee (e.g. 0.05%), and
     ///     * the remaining part that actually enters the pool and is priced
-    ///       with the Uniswap V2 formula (0.3% fee via 997/1000 multiplier).
+    ///       with the Uniswap V2 formula and the pair's swap fee.
     /// - If `treasury` is not configured (zero address), the behavior matches
-    ///   the classic Uniswap V2 `getAmountOut` with 0.3% fee.
+    ///   the classic Uniswap V2 `getAmountOut` (997/1000 for the 0.3% tier).
+    /// - Stable pairs price the same input with the StableSwap invariant instead.
     ///
     /// # Arguments
     /// * `amount_in` - Amount of input asset being swapped
@@ -411,35 +1037,35 @@ impl<'a> PairService<'a> {
             } else {
                 (st.reserve1, st.reserve0)
             };
-            let treasury_fee_bps = if st.treasury_id.is_zero() {
-                0
-            } else {
-                amm_math::TREASURY_FEE_BPS
-            };
+            let treasury_fee_bps = st.active_treasury_fee_bps();
 
-            amm_math::get_amount_out_with_treasury(
-                amount_in,
-                reserve_in,
-                reserve_out,
-                treasury_fee_bps,
-            )
-            .map(|(_, amount_out, _)| amount_out)
-            .unwrap_or_default()
+            st.curve
+                .get_amount_out_with_treasury(
+                    amount_in,
+                    reserve_in,
+                    reserve_out,
+                    st.swap_fee_bps,
+                    treasury_fee_bps,
+                    exec::block_timestamp(),
+                )
+                .map(|(_, amount_out, _)| amount_out)
+                .unwrap_or_default()
         })
     }
 
     /// Calculates the required input amount for a desired output, given current reserves,
-    /// including both the internal 0.3% swap fee (Uniswap-style) and the optional
+    /// including both the pair's swap fee (Uniswap-style) and the optional
     /// treasury fee in the input token.
     ///
     /// - First, the function determines how much must actually enter the pool
-    ///   (`amount_in_for_pool`) using the standard Uniswap 0.3% math.
+    ///   (`amount_in_for_pool`) using the standard Uniswap math.
     /// - Then, if treasury fee is enabled, it computes a higher total input
     ///   `amount_in_total` such that:
     ///       amount_in_for_pool = amount_in_total * (1 - treasury_fee_bps / 10_000)
     ///   and the difference `amount_in_total - amount_in_for_pool` is the treasury fee.
     /// - If treasury is disabled, the result matches classic Uniswap V2
-    ///   `getAmountIn` with 0.3% fee.
+    ///   `getAmountIn` with the pair's swap fee.
+    /// - Stable pairs use the StableSwap invariant instead of the Uniswap math.
     ///
     /// # Arguments
     /// * `amount_out` - Desired amount of output asset
@@ -452,20 +1078,19 @@ impl<'a> PairService<'a> {
             } else {
                 (st.reserve1, st.reserve0)
             };
-            let treasury_fee_bps = if st.treasury_id.is_zero() {
-                0
-            } else {
-                amm_math::TREASURY_FEE_BPS
-            };
+            let treasury_fee_bps = st.active_treasury_fee_bps();
 
-            amm_math::get_amount_in_with_treasury(
-                amount_out,
-                reserve_in,
-                reserve_out,
-                treasury_fee_bps,
-            )
-            .map(|(_, amount_in_total, _)| amount_in_total)
-            .unwrap_or_default()
+            st.curve
+                .get_amount_in_with_treasury(
+                    amount_out,
+                    reserve_in,
+                    reserve_out,
+                    st.swap_fee_bps,
+                    treasury_fee_bps,
+                    exec::block_timestamp(),
+                )
+                .map(|(_, amount_in_total, _)| amount_in_total)
+                .unwrap_or_default()
         })
     }
 
@@ -479,22 +1104,210 @@ impl<'a> PairService<'a> {
 
         Ok(())
     }
+    /// Called by the factory when its treasury changes, or by an admin.
     #[export(unwrap_result)]
     pub fn change_treasury_id(&mut self, new_treasury_id: ActorId) -> Result<(), PairError> {
-        self.ensure_admin()?;
+        self.ensure_factory_or_admin()?;
         self.with_state_mut(|st| {
             st.treasury_id = new_treasury_id;
         });
         Ok(())
     }
 
+    /// Replaces the config. The fee tier the pair was created with is kept
+    /// as a record, so `config` must carry the current one.
     #[export(unwrap_result)]
     pub fn update_config(&mut self, config: Config) -> Result<(), PairError> {
         self.ensure_admin()?;
         self.with_state_mut(|st| {
+            if config.swap_fee_bps != st.config.swap_fee_bps {
+                return Err(PairError::InvalidSwapFee);
+            }
             st.config = config;
-        });
-        Ok(())
+            Ok(())
+        })
+    }
+
+    /// Sets the swap fee kept in the pool, at most `MAX_SWAP_FEE_BPS` (10%).
+    #[export(unwrap_result)]
+    pub fn set_swap_fee_bps(&mut self, fee_bps: u64) -> Result<(), PairError> {
+        self.ensure_admin()?;
+        if fee_bps > amm_math::MAX_SWAP_FEE_BPS {
+            return Err(PairError::InvalidSwapFee);
+        }
+        self.with_state_mut(|st| st.swap_fee_bps = fee_bps);
+        self.emit_pair_event(PairEvent::SwapFeeChanged { fee_bps })
+    }
+
+    /// Sets the fee sent to the treasury, at most `MAX_TREASURY_FEE_BPS` (1%).
+    /// Only charged while a treasury is set.
+    #[export(unwrap_result)]
+    pub fn set_treasury_fee_bps(&mut self, fee_bps: u64) -> Result<(), PairError> {
+        self.ensure_admin()?;
+        if fee_bps > amm_math::MAX_TREASURY_FEE_BPS {
+            return Err(PairError::InvalidTreasuryFee);
+        }
+        self.with_state_mut(|st| st.treasury_fee_bps = fee_bps);
+        self.emit_pair_event(PairEvent::TreasuryFeeChanged { fee_bps })
+    }
+
+    /// Sets the protocol share of the swap fee to `1 / divisor`, with `divisor`
+    /// at least `MIN_PROTOCOL_FEE_DIVISOR`. The protocol fee accrued so far is
+    /// minted to `fee_to` at the previous share first.
+    #[export(unwrap_result)]
+    pub fn set_protocol_fee_divisor(&mut self, divisor: u64) -> Result<(), PairError> {
+        self.ensure_admin()?;
+        if divisor < amm_math::MIN_PROTOCOL_FEE_DIVISOR {
+            return Err(PairError::InvalidProtocolFeeShare);
+        }
+        self.change_protocol_fee_divisor(divisor)?;
+        self.emit_pair_event(PairEvent::ProtocolFeeShareChanged { divisor })
+    }
+
+    /// Swap fee of the pair in basis points.
+    #[export]
+    pub fn swap_fee_bps(&self) -> u64 {
+        self.with_state(|st| st.swap_fee_bps)
+    }
+
+    /// Treasury fee in basis points, charged while a treasury is set.
+    #[export]
+    pub fn treasury_fee_bps(&self) -> u64 {
+        self.with_state(|st| st.treasury_fee_bps)
+    }
+
+    #[export]
+    pub fn protocol_fee_divisor(&self) -> u64 {
+        self.with_state(|st| st.protocol_fee_divisor)
+    }
+
+    /// Sets the program called before and after swaps and liquidity changes, zero to remove it.
+    /// The hook may reject an operation or set the swap fee of a swap, see the `hook` program;
+    /// `get_amount_out` and `get_amount_in` still quote at the pair's own fee.
+    /// While a hook is set, the operations that can't call it (internal balances, core and
+    /// flash swaps, zaps) are disabled.
+    #[export(unwrap_result)]
+    pub fn set_hook(&mut self, hook: ActorId) -> Result<(), PairError> {
+        self.ensure_admin()?;
+        self.with_state_mut(|st| st.hook = hook);
+        self.emit_pair_event(PairEvent::HookSet { hook })
+    }
+
+    /// Hook of the pair, zero if none is set.
+    #[export]
+    pub fn hook(&self) -> ActorId {
+        self.with_state(|st| st.hook)
+    }
+
+    /// Moves the amplification coefficient of a stable pair linearly from its current
+    /// value to `future_amp` at `future_time` (block timestamp in milliseconds).
+    /// The ramp must last at least `MIN_RAMP_TIME`, start at least `MIN_RAMP_TIME`
+    /// after the previous change, and change the coefficient at most tenfold.
+    /// As `D` follows the coefficient, `fee_to` shares its growth during a ramp too.
+    #[export(unwrap_result)]
+    pub fn ramp_amp(&mut self, future_amp: u64, future_time: u64) -> Result<(), PairError> {
+        self.ensure_admin()?;
+        let ramp = self.with_state_mut(|st| {
+            let Curve::Stable(ramp) = st.curve else {
+                return Err(PairError::UnsupportedCurve);
+            };
+            let ramp = ramp.start(future_amp, future_time, exec::block_timestamp())?;
+            st.curve = Curve::Stable(ramp);
+            Ok(ramp)
+        })?;
+        self.emit_pair_event(PairEvent::AmpRampStarted {
+            initial_amp: ramp.initial_amp / stable_math::A_PRECISION,
+            future_amp: ramp.future_amp / stable_math::A_PRECISION,
+            initial_time: ramp.initial_time,
+            future_time: ramp.future_time,
+        })
+    }
+
+    /// Stops the ramp of a stable pair at the current amplification coefficient.
+    #[export(unwrap_result)]
+    pub fn stop_ramp_amp(&mut self) -> Result<(), PairError> {
+        self.ensure_admin()?;
+        let ramp = self.with_state_mut(|st| {
+            let Curve::Stable(ramp) = st.curve else {
+                return Err(PairError::UnsupportedCurve);
+            };
+            let ramp = ramp.stop(exec::block_timestamp());
+            st.curve = Curve::Stable(ramp);
+            Ok(ramp)
+        })?;
+        self.emit_pair_event(PairEvent::AmpRampStopped {
+            amp: ramp.future_amp / stable_math::A_PRECISION,
+        })
+    }
+
+    #[export]
+    pub fn curve(&self) -> Curve {
+        self.with_state(|st| st.curve)
+    }
+
+    /// Current amplification coefficient of a stable pair, `None` for constant product.
+    #[export]
+    pub fn amp(&self) -> Option<u64> {
+        self.with_state(|st| st.curve.amp(exec::block_timestamp()))
+    }
+
+    /// Returns the time-weighted average prices `(price0, price1)` over the last
+    /// `seconds_ago[i]` seconds for every `i`; `0` gives the spot prices.
+    /// `price0` is the price of token0 in token1, `price1` of token1 in token0,
+    /// both as UQ112.112 fixed-point numbers (`2^112` is 1.0).
+    /// Fails with `ObservationTooOld` if a window starts before the oldest observation.
+    #[export(unwrap_result)]
+    pub fn observe(&self, seconds_ago: Vec<u32>) -> Result<Vec<(U256, U256)>, PairError> {
+        self.with_state(|st| {
+            st.oracle.observe(
+                &seconds_ago,
+                st.reserve0,
+                st.reserve1,
+                exec::block_timestamp(),
+            )
+        })
+    }
+
+    /// Returns `(price0_cumulative, price1_cumulative, timestamp)` as of the current block,
+    /// like Uniswap V2's `price0CumulativeLast` extended to now.
+    /// The accumulators wrap on overflow; only differences between two readings are meaningful.
+    #[export]
+    pub fn current_cumulative_prices(&self) -> (U256, U256, u64) {
+        self.with_state(|st| {
+            let current = st
+                .oracle
+                .current(st.reserve0, st.reserve1, exec::block_timestamp());
+            (
+                current.price0_cumulative,
+                current.price1_cumulative,
+                current.timestamp,
+            )
+        })
+    }
+
+    /// Stored observations from the oldest to the latest.
+    #[export]
+    pub fn observations(&self) -> Vec<Observation> {
+        self.with_state(|st| st.oracle.observations())
+    }
+
+    #[export]
+    pub fn observation_cardinality(&self) -> u32 {
+        self.with_state(|st| st.oracle.cardinality())
+    }
+
+    /// Grows the number of observations kept, up to `MAX_OBSERVATION_CARDINALITY`.
+    /// Longer windows can be observed once the new slots are filled.
+    #[export(unwrap_result)]
+    pub fn increase_observation_cardinality(&mut self, cardinality: u32) -> Result<(), PairError> {
+        self.ensure_admin()?;
+        self.with_state_mut(|st| st.oracle.increase_cardinality(cardinality))?;
+        self.emit_pair_event(PairEvent::ObservationCardinalityIncreased { cardinality })
+    }
+
+    #[export]
+    pub fn fee_to(&self) -> ActorId {
+        self.with_state(|st| st.fee_to)
     }
 
     #[export]
@@ -516,6 +1329,15 @@ impl<'a> PairService<'a> {
         Ok(())
     }
 
+    /// Forgets the operation `op_id` without any transfer, e.g. one whose transfer never got
+    /// a reply and still holds its reservation.
+    #[export(unwrap_result)]
+    pub fn remove_operation(&mut self, op_id: MessageId) -> Result<(), PairError> {
+        self.ensure_admin()?;
+        self.with_state_mut(|st| st.ops.finish(&op_id));
+        Ok(())
+    }
+
     #[export(unwrap_result)]
     pub fn clear_msg_tracker(&mut self) -> Result<(), PairError> {
         self.ensure_admin()?;
@@ -533,11 +1355,24 @@ impl<'a> PairService<'a> {
         self.with_state(|st| st.lock.clone())
     }
 
+    /// Swaps, liquidity changes, treasury payouts, deposits and withdrawals in flight (`Busy`)
+    /// or waiting for `recover_operation` (`Paused`), by the id of the message that started them.
+    #[export]
+    pub fn operations(&self) -> Vec<(MessageId, LockState)> {
+        self.with_state(|st| st.ops.states())
+    }
+
     #[export]
     pub fn migrated(&self) -> bool {
         self.with_state(|st| st.migrated)
     }
 
+    /// `(token0, token1)` deposited by `account`.
+    #[export]
+    pub fn balance_of_internal(&self, account: ActorId) -> (U256, U256) {
+        self.with_state(|st| st.vault.balance_of(&account))
+    }
+
     #[export]
     pub fn get_tokens(&self) -> (ActorId, ActorId) {
         self.with_state(|st| (st.token0, st.token1))
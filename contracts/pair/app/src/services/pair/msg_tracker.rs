@@ -48,6 +48,31 @@ pub enum MessageStatus {
     SendingMsgToUnlockTokenB,
     /// Reply is received for a token deposit message.
     TokenBUnlocked(bool),
+
+    // during flash swap
+    SendingFlashToken0,
+    FlashToken0Sent(bool),
+    SendingFlashToken1,
+    FlashToken1Sent(bool),
+    CallingFlashBorrower,
+    /// Reply is received from the borrower callback.
+    FlashBorrowerReplied(bool),
+
+    // during skim
+    SendingSkimToken0,
+    SkimToken0Sent(bool),
+    SendingSkimToken1,
+    SkimToken1Sent(bool),
+
+    // during zap out
+    SendingZapOut,
+    ZapOutSent(bool),
+
+    // during deposit / withdraw
+    SendingDeposit,
+    DepositReceived(bool),
+    SendingWithdrawal,
+    WithdrawalSent(bool),
 }
 
 impl MessageTracker {
@@ -91,6 +116,8 @@ impl MessageTracker {
 pub enum ReplyCodec {
     Transfer,
     TransferFrom,
+    /// Only the reply code matters.
+    ReplyCode,
     None,
 }
 
@@ -98,9 +125,10 @@ impl MessageStatus {
     pub fn reply_codec(&self) -> ReplyCodec {
         use MessageStatus::*;
         match self {
-            SendingMsgToLockTokenA | SendingMsgToLockTokenB | SendingMsgToTransferTokenIn => {
-                ReplyCodec::TransferFrom
-            }
+            SendingMsgToLockTokenA
+            | SendingMsgToLockTokenB
+            | SendingMsgToTransferTokenIn
+            | SendingDeposit => ReplyCodec::TransferFrom,
 
             SendingMessageToReturnTokensA
             | SendingMsgToTransferTokenOut
@@ -108,7 +136,15 @@ impl MessageStatus {
             | SendingMsgToUnlockTokenA
             | SendingMsgToUnlockTokenB
             | SendingTreasuryTokenA
-            | SendingTreasuryTokenB => ReplyCodec::Transfer,
+            | SendingTreasuryTokenB
+            | SendingFlashToken0
+            | SendingFlashToken1
+            | SendingSkimToken0
+            | SendingSkimToken1
+            | SendingZapOut
+            | SendingWithdrawal => ReplyCodec::Transfer,
+
+            CallingFlashBorrower => ReplyCodec::ReplyCode,
 
             _ => ReplyCodec::None,
         }
@@ -130,8 +166,7 @@ impl MessageStatus {
             SendingMsgToLockTokenA => {
                 tr.update_msg_status(msg_id, TokenALocked(ok));
                 if !ok {
-                    state.lock.set_free();
-                    let _ = lp.pause.resume();
+                    set_free(state, lp, msg_id);
                 }
             }
             SendingMsgToLockTokenB => {
@@ -140,18 +175,16 @@ impl MessageStatus {
             SendingMessageToReturnTokensA => {
                 tr.update_msg_status(msg_id, TokensAReturnComplete(ok));
                 if ok {
-                    state.lock.set_free();
-                    let _ = lp.pause.resume();
+                    set_free(state, lp, msg_id);
                 } else {
-                    state.lock.pause_keep_ctx();
+                    pause_keep_ctx(state, msg_id);
                 }
             }
 
             SendingMsgToTransferTokenIn => {
                 tr.update_msg_status(msg_id, TokenInTransfered(ok));
                 if !ok {
-                    state.lock.set_free();
-                    let _ = lp.pause.resume();
+                    set_free(state, lp, msg_id);
                 }
             }
             SendingMsgToTransferTokenOut => {
@@ -160,40 +193,96 @@ impl MessageStatus {
             SendingMessageToReturnTokenIn => {
                 tr.update_msg_status(msg_id, TokenInReturnComplete(ok));
                 if ok {
-                    state.lock.set_free();
-                    let _ = lp.pause.resume();
+                    set_free(state, lp, msg_id);
                 } else {
-                    state.lock.pause_keep_ctx();
+                    pause_keep_ctx(state, msg_id);
                 }
             }
 
             SendingMsgToUnlockTokenA => {
                 tr.update_msg_status(msg_id, TokenAUnlocked(ok));
                 if ok {
-                    state.lock.advance_after_token0_ok();
+                    advance_after_token0_ok(state, msg_id);
                 } else {
-                    state.lock.pause_keep_ctx();
+                    pause_keep_ctx(state, msg_id);
                 }
             }
             SendingMsgToUnlockTokenB => {
                 tr.update_msg_status(msg_id, TokenBUnlocked(ok));
                 if !ok {
-                    state.lock.pause_keep_ctx();
+                    pause_keep_ctx(state, msg_id);
                 }
             }
 
             SendingTreasuryTokenA => {
                 tr.update_msg_status(msg_id, TreasuryTokenASent(ok));
                 if ok {
-                    state.lock.advance_after_token0_ok();
+                    advance_after_token0_ok(state, msg_id);
                 } else {
-                    state.lock.pause_keep_ctx();
+                    pause_keep_ctx(state, msg_id);
                 }
             }
             SendingTreasuryTokenB => {
                 tr.update_msg_status(msg_id, TreasuryTokenBSent(ok));
                 if !ok {
-                    state.lock.pause_keep_ctx();
+                    pause_keep_ctx(state, msg_id);
+                }
+            }
+
+            // nothing is lent yet
+            SendingFlashToken0 => {
+                tr.update_msg_status(msg_id, FlashToken0Sent(ok));
+                if !ok {
+                    set_free(state, lp, msg_id);
+                }
+            }
+            SendingFlashToken1 => {
+                tr.update_msg_status(msg_id, FlashToken1Sent(ok));
+                if !ok {
+                    pause_keep_ctx(state, msg_id);
+                }
+            }
+            CallingFlashBorrower => {
+                tr.update_msg_status(msg_id, FlashBorrowerReplied(ok));
+                if !ok {
+                    pause_keep_ctx(state, msg_id);
+                }
+            }
+
+            // the excess is not accounted anywhere, so a failed skim is just retried
+            SendingSkimToken0 => {
+                tr.update_msg_status(msg_id, SkimToken0Sent(ok));
+                if !ok {
+                    set_free(state, lp, msg_id);
+                }
+            }
+            SendingSkimToken1 => {
+                tr.update_msg_status(msg_id, SkimToken1Sent(ok));
+                if !ok {
+                    set_free(state, lp, msg_id);
+                }
+            }
+
+            // the LP is burnt only after the payout
+            SendingZapOut => {
+                tr.update_msg_status(msg_id, ZapOutSent(ok));
+                if !ok {
+                    set_free(state, lp, msg_id);
+                }
+            }
+
+            // nothing is credited yet
+            SendingDeposit => {
+                tr.update_msg_status(msg_id, DepositReceived(ok));
+                if !ok {
+                    set_free(state, lp, msg_id);
+                }
+            }
+            // the internal balance is already debited
+            SendingWithdrawal => {
+                tr.update_msg_status(msg_id, WithdrawalSent(ok));
+                if !ok {
+                    pause_keep_ctx(state, msg_id);
                 }
             }
 
@@ -202,6 +291,33 @@ impl MessageStatus {
     }
 }
 
+/// Ends the operation started by `msg_id`, or frees the pair-wide lock (and the LP token)
+/// if it isn't one of the overlapping operations.
+fn set_free(state: &mut State, lp: &LpTokenState, msg_id: MessageId) {
+    if state.ops.contains(&msg_id) {
+        state.ops.finish(&msg_id);
+    } else {
+        state.lock.set_free();
+        let _ = lp.pause.resume();
+    }
+}
+
+fn pause_keep_ctx(state: &mut State, msg_id: MessageId) {
+    if state.ops.contains(&msg_id) {
+        state.ops.pause(&msg_id);
+    } else {
+        state.lock.pause_keep_ctx();
+    }
+}
+
+fn advance_after_token0_ok(state: &mut State, msg_id: MessageId) {
+    if state.ops.contains(&msg_id) {
+        state.ops.advance_after_token0_ok(&msg_id);
+    } else {
+        state.lock.advance_after_token0_ok();
+    }
+}
+
 impl<'a> PairService<'a> {
     pub fn on_reply(&self) {
         let reply_to_id = msg::reply_to().expect("reply_to only in reply context"); // :contentReference[oaicite:3]{index=3}
@@ -221,6 +337,7 @@ impl<'a> PairService<'a> {
         let ok = match status.reply_codec() {
             ReplyCodec::TransferFrom => token_operations::decode_transfer_from_reply(&bytes),
             ReplyCodec::Transfer => token_operations::decode_transfer_reply(&bytes),
+            ReplyCodec::ReplyCode => msg::reply_code().is_ok_and(|code| code.is_success()),
             ReplyCodec::None => return,
         };
 
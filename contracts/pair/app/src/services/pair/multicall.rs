@@ -0,0 +1,152 @@
+use crate::services::pair::{PairError, PairEvent, PairService, funcs::SwapType};
+use sails_rs::prelude::*;
+
+/// An operation of `multicall`. Swaps and liquidity changes work on the caller's
+/// internal balance, as their `*_internal` counterparts; there is no call pulling
+/// tokens in, they are deposited before the batch.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo)]
+pub enum Call {
+    SwapExactTokensForTokens {
+        amount_in: U256,
+        amount_out_min: U256,
+        is_token0_to_token1: bool,
+    },
+    SwapTokensForExactTokens {
+        amount_out: U256,
+        amount_in_max: U256,
+        is_token0_to_token1: bool,
+    },
+    AddLiquidity {
+        amount_a_desired: U256,
+        amount_b_desired: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+    },
+    RemoveLiquidity {
+        liquidity: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+    },
+    /// Sends `amount` of `token` from the caller's internal balance to the caller.
+    /// Only allowed after every other call.
+    Withdraw { token: ActorId, amount: U256 },
+}
+
+/// What a `Call` moved, as returned by the export of the same name.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum CallResult {
+    Swap {
+        amount_in: U256,
+        amount_out: U256,
+    },
+    AddLiquidity {
+        amount_a: U256,
+        amount_b: U256,
+        liquidity: U256,
+    },
+    RemoveLiquidity {
+        amount_a: U256,
+        amount_b: U256,
+    },
+    Withdraw,
+}
+
+impl CallResult {
+    fn of(call: &Call, event: &PairEvent) -> Self {
+        match call {
+            Call::SwapExactTokensForTokens { .. } | Call::SwapTokensForExactTokens { .. } => {
+                let (amount_in, amount_out) = event.swap_amounts();
+                CallResult::Swap {
+                    amount_in,
+                    amount_out,
+                }
+            }
+            Call::AddLiquidity { .. } => {
+                let (amount_a, amount_b, liquidity) = event.liquidity_amounts();
+                CallResult::AddLiquidity {
+                    amount_a,
+                    amount_b,
+                    liquidity,
+                }
+            }
+            Call::RemoveLiquidity { .. } => {
+                let (amount_a, amount_b, _) = event.liquidity_amounts();
+                CallResult::RemoveLiquidity { amount_a, amount_b }
+            }
+            Call::Withdraw { .. } => CallResult::Withdraw,
+        }
+    }
+}
+
+impl<'a> PairService<'a> {
+    /// Runs `calls` in order and emits the event of each. The swaps and liquidity changes
+    /// settle without awaiting, so the first one to fail undoes all of them; the withdrawals
+    /// come last, once the others are final.
+    pub async fn multicall_core(
+        &self,
+        calls: Vec<Call>,
+        deadline: u64,
+    ) -> Result<Vec<CallResult>, PairError> {
+        let is_withdrawal = |call: &Call| matches!(call, Call::Withdraw { .. });
+        let first_withdrawal = calls.iter().position(is_withdrawal).unwrap_or(calls.len());
+        if calls.is_empty() || !calls[first_withdrawal..].iter().all(is_withdrawal) {
+            return Err(PairError::InvalidCalls);
+        }
+
+        let mut results = Vec::with_capacity(calls.len());
+        for call in calls {
+            let event = match call {
+                Call::SwapExactTokensForTokens {
+                    amount_in,
+                    amount_out_min,
+                    is_token0_to_token1,
+                } => self.swap_internal_core(
+                    SwapType::ExactInput {
+                        amount_in,
+                        amount_out_min,
+                    },
+                    is_token0_to_token1,
+                    deadline,
+                )?,
+                Call::SwapTokensForExactTokens {
+                    amount_out,
+                    amount_in_max,
+                    is_token0_to_token1,
+                } => self.swap_internal_core(
+                    SwapType::ExactOutput {
+                        amount_out,
+                        amount_in_max,
+                    },
+                    is_token0_to_token1,
+                    deadline,
+                )?,
+                Call::AddLiquidity {
+                    amount_a_desired,
+                    amount_b_desired,
+                    amount_a_min,
+                    amount_b_min,
+                } => self.add_liquidity_internal_core(
+                    amount_a_desired,
+                    amount_b_desired,
+                    amount_a_min,
+                    amount_b_min,
+                    deadline,
+                )?,
+                Call::RemoveLiquidity {
+                    liquidity,
+                    amount_a_min,
+                    amount_b_min,
+                } => self.remove_liquidity_internal_core(
+                    liquidity,
+                    amount_a_min,
+                    amount_b_min,
+                    deadline,
+                )?,
+                Call::Withdraw { token, amount } => self.withdraw_core(token, amount).await?,
+            };
+            results.push(CallResult::of(&call, &event));
+            self.emit_pair_event(event)?;
+        }
+        Ok(results)
+    }
+}
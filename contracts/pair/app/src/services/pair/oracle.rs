@@ -0,0 +1,215 @@
+use crate::services::pair::PairError;
+use sails_rs::{U256, prelude::*};
+
+/// Prices are fixed-point numbers with 112 fractional bits, as Uniswap V2's UQ112x112:
+/// a price of 1.0 is `2^112`.
+pub const PRICE_RESOLUTION: usize = 112;
+pub const DEFAULT_OBSERVATION_CARDINALITY: u32 = 64;
+/// Bounds the ring buffer, and so the gas `observe` needs to search it.
+pub const MAX_OBSERVATION_CARDINALITY: u32 = 1_000;
+
+/// Cumulative prices at a block timestamp.
+/// Each cumulative price is the sum of `price * elapsed milliseconds` since the
+/// first liquidity was added; it wraps around on overflow, as only the
+/// difference between two observations is meaningful.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Observation {
+    /// Block timestamp in milliseconds.
+    pub timestamp: u64,
+    /// Cumulative price of token0 in token1.
+    pub price0_cumulative: U256,
+    /// Cumulative price of token1 in token0.
+    pub price1_cumulative: U256,
+}
+
+/// Ring buffer of the latest observations, at most one per block.
+#[derive(Debug)]
+pub struct Oracle {
+    observations: Vec<Observation>,
+    /// Position of the latest observation.
+    index: u32,
+    /// Number of observations kept once the buffer is full.
+    cardinality: u32,
+}
+
+impl Default for Oracle {
+    fn default() -> Self {
+        Self {
+            observations: Vec::new(),
+            index: 0,
+            cardinality: DEFAULT_OBSERVATION_CARDINALITY,
+        }
+    }
+}
+
+/// Returns the prices of token0 in token1 and of token1 in token0,
+/// or `None` while either reserve is empty.
+pub fn spot_prices(reserve0: U256, reserve1: U256) -> Option<(U256, U256)> {
+    if reserve0.is_zero() || reserve1.is_zero() {
+        return None;
+    }
+    let price0 = reserve1.saturating_mul(U256::one() << PRICE_RESOLUTION) / reserve0;
+    let price1 = reserve0.saturating_mul(U256::one() << PRICE_RESOLUTION) / reserve1;
+    Some((price0, price1))
+}
+
+impl Oracle {
+    pub fn cardinality(&self) -> u32 {
+        self.cardinality
+    }
+
+    pub fn observations(&self) -> Vec<Observation> {
+        self.chronological().copied().collect()
+    }
+
+    /// The buffer can only grow, so observations already written stay in order.
+    pub fn increase_cardinality(&mut self, cardinality: u32) -> Result<(), PairError> {
+        if cardinality <= self.cardinality || cardinality > MAX_OBSERVATION_CARDINALITY {
+            return Err(PairError::InvalidObservationCardinality);
+        }
+        self.cardinality = cardinality;
+        Ok(())
+    }
+
+    /// Accumulates the prices of the reserves held until `now` and records an observation.
+    /// Must be called with the reserves *before* they change. Only the first
+    /// change in a block is recorded, so a price can't be moved within a block.
+    pub fn update(&mut self, reserve0: U256, reserve1: U256, now: u64) {
+        let last = self.latest();
+        if !self.observations.is_empty() && now <= last.timestamp {
+            return;
+        }
+        let observation = accumulate(last, reserve0, reserve1, now);
+        self.write(observation);
+    }
+
+    /// Cumulative prices at `now`, extrapolating the latest observation with the current reserves.
+    pub fn current(&self, reserve0: U256, reserve1: U256, now: u64) -> Observation {
+        accumulate(self.latest(), reserve0, reserve1, now)
+    }
+
+    /// Returns the time-weighted average prices `(price0, price1)` over the last
+    /// `seconds_ago[i]` seconds for every `i`. Zero seconds gives the spot prices.
+    pub fn observe(
+        &self,
+        seconds_ago: &[u32],
+        reserve0: U256,
+        reserve1: U256,
+        now: u64,
+    ) -> Result<Vec<(U256, U256)>, PairError> {
+        let current = self.current(reserve0, reserve1, now);
+        seconds_ago
+            .iter()
+            .map(|&seconds| {
+                if seconds == 0 {
+                    return spot_prices(reserve0, reserve1).ok_or(PairError::InsufficientLiquidity);
+                }
+                let window = u64::from(seconds) * 1_000;
+                let target = now
+                    .checked_sub(window)
+                    .ok_or(PairError::ObservationTooOld)?;
+                let past = self.at(target, reserve0, reserve1)?;
+                let window = U256::from(window);
+                Ok((
+                    current
+                        .price0_cumulative
+                        .overflowing_sub(past.price0_cumulative)
+                        .0
+                        / window,
+                    current
+                        .price1_cumulative
+                        .overflowing_sub(past.price1_cumulative)
+                        .0
+                        / window,
+                ))
+            })
+            .collect()
+    }
+
+    /// Cumulative prices at `target`, interpolated between the observations around it.
+    fn at(&self, target: u64, reserve0: U256, reserve1: U256) -> Result<Observation, PairError> {
+        let latest = self.latest();
+        if target >= latest.timestamp {
+            return Ok(accumulate(latest, reserve0, reserve1, target));
+        }
+
+        let mut before: Option<&Observation> = None;
+        for observation in self.chronological() {
+            if observation.timestamp > target {
+                // The price was constant between two consecutive observations
+                let before = before.ok_or(PairError::ObservationTooOld)?;
+                let elapsed = U256::from(target - before.timestamp);
+                let span = U256::from(observation.timestamp - before.timestamp);
+                let interpolate = |from: U256, to: U256| {
+                    let delta = to.overflowing_sub(from).0 / span;
+                    from.overflowing_add(delta.overflowing_mul(elapsed).0).0
+                };
+                return Ok(Observation {
+                    timestamp: target,
+                    price0_cumulative: interpolate(
+                        before.price0_cumulative,
+                        observation.price0_cumulative,
+                    ),
+                    price1_cumulative: interpolate(
+                        before.price1_cumulative,
+                        observation.price1_cumulative,
+                    ),
+                });
+            }
+            before = Some(observation);
+        }
+        Err(PairError::ObservationTooOld)
+    }
+
+    fn latest(&self) -> Observation {
+        self.observations
+            .get(self.index as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn write(&mut self, observation: Observation) {
+        let len = self.observations.len() as u32;
+        if len == 0 {
+            self.observations.push(observation);
+            self.index = 0;
+            return;
+        }
+        let next = self.index + 1;
+        if next == len && len < self.cardinality {
+            self.observations.push(observation);
+            self.index = next;
+        } else {
+            let next = next % len;
+            self.observations[next as usize] = observation;
+            self.index = next;
+        }
+    }
+
+    /// Observations from the oldest to the latest.
+    fn chronological(&self) -> impl Iterator<Item = &Observation> {
+        let split = (self.index as usize + 1).min(self.observations.len());
+        let (newer, older) = self.observations.split_at(split);
+        older.iter().chain(newer.iter())
+    }
+}
+
+fn accumulate(last: Observation, reserve0: U256, reserve1: U256, now: u64) -> Observation {
+    let elapsed = U256::from(now.saturating_sub(last.timestamp));
+    let (price0_cumulative, price1_cumulative) = match spot_prices(reserve0, reserve1) {
+        Some((price0, price1)) if !elapsed.is_zero() => (
+            last.price0_cumulative
+                .overflowing_add(price0.overflowing_mul(elapsed).0)
+                .0,
+            last.price1_cumulative
+                .overflowing_add(price1.overflowing_mul(elapsed).0)
+                .0,
+        ),
+        _ => (last.price0_cumulative, last.price1_cumulative),
+    };
+    Observation {
+        timestamp: now,
+        price0_cumulative,
+        price1_cumulative,
+    }
+}
@@ -0,0 +1,188 @@
+use crate::services::pair::{
+    Config, LockState, PairError, PairEvent, PairService, State, lock::LockCtx,
+    msg_tracker::MessageStatus, token_operations,
+};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// What the pair holds of one token compared to what it accounts for.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct TokenAudit {
+    pub token: ActorId,
+    /// Balance reported by the token program.
+    pub balance: U256,
+    pub reserve: U256,
+    pub accrued_treasury_fee: U256,
+    /// Internal balances deposited with `deposit`.
+    pub deposits: U256,
+    /// `balance - reserve - accrued_treasury_fee - deposits`, e.g. tokens sent to the pair
+    /// directly.
+    pub excess: U256,
+    /// `reserve + accrued_treasury_fee + deposits - balance`, should always be zero.
+    pub shortfall: U256,
+}
+
+impl TokenAudit {
+    fn new(
+        token: ActorId,
+        balance: U256,
+        reserve: U256,
+        accrued_treasury_fee: U256,
+        deposits: U256,
+    ) -> Self {
+        let accounted = reserve
+            .saturating_add(accrued_treasury_fee)
+            .saturating_add(deposits);
+        Self {
+            token,
+            balance,
+            reserve,
+            accrued_treasury_fee,
+            deposits,
+            excess: balance.saturating_sub(accounted),
+            shortfall: accounted.saturating_sub(balance),
+        }
+    }
+}
+
+impl<'a> PairService<'a> {
+    pub async fn audit_core(&self) -> Result<(TokenAudit, TokenAudit), PairError> {
+        let (token0, token1, config) = self.with_state(|st| {
+            if !st.is_idle() {
+                return Err(PairError::AnotherTxInProgress);
+            }
+            Ok((st.token0, st.token1, st.config.clone()))
+        })?;
+        let (balance0, balance1) = pair_balances(token0, token1, &config).await?;
+
+        Ok(self.with_state(|st| audit(st, balance0, balance1)))
+    }
+
+    /// Sets the reserves to the token balances less the accrued treasury fees and deposits.
+    pub async fn sync_core(&self) -> Result<PairEvent, PairError> {
+        let (token0, token1, config) = self.lock_for_reconcile()?;
+        let (balance0, balance1) = self.locked_balances(token0, token1, &config).await?;
+
+        let (reserve0, reserve1) = self.with_state_mut(|st| {
+            let (outside0, outside1) = st.outside_reserves();
+            let reserve0 = balance0.saturating_sub(outside0);
+            let reserve1 = balance1.saturating_sub(outside1);
+            st.set_reserves(reserve0, reserve1);
+            st.lock.set_free();
+            (reserve0, reserve1)
+        });
+        let _ = self.lp.pause.resume();
+
+        Ok(PairEvent::Sync { reserve0, reserve1 })
+    }
+
+    /// Sends the tokens held above the reserves, accrued treasury fees and deposits to `to`.
+    pub async fn skim_core(&self, to: ActorId) -> Result<PairEvent, PairError> {
+        if to.is_zero() {
+            return Err(PairError::InvalidRecipient);
+        }
+        let (token0, token1, config) = self.lock_for_reconcile()?;
+        let (balance0, balance1) = self.locked_balances(token0, token1, &config).await?;
+        let (audit0, audit1) = self.with_state(|st| audit(st, balance0, balance1));
+        let (amount0, amount1) = (audit0.excess, audit1.excess);
+
+        // A failed transfer frees the lock, the skim can just be repeated (see `apply_reply`)
+        let msg_id = msg::id();
+        let sent = async {
+            if !amount0.is_zero() {
+                self.with_tracker_mut(|tr| {
+                    tr.insert_msg_status(msg_id, MessageStatus::SendingSkimToken0);
+                });
+                self.transfer(token0, to, amount0, &config, msg_id).await?;
+            }
+            if !amount1.is_zero() {
+                self.with_tracker_mut(|tr| {
+                    tr.insert_msg_status(msg_id, MessageStatus::SendingSkimToken1);
+                });
+                self.transfer(token1, to, amount1, &config, msg_id).await?;
+            }
+            Ok::<_, PairError>(())
+        }
+        .await;
+
+        self.unlock_reconcile();
+        self.with_tracker_mut(|tr| tr.clear_all());
+        sent?;
+
+        Ok(PairEvent::Skim {
+            to,
+            amount0,
+            amount1,
+        })
+    }
+
+    fn lock_for_reconcile(&self) -> Result<(ActorId, ActorId, Config), PairError> {
+        self.with_state_mut(|st| {
+            if st.migrated {
+                return Err(PairError::PoolMigrated);
+            }
+            if exec::gas_available() < st.config.gas_for_full_tx {
+                return Err(PairError::NotEnoghAttachedGas);
+            }
+            if !st.is_idle() {
+                return Err(PairError::AnotherTxInProgress);
+            }
+            st.lock = LockState::Busy(LockCtx::Reconcile);
+            let _ = self.lp.pause.pause();
+            Ok((st.token0, st.token1, st.config.clone()))
+        })
+    }
+
+    /// Reads the pair balances under the reconcile lock. Nothing moved yet,
+    /// so a failure just unlocks.
+    async fn locked_balances(
+        &self,
+        token0: ActorId,
+        token1: ActorId,
+        config: &Config,
+    ) -> Result<(U256, U256), PairError> {
+        let balances = pair_balances(token0, token1, config).await;
+        if balances.is_err() {
+            self.unlock_reconcile();
+        }
+        balances
+    }
+
+    fn unlock_reconcile(&self) {
+        self.with_state_mut(|st| st.lock.set_free());
+        let _ = self.lp.pause.resume();
+    }
+}
+
+async fn pair_balances(
+    token0: ActorId,
+    token1: ActorId,
+    config: &Config,
+) -> Result<(U256, U256), PairError> {
+    let program_id = exec::program_id();
+    let balance0 = token_operations::balance_of(token0, program_id, config).await?;
+    let balance1 = token_operations::balance_of(token1, program_id, config).await?;
+    Ok((balance0, balance1))
+}
+
+fn audit(st: &State, balance0: U256, balance1: U256) -> (TokenAudit, TokenAudit) {
+    let (deposits0, deposits1) = st.vault.totals();
+    (
+        TokenAudit::new(
+            st.token0,
+            balance0,
+            st.reserve0,
+            st.accrued_treasury_fee0,
+            deposits0,
+        ),
+        TokenAudit::new(
+            st.token1,
+            balance1,
+            st.reserve1,
+            st.accrued_treasury_fee1,
+            deposits1,
+        ),
+    )
+}
@@ -0,0 +1,502 @@
+use crate::services::pair::PairError;
+use crate::services::pair::amm_math::FEE_DENOM_BPS;
+use sails_rs::{U256, prelude::*};
+
+/// Amplification coefficients are stored multiplied by `A_PRECISION`, so a ramp
+/// moves them smoothly between whole values.
+pub const A_PRECISION: u64 = 100;
+pub const MIN_AMP: u64 = 1;
+pub const MAX_AMP: u64 = 1_000_000;
+/// A ramp can change the amplification at most tenfold, up or down.
+pub const MAX_AMP_CHANGE: u64 = 10;
+/// A ramp lasts at least one day, and a new one starts at least one day after the last.
+pub const MIN_RAMP_TIME: u64 = 86_400_000;
+const MAX_ITERATIONS: usize = 255;
+
+/// Amplification coefficient moving linearly from `initial_amp` at `initial_time`
+/// to `future_amp` at `future_time`. Both values include `A_PRECISION`,
+/// times are block timestamps in milliseconds.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct AmpRamp {
+    pub initial_amp: u64,
+    pub future_amp: u64,
+    pub initial_time: u64,
+    pub future_time: u64,
+}
+
+impl AmpRamp {
+    /// A constant amplification of `amp`, without `A_PRECISION`.
+    pub fn new(amp: u64, now: u64) -> Result<Self, PairError> {
+        if !(MIN_AMP..=MAX_AMP).contains(&amp) {
+            return Err(PairError::InvalidAmp);
+        }
+        let amp = amp * A_PRECISION;
+        Ok(Self {
+            initial_amp: amp,
+            future_amp: amp,
+            initial_time: now,
+            future_time: now,
+        })
+    }
+
+    /// Amplification at `now`, with `A_PRECISION`.
+    pub fn amp_at(&self, now: u64) -> u64 {
+        if now >= self.future_time || self.future_time <= self.initial_time {
+            return self.future_amp;
+        }
+        let elapsed = u128::from(now.saturating_sub(self.initial_time));
+        let duration = u128::from(self.future_time - self.initial_time);
+        let (initial, future) = (u128::from(self.initial_amp), u128::from(self.future_amp));
+        let amp = if future > initial {
+            initial + (future - initial) * elapsed / duration
+        } else {
+            initial - (initial - future) * elapsed / duration
+        };
+        amp as u64
+    }
+
+    /// Starts moving from the current amplification to `future_amp` (without `A_PRECISION`)
+    /// at `future_time`.
+    pub fn start(&self, future_amp: u64, future_time: u64, now: u64) -> Result<Self, PairError> {
+        if now < self.initial_time.saturating_add(MIN_RAMP_TIME)
+            || future_time < now.saturating_add(MIN_RAMP_TIME)
+        {
+            return Err(PairError::InvalidRampTime);
+        }
+        if !(MIN_AMP..=MAX_AMP).contains(&future_amp) {
+            return Err(PairError::InvalidAmp);
+        }
+        let initial_amp = self.amp_at(now);
+        let future_amp = future_amp * A_PRECISION;
+        if future_amp > initial_amp * MAX_AMP_CHANGE || future_amp * MAX_AMP_CHANGE < initial_amp {
+            return Err(PairError::InvalidAmp);
+        }
+        Ok(Self {
+            initial_amp,
+            future_amp,
+            initial_time: now,
+            future_time,
+        })
+    }
+
+    /// Keeps the current amplification from `now` on.
+    pub fn stop(&self, now: u64) -> Self {
+        let amp = self.amp_at(now);
+        Self {
+            initial_amp: amp,
+            future_amp: amp,
+            initial_time: now,
+            future_time: now,
+        }
+    }
+}
+
+/// Calculates the StableSwap invariant `D` of two balances:
+///   4A(x + y) + D = 4AD + D^3 / (4xy)
+/// Newton's method from `x + y`, which is never below `D`, stopping once `D` stops decreasing.
+/// The balances are sorted first so that `D` doesn't depend on their order.
+/// # Arguments
+/// * `x0`, `x1` - Balances of the two tokens
+/// * `amp` - Amplification coefficient with `A_PRECISION`
+pub fn compute_d(x0: U256, x1: U256, amp: u64) -> Result<U256, PairError> {
+    if x0.is_zero() || x1.is_zero() {
+        return Ok(U256::zero());
+    }
+    let (x0, x1) = if x0 <= x1 { (x0, x1) } else { (x1, x0) };
+    let sum = x0.checked_add(x1).ok_or(PairError::Overflow)?;
+    let ann = U256::from(4 * amp);
+    let precision = U256::from(A_PRECISION);
+    let ann_sum = ann.checked_mul(sum).ok_or(PairError::Overflow)?;
+    let two = U256::from(2);
+
+    let mut d = sum;
+    for _ in 0..MAX_ITERATIONS {
+        // d_p = D^3 / (4 * x0 * x1)
+        let d_p = mul_div(d, d, x0.saturating_mul(two))?;
+        let d_p = mul_div(d_p, d, x1.saturating_mul(two))?;
+
+        // D' = (Ann * S + 2 * D_P) * D / ((Ann - 1) * D + 3 * D_P)
+        let numerator = d_p
+            .checked_mul(U256::from(2 * A_PRECISION))
+            .and_then(|v| v.checked_add(ann_sum))
+            .ok_or(PairError::Overflow)?;
+        let denominator = d_p
+            .checked_mul(U256::from(3 * A_PRECISION))
+            .and_then(|v| v.checked_add((ann - precision).checked_mul(d)?))
+            .ok_or(PairError::Overflow)?;
+        let next = mul_div(numerator, d, denominator)?;
+
+        if next >= d {
+            return Ok(d);
+        }
+        d = next;
+    }
+    Err(PairError::InvariantViolation)
+}
+
+/// Calculates the balance of one token that keeps the invariant `d` when the
+/// other token's balance is `x`, solving
+///   y^2 + (x + D / 4A - D) * y = D^3 / (16Ax)
+/// Rounding is upward, so the result is never below the exact curve.
+/// # Arguments
+/// * `x` - Balance of the other token
+/// * `d` - Invariant, as returned by `compute_d`
+/// * `amp` - Amplification coefficient with `A_PRECISION`
+pub fn compute_y(x: U256, d: U256, amp: u64) -> Result<U256, PairError> {
+    if x.is_zero() || d.is_zero() {
+        return Err(PairError::InsufficientLiquidity);
+    }
+    let ann = U256::from(4 * amp);
+    let precision = U256::from(A_PRECISION);
+
+    // c = D^3 / (4x * Ann), b = x + D / Ann
+    let two = U256::from(2);
+    let c = mul_div_ceil(d, d, x.saturating_mul(two))?;
+    let c = mul_div_ceil(
+        c,
+        d.checked_mul(precision).ok_or(PairError::Overflow)?,
+        U256::from(8 * amp),
+    )?;
+    let b = x
+        .checked_add(mul_div(d, precision, ann)?)
+        .ok_or(PairError::Overflow)?;
+
+    let mut y = d;
+    for i in 0..MAX_ITERATIONS {
+        // y' = (y^2 + c) / (2y + b - D)
+        let numerator = y
+            .checked_mul(y)
+            .and_then(|v| v.checked_add(c))
+            .ok_or(PairError::Overflow)?;
+        let denominator = y
+            .checked_mul(two)
+            .and_then(|v| v.checked_add(b))
+            .and_then(|v| v.checked_sub(d))
+            .filter(|v| !v.is_zero())
+            .ok_or(PairError::InvariantViolation)?;
+        let next = div_ceil(numerator, denominator);
+
+        // The first step may move up, then the iteration decreases towards the curve
+        if i > 0 && next >= y {
+            return Ok(y);
+        }
+        y = next;
+    }
+    Err(PairError::InvariantViolation)
+}
+
+/// Calculates the output amount of a stable swap, the swap fee taken from the input
+/// as in `amm_math::get_amount_out`.
+/// # Arguments
+/// * `amount_in` - Amount of input asset being swapped
+/// * `reserve_in` - Reserve of input asset in the pool
+/// * `reserve_out` - Reserve of output asset in the pool
+/// * `amp` - Amplification coefficient with `A_PRECISION`
+/// * `swap_fee_bps` - Swap fee in basis points, below `FEE_DENOM_BPS`
+pub fn get_amount_out(
+    amount_in: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    amp: u64,
+    swap_fee_bps: u64,
+) -> Result<U256, PairError> {
+    if amount_in.is_zero() {
+        return Err(PairError::InsufficientAmount);
+    }
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return Err(PairError::InsufficientLiquidity);
+    }
+    let fee_multiplier = fee_multiplier(swap_fee_bps)?;
+
+    let amount_in_with_fee = mul_div(amount_in, fee_multiplier, U256::from(FEE_DENOM_BPS))?;
+    let d = compute_d(reserve_in, reserve_out, amp)?;
+    let x = reserve_in
+        .checked_add(amount_in_with_fee)
+        .ok_or(PairError::Overflow)?;
+    let y = compute_y(x, d, amp)?;
+
+    Ok(reserve_out.saturating_sub(y))
+}
+
+/// Calculates the input amount a stable swap needs to send out `amount_out`.
+/// The balance found on the curve is raised until `compute_y` agrees with it,
+/// so that the swap passes `verify_invariant`.
+/// # Arguments
+/// * `amount_out` - Desired amount of output asset
+/// * `reserve_in` - Reserve of input asset in the pool
+/// * `reserve_out` - Reserve of output asset in the pool
+/// * `amp` - Amplification coefficient with `A_PRECISION`
+/// * `swap_fee_bps` - Swap fee in basis points, below `FEE_DENOM_BPS`
+pub fn get_amount_in(
+    amount_out: U256,
+    reserve_in: U256,
+    reserve_out: U256,
+    amp: u64,
+    swap_fee_bps: u64,
+) -> Result<U256, PairError> {
+    if amount_out.is_zero() {
+        return Err(PairError::InsufficientAmount);
+    }
+    if reserve_in.is_zero() || reserve_out.is_zero() || amount_out >= reserve_out {
+        return Err(PairError::InsufficientLiquidity);
+    }
+    let fee_multiplier = fee_multiplier(swap_fee_bps)?;
+
+    let d = compute_d(reserve_in, reserve_out, amp)?;
+    let y = reserve_out - amount_out;
+    let mut x = compute_y(y, d, amp)?;
+    let mut step = U256::one();
+    while compute_y(x, d, amp)? > y {
+        x = x.checked_add(step).ok_or(PairError::Overflow)?;
+        step = step.checked_mul(U256::from(2)).ok_or(PairError::Overflow)?;
+    }
+
+    let amount_in_with_fee = x.saturating_sub(reserve_in).max(U256::one());
+    mul_div_ceil(
+        amount_in_with_fee,
+        U256::from(FEE_DENOM_BPS),
+        fee_multiplier,
+    )
+}
+
+/// Verifies the balances after a swap are on or above the curve of the reserves before it,
+/// once the swap fee is taken off the inputs.
+/// `compute_y` never rounds below the curve, so either token's balance being at least
+/// `compute_y` of the other's is enough.
+/// # Arguments
+/// * `balance0`, `balance1` - Balances after the swap
+/// * `amount0_in`, `amount1_in` - Inputs of the swap
+/// * `reserve0`, `reserve1` - Reserves before the swap
+/// * `amp` - Amplification coefficient with `A_PRECISION`
+/// * `swap_fee_bps` - Swap fee of the pair in basis points
+#[allow(clippy::too_many_arguments)]
+pub fn verify_invariant(
+    balance0: U256,
+    balance1: U256,
+    amount0_in: U256,
+    amount1_in: U256,
+    reserve0: U256,
+    reserve1: U256,
+    amp: u64,
+    swap_fee_bps: u64,
+) -> Result<(), PairError> {
+    let fee = U256::from(swap_fee_bps);
+    let denom = U256::from(FEE_DENOM_BPS);
+    let adjusted0 = balance0
+        .checked_sub(mul_div_ceil(amount0_in, fee, denom)?)
+        .ok_or(PairError::InvariantViolation)?;
+    let adjusted1 = balance1
+        .checked_sub(mul_div_ceil(amount1_in, fee, denom)?)
+        .ok_or(PairError::InvariantViolation)?;
+    if adjusted0.is_zero() || adjusted1.is_zero() {
+        return Err(PairError::InvariantViolation);
+    }
+
+    let d = compute_d(reserve0, reserve1, amp)?;
+    if adjusted1 >= compute_y(adjusted0, d, amp)? || adjusted0 >= compute_y(adjusted1, d, amp)? {
+        Ok(())
+    } else {
+        Err(PairError::InvariantViolation)
+    }
+}
+
+fn fee_multiplier(swap_fee_bps: u64) -> Result<U256, PairError> {
+    FEE_DENOM_BPS
+        .checked_sub(swap_fee_bps)
+        .filter(|m| *m != 0)
+        .map(U256::from)
+        .ok_or(PairError::InvalidSwapFee)
+}
+
+/// `a * b / denominator` with a 512-bit intermediate product.
+fn mul_div(a: U256, b: U256, denominator: U256) -> Result<U256, PairError> {
+    if denominator.is_zero() {
+        return Err(PairError::InsufficientLiquidity);
+    }
+    U256::try_from(a.full_mul(b) / denominator.full_mul(U256::one()))
+        .map_err(|_| PairError::Overflow)
+}
+
+/// Same as `mul_div`, rounding up.
+fn mul_div_ceil(a: U256, b: U256, denominator: U256) -> Result<U256, PairError> {
+    let quotient = mul_div(a, b, denominator)?;
+    if (a.full_mul(b) % denominator.full_mul(U256::one())).is_zero() {
+        Ok(quotient)
+    } else {
+        quotient.checked_add(U256::one()).ok_or(PairError::Overflow)
+    }
+}
+
+fn div_ceil(a: U256, b: U256) -> U256 {
+    let (quotient, remainder) = a.div_mod(b);
+    if remainder.is_zero() {
+        quotient
+    } else {
+        quotient + U256::one()
+    }
+}
+
+#[cfg(test)]
+mod prop_tests {
+    use crate::pair::stable_math::{
+        A_PRECISION, AmpRamp, MIN_RAMP_TIME, compute_d, compute_y, get_amount_in, get_amount_out,
+        verify_invariant,
+    };
+    use proptest::prelude::*;
+    use sails_rs::U256;
+
+    fn reserve() -> impl Strategy<Value = U256> {
+        (1_000u128..=u128::MAX >> 20).prop_map(U256::from)
+    }
+
+    fn amp() -> impl Strategy<Value = u64> {
+        prop::sample::select(vec![1u64, 10, 100, 1_000, 100_000]).prop_map(|a| a * A_PRECISION)
+    }
+
+    fn fee() -> impl Strategy<Value = u64> {
+        prop::sample::select(vec![0u64, 4, 30, 100])
+    }
+
+    /// The two reserves stay within a 1000:1 ratio.
+    fn reserves() -> impl Strategy<Value = (U256, U256)> {
+        (reserve(), 1u64..=1_000_000).prop_map(|(r0, ratio)| {
+            let r1 = (r0 * U256::from(ratio) / U256::from(1_000)).max(U256::from(1_000));
+            (r0, r1)
+        })
+    }
+
+    proptest! {
+        /// D doesn't depend on the order of the balances and is at most their sum.
+        #[test]
+        fn prop_d_symmetric_and_bounded((r0, r1) in reserves(), amp in amp()) {
+            let d = compute_d(r0, r1, amp).unwrap();
+            prop_assert_eq!(d, compute_d(r1, r0, amp).unwrap());
+            prop_assert!(d <= r0 + r1);
+        }
+
+        /// Balanced reserves have `D = x + y`, whatever the amplification.
+        #[test]
+        fn prop_d_of_balanced_pool(r in reserve(), amp in amp()) {
+            let d = compute_d(r, r, amp).unwrap();
+            prop_assert!(U256::from(2) * r - d <= U256::one());
+        }
+
+        /// `compute_y` recovers the other reserve, rounding up by a few units at most.
+        #[test]
+        fn prop_y_recovers_reserve((r0, r1) in reserves(), amp in amp()) {
+            let d = compute_d(r0, r1, amp).unwrap();
+            let y = compute_y(r0, d, amp).unwrap();
+            let diff = if y > r1 { y - r1 } else { r1 - y };
+            prop_assert!(diff <= r1 / U256::from(1_000_000_000_000u64) + U256::from(2));
+        }
+
+        /// A swap quoted by `get_amount_out` passes the invariant check.
+        #[test]
+        fn prop_amount_out_keeps_invariant(
+            (r0, r1) in reserves(),
+            pct in 1u64..=100,
+            amp in amp(),
+            fee in fee(),
+        ) {
+            let amount_in = (r0 * U256::from(pct) / U256::from(100)).max(U256::one());
+            let out = get_amount_out(amount_in, r0, r1, amp, fee).unwrap();
+            prop_assert!(out < r1);
+            prop_assert!(
+                verify_invariant(r0 + amount_in, r1 - out, amount_in, U256::zero(), r0, r1, amp, fee)
+                    .is_ok()
+            );
+        }
+
+        /// A swap quoted by `get_amount_in` passes the invariant check, and paying
+        /// that input gets at least the requested output.
+        #[test]
+        fn prop_amount_in_keeps_invariant(
+            (r0, r1) in reserves(),
+            pct in 1u64..=90,
+            amp in amp(),
+            fee in fee(),
+        ) {
+            let amount_out = (r1 * U256::from(pct) / U256::from(100)).max(U256::one());
+            let amount_in = get_amount_in(amount_out, r0, r1, amp, fee).unwrap();
+            prop_assert!(
+                verify_invariant(r1 - amount_out, r0 + amount_in, U256::zero(), amount_in, r1, r0, amp, fee)
+                    .is_ok()
+            );
+            prop_assert!(get_amount_out(amount_in, r0, r1, amp, fee).unwrap() + U256::from(2) >= amount_out);
+        }
+
+        /// Taking one more unit than quoted breaks the invariant.
+        #[test]
+        fn prop_extra_output_rejected(
+            (r0, r1) in reserves(),
+            pct in 1u64..=50,
+            amp in amp(),
+        ) {
+            let amount_in = (r0 * U256::from(pct) / U256::from(100)).max(U256::one());
+            let out = get_amount_out(amount_in, r0, r1, amp, 30).unwrap();
+            // A few units of slack cover the rounding of `compute_y`
+            let greedy = out + r1 / U256::from(1_000_000_000u64) + U256::from(10);
+            prop_assume!(greedy < r1);
+            prop_assert!(
+                verify_invariant(r0 + amount_in, r1 - greedy, amount_in, U256::zero(), r0, r1, amp, 30)
+                    .is_err()
+            );
+        }
+
+        /// Near balance a highly amplified pool prices close to 1:1, much better than x*y=k.
+        #[test]
+        fn prop_amplified_pool_beats_constant_product(r in reserve(), pct in 1u64..=10) {
+            let amount_in = r * U256::from(pct) / U256::from(100);
+            let stable = get_amount_out(amount_in, r, r, 100 * A_PRECISION, 0).unwrap();
+            let constant_product = amount_in * r / (r + amount_in);
+            prop_assert!(stable >= constant_product);
+            prop_assert!(stable * U256::from(100) >= amount_in * U256::from(99));
+        }
+
+        /// The amplification moves linearly and monotonically during a ramp.
+        #[test]
+        fn prop_ramp_is_monotone(
+            base in 1u64..=1_000,
+            factor in 1u64..=10,
+            up in any::<bool>(),
+            t1 in 0u64..=2 * MIN_RAMP_TIME,
+            t2 in 0u64..=2 * MIN_RAMP_TIME,
+        ) {
+            let (initial, future) = if up { (base, base * factor) } else { (base * factor, base) };
+            let ramp = AmpRamp::new(initial, 0)
+                .unwrap()
+                .start(future, 2 * MIN_RAMP_TIME, MIN_RAMP_TIME)
+                .unwrap();
+            let (early, late) = (t1.min(t2) + MIN_RAMP_TIME, t1.max(t2) + MIN_RAMP_TIME);
+            let (a_early, a_late) = (ramp.amp_at(early), ramp.amp_at(late));
+            if future >= initial {
+                prop_assert!(a_early <= a_late);
+            } else {
+                prop_assert!(a_early >= a_late);
+            }
+            prop_assert_eq!(ramp.amp_at(u64::MAX), future * A_PRECISION);
+        }
+    }
+
+    #[test]
+    fn ramp_limits() {
+        let ramp = AmpRamp::new(100, 0).unwrap();
+        assert!(AmpRamp::new(0, 0).is_err());
+        // Too soon after the previous change, too short, or more than tenfold
+        assert!(
+            ramp.start(200, 3 * MIN_RAMP_TIME, MIN_RAMP_TIME - 1)
+                .is_err()
+        );
+        assert!(
+            ramp.start(200, 2 * MIN_RAMP_TIME - 1, MIN_RAMP_TIME)
+                .is_err()
+        );
+        assert!(ramp.start(1_001, 3 * MIN_RAMP_TIME, MIN_RAMP_TIME).is_err());
+        assert!(ramp.start(9, 3 * MIN_RAMP_TIME, MIN_RAMP_TIME).is_err());
+
+        let ramp = ramp.start(200, 3 * MIN_RAMP_TIME, MIN_RAMP_TIME).unwrap();
+        assert_eq!(ramp.amp_at(2 * MIN_RAMP_TIME), 150 * A_PRECISION);
+        let stopped = ramp.stop(2 * MIN_RAMP_TIME);
+        assert_eq!(stopped.amp_at(u64::MAX), 150 * A_PRECISION);
+    }
+}
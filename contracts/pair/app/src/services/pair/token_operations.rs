@@ -1,8 +1,34 @@
 use crate::PairService;
-use crate::services::pair::{Config, PairError, msg_tracker::MessageStatus};
-use extended_vft_client::vft::io::{BalanceOf, Transfer, TransferFrom};
-use sails_rs::client::CallCodec;
-use sails_rs::{U256, prelude::*};
+use crate::services::pair::{
+    Config, PairError,
+    lock::{LockCtx, Reservation},
+    msg_tracker::MessageStatus,
+};
+use sails_rs::{U256, gstd::msg, prelude::*};
+use token_ops::{GasConfig, TokenOpError};
+pub use token_ops::{decode_transfer_from_reply, decode_transfer_reply};
+
+impl From<TokenOpError> for PairError {
+    fn from(err: TokenOpError) -> Self {
+        match err {
+            TokenOpError::SendFailure => PairError::SendFailure,
+            TokenOpError::ReplyTimeout => PairError::ReplyTimeout,
+            TokenOpError::ReplyFailure => PairError::ReplyFailure,
+            TokenOpError::UnableToDecode => PairError::UnableToDecode,
+            TokenOpError::TransferFailed => PairError::TokenTransferFailed,
+        }
+    }
+}
+
+impl Config {
+    fn gas(&self) -> GasConfig {
+        GasConfig {
+            gas_for_token_ops: self.gas_for_token_ops,
+            gas_for_reply_deposit: self.gas_for_reply_deposit,
+            reply_timeout: self.reply_timeout,
+        }
+    }
+}
 
 impl<'a> PairService<'a> {
     pub async fn transfer_from(
@@ -14,17 +40,9 @@ impl<'a> PairService<'a> {
         config: &Config,
         msg_id: MessageId,
     ) -> Result<(), PairError> {
-        let bytes: Vec<u8> =
-            TransferFrom::encode_params_with_prefix("Vft", sender, receiver, amount);
-        self.send_message_with_gas_for_reply(
-            token_id,
-            bytes,
-            config.gas_for_token_ops,
-            config.gas_for_reply_deposit,
-            config.reply_timeout,
-            msg_id,
-        )
-        .await
+        let bytes = token_ops::transfer_from_payload(sender, receiver, amount);
+        self.send_message_with_gas_for_reply(token_id, bytes, config, msg_id)
+            .await
     }
 
     pub async fn transfer(
@@ -35,46 +53,61 @@ impl<'a> PairService<'a> {
         config: &Config,
         msg_id: MessageId,
     ) -> Result<(), PairError> {
-        let bytes: Vec<u8> = Transfer::encode_params_with_prefix("Vft", receiver, amount);
+        let bytes = token_ops::transfer_payload(receiver, amount);
+        self.send_message_with_gas_for_reply(token_id, bytes, config, msg_id)
+            .await
+    }
 
-        self.send_message_with_gas_for_reply(
-            token_id,
-            bytes,
-            config.gas_for_token_ops,
-            config.gas_for_reply_deposit,
-            config.reply_timeout,
-            msg_id,
-        )
-        .await
+    /// Sends `amount` of `token` back to `user` as an operation of its own, keyed by the
+    /// refund message. A failed refund is paused as `AddLiqRefund` for `recover_operation`,
+    /// so refunds of several tokens don't depend on each other.
+    pub async fn refund_as_operation(
+        &self,
+        token: ActorId,
+        user: ActorId,
+        amount: U256,
+        config: &Config,
+    ) {
+        if amount.is_zero() {
+            return;
+        }
+        let ctx = LockCtx::AddLiqRefund {
+            user,
+            token,
+            amount,
+        };
+        let payload = token_ops::transfer_payload(user, amount);
+        let sent = token_ops::send_with_reply(token, payload, &config.gas(), |op_id| {
+            self.with_state_mut(|st| st.ops.start(op_id, ctx.clone(), Reservation::default()));
+            self.with_tracker_mut(|tr| {
+                tr.insert_msg_status(op_id, MessageStatus::SendingMessageToReturnTokensA);
+                tr.bind_reply(op_id, op_id);
+            });
+        })
+        .await;
+        // The reply hook finishes or pauses a refund that was sent
+        if sent == Err(TokenOpError::SendFailure) {
+            let op_id = msg::id();
+            self.with_state_mut(|st| {
+                st.ops.start(op_id, ctx, Reservation::default());
+                st.ops.pause(&op_id);
+            });
+        }
     }
 
     async fn send_message_with_gas_for_reply(
         &self,
         destination: ActorId,
         message: Vec<u8>,
-        gas_to_send: u64,
-        gas_deposit: u64,
-        reply_timeout: u32,
+        config: &Config,
         root_msg_id: MessageId,
     ) -> Result<(), PairError> {
-        let fut = sails_rs::gstd::msg::send_bytes_with_gas_for_reply(
-            destination,
-            message,
-            gas_to_send,
-            0,
-            gas_deposit,
-        )
-        .map_err(|_| PairError::SendFailure)?;
-
-        let reply_to_id = fut.waiting_reply_to;
-        self.with_tracker_mut(|tr| {
-            tr.bind_reply(reply_to_id, root_msg_id);
-        });
-
-        fut.up_to(Some(reply_timeout))
-            .map_err(|_| PairError::ReplyTimeout)?
-            .await
-            .map_err(|_| PairError::ReplyFailure)?;
+        token_ops::send_with_reply(destination, message, &config.gas(), |reply_to_id| {
+            self.with_tracker_mut(|tr| {
+                tr.bind_reply(reply_to_id, root_msg_id);
+            });
+        })
+        .await?;
 
         self.fetch_transfer_result(&root_msg_id)
     }
@@ -101,6 +134,13 @@ impl<'a> PairService<'a> {
                 | MessageStatus::TokenAUnlocked(s)
                 | MessageStatus::TreasuryTokenASent(s)
                 | MessageStatus::TreasuryTokenBSent(s)
+                | MessageStatus::FlashToken0Sent(s)
+                | MessageStatus::FlashToken1Sent(s)
+                | MessageStatus::SkimToken0Sent(s)
+                | MessageStatus::SkimToken1Sent(s)
+                | MessageStatus::ZapOutSent(s)
+                | MessageStatus::DepositReceived(s)
+                | MessageStatus::WithdrawalSent(s)
                 | MessageStatus::TokenBUnlocked(s) => *s,
                 _ => return Err(PairError::InvalidMessageStatus),
             };
@@ -114,34 +154,18 @@ impl<'a> PairService<'a> {
     }
 }
 
+/// Tells the factory this pair was migrated, so a new pair can replace it in the registry.
+/// The payload is encoded by hand because the factory client depends on this crate.
+pub fn notify_factory_migrated(factory_id: ActorId) {
+    let payload = ("Factory", "MarkPairMigrated").encode();
+    // The factory admin can still mark the pair if this message is lost
+    let _ = sails_rs::gstd::msg::send_bytes(factory_id, payload, 0);
+}
+
 pub async fn balance_of(
     token_id: ActorId,
     account_id: ActorId,
     config: &Config,
 ) -> Result<U256, PairError> {
-    let bytes: Vec<u8> = BalanceOf::encode_params_with_prefix("Vft", account_id);
-
-    let reply_bytes = sails_rs::gstd::msg::send_bytes_with_gas_for_reply(
-        token_id,
-        bytes,
-        config.gas_for_token_ops,
-        0,
-        config.gas_for_reply_deposit,
-    )
-    .map_err(|_| PairError::SendFailure)?
-    .up_to(Some(config.reply_timeout))
-    .map_err(|_| PairError::ReplyTimeout)?
-    .await
-    .map_err(|_| PairError::ReplyFailure)?;
-
-    BalanceOf::decode_reply_with_prefix("Vft", &reply_bytes).map_err(|_| PairError::UnableToDecode)
-}
-
-/// Decode reply received from the TransferFrom method.
-pub fn decode_transfer_from_reply(bytes: &[u8]) -> bool {
-    TransferFrom::decode_reply_with_prefix("Vft", bytes).unwrap_or(false)
-}
-/// Decode reply received from the TransferFrom method.
-pub fn decode_transfer_reply(bytes: &[u8]) -> bool {
-    Transfer::decode_reply_with_prefix("Vft", bytes).unwrap_or(false)
+    Ok(token_ops::balance_of(token_id, account_id, &config.gas()).await?)
 }
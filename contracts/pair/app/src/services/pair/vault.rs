@@ -0,0 +1,310 @@
+use crate::services::pair::funcs::{
+    SwapType, quote_swap, settle_add_liquidity, settle_remove_liquidity, settle_swap,
+};
+use crate::services::pair::{
+    PairError, PairEvent, PairService, State, amm_math,
+    lock::{LockCtx, Reservation},
+    msg_tracker::MessageStatus,
+    zap::token_side,
+};
+use sails_rs::{
+    collections::HashMap,
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// Tokens deposited into the pair, per account, outside the reserves.
+///
+/// Swaps and liquidity changes made against these balances settle within the message,
+/// without any token transfer.
+#[derive(Debug, Default)]
+pub struct Vault {
+    balances: HashMap<ActorId, (U256, U256)>,
+    total0: U256,
+    total1: U256,
+}
+
+impl Vault {
+    /// `(token0, token1)` deposited by `account`.
+    pub fn balance_of(&self, account: &ActorId) -> (U256, U256) {
+        self.balances.get(account).copied().unwrap_or_default()
+    }
+
+    /// `(token0, token1)` deposited by all accounts.
+    pub fn totals(&self) -> (U256, U256) {
+        (self.total0, self.total1)
+    }
+
+    pub fn credit(
+        &mut self,
+        account: ActorId,
+        is_token0: bool,
+        amount: U256,
+    ) -> Result<(), PairError> {
+        let (balance, total) = self.side(account, is_token0);
+        *balance = balance.checked_add(amount).ok_or(PairError::Overflow)?;
+        *total = total.checked_add(amount).ok_or(PairError::Overflow)?;
+        Ok(())
+    }
+
+    pub fn debit(
+        &mut self,
+        account: ActorId,
+        is_token0: bool,
+        amount: U256,
+    ) -> Result<(), PairError> {
+        let (balance, total) = self.side(account, is_token0);
+        *balance = balance
+            .checked_sub(amount)
+            .ok_or(PairError::InsufficientInternalBalance)?;
+        *total = total.checked_sub(amount).ok_or(PairError::Overflow)?;
+        if self.balance_of(&account) == (U256::zero(), U256::zero()) {
+            self.balances.remove(&account);
+        }
+        Ok(())
+    }
+
+    fn side(&mut self, account: ActorId, is_token0: bool) -> (&mut U256, &mut U256) {
+        let (balance0, balance1) = self.balances.entry(account).or_default();
+        if is_token0 {
+            (balance0, &mut self.total0)
+        } else {
+            (balance1, &mut self.total1)
+        }
+    }
+}
+
+impl<'a> PairService<'a> {
+    /// Takes `amount` of `token` from the caller and credits it to its internal balance.
+    pub async fn deposit_core(&self, token: ActorId, amount: U256) -> Result<PairEvent, PairError> {
+        let sender = msg::source();
+        let msg_id = msg::id();
+
+        let (is_token0, config) = self.with_state_mut(|st| {
+            if st.migrated {
+                return Err(PairError::PoolMigrated);
+            }
+            if exec::gas_available() < st.config.gas_for_full_tx {
+                return Err(PairError::NotEnoghAttachedGas);
+            }
+            if !st.lock.is_free() {
+                return Err(PairError::AnotherTxInProgress);
+            }
+            if amount.is_zero() {
+                return Err(PairError::InsufficientAmount);
+            }
+            let is_token0 = token_side(st, token)?;
+            // Keeps the arriving tokens out of sync, skim and migration until credited
+            st.ops.start(
+                msg_id,
+                LockCtx::Deposit {
+                    user: sender,
+                    token,
+                    amount,
+                },
+                Reservation::default(),
+            );
+            Ok((is_token0, st.config.clone()))
+        })?;
+
+        // A failed transfer ends the operation (see `apply_reply`)
+        self.with_tracker_mut(|tr| {
+            tr.insert_msg_status(msg_id, MessageStatus::SendingDeposit);
+        });
+        self.transfer_from(token, sender, exec::program_id(), amount, &config, msg_id)
+            .await?;
+
+        self.with_state_mut(|st| -> Result<(), PairError> {
+            st.vault.credit(sender, is_token0, amount)?;
+            st.ops.finish(&msg_id);
+            Ok(())
+        })?;
+        self.with_tracker_mut(|tr| {
+            tr.remove_msg_status(&msg_id);
+        });
+
+        Ok(PairEvent::Deposited {
+            user_id: sender,
+            token,
+            amount,
+        })
+    }
+
+    /// Debits `amount` of `token` from the caller's internal balance and sends it to the caller.
+    /// Also available once the pair is migrated.
+    pub async fn withdraw_core(
+        &self,
+        token: ActorId,
+        amount: U256,
+    ) -> Result<PairEvent, PairError> {
+        let sender = msg::source();
+        let msg_id = msg::id();
+
+        let config = self.with_state_mut(|st| {
+            if exec::gas_available() < st.config.gas_for_full_tx {
+                return Err(PairError::NotEnoghAttachedGas);
+            }
+            if !st.lock.is_free() {
+                return Err(PairError::AnotherTxInProgress);
+            }
+            if amount.is_zero() {
+                return Err(PairError::InsufficientAmount);
+            }
+            let is_token0 = token_side(st, token)?;
+            st.vault.debit(sender, is_token0, amount)?;
+            st.ops.start(
+                msg_id,
+                LockCtx::Withdraw {
+                    user: sender,
+                    token,
+                    amount,
+                },
+                Reservation::default(),
+            );
+            Ok(st.config.clone())
+        })?;
+
+        // A failed transfer pauses the operation, resent with `recover_operation`
+        self.with_tracker_mut(|tr| {
+            tr.insert_msg_status(msg_id, MessageStatus::SendingWithdrawal);
+        });
+        self.transfer(token, sender, amount, &config, msg_id)
+            .await?;
+
+        self.with_state_mut(|st| st.ops.finish(&msg_id));
+        self.with_tracker_mut(|tr| {
+            tr.remove_msg_status(&msg_id);
+        });
+
+        Ok(PairEvent::Withdrawn {
+            user_id: sender,
+            token,
+            amount,
+        })
+    }
+
+    /// Swaps from the caller's internal balance into it.
+    pub fn swap_internal_core(
+        &self,
+        swap_type: SwapType,
+        is_token0_to_token1: bool,
+        deadline: u64,
+    ) -> Result<PairEvent, PairError> {
+        let sender = msg::source();
+
+        let quote = self.with_state_mut(|st| -> Result<_, PairError> {
+            check_internal(st, deadline)?;
+            let quote = quote_swap(st, swap_type, is_token0_to_token1, st.swap_fee_bps)?;
+            st.vault
+                .debit(sender, is_token0_to_token1, quote.amount_in_total)?;
+            settle_swap(st, &quote, is_token0_to_token1)?;
+            st.vault
+                .credit(sender, !is_token0_to_token1, quote.amount_out)?;
+            Ok(quote)
+        })?;
+
+        Ok(PairEvent::Swap {
+            user_id: sender,
+            amount_in: quote.amount_in_total,
+            amount_out: quote.amount_out,
+            is_token0_to_token1,
+        })
+    }
+
+    /// Adds liquidity from the caller's internal balance, the LP tokens are minted to the caller.
+    pub fn add_liquidity_internal_core(
+        &self,
+        amount_a_desired: U256,
+        amount_b_desired: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        deadline: u64,
+    ) -> Result<PairEvent, PairError> {
+        let sender = msg::source();
+        if amount_a_desired.is_zero() || amount_b_desired.is_zero() {
+            return Err(PairError::ZeroLiquidity);
+        }
+
+        let mut lp = self.lp_service();
+        self.with_state_mut(|st| {
+            check_internal(st, deadline)?;
+            let (amount_a, amount_b) = amm_math::calculate_optimal_amounts(
+                st.reserve0,
+                st.reserve1,
+                amount_a_desired,
+                amount_b_desired,
+                amount_a_min,
+                amount_b_min,
+            )?;
+            st.vault.debit(sender, true, amount_a)?;
+            st.vault.debit(sender, false, amount_b)?;
+            let liquidity = settle_add_liquidity(st, &mut lp, amount_a, amount_b, sender)?;
+
+            Ok(PairEvent::LiquidityAdded {
+                user_id: sender,
+                amount_a,
+                amount_b,
+                liquidity,
+            })
+        })
+    }
+
+    /// Burns the caller's LP tokens and credits the underlying tokens to its internal balance.
+    pub fn remove_liquidity_internal_core(
+        &self,
+        liquidity: U256,
+        amount_a_min: U256,
+        amount_b_min: U256,
+        deadline: u64,
+    ) -> Result<PairEvent, PairError> {
+        let sender = msg::source();
+        if liquidity.is_zero() {
+            return Err(PairError::ZeroLiquidity);
+        }
+
+        let mut lp = self.lp_service();
+        self.with_state_mut(|st| {
+            check_internal(st, deadline)?;
+            // A swap in flight was priced against the current pool, which must not shrink
+            // before it settles
+            if !st.ops.reserved().is_zero() {
+                return Err(PairError::AnotherTxInProgress);
+            }
+            let (amount_a, amount_b) = settle_remove_liquidity(
+                st,
+                &mut lp,
+                sender,
+                liquidity,
+                amount_a_min,
+                amount_b_min,
+            )?;
+            st.vault.credit(sender, true, amount_a)?;
+            st.vault.credit(sender, false, amount_b)?;
+
+            Ok(PairEvent::LiquidityRemoved {
+                user_id: sender,
+                amount_a,
+                amount_b,
+                liquidity,
+            })
+        })
+    }
+}
+
+/// Checks shared by the operations on internal balances. They don't await, so they only
+/// have to wait for the operations that run alone.
+fn check_internal(st: &State, deadline: u64) -> Result<(), PairError> {
+    if st.migrated {
+        return Err(PairError::PoolMigrated);
+    }
+    if !st.hook.is_zero() {
+        return Err(PairError::UnsupportedWithHook);
+    }
+    if !st.lock.is_free() {
+        return Err(PairError::AnotherTxInProgress);
+    }
+    if exec::block_timestamp() > deadline {
+        return Err(PairError::DeadlineExpired);
+    }
+    Ok(())
+}
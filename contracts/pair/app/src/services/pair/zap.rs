@@ -0,0 +1,332 @@
+use crate::services::pair::funcs::{
+    burn_liquidity, calculate_protocol_fee, mint_fee_lp, mint_liquidity, protocol_fee_at,
+    set_new_k_last,
+};
+use crate::services::pair::{
+    Curve, LockState, PairError, PairEvent, PairService, State, amm_math, lock::LockCtx,
+    msg_tracker::MessageStatus,
+};
+use sails_rs::{
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// Reserves and amounts of a zap, computed up front so the slippage check
+/// happens before any token moves.
+struct ZapPlan {
+    /// Reserves after the internal swap.
+    swapped_reserves: (U256, U256),
+    /// Reserves once the zap is complete.
+    final_reserves: (U256, U256),
+    /// Treasury fee of the internal swap, in the token swapped in.
+    treasury_fee: (U256, U256),
+    /// Part of the input swapped to the other token (zap in).
+    amount_swapped: U256,
+    /// LP minted (zap in) or burnt (zap out).
+    liquidity: U256,
+    /// Tokens sent to the user (zap out).
+    amount_out: U256,
+}
+
+impl<'a> PairService<'a> {
+    /// Swaps the part of `amount` of `token` given by `amm_math::zap_swap_amount` inside
+    /// the pool and adds the rest, together with the swap output, as liquidity.
+    pub async fn add_liquidity_single_core(
+        &self,
+        token: ActorId,
+        amount: U256,
+        min_liquidity: U256,
+        deadline: u64,
+    ) -> Result<PairEvent, PairError> {
+        let sender = msg::source();
+        let msg_id = msg::id();
+        let total_supply = self.lp_service().total_supply().unwrap_or(U256::zero());
+
+        let (plan, config) = self.with_state_mut(|st| {
+            check_zap(st, deadline)?;
+            let is_token0 = token_side(st, token)?;
+            if amount.is_zero() {
+                return Err(PairError::InsufficientAmount);
+            }
+            if total_supply.is_zero() {
+                return Err(PairError::InsufficientLiquidity);
+            }
+            let plan = plan_zap_in(st, is_token0, amount, total_supply)?;
+            if plan.liquidity < min_liquidity {
+                return Err(PairError::InsufficientLiquidityMinted);
+            }
+
+            st.lock = LockState::Busy(LockCtx::AddLiqRefund {
+                user: sender,
+                token,
+                amount,
+            });
+            Ok((plan, st.config.clone()))
+        })?;
+        let _ = self.lp.pause.pause();
+
+        // A failed transfer frees the lock (see `apply_reply`)
+        self.with_tracker_mut(|tr| {
+            tr.insert_msg_status(msg_id, MessageStatus::SendingMsgToTransferTokenIn);
+        });
+        self.transfer_from(token, sender, exec::program_id(), amount, &config, msg_id)
+            .await?;
+
+        let mut lp = self.lp_service();
+        self.with_state_mut(|st| -> Result<(), PairError> {
+            let (swapped0, swapped1) = plan.swapped_reserves;
+            st.set_reserves(swapped0, swapped1);
+            mint_fee_lp(st, &mut lp)?;
+            mint_liquidity(&mut lp, sender, plan.liquidity)?;
+            apply_final(st, &plan)?;
+            st.lock.set_free();
+            Ok(())
+        })?;
+        let _ = self.lp.pause.resume();
+        self.with_tracker_mut(|tr| tr.clear_all());
+
+        Ok(PairEvent::ZapIn {
+            user_id: sender,
+            token,
+            amount_in: amount,
+            amount_swapped: plan.amount_swapped,
+            liquidity: plan.liquidity,
+        })
+    }
+
+    /// Burns `liquidity` and swaps the tokens of the other side into `token` inside the pool,
+    /// sending a single transfer of `token`.
+    pub async fn remove_liquidity_single_core(
+        &self,
+        liquidity: U256,
+        token: ActorId,
+        amount_out_min: U256,
+        deadline: u64,
+    ) -> Result<PairEvent, PairError> {
+        let sender = msg::source();
+        let msg_id = msg::id();
+        if liquidity.is_zero() {
+            return Err(PairError::ZeroLiquidity);
+        }
+        let user_balance = self.lp_service().balance_of(sender).unwrap_or(U256::zero());
+        if user_balance < liquidity {
+            return Err(PairError::InsufficientLiquidity);
+        }
+        let total_supply = self.lp_service().total_supply().unwrap_or(U256::zero());
+        if total_supply.is_zero() {
+            return Err(PairError::InsufficientLiquidity);
+        }
+
+        let (plan, config) = self.with_state_mut(|st| {
+            check_zap(st, deadline)?;
+            let is_token0 = token_side(st, token)?;
+            let plan = plan_zap_out(st, is_token0, liquidity, total_supply)?;
+            if plan.amount_out < amount_out_min {
+                return Err(PairError::InsufficientAmount);
+            }
+
+            st.lock = LockState::Busy(LockCtx::ZapOut {
+                user: sender,
+                token,
+                liquidity,
+                amount: plan.amount_out,
+            });
+            Ok((plan, st.config.clone()))
+        })?;
+        let _ = self.lp.pause.pause();
+
+        // Nothing is burnt before the transfer, so a failure just frees the lock (see `apply_reply`)
+        self.with_tracker_mut(|tr| {
+            tr.insert_msg_status(msg_id, MessageStatus::SendingZapOut);
+        });
+        self.transfer(token, sender, plan.amount_out, &config, msg_id)
+            .await?;
+
+        let mut lp = self.lp_service();
+        self.with_state_mut(|st| -> Result<(), PairError> {
+            mint_fee_lp(st, &mut lp)?;
+            burn_liquidity(&mut lp, sender, liquidity)?;
+            apply_final(st, &plan)?;
+            st.lock.set_free();
+            Ok(())
+        })?;
+        let _ = self.lp.pause.resume();
+        self.with_tracker_mut(|tr| tr.clear_all());
+
+        Ok(PairEvent::ZapOut {
+            user_id: sender,
+            token,
+            liquidity,
+            amount_out: plan.amount_out,
+        })
+    }
+}
+
+fn check_zap(st: &State, deadline: u64) -> Result<(), PairError> {
+    if st.migrated {
+        return Err(PairError::PoolMigrated);
+    }
+    if !st.hook.is_zero() {
+        return Err(PairError::UnsupportedWithHook);
+    }
+    if exec::gas_available() < st.config.gas_for_full_tx {
+        return Err(PairError::NotEnoghAttachedGas);
+    }
+    if !st.is_idle() {
+        return Err(PairError::AnotherTxInProgress);
+    }
+    if exec::block_timestamp() > deadline {
+        return Err(PairError::DeadlineExpired);
+    }
+    Ok(())
+}
+
+/// Returns whether `token` is token0 of the pair.
+pub(super) fn token_side(st: &State, token: ActorId) -> Result<bool, PairError> {
+    if token == st.token0 {
+        Ok(true)
+    } else if token == st.token1 {
+        Ok(false)
+    } else {
+        Err(PairError::InvalidToken)
+    }
+}
+
+/// Orders `(in, out)` values as `(token0, token1)`.
+fn ordered<T>(is_token0_in: bool, value_in: T, value_out: T) -> (T, T) {
+    if is_token0_in {
+        (value_in, value_out)
+    } else {
+        (value_out, value_in)
+    }
+}
+
+fn plan_zap_in(
+    st: &State,
+    is_token0: bool,
+    amount: U256,
+    total_supply: U256,
+) -> Result<ZapPlan, PairError> {
+    // The closed-form swap amount only holds for x * y = k
+    if st.curve != Curve::ConstantProduct {
+        return Err(PairError::UnsupportedCurve);
+    }
+    let (reserve_in, reserve_out) = ordered(is_token0, st.reserve0, st.reserve1);
+    let treasury_fee_bps = st.active_treasury_fee_bps();
+
+    let amount_swapped =
+        amm_math::zap_swap_amount(amount, reserve_in, st.swap_fee_bps, treasury_fee_bps)?;
+    let (in_for_pool, amount_out, treasury_fee) = amm_math::get_amount_out_with_treasury(
+        amount_swapped,
+        reserve_in,
+        reserve_out,
+        st.swap_fee_bps,
+        treasury_fee_bps,
+    )?;
+
+    let swapped_in = reserve_in
+        .checked_add(in_for_pool)
+        .ok_or(PairError::Overflow)?;
+    let swapped_out = reserve_out
+        .checked_sub(amount_out)
+        .ok_or(PairError::InsufficientLiquidity)?;
+    let (swapped0, swapped1) = ordered(is_token0, swapped_in, swapped_out);
+
+    // Same LP supply the deposit sees after `mint_fee_lp` at the swapped reserves
+    let protocol_fee = protocol_fee_at(st, swapped0, swapped1, total_supply)?;
+    let deposit_in = amount - amount_swapped;
+    let liquidity = amm_math::calculate_liquidity(
+        swapped_in,
+        swapped_out,
+        deposit_in,
+        amount_out,
+        total_supply + protocol_fee,
+    )?;
+
+    // The deposit returns the swap output, so only the input side grows
+    let final_in = swapped_in
+        .checked_add(deposit_in)
+        .ok_or(PairError::Overflow)?;
+
+    Ok(ZapPlan {
+        swapped_reserves: (swapped0, swapped1),
+        final_reserves: ordered(is_token0, final_in, reserve_out),
+        treasury_fee: ordered(is_token0, treasury_fee, U256::zero()),
+        amount_swapped,
+        liquidity,
+        amount_out: U256::zero(),
+    })
+}
+
+fn plan_zap_out(
+    st: &State,
+    is_token0: bool,
+    liquidity: U256,
+    total_supply: U256,
+) -> Result<ZapPlan, PairError> {
+    // Proportional share, as in `remove_liquidity`
+    let supply = total_supply + calculate_protocol_fee(st, total_supply)?;
+    let amount0 = liquidity
+        .checked_mul(st.reserve0)
+        .ok_or(PairError::Overflow)?
+        / supply;
+    let amount1 = liquidity
+        .checked_mul(st.reserve1)
+        .ok_or(PairError::Overflow)?
+        / supply;
+    if amount0.is_zero() || amount1.is_zero() {
+        return Err(PairError::InsufficientLiquidityBurned);
+    }
+    let removed0 = st.reserve0 - amount0;
+    let removed1 = st.reserve1 - amount1;
+
+    // The other side is swapped into `token` against the reserves left after the burn
+    let (amount_kept, amount_other) = ordered(is_token0, amount0, amount1);
+    let (reserve_kept, reserve_other) = ordered(is_token0, removed0, removed1);
+    let (in_for_pool, swap_out, treasury_fee) = st.curve.get_amount_out_with_treasury(
+        amount_other,
+        reserve_other,
+        reserve_kept,
+        st.swap_fee_bps,
+        st.active_treasury_fee_bps(),
+        exec::block_timestamp(),
+    )?;
+
+    let final_kept = reserve_kept
+        .checked_sub(swap_out)
+        .ok_or(PairError::InsufficientLiquidity)?;
+    let final_other = reserve_other
+        .checked_add(in_for_pool)
+        .ok_or(PairError::Overflow)?;
+
+    Ok(ZapPlan {
+        swapped_reserves: (removed0, removed1),
+        final_reserves: ordered(is_token0, final_kept, final_other),
+        treasury_fee: ordered(is_token0, U256::zero(), treasury_fee),
+        amount_swapped: amount_other,
+        liquidity,
+        amount_out: amount_kept
+            .checked_add(swap_out)
+            .ok_or(PairError::Overflow)?,
+    })
+}
+
+fn apply_final(st: &mut State, plan: &ZapPlan) -> Result<(), PairError> {
+    let (reserve0, reserve1) = plan.final_reserves;
+    st.set_reserves(reserve0, reserve1);
+
+    let (fee0, fee1) = plan.treasury_fee;
+    st.accrued_treasury_fee0 = st
+        .accrued_treasury_fee0
+        .checked_add(fee0)
+        .ok_or(PairError::Overflow)?;
+    st.accrued_treasury_fee1 = st
+        .accrued_treasury_fee1
+        .checked_add(fee1)
+        .ok_or(PairError::Overflow)?;
+
+    if !st.fee_to.is_zero() {
+        set_new_k_last(st)?;
+    }
+    Ok(())
+}
@@ -1,4 +1,7 @@
 mod add_liquidity;
 mod first_liquidity;
 mod migration;
+mod recipient;
+mod reconcile;
 mod remove_liquidity;
+mod zap;
@@ -0,0 +1,69 @@
+use crate::*;
+
+#[tokio::test]
+async fn test_liquidity_to_recipient() {
+    let treasury_id = ActorId::zero();
+    let mut env = TestEnv::new(treasury_id).await;
+    let user = ACTOR_ID.into();
+    let recipient = ActorId::from(TRADER_2);
+
+    let amount = medium_amount();
+    env.setup_user(ACTOR_ID, amount).await;
+
+    // LP is minted to the recipient, tokens are taken from the caller
+    env.pair
+        .add_liquidity_to(
+            amount,
+            amount,
+            U256::zero(),
+            U256::zero(),
+            recipient,
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(user))
+        .await
+        .unwrap();
+
+    let (user_a, user_b, user_lp) = env.get_balances(user).await;
+    assert!(user_a.is_zero() && user_b.is_zero() && user_lp.is_zero());
+    let (_, _, lp) = env.get_balances(recipient).await;
+    assert_eq!(lp, amount - U256::from(MINIMUM_LIQUIDITY));
+
+    // The recipient burns its LP and sends the tokens back to the user
+    let to_remove = lp / U256::from(2);
+    let (reserve_a, reserve_b) = env.get_reserves().await;
+    let total_supply = env.get_total_supply().await;
+    env.env.system().mint_to(TRADER_2, 1_000_000_000_000_000);
+    env.pair
+        .remove_liquidity_to(
+            to_remove,
+            U256::zero(),
+            U256::zero(),
+            user,
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(recipient))
+        .await
+        .unwrap();
+
+    let (user_a, user_b, _) = env.get_balances(user).await;
+    assert_eq!(user_a, to_remove * reserve_a / total_supply);
+    assert_eq!(user_b, to_remove * reserve_b / total_supply);
+    let (recipient_a, recipient_b, recipient_lp) = env.get_balances(recipient).await;
+    assert!(recipient_a.is_zero() && recipient_b.is_zero());
+    assert_eq!(recipient_lp, lp - to_remove);
+
+    // Tokens can't be sent to the zero address
+    let res = env
+        .pair
+        .remove_liquidity_to(
+            U256::one(),
+            U256::zero(),
+            U256::zero(),
+            ActorId::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(recipient))
+        .await;
+    assert!(res.is_err());
+}
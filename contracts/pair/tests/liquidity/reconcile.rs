@@ -0,0 +1,83 @@
+use crate::*;
+use extended_vft_client::vft::Vft;
+use pair_client::TokenAudit;
+
+#[tokio::test]
+async fn test_audit_skim_and_sync_direct_transfers() {
+    let treasury_id = ActorId::zero();
+    let mut env = TestEnv::new(treasury_id).await;
+    let admin: ActorId = ACTOR_ID.into();
+    let donor = ActorId::from(TRADER_1);
+    let recipient = ActorId::from(TRADER_2);
+
+    let amount = medium_amount();
+    let donation = small_amount();
+    env.setup_user(ACTOR_ID, amount).await;
+    env.setup_user(TRADER_1, donation * 2).await;
+    setup_initial_liquidity(&mut env, admin, amount, amount).await;
+    let pair_id = env.pair.actor_id();
+
+    // Tokens sent to the pair directly are invisible to the reserves
+    env.token_a
+        .transfer(pair_id, donation)
+        .with_params(|args| args.with_actor_id(donor))
+        .await
+        .unwrap();
+    let (audit0, audit1) = env
+        .pair
+        .audit()
+        .with_params(|args| args.with_actor_id(donor))
+        .await
+        .unwrap();
+    assert_eq!(
+        audit0,
+        TokenAudit {
+            token: env.token_a.actor_id(),
+            balance: amount + donation,
+            reserve: amount,
+            accrued_treasury_fee: U256::zero(),
+            deposits: U256::zero(),
+            excess: donation,
+            shortfall: U256::zero(),
+        }
+    );
+    assert!(audit1.excess.is_zero() && audit1.shortfall.is_zero());
+
+    // Only the admin can skim
+    let res = env
+        .pair
+        .skim(donor)
+        .with_params(|args| args.with_actor_id(donor))
+        .await;
+    assert!(res.is_err());
+    env.pair
+        .skim(recipient)
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+    let (recipient_a, _, _) = env.get_balances(recipient).await;
+    assert_eq!(recipient_a, donation);
+    assert_eq!(env.get_reserves().await, (amount, amount));
+
+    // Anyone can sync a donation into the reserves
+    env.token_b
+        .transfer(pair_id, donation)
+        .with_params(|args| args.with_actor_id(donor))
+        .await
+        .unwrap();
+    env.pair
+        .sync()
+        .with_params(|args| args.with_actor_id(donor))
+        .await
+        .unwrap();
+    assert_eq!(env.get_reserves().await, (amount, amount + donation));
+
+    let (audit0, audit1) = env
+        .pair
+        .audit()
+        .with_params(|args| args.with_actor_id(donor))
+        .await
+        .unwrap();
+    assert!(audit0.excess.is_zero() && audit1.excess.is_zero());
+    assert_eq!(env.pair.lock().await.unwrap(), pair_client::LockState::Free);
+}
@@ -0,0 +1,77 @@
+use crate::*;
+
+#[tokio::test]
+async fn test_zap_in_and_out_single_token() {
+    let treasury_id = ActorId::zero();
+    let mut env = TestEnv::new(treasury_id).await;
+    let admin: ActorId = ACTOR_ID.into();
+    let user = ActorId::from(TRADER_1);
+
+    let amount = medium_amount();
+    let zap_amount = small_amount();
+    env.setup_user(ACTOR_ID, amount).await;
+    env.setup_user(TRADER_1, zap_amount).await;
+    setup_initial_liquidity(&mut env, admin, amount, amount).await;
+    let token_a = env.token_a.actor_id();
+    let token_b = env.token_b.actor_id();
+
+    // Slippage check happens before any token moves
+    let res = env
+        .pair
+        .add_liquidity_single(token_a, zap_amount, U256::MAX, env.get_deadline())
+        .with_params(|args| args.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    // Only pair tokens can be zapped
+    let res = env
+        .pair
+        .add_liquidity_single(user, zap_amount, U256::zero(), env.get_deadline())
+        .with_params(|args| args.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    // Zap in: the whole input stays in the pool, the swap output is deposited back
+    let (reserve_a, reserve_b) = env.get_reserves().await;
+    let total_supply = env.get_total_supply().await;
+    let liquidity = env
+        .pair
+        .add_liquidity_single(token_a, zap_amount, U256::zero(), env.get_deadline())
+        .with_params(|args| args.with_actor_id(user))
+        .await
+        .unwrap();
+
+    let (user_a, user_b, user_lp) = env.get_balances(user).await;
+    assert!(user_a.is_zero());
+    assert_eq!(user_b, zap_amount);
+    assert_eq!(user_lp, liquidity);
+    assert_eq!(
+        env.get_reserves().await,
+        (reserve_a + zap_amount, reserve_b)
+    );
+    assert_eq!(env.get_total_supply().await, total_supply + liquidity);
+
+    // Fees and price impact keep the zap just below a two-sided deposit of the same value
+    let two_sided = zap_amount * total_supply / (reserve_a * U256::from(2));
+    assert!(liquidity < two_sided);
+    assert!(liquidity > two_sided * U256::from(99) / U256::from(100));
+
+    // Zap out into token B: the token A share is swapped inside the pool
+    let (reserve_a, reserve_b) = env.get_reserves().await;
+    let total_supply = env.get_total_supply().await;
+    let amount_out = env
+        .pair
+        .remove_liquidity_single(liquidity, token_b, U256::zero(), env.get_deadline())
+        .with_params(|args| args.with_actor_id(user))
+        .await
+        .unwrap();
+
+    let (user_a, user_b, user_lp) = env.get_balances(user).await;
+    assert!(user_a.is_zero());
+    assert!(user_lp.is_zero());
+    assert_eq!(user_b, zap_amount + amount_out);
+    let share_b = liquidity * reserve_b / total_supply;
+    assert!(amount_out > share_b);
+    assert_eq!(env.get_reserves().await.1, reserve_b - amount_out);
+    assert_eq!(env.get_total_supply().await, total_supply - liquidity);
+}
@@ -1,6 +1,6 @@
 use crate::recovery::{deploy_pair_with_mocks, vft_ok_t, vft_ok_tf, Deployed};
 use crate::{recovery::vft_no_t, *};
-use pair_client::vft::Vft;
+use pair_client::{vft::Vft, LockCtx, LockState, SendTokenStage};
 #[tokio::test]
 async fn add_liquidity_failed_on_token0_does_not_leave_lp_paused_forever() {
     let treasury_id = ActorId::zero();
@@ -100,18 +100,30 @@ async fn send_treasury_fee_first_transfer_fail_pause_state_expected() {
     assert!(!fee0_before.is_zero(), "fee0 must be > 0 after A->B swap");
     assert!(!fee1_before.is_zero(), "fee1 must be > 0 after B->A swap");
 
-    // payout should fail on token1 transfer and pause
+    // payout should fail on token0 transfer and pause the payout
     let res = pair
         .send_treasury_fees()
         .with_params(|p| p.with_actor_id(treasury_id)) // caller must be treasury_id
         .await;
     assert!(res.is_err());
 
-    assert!(lp_vft.is_paused().await.unwrap());
+    assert!(!lp_vft.is_paused().await.unwrap());
 
+    // The fees are taken out up front and owed by the paused payout
     let (_, fee0_after, fee1_after) = pair.get_treasury_info().await.unwrap();
-    assert_eq!(fee0_before, fee0_after);
-    assert_eq!(fee1_before, fee1_after);
+    assert!(fee0_after.is_zero() && fee1_after.is_zero());
+
+    let ops = pair.operations().await.unwrap();
+    assert_eq!(ops.len(), 1);
+    assert_eq!(
+        ops[0].1,
+        LockState::Paused(LockCtx::TreasuryPayout {
+            treasury: treasury_id,
+            amount0: fee0_before,
+            amount1: fee1_before,
+            stage: SendTokenStage::SendToken0,
+        })
+    );
 }
 
 #[tokio::test]
@@ -0,0 +1,138 @@
+use crate::recovery::*;
+
+const BORROWER_ID: u64 = 200;
+
+/// Deploys a borrower whose `OnFlashSwap` callback just replies;
+/// the token mocks script whether the loan shows up as repaid.
+fn deploy_borrower(env: &GtestEnv) -> ActorId {
+    let borrower = Program::mock_with_id(env.system(), BORROWER_ID, TokenMock::new(vec![vec![]]));
+    let init = borrower.send_bytes(ACTOR_ID, b"init");
+    let r = env.system().run_next_block();
+    assert!(r.succeed.contains(&init));
+    BORROWER_ID.into()
+}
+
+#[tokio::test]
+async fn flash_swap_repaid_in_callback() {
+    let system = System::new();
+    system.mint_to(ACTOR_ID, 1_000_000_000_000_000);
+    let user = ACTOR_ID.into();
+    let amount = medium_amount();
+    let borrowed = U256::from(9_970u64);
+    // borrowed * 10_000 / (10_000 - 30)
+    let repaid = U256::from(10_000u64);
+
+    let token_a = vec![
+        vft_ok_tf(),                             // add_liq: tokenA transfer_from
+        vft_ok_t(),                              // flash: tokenA lent to the borrower
+        vft_balance(amount - borrowed + repaid), // flash: tokenA balance after the callback
+    ];
+    let token_b = vec![
+        vft_ok_tf(),         // add_liq: tokenB transfer_from
+        vft_balance(amount), // flash: tokenB balance after the callback
+    ];
+
+    let Deployed {
+        env,
+        mut pair,
+        lp_vft,
+        ..
+    } = deploy_pair_with_mocks(system, token_a, token_b).await;
+    let borrower = deploy_borrower(&env);
+
+    let deadline = env.system().block_timestamp() + 10_000;
+    pair.add_liquidity(amount, amount, amount / 2, amount / 2, deadline)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+
+    // Nothing to borrow, or more than the pool has
+    assert!(pair
+        .flash_swap(U256::zero(), U256::zero(), borrower, vec![])
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .is_err());
+    assert!(pair
+        .flash_swap(amount, U256::zero(), borrower, vec![])
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .is_err());
+
+    pair.flash_swap(borrowed, U256::zero(), borrower, b"arbitrage".to_vec())
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+
+    assert_free(&pair).await;
+    assert!(!lp_vft.is_paused().await.unwrap());
+    assert_eq!(
+        pair.get_reserves().await.unwrap(),
+        (amount - borrowed + repaid, amount)
+    );
+}
+
+#[tokio::test]
+async fn flash_swap_default_is_recovered() {
+    let system = System::new();
+    system.mint_to(ACTOR_ID, 1_000_000_000_000_000);
+    let user = ACTOR_ID.into();
+    let admin = ACTOR_ID.into();
+    let amount = medium_amount();
+    let borrowed = U256::from(9_970u64);
+
+    let token_a = vec![
+        vft_ok_tf(),                    // add_liq: tokenA transfer_from
+        vft_ok_t(),                     // flash: tokenA lent to the borrower
+        vft_balance(amount - borrowed), // flash: nothing paid back
+        vft_balance(amount - borrowed), // recovery: still nothing paid back
+    ];
+    let token_b = vec![
+        vft_ok_tf(),         // add_liq: tokenB transfer_from
+        vft_balance(amount), // flash: tokenB balance after the callback
+        vft_balance(amount), // recovery: tokenB balance
+    ];
+
+    let Deployed {
+        env,
+        mut pair,
+        lp_vft,
+        ..
+    } = deploy_pair_with_mocks(system, token_a, token_b).await;
+    let borrower = deploy_borrower(&env);
+
+    let deadline = env.system().block_timestamp() + 10_000;
+    pair.add_liquidity(amount, amount, amount / 2, amount / 2, deadline)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+
+    // The loan is not repaid: the pair pauses instead of reverting the transfer
+    pair.flash_swap(borrowed, U256::zero(), borrower, vec![])
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_paused(
+        &pair,
+        LockState::Paused(LockCtx::FlashSwap {
+            borrower,
+            amount0_out: borrowed,
+            amount1_out: U256::zero(),
+        }),
+    )
+    .await;
+    assert!(lp_vft.is_paused().await.unwrap());
+    assert_eq!(pair.get_reserves().await.unwrap(), (amount, amount));
+
+    // Recovery writes the reserves down to the pool balances
+    pair.recover_paused()
+        .with_params(|p| p.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    assert_free(&pair).await;
+    assert!(!lp_vft.is_paused().await.unwrap());
+    assert_eq!(
+        pair.get_reserves().await.unwrap(),
+        (amount - borrowed, amount)
+    );
+}
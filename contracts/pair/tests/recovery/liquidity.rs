@@ -37,24 +37,24 @@ async fn add_liquidity_recovery() {
 
     assert!(res.is_err());
 
-    assert_paused(
-        &pair,
-        LockState::Paused(LockCtx::AddLiqRefund {
+    let (op_id, ctx) = paused_operation(&pair).await;
+    assert_eq!(
+        ctx,
+        LockCtx::AddLiqRefund {
             user,
             token: token_a_id.into(),
             amount,
-        }),
-    )
-    .await;
-
-    assert!(lp_vft.is_paused().await.unwrap());
+        }
+    );
+    // Only the operation is paused, the pair and its LP token stay usable
+    assert_free(&pair).await;
+    assert!(!lp_vft.is_paused().await.unwrap());
 
-    pair.recover_paused()
+    pair.recover_operation(op_id)
         .with_params(|p| p.with_actor_id(user))
         .await
         .unwrap();
-    assert_free(&pair).await;
-    assert!(!lp_vft.is_paused().await.unwrap());
+    assert_no_operations(&pair).await;
 }
 
 #[tokio::test]
@@ -99,31 +99,37 @@ async fn remove_liquidity_recovery_unlock_b() {
 
     assert!(res.is_err());
 
-    assert_paused(
-        &pair,
-        LockState::Paused(LockCtx::RemLiq {
+    let (op_id, ctx) = paused_operation(&pair).await;
+    assert_eq!(
+        ctx,
+        LockCtx::RemLiq {
             user,
+            to: user,
             liquidity: lp,
             amount_a,
             amount_b,
             stage: SendTokenStage::SendToken1,
-        }),
-    )
-    .await;
-
-    assert!(lp_vft.is_paused().await.unwrap());
-
-    pair.recover_paused()
-        .with_params(|p| p.with_actor_id(user))
-        .await
-        .unwrap();
+        }
+    );
+    // Only the operation is paused, the pair and its LP token stay usable
+    assert_free(&pair).await;
+    assert!(!lp_vft.is_paused().await.unwrap());
 
+    // The removal settled before the payouts
     let (reserve_0_after, reserve_1_after) = pair.get_reserves().await.unwrap();
     assert_eq!(reserve_0_after + amount_a, reserve_0_before);
     assert_eq!(reserve_1_after + amount_b, reserve_1_before);
 
     let lp_after = lp_vft.balance_of(user).await.unwrap();
     assert!(lp_after.is_zero());
-    assert_free(&pair).await;
-    assert!(!lp_vft.is_paused().await.unwrap());
+
+    pair.recover_operation(op_id)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_no_operations(&pair).await;
+    assert_eq!(
+        pair.get_reserves().await.unwrap(),
+        (reserve_0_after, reserve_1_after)
+    );
 }
@@ -1,6 +1,7 @@
 use crate::*;
 use gtest::{Program, WasmProgram};
 use pair_client::{vft::VftImpl, LockCtx, LockState, Pair as PairClient, PairCtors};
+mod flash;
 mod liquidity;
 mod migration;
 mod swap;
@@ -51,6 +52,20 @@ async fn assert_free(pair: &Service<PairImpl, GtestEnv>) {
     assert_eq!(lock, LockState::Free);
 }
 
+/// Id and context of the only operation of the pair, which must be paused.
+async fn paused_operation(pair: &Service<PairImpl, GtestEnv>) -> (MessageId, LockCtx) {
+    let ops = pair.operations().await.unwrap();
+    assert_eq!(ops.len(), 1);
+    match ops[0].clone() {
+        (op_id, LockState::Paused(ctx)) => (op_id, ctx),
+        (_, state) => panic!("operation is not paused: {state:?}"),
+    }
+}
+
+async fn assert_no_operations(pair: &Service<PairImpl, GtestEnv>) {
+    assert!(pair.operations().await.unwrap().is_empty());
+}
+
 pub struct Deployed {
     pub env: GtestEnv,
     pub pair: Service<PairImpl, GtestEnv>,
@@ -103,6 +118,7 @@ pub async fn deploy_pair_with_mocks(
         gas_for_reply_deposit: 20_000_000_000,
         reply_timeout: 100,
         gas_for_full_tx: 100_000_000_000,
+        swap_fee_bps: 30,
     };
 
     let pair_program = env
@@ -169,6 +185,7 @@ async fn swap_recovery_exact_input() {
         gas_for_reply_deposit: 20_000_000_000,
         reply_timeout: 100,
         gas_for_full_tx: 100_000_000_000,
+        swap_fee_bps: 30,
     };
     let pair_program = env
         .deploy::<pair_client::PairProgram>(program_code_id, b"salt".to_vec())
@@ -213,22 +230,22 @@ async fn swap_recovery_exact_input() {
         .await;
     assert!(
         result.is_err(),
-        "swap_exact_tokens_for_tokens should fail and put contract into paused state"
+        "swap_exact_tokens_for_tokens should fail and pause its operation"
     );
-    let lock = pair.lock().await.unwrap();
-    let exp_lock = LockState::Paused(LockCtx::SwapRefund {
+    let (op_id, ctx) = paused_operation(&pair).await;
+    let exp_ctx = LockCtx::SwapRefund {
         user,
         token: token_a_id.into(),
         amount: amount_in,
-    });
-    assert_eq!(lock, exp_lock);
+    };
+    assert_eq!(ctx, exp_ctx);
+    assert_free(&pair).await;
 
-    pair.recover_paused()
+    pair.recover_operation(op_id)
         .with_params(|args| args.with_actor_id(user))
         .await
         .unwrap();
-    let lock = pair.lock().await.unwrap();
-    assert_eq!(lock, LockState::Free);
+    assert_no_operations(&pair).await;
 }
 
 #[tokio::test]
@@ -273,6 +290,7 @@ async fn swap_recovery_exact_output() {
         gas_for_reply_deposit: 20_000_000_000,
         reply_timeout: 100,
         gas_for_full_tx: 100_000_000_000,
+        swap_fee_bps: 30,
     };
     let pair_program = env
         .deploy::<pair_client::PairProgram>(program_code_id, b"salt".to_vec())
@@ -319,20 +337,20 @@ async fn swap_recovery_exact_output() {
         .await;
     assert!(
         result.is_err(),
-        "swap_exact_tokens_for_tokens should fail and put contract into paused state"
+        "swap_exact_tokens_for_tokens should fail and pause its operation"
     );
-    let lock = pair.lock().await.unwrap();
-    let exp_lock = LockState::Paused(LockCtx::SwapRefund {
+    let (op_id, ctx) = paused_operation(&pair).await;
+    let exp_ctx = LockCtx::SwapRefund {
         user,
         token: token_a_id.into(),
         amount,
-    });
-    assert_eq!(lock, exp_lock);
+    };
+    assert_eq!(ctx, exp_ctx);
+    assert_free(&pair).await;
 
-    pair.recover_paused()
+    pair.recover_operation(op_id)
         .with_params(|args| args.with_actor_id(user))
         .await
         .unwrap();
-    let lock = pair.lock().await.unwrap();
-    assert_eq!(lock, LockState::Free);
+    assert_no_operations(&pair).await;
 }
@@ -43,24 +43,24 @@ async fn swap_recovery_exact_input_a_to_b() {
 
     assert!(res.is_err());
 
-    assert_paused(
-        &pair,
-        LockState::Paused(LockCtx::SwapRefund {
+    let (op_id, ctx) = paused_operation(&pair).await;
+    assert_eq!(
+        ctx,
+        LockCtx::SwapRefund {
             user,
             token: token_a_id.into(),
             amount: amount_in,
-        }),
-    )
-    .await;
-
-    assert!(lp_vft.is_paused().await.unwrap());
+        }
+    );
+    // Only the operation is paused, the pair and its LP token stay usable
+    assert_free(&pair).await;
+    assert!(!lp_vft.is_paused().await.unwrap());
 
-    pair.recover_paused()
+    pair.recover_operation(op_id)
         .with_params(|p| p.with_actor_id(user))
         .await
         .unwrap();
-    assert_free(&pair).await;
-    assert!(!lp_vft.is_paused().await.unwrap());
+    assert_no_operations(&pair).await;
 }
 
 #[tokio::test]
@@ -104,24 +104,24 @@ async fn swap_recovery_exact_input_b_to_a() {
 
     assert!(res.is_err());
 
-    assert_paused(
-        &pair,
-        LockState::Paused(LockCtx::SwapRefund {
+    let (op_id, ctx) = paused_operation(&pair).await;
+    assert_eq!(
+        ctx,
+        LockCtx::SwapRefund {
             user,
             token: token_b_id.into(),
             amount: amount_in,
-        }),
-    )
-    .await;
-
-    assert!(lp_vft.is_paused().await.unwrap());
+        }
+    );
+    // Only the operation is paused, the pair and its LP token stay usable
+    assert_free(&pair).await;
+    assert!(!lp_vft.is_paused().await.unwrap());
 
-    pair.recover_paused()
+    pair.recover_operation(op_id)
         .with_params(|p| p.with_actor_id(user))
         .await
         .unwrap();
-    assert_free(&pair).await;
-    assert!(!lp_vft.is_paused().await.unwrap());
+    assert_no_operations(&pair).await;
 }
 
 #[tokio::test]
@@ -164,24 +164,24 @@ async fn swap_recovery_exact_output_a_to_b() {
 
     assert!(res.is_err());
 
-    assert_paused(
-        &pair,
-        LockState::Paused(LockCtx::SwapRefund {
+    let (op_id, ctx) = paused_operation(&pair).await;
+    assert_eq!(
+        ctx,
+        LockCtx::SwapRefund {
             user,
             token: token_a_id.into(),
             amount: amount_in_total,
-        }),
-    )
-    .await;
-
-    assert!(lp_vft.is_paused().await.unwrap());
+        }
+    );
+    // Only the operation is paused, the pair and its LP token stay usable
+    assert_free(&pair).await;
+    assert!(!lp_vft.is_paused().await.unwrap());
 
-    pair.recover_paused()
+    pair.recover_operation(op_id)
         .with_params(|p| p.with_actor_id(user))
         .await
         .unwrap();
-    assert_free(&pair).await;
-    assert!(!lp_vft.is_paused().await.unwrap());
+    assert_no_operations(&pair).await;
 }
 
 #[tokio::test]
@@ -224,22 +224,22 @@ async fn swap_recovery_exact_output_b_to_a() {
 
     assert!(res.is_err());
 
-    assert_paused(
-        &pair,
-        LockState::Paused(LockCtx::SwapRefund {
+    let (op_id, ctx) = paused_operation(&pair).await;
+    assert_eq!(
+        ctx,
+        LockCtx::SwapRefund {
             user,
             token: token_b_id.into(),
             amount: amount_in_total,
-        }),
-    )
-    .await;
-
-    assert!(lp_vft.is_paused().await.unwrap());
+        }
+    );
+    // Only the operation is paused, the pair and its LP token stay usable
+    assert_free(&pair).await;
+    assert!(!lp_vft.is_paused().await.unwrap());
 
-    pair.recover_paused()
+    pair.recover_operation(op_id)
         .with_params(|p| p.with_actor_id(user))
         .await
         .unwrap();
-    assert_free(&pair).await;
-    assert!(!lp_vft.is_paused().await.unwrap());
+    assert_no_operations(&pair).await;
 }
@@ -86,26 +86,26 @@ async fn treasury_payout_recovery_send_token1() {
         .await;
     assert!(res.is_err());
 
-    assert_paused(
-        &pair,
-        LockState::Paused(LockCtx::TreasuryPayout {
+    let (op_id, ctx) = paused_operation(&pair).await;
+    assert_eq!(
+        ctx,
+        LockCtx::TreasuryPayout {
             treasury: treasury_id,
             amount0: fee0,
             amount1: fee1,
             stage: SendTokenStage::SendToken1,
-        }),
-    )
-    .await;
-
-    assert!(lp_vft.is_paused().await.unwrap());
+        }
+    );
+    // Only the operation is paused, the pair and its LP token stay usable
+    assert_free(&pair).await;
+    assert!(!lp_vft.is_paused().await.unwrap());
 
-    // recovery (your recover_paused requires admin)
-    pair.recover_paused()
+    pair.recover_operation(op_id)
         .with_params(|p| p.with_actor_id(admin))
         .await
         .unwrap();
 
-    assert_free(&pair).await;
+    assert_no_operations(&pair).await;
 
     // After successful recovery accrued fees must be cleared
     let (_, fee0_after, fee1_after) = pair.get_treasury_info().await.unwrap();
@@ -113,5 +113,4 @@ async fn treasury_payout_recovery_send_token1() {
         fee0_after.is_zero() && fee1_after.is_zero(),
         "treasury fees must be cleared after recovery"
     );
-    assert!(!lp_vft.is_paused().await.unwrap());
 }
@@ -0,0 +1,176 @@
+use crate::*;
+use pair_client::pair::io;
+
+/// Sends `payload` to the pair without waiting for the reply, so that several
+/// operations are in flight within the same block.
+fn send_to_pair(env: &TestEnv, from: u64, payload: Vec<u8>) -> MessageId {
+    let program = env.env.system().get_program(env.pair.actor_id()).unwrap();
+    program.send_bytes(from, payload)
+}
+
+async fn setup_pool(env: &mut TestEnv) {
+    let liquidity_amount = large_amount();
+    env.setup_user(ACTOR_ID, liquidity_amount * 2).await;
+    env.setup_user(TRADER_1, liquidity_amount).await;
+    env.setup_user(TRADER_2, liquidity_amount).await;
+
+    env.pair
+        .add_liquidity(
+            liquidity_amount,
+            liquidity_amount,
+            liquidity_amount / U256::from(2),
+            liquidity_amount / U256::from(2),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(ACTOR_ID.into()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn overlapping_swaps_price_as_if_sequential() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    setup_pool(&mut env).await;
+
+    let (reserve_a, reserve_b) = env.get_reserves().await;
+    let amount_in = calculate_swap_amount_from_percent(reserve_a, 1);
+    let (_, balance_b_1, _) = env.get_balances(TRADER_1.into()).await;
+    let (_, balance_b_2, _) = env.get_balances(TRADER_2.into()).await;
+
+    let payload = io::SwapExactTokensForTokens::encode_params_with_prefix(
+        "Pair",
+        amount_in,
+        U256::zero(),
+        true,
+        env.get_deadline(),
+    );
+    let first = send_to_pair(&env, TRADER_1, payload.clone());
+    let second = send_to_pair(&env, TRADER_2, payload);
+    let result = env.env.system().run_next_block();
+    assert!(result.succeed.contains(&first));
+    assert!(result.succeed.contains(&second));
+
+    // The second swap is priced with the first one's amounts reserved
+    let out_first = SwapCalculator::calculate_exact_output(amount_in, reserve_a, reserve_b);
+    let out_second = SwapCalculator::calculate_exact_output(
+        amount_in,
+        reserve_a + amount_in,
+        reserve_b - out_first,
+    );
+    let (_, balance_b_1_after, _) = env.get_balances(TRADER_1.into()).await;
+    let (_, balance_b_2_after, _) = env.get_balances(TRADER_2.into()).await;
+    assert_eq!(balance_b_1_after - balance_b_1, out_first);
+    assert_eq!(balance_b_2_after - balance_b_2, out_second);
+
+    assert_eq!(
+        env.get_reserves().await,
+        (
+            reserve_a + amount_in * 2,
+            reserve_b - out_first - out_second
+        )
+    );
+    assert!(env.pair.operations().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn removing_liquidity_waits_for_swaps_in_flight() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    setup_pool(&mut env).await;
+
+    let (reserve_a, _) = env.get_reserves().await;
+    let amount_in = calculate_swap_amount_from_percent(reserve_a, 1);
+    let (_, _, lp_balance) = env.get_balances(ACTOR_ID.into()).await;
+
+    let swap = send_to_pair(
+        &env,
+        TRADER_1,
+        io::SwapExactTokensForTokens::encode_params_with_prefix(
+            "Pair",
+            amount_in,
+            U256::zero(),
+            true,
+            env.get_deadline(),
+        ),
+    );
+    let removal = send_to_pair(
+        &env,
+        ACTOR_ID,
+        io::RemoveLiquidity::encode_params_with_prefix(
+            "Pair",
+            lp_balance / U256::from(2),
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        ),
+    );
+    let result = env.env.system().run_next_block();
+    assert!(result.succeed.contains(&swap));
+    assert!(result.failed.contains(&removal));
+
+    // Once the swap settled, the same removal goes through
+    env.pair
+        .remove_liquidity(
+            lp_balance / U256::from(2),
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(ACTOR_ID.into()))
+        .await
+        .unwrap();
+    assert!(env.pair.operations().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn liquidity_added_during_a_swap_keeps_the_new_ratio() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    setup_pool(&mut env).await;
+
+    let (reserve_a, reserve_b) = env.get_reserves().await;
+    let amount_in = calculate_swap_amount_from_percent(reserve_a, 1);
+    let amount = medium_amount();
+    let (balance_a, balance_b, _) = env.get_balances(ACTOR_ID.into()).await;
+
+    let swap = send_to_pair(
+        &env,
+        TRADER_1,
+        io::SwapExactTokensForTokens::encode_params_with_prefix(
+            "Pair",
+            amount_in,
+            U256::zero(),
+            true,
+            env.get_deadline(),
+        ),
+    );
+    let addition = send_to_pair(
+        &env,
+        ACTOR_ID,
+        io::AddLiquidity::encode_params_with_prefix(
+            "Pair",
+            amount,
+            amount,
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        ),
+    );
+    let result = env.env.system().run_next_block();
+    assert!(result.succeed.contains(&swap));
+    assert!(result.succeed.contains(&addition));
+
+    // Taken at the ratio it was sent with, the addition settles after the swap:
+    // only the B matching the new ratio is added, the rest is sent back
+    let out = SwapCalculator::calculate_exact_output(amount_in, reserve_a, reserve_b);
+    let (reserve_a, reserve_b) = (reserve_a + amount_in, reserve_b - out);
+    let added_b = amount * reserve_b / reserve_a;
+    let (balance_a_after, balance_b_after, _) = env.get_balances(ACTOR_ID.into()).await;
+    assert_eq!(
+        (balance_a_after, balance_b_after),
+        (balance_a - amount, balance_b - added_b)
+    );
+    assert_eq!(
+        env.get_reserves().await,
+        (reserve_a + amount, reserve_b + added_b)
+    );
+    assert!(env.pair.operations().await.unwrap().is_empty());
+}
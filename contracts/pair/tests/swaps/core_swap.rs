@@ -0,0 +1,112 @@
+use crate::*;
+use extended_vft_client::vft::Vft;
+use pair_client::LockState;
+
+async fn setup_pool_and_deposit(env: &mut TestEnv, deposit: U256) {
+    let liquidity_amount = large_amount();
+    env.setup_user(ACTOR_ID, liquidity_amount).await;
+    env.setup_user(TRADER_1, medium_amount()).await;
+    setup_initial_liquidity(env, ACTOR_ID.into(), liquidity_amount, liquidity_amount).await;
+
+    env.pair
+        .deposit(env.token_a.actor_id(), deposit)
+        .with_params(|args| args.with_actor_id(TRADER_1.into()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn swap_takes_the_tokens_deposited_first() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let trader: ActorId = TRADER_1.into();
+    let (reserve_a, reserve_b) = (large_amount(), large_amount());
+    let amount_in = calculate_swap_amount_from_percent(reserve_a, 1);
+    setup_pool_and_deposit(&mut env, amount_in).await;
+
+    let amount_out = SwapCalculator::calculate_exact_output(amount_in, reserve_a, reserve_b);
+    let (_, balance_b, _) = env.get_balances(trader).await;
+
+    let amounts_in = env
+        .pair
+        .swap(U256::zero(), amount_out, trader)
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!(amounts_in, (amount_in, U256::zero()));
+
+    assert_eq!(env.get_balances(trader).await.1, balance_b + amount_out);
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (U256::zero(), U256::zero())
+    );
+    assert_eq!(
+        env.get_reserves().await,
+        (reserve_a + amount_in, reserve_b - amount_out)
+    );
+    assert_eq!(env.pair.lock().await.unwrap(), LockState::Free);
+}
+
+#[tokio::test]
+async fn swap_without_enough_input_releases_the_pair() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let trader: ActorId = TRADER_1.into();
+    let (reserve_a, reserve_b) = (large_amount(), large_amount());
+    let amount_in = calculate_swap_amount_from_percent(reserve_a, 1);
+    setup_pool_and_deposit(&mut env, amount_in).await;
+
+    let amount_out = SwapCalculator::calculate_exact_output(amount_in, reserve_a, reserve_b);
+
+    // Asking for more than the input pays for breaks the invariant
+    let res = env
+        .pair
+        .swap(U256::zero(), amount_out + U256::one(), trader)
+        .with_params(|args| args.with_actor_id(trader))
+        .await;
+    assert!(res.is_err());
+    assert_eq!(env.pair.lock().await.unwrap(), LockState::Free);
+    assert_eq!(env.get_reserves().await, (reserve_a, reserve_b));
+
+    // The deposit is still there for the next swap
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (amount_in, U256::zero())
+    );
+    env.pair
+        .swap(U256::zero(), amount_out, trader)
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn swap_does_not_take_the_deposit_of_another_account() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let trader: ActorId = TRADER_1.into();
+    let other: ActorId = TRADER_2.into();
+    let (reserve_a, reserve_b) = (large_amount(), large_amount());
+    let amount_in = calculate_swap_amount_from_percent(reserve_a, 1);
+    setup_pool_and_deposit(&mut env, amount_in).await;
+    env.setup_user(TRADER_2, medium_amount()).await;
+
+    let amount_out = SwapCalculator::calculate_exact_output(amount_in, reserve_a, reserve_b);
+
+    // Neither the trader's deposit nor tokens transferred to the pair pay for it
+    env.token_a.mint(trader, amount_in).await.unwrap();
+    env.token_a
+        .transfer(env.pair.actor_id(), amount_in)
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    let res = env
+        .pair
+        .swap(U256::zero(), amount_out, other)
+        .with_params(|args| args.with_actor_id(other))
+        .await;
+    assert!(res.is_err());
+    assert_eq!(env.get_reserves().await, (reserve_a, reserve_b));
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (amount_in, U256::zero())
+    );
+    assert_eq!(env.pair.lock().await.unwrap(), LockState::Free);
+}
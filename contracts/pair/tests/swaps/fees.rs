@@ -0,0 +1,116 @@
+use crate::*;
+
+#[tokio::test]
+async fn test_fee_setters_are_admin_only_and_bounded() {
+    let treasury_id = ActorId::from([1u8; 32]);
+    let mut env = TestEnv::new(treasury_id).await;
+    let admin: ActorId = ACTOR_ID.into();
+    let trader = ActorId::from(TRADER_1);
+    env.env.system().mint_to(TRADER_1, 1_000_000_000_000_000);
+
+    assert_eq!(env.pair.swap_fee_bps().await.unwrap(), 30);
+    assert_eq!(env.pair.treasury_fee_bps().await.unwrap(), TREASURY_FEE_BPS);
+    assert_eq!(env.pair.protocol_fee_divisor().await.unwrap(), 6);
+
+    let res = env
+        .pair
+        .set_swap_fee_bps(100)
+        .with_params(|args| args.with_actor_id(trader))
+        .await;
+    assert!(res.is_err());
+    let res = env
+        .pair
+        .set_swap_fee_bps(1_001)
+        .with_params(|args| args.with_actor_id(admin))
+        .await;
+    assert!(res.is_err());
+    let res = env
+        .pair
+        .set_treasury_fee_bps(101)
+        .with_params(|args| args.with_actor_id(admin))
+        .await;
+    assert!(res.is_err());
+    let res = env
+        .pair
+        .set_protocol_fee_divisor(1)
+        .with_params(|args| args.with_actor_id(admin))
+        .await;
+    assert!(res.is_err());
+
+    env.pair
+        .set_swap_fee_bps(100)
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+    env.pair
+        .set_treasury_fee_bps(0)
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+    env.pair
+        .set_protocol_fee_divisor(4)
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(env.pair.swap_fee_bps().await.unwrap(), 100);
+    assert_eq!(env.pair.treasury_fee_bps().await.unwrap(), 0);
+    assert_eq!(env.pair.protocol_fee_divisor().await.unwrap(), 4);
+}
+
+#[tokio::test]
+async fn test_swap_uses_stored_fees() {
+    let treasury_id = ActorId::from([1u8; 32]);
+    let mut env = TestEnv::new(treasury_id).await;
+    let admin: ActorId = ACTOR_ID.into();
+    let trader = ActorId::from(TRADER_1);
+
+    let liquidity_amount = large_amount();
+    env.setup_user(TRADER_1, small_amount()).await;
+    env.setup_user(ACTOR_ID, liquidity_amount).await;
+    env.pair
+        .add_liquidity(
+            liquidity_amount,
+            liquidity_amount,
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    // 1% swap fee, 0.1% treasury fee
+    env.pair
+        .set_swap_fee_bps(100)
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+    env.pair
+        .set_treasury_fee_bps(10)
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    let amount_in = tiny_amount();
+    let (reserve_in, reserve_out) = env.get_reserves().await;
+    let treasury_fee = amount_in * U256::from(10) / U256::from(FEE_DENOM_BPS);
+    let in_for_pool = amount_in - treasury_fee;
+    let in_with_fee = in_for_pool * U256::from(FEE_DENOM_BPS - 100);
+    let expected_out =
+        in_with_fee * reserve_out / (reserve_in * U256::from(FEE_DENOM_BPS) + in_with_fee);
+    assert_eq!(
+        env.pair.get_amount_out(amount_in, true).await.unwrap(),
+        expected_out
+    );
+
+    let (swapped_in, swapped_out) = env
+        .pair
+        .swap_exact_tokens_for_tokens(amount_in, expected_out, true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!((swapped_in, swapped_out), (amount_in, expected_out));
+
+    let (_, fee0, _) = env.pair.get_treasury_info().await.unwrap();
+    assert_eq!(fee0, treasury_fee);
+}
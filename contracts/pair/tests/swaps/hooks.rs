@@ -0,0 +1,186 @@
+use crate::*;
+use hook_client::{
+    hook::{Hook as _, HookImpl},
+    Hook as HookClient, HookCtors, HookProgram,
+};
+
+/// Deploys the reference hook with `ACTOR_ID` as its admin, sets it on the pair and
+/// adds liquidity before that.
+async fn setup_hooked_pair(env: &mut TestEnv) -> Service<HookImpl, GtestEnv> {
+    let liquidity_amount = large_amount();
+    env.setup_user(ACTOR_ID, liquidity_amount * 2).await;
+    env.setup_user(TRADER_1, medium_amount()).await;
+    env.setup_user(TRADER_2, medium_amount()).await;
+    env.pair
+        .add_liquidity(
+            liquidity_amount,
+            liquidity_amount,
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(ACTOR_ID.into()))
+        .await
+        .unwrap();
+
+    let hook_code_id = env.env.system().submit_code(hook::WASM_BINARY);
+    let hook = env
+        .env
+        .deploy::<HookProgram>(hook_code_id, b"hook".to_vec())
+        .new(ACTOR_ID.into())
+        .await
+        .unwrap()
+        .hook();
+    env.pair
+        .set_hook(hook.actor_id())
+        .with_params(|args| args.with_actor_id(ACTOR_ID.into()))
+        .await
+        .unwrap();
+    hook
+}
+
+#[tokio::test]
+async fn test_set_hook_is_admin_only() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let hook = setup_hooked_pair(&mut env).await;
+    assert_eq!(env.pair.hook().await.unwrap(), hook.actor_id());
+
+    let res = env
+        .pair
+        .set_hook(ActorId::zero())
+        .with_params(|args| args.with_actor_id(TRADER_1.into()))
+        .await;
+    assert!(res.is_err());
+    assert_eq!(env.pair.hook().await.unwrap(), hook.actor_id());
+}
+
+#[tokio::test]
+async fn test_hook_blocks_swaps_and_liquidity() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let mut hook = setup_hooked_pair(&mut env).await;
+    let trader: ActorId = TRADER_1.into();
+    hook.set_blocked(trader, true).await.unwrap();
+
+    let amount = small_amount();
+    let balances = env.get_balances(trader).await;
+    let res = env
+        .pair
+        .swap_exact_tokens_for_tokens(amount, U256::zero(), true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await;
+    assert!(res.is_err());
+    let res = env
+        .pair
+        .add_liquidity(
+            amount,
+            amount,
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(trader))
+        .await;
+    assert!(res.is_err());
+    // Nothing was taken
+    assert_eq!(env.get_balances(trader).await, balances);
+
+    // Nor can a blocked account receive the output of someone else's swap
+    let res = env
+        .pair
+        .swap_exact_tokens_for_tokens_to(amount, U256::zero(), true, trader, env.get_deadline())
+        .with_params(|args| args.with_actor_id(TRADER_2.into()))
+        .await;
+    assert!(res.is_err());
+
+    hook.set_blocked(trader, false).await.unwrap();
+    env.pair
+        .swap_exact_tokens_for_tokens(amount, U256::zero(), true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_hook_sets_swap_fee_and_sees_swaps() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let mut hook = setup_hooked_pair(&mut env).await;
+    let trader: ActorId = TRADER_1.into();
+    let fee_bps = 100;
+    hook.set_swap_fee(Some(fee_bps)).await.unwrap();
+
+    let amount_in = small_amount();
+    let (reserve_in, reserve_out) = env.get_reserves().await;
+    let in_with_fee = amount_in * U256::from(FEE_DENOM_BPS - fee_bps);
+    let expected_out =
+        in_with_fee * reserve_out / (reserve_in * U256::from(FEE_DENOM_BPS) + in_with_fee);
+    let (swapped_in, swapped_out) = env
+        .pair
+        .swap_exact_tokens_for_tokens(amount_in, U256::zero(), true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!((swapped_in, swapped_out), (amount_in, expected_out));
+    assert_eq!(
+        hook.volume_of(env.pair.actor_id(), trader).await.unwrap(),
+        amount_in
+    );
+
+    // Without a hook the pair's own fee applies again
+    env.pair
+        .set_hook(ActorId::zero())
+        .with_params(|args| args.with_actor_id(ACTOR_ID.into()))
+        .await
+        .unwrap();
+    let (reserve_in, reserve_out) = env.get_reserves().await;
+    let expected_out = SwapCalculator::calculate_exact_output(amount_in, reserve_in, reserve_out);
+    let (_, swapped_out) = env
+        .pair
+        .swap_exact_tokens_for_tokens(amount_in, U256::zero(), true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!(swapped_out, expected_out);
+    assert_eq!(
+        hook.volume_of(env.pair.actor_id(), trader).await.unwrap(),
+        amount_in
+    );
+}
+
+#[tokio::test]
+async fn test_operations_bypassing_the_hook_are_disabled() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let _hook = setup_hooked_pair(&mut env).await;
+    let trader: ActorId = TRADER_1.into();
+    let amount = small_amount();
+
+    // Deposits are allowed, but not swapping them
+    env.pair
+        .deposit(env.token_a.actor_id(), amount)
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    let res = env
+        .pair
+        .swap_exact_tokens_for_tokens_internal(amount, U256::zero(), true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await;
+    assert!(res.is_err());
+    let res = env
+        .pair
+        .add_liquidity_single(
+            env.token_a.actor_id(),
+            amount,
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(trader))
+        .await;
+    assert!(res.is_err());
+
+    env.pair
+        .withdraw(env.token_a.actor_id(), amount)
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!(env.get_balances(trader).await.0, medium_amount());
+}
@@ -0,0 +1,171 @@
+use crate::*;
+
+async fn setup_pool_and_deposit(env: &mut TestEnv, deposit: U256) {
+    let liquidity_amount = large_amount();
+    env.setup_user(ACTOR_ID, liquidity_amount).await;
+    env.setup_user(TRADER_1, deposit).await;
+
+    env.pair
+        .add_liquidity(
+            liquidity_amount,
+            liquidity_amount,
+            liquidity_amount / U256::from(2),
+            liquidity_amount / U256::from(2),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(ACTOR_ID.into()))
+        .await
+        .unwrap();
+
+    env.pair
+        .deposit(env.token_a.actor_id(), deposit)
+        .with_params(|args| args.with_actor_id(TRADER_1.into()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn deposit_swap_internal_and_withdraw() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let deposit = medium_amount();
+    setup_pool_and_deposit(&mut env, deposit).await;
+    let trader: ActorId = TRADER_1.into();
+
+    assert_eq!(env.get_balances(trader).await.0, U256::zero());
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (deposit, U256::zero())
+    );
+
+    let (reserve_a, reserve_b) = env.get_reserves().await;
+    let amount_in = deposit / U256::from(2);
+    let expected_out = SwapCalculator::calculate_exact_output(amount_in, reserve_a, reserve_b);
+    let (swapped_in, swapped_out) = env
+        .pair
+        .swap_exact_tokens_for_tokens_internal(amount_in, U256::zero(), true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!((swapped_in, swapped_out), (amount_in, expected_out));
+    assert_eq!(
+        env.get_reserves().await,
+        (reserve_a + amount_in, reserve_b - expected_out)
+    );
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (deposit - amount_in, expected_out)
+    );
+    // Nothing was transferred, nothing is in flight
+    assert!(env.pair.msgs_in_msg_tracker().await.unwrap().is_empty());
+    assert!(env.pair.operations().await.unwrap().is_empty());
+
+    // More than the internal balance can't be swapped
+    let res = env
+        .pair
+        .swap_exact_tokens_for_tokens_internal(deposit, U256::zero(), true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await;
+    assert!(res.is_err());
+
+    let (_, balance_b, _) = env.get_balances(trader).await;
+    env.pair
+        .withdraw(env.token_b.actor_id(), expected_out)
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!(env.get_balances(trader).await.1, balance_b + expected_out);
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (deposit - amount_in, U256::zero())
+    );
+}
+
+#[tokio::test]
+async fn liquidity_from_internal_balance() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let deposit = medium_amount();
+    setup_pool_and_deposit(&mut env, deposit).await;
+    let trader: ActorId = TRADER_1.into();
+
+    // Half of token A for token B, then both sides as liquidity
+    let (amount_in, amount_out) = env
+        .pair
+        .swap_exact_tokens_for_tokens_internal(
+            deposit / U256::from(2),
+            U256::zero(),
+            true,
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    let (amount_a, amount_b, liquidity) = env
+        .pair
+        .add_liquidity_internal(
+            deposit - amount_in,
+            amount_out,
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!(env.get_balances(trader).await.2, liquidity);
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (deposit - amount_in - amount_a, amount_out - amount_b)
+    );
+
+    let (reserve_a, reserve_b) = env.get_reserves().await;
+    let (removed_a, removed_b) = env
+        .pair
+        .remove_liquidity_internal(liquidity, U256::zero(), U256::zero(), env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!(env.get_balances(trader).await.2, U256::zero());
+    assert_eq!(
+        env.get_reserves().await,
+        (reserve_a - removed_a, reserve_b - removed_b)
+    );
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (
+            deposit - amount_in - amount_a + removed_a,
+            amount_out - amount_b + removed_b
+        )
+    );
+}
+
+#[tokio::test]
+async fn deposits_are_not_reserves() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    let deposit = medium_amount();
+    setup_pool_and_deposit(&mut env, deposit).await;
+    let reserves = env.get_reserves().await;
+
+    let (audit0, _) = env.pair.audit().await.unwrap();
+    assert_eq!(audit0.deposits, deposit);
+    assert!(audit0.excess.is_zero() && audit0.shortfall.is_zero());
+
+    // Sync doesn't count the deposits as reserves
+    env.pair.sync().await.unwrap();
+    assert_eq!(env.get_reserves().await, reserves);
+
+    // Migration leaves them to be withdrawn
+    let target = ActorId::from([9u8; 32]);
+    env.pair
+        .migrate_all_liquidity(target)
+        .with_params(|args| args.with_actor_id(ACTOR_ID.into()))
+        .await
+        .unwrap();
+    assert_eq!(env.get_balances(target).await.0, reserves.0);
+
+    env.pair
+        .withdraw(env.token_a.actor_id(), deposit)
+        .with_params(|args| args.with_actor_id(TRADER_1.into()))
+        .await
+        .unwrap();
+    assert_eq!(env.get_balances(TRADER_1.into()).await.0, deposit);
+}
@@ -1,8 +1,17 @@
+mod concurrent;
+mod core_swap;
 mod exact_input;
 mod exact_input_treasury;
 mod exact_output;
 mod exact_output_treasury;
+mod fees;
 mod full_workflow;
+mod hooks;
+mod internal;
+mod multicall;
+mod oracle;
+mod recipient;
+mod stable;
 mod treasury;
 
 pub use exact_input::*;
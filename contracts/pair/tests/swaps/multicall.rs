@@ -0,0 +1,216 @@
+use crate::*;
+use pair_client::{Call, CallResult};
+
+async fn setup_pool(env: &mut TestEnv) {
+    let liquidity_amount = large_amount();
+    env.setup_user(ACTOR_ID, liquidity_amount).await;
+    env.setup_user(TRADER_1, medium_amount()).await;
+
+    env.pair
+        .add_liquidity(
+            liquidity_amount,
+            liquidity_amount,
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(ACTOR_ID.into()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn remove_liquidity_swap_and_withdraw_in_one_message() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    setup_pool(&mut env).await;
+    let provider: ActorId = ACTOR_ID.into();
+    let (balance_a, balance_b, lp_balance) = env.get_balances(provider).await;
+
+    // Take half of the position out, all in token A
+    let liquidity = lp_balance / U256::from(2);
+    let (removed_a, removed_b) = env
+        .pair
+        .calculate_remove_liquidity(liquidity)
+        .await
+        .unwrap();
+    let (reserve_a, reserve_b) = env.get_reserves().await;
+    let (reserve_a, reserve_b) = (reserve_a - removed_a, reserve_b - removed_b);
+    let expected_out = SwapCalculator::calculate_exact_output(removed_b, reserve_b, reserve_a);
+
+    let results = env
+        .pair
+        .multicall(
+            vec![
+                Call::RemoveLiquidity {
+                    liquidity,
+                    amount_a_min: removed_a,
+                    amount_b_min: removed_b,
+                },
+                Call::SwapExactTokensForTokens {
+                    amount_in: removed_b,
+                    amount_out_min: expected_out,
+                    is_token0_to_token1: false,
+                },
+                Call::Withdraw {
+                    token: env.token_a.actor_id(),
+                    amount: removed_a + expected_out,
+                },
+            ],
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(provider))
+        .await
+        .unwrap();
+    assert_eq!(
+        results,
+        vec![
+            CallResult::RemoveLiquidity {
+                amount_a: removed_a,
+                amount_b: removed_b,
+            },
+            CallResult::Swap {
+                amount_in: removed_b,
+                amount_out: expected_out,
+            },
+            CallResult::Withdraw,
+        ]
+    );
+
+    assert_eq!(
+        env.get_balances(provider).await,
+        (
+            balance_a + removed_a + expected_out,
+            balance_b,
+            lp_balance - liquidity
+        )
+    );
+    assert_eq!(
+        env.get_reserves().await,
+        (reserve_a - expected_out, reserve_b + removed_b)
+    );
+    assert_eq!(
+        env.pair.balance_of_internal(provider).await.unwrap(),
+        (U256::zero(), U256::zero())
+    );
+    assert!(env.pair.operations().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn failed_call_undoes_the_whole_batch() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    setup_pool(&mut env).await;
+    let trader: ActorId = TRADER_1.into();
+    let deposit = medium_amount();
+    env.pair
+        .deposit(env.token_a.actor_id(), deposit)
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    let reserves = env.get_reserves().await;
+
+    // The swap would go through, the liquidity minimum doesn't hold once it moved the price
+    let half = deposit / U256::from(2);
+    let expected_out = env.pair.get_amount_out(half, true).await.unwrap();
+    let res = env
+        .pair
+        .multicall(
+            vec![
+                Call::SwapExactTokensForTokens {
+                    amount_in: half,
+                    amount_out_min: expected_out,
+                    is_token0_to_token1: true,
+                },
+                Call::AddLiquidity {
+                    amount_a_desired: half,
+                    amount_b_desired: expected_out,
+                    amount_a_min: half,
+                    amount_b_min: expected_out,
+                },
+            ],
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(trader))
+        .await;
+    assert!(res.is_err());
+    assert_eq!(env.get_reserves().await, reserves);
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (deposit, U256::zero())
+    );
+
+    // Swap then add liquidity with what came out
+    let results = env
+        .pair
+        .multicall(
+            vec![
+                Call::SwapExactTokensForTokens {
+                    amount_in: half,
+                    amount_out_min: expected_out,
+                    is_token0_to_token1: true,
+                },
+                Call::AddLiquidity {
+                    amount_a_desired: half,
+                    amount_b_desired: expected_out,
+                    amount_a_min: U256::zero(),
+                    amount_b_min: U256::zero(),
+                },
+            ],
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    let CallResult::AddLiquidity {
+        amount_a,
+        amount_b,
+        liquidity,
+    } = results[1]
+    else {
+        panic!("unexpected result {:?}", results[1]);
+    };
+    assert_eq!(env.get_balances(trader).await.2, liquidity);
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (half - amount_a, expected_out - amount_b)
+    );
+}
+
+#[tokio::test]
+async fn batch_with_a_withdrawal_before_another_call_is_rejected() {
+    let mut env = TestEnv::new(ActorId::zero()).await;
+    setup_pool(&mut env).await;
+    let trader: ActorId = TRADER_1.into();
+    let deposit = medium_amount();
+    env.pair
+        .deposit(env.token_a.actor_id(), deposit)
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+
+    let withdraw = Call::Withdraw {
+        token: env.token_a.actor_id(),
+        amount: U256::one(),
+    };
+    let swap = Call::SwapExactTokensForTokens {
+        amount_in: U256::one(),
+        amount_out_min: U256::zero(),
+        is_token0_to_token1: true,
+    };
+    for calls in [
+        vec![],
+        vec![withdraw.clone(), swap.clone()],
+        vec![swap.clone(), withdraw.clone(), swap.clone()],
+        vec![withdraw.clone(), swap, withdraw],
+    ] {
+        let res = env
+            .pair
+            .multicall(calls, env.get_deadline())
+            .with_params(|args| args.with_actor_id(trader))
+            .await;
+        assert!(res.is_err());
+    }
+    assert_eq!(
+        env.pair.balance_of_internal(trader).await.unwrap(),
+        (deposit, U256::zero())
+    );
+}
@@ -0,0 +1,95 @@
+use crate::*;
+
+fn run_blocks(env: &TestEnv, blocks: u32) {
+    for _ in 0..blocks {
+        env.env.system().run_next_block();
+    }
+}
+
+#[tokio::test]
+async fn test_observe_returns_time_weighted_prices() {
+    let treasury_id = ActorId::from([1u8; 32]);
+    let mut env = TestEnv::new(treasury_id).await;
+    let admin: ActorId = ACTOR_ID.into();
+    let trader = ActorId::from(TRADER_1);
+
+    let liquidity_amount = large_amount();
+    env.setup_user(TRADER_1, small_amount()).await;
+    env.setup_user(ACTOR_ID, liquidity_amount).await;
+    env.pair
+        .add_liquidity(
+            liquidity_amount,
+            liquidity_amount,
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    // Equal reserves: both prices are 1.0 in UQ112.112
+    let one = U256::one() << 112;
+    run_blocks(&env, 10);
+    let prices = env.env.pair.observe(vec![0, 10]).await.unwrap();
+    assert_eq!(prices, vec![(one, one), (one, one)]);
+
+    env.pair
+        .swap_exact_tokens_for_tokens(small_amount(), U256::zero(), true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    run_blocks(&env, 10);
+
+    let prices = env.env.pair.observe(vec![0, 10, 50]).await.unwrap();
+    let (spot0, spot1) = prices[0];
+    assert!(spot0 < one && spot1 > one);
+    // The window after the swap sees only the new price
+    assert_eq!(prices[1], prices[0]);
+    // The window across the swap averages the old and the new price
+    let (twap0, twap1) = prices[2];
+    assert!(spot0 < twap0 && twap0 < one);
+    assert!(one < twap1 && twap1 < spot1);
+
+    // No observation is that old
+    assert!(env.env.pair.observe(vec![10_000]).await.is_err());
+
+    let (price0_cumulative, price1_cumulative, _) =
+        env.env.pair.current_cumulative_prices().await.unwrap();
+    assert!(!price0_cumulative.is_zero() && !price1_cumulative.is_zero());
+    assert!(env.env.pair.observations().await.unwrap().len() >= 2);
+}
+
+#[tokio::test]
+async fn test_observation_cardinality_only_grows() {
+    let treasury_id = ActorId::from([1u8; 32]);
+    let mut env = TestEnv::new(treasury_id).await;
+    let admin: ActorId = ACTOR_ID.into();
+    let trader = ActorId::from(TRADER_1);
+    env.env.system().mint_to(TRADER_1, 1_000_000_000_000_000);
+
+    assert_eq!(env.pair.observation_cardinality().await.unwrap(), 64);
+
+    let res = env
+        .pair
+        .increase_observation_cardinality(128)
+        .with_params(|args| args.with_actor_id(trader))
+        .await;
+    assert!(res.is_err());
+
+    env.pair
+        .increase_observation_cardinality(128)
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(env.pair.observation_cardinality().await.unwrap(), 128);
+
+    for cardinality in [100, 1_001] {
+        let res = env
+            .pair
+            .increase_observation_cardinality(cardinality)
+            .with_params(|args| args.with_actor_id(admin))
+            .await;
+        assert!(res.is_err());
+    }
+}
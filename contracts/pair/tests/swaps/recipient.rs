@@ -0,0 +1,63 @@
+use crate::*;
+
+#[tokio::test]
+async fn test_swaps_to_recipient() {
+    let treasury_id = ActorId::zero();
+    let mut env = TestEnv::new(treasury_id).await;
+    let admin: ActorId = ACTOR_ID.into();
+    let trader = ActorId::from(TRADER_1);
+    let recipient = ActorId::from(TRADER_2);
+
+    let liquidity_amount = large_amount();
+    env.setup_user(TRADER_1, small_amount()).await;
+    env.setup_user(ACTOR_ID, liquidity_amount).await;
+    env.pair
+        .add_liquidity(
+            liquidity_amount,
+            liquidity_amount,
+            U256::zero(),
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+
+    let amount_in = tiny_amount();
+    let (_, amount_out) = env
+        .pair
+        .swap_exact_tokens_for_tokens_to(
+            amount_in,
+            U256::zero(),
+            true,
+            recipient,
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+
+    let (trader_a, trader_b, _) = env.get_balances(trader).await;
+    assert_eq!(trader_a, small_amount() - amount_in);
+    assert_eq!(trader_b, small_amount());
+    let (_, recipient_b, _) = env.get_balances(recipient).await;
+    assert_eq!(recipient_b, amount_out);
+
+    let (amount_in, _) = env
+        .pair
+        .swap_tokens_for_exact_tokens_to(
+            amount_out,
+            small_amount(),
+            false,
+            recipient,
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+
+    let (_, trader_b, _) = env.get_balances(trader).await;
+    assert_eq!(trader_b, small_amount() - amount_in);
+    let (recipient_a, _, _) = env.get_balances(recipient).await;
+    assert_eq!(recipient_a, amount_out);
+}
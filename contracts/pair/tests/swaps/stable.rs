@@ -0,0 +1,110 @@
+use crate::*;
+
+#[tokio::test]
+async fn test_stable_pair_swaps_near_peg() {
+    let treasury_id = ActorId::zero();
+    let mut env = TestEnv::new_stable(treasury_id, 100).await;
+    let admin: ActorId = ACTOR_ID.into();
+    let trader = ActorId::from(TRADER_1);
+
+    let amount = medium_amount();
+    env.setup_user(ACTOR_ID, amount).await;
+    env.setup_user(TRADER_1, small_amount() * U256::from(2))
+        .await;
+    setup_initial_liquidity(&mut env, admin, amount, amount).await;
+    assert_eq!(env.pair.amp().await.unwrap(), Some(100));
+
+    // Exact input: much less slippage than x * y = k, never above the input
+    let amount_in = small_amount();
+    let quote = env.pair.get_amount_out(amount_in, true).await.unwrap();
+    let (used, received) = env
+        .pair
+        .swap_exact_tokens_for_tokens(amount_in, U256::zero(), true, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!(used, amount_in);
+    assert_eq!(received, quote);
+    let constant_product = SwapCalculator::calculate_exact_output(amount_in, amount, amount);
+    assert!(received > constant_product);
+    assert!(received < amount_in);
+    assert_eq!(
+        env.get_reserves().await,
+        (amount + amount_in, amount - received)
+    );
+
+    // Exact output back the other way
+    let (reserve_a, reserve_b) = env.get_reserves().await;
+    let amount_out = small_amount() / U256::from(2);
+    let quote = env.pair.get_amount_in(amount_out, false).await.unwrap();
+    let (used, received) = env
+        .pair
+        .swap_tokens_for_exact_tokens(amount_out, quote, false, env.get_deadline())
+        .with_params(|args| args.with_actor_id(trader))
+        .await
+        .unwrap();
+    assert_eq!((used, received), (quote, amount_out));
+    assert!(used < amount_out * U256::from(101) / U256::from(100));
+    assert_eq!(
+        env.get_reserves().await,
+        (reserve_a - amount_out, reserve_b + used)
+    );
+}
+
+#[tokio::test]
+async fn test_stable_pair_amp_admin_controls() {
+    let treasury_id = ActorId::zero();
+    let mut env = TestEnv::new_stable(treasury_id, 100).await;
+    let admin: ActorId = ACTOR_ID.into();
+    let user = ActorId::from(TRADER_1);
+
+    let amount = medium_amount();
+    env.setup_user(ACTOR_ID, amount).await;
+    env.setup_user(TRADER_1, small_amount()).await;
+    setup_initial_liquidity(&mut env, admin, amount, amount).await;
+
+    let now = env.env.system().block_timestamp();
+    let day = 86_400_000;
+    // Only the admin ramps, and not within a day of the last change
+    let res = env
+        .pair
+        .ramp_amp(200, now + 2 * day)
+        .with_params(|args| args.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    let res = env
+        .pair
+        .ramp_amp(200, now + 2 * day)
+        .with_params(|args| args.with_actor_id(admin))
+        .await;
+    assert!(res.is_err());
+    env.pair
+        .stop_ramp_amp()
+        .with_params(|args| args.with_actor_id(admin))
+        .await
+        .unwrap();
+    assert_eq!(env.pair.amp().await.unwrap(), Some(100));
+
+    // The single-sided deposit relies on the constant-product closed form
+    let res = env
+        .pair
+        .add_liquidity_single(
+            env.token_a.actor_id(),
+            small_amount(),
+            U256::zero(),
+            env.get_deadline(),
+        )
+        .with_params(|args| args.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    // Constant-product pairs have no amplification
+    let mut env = TestEnv::new(treasury_id).await;
+    assert_eq!(env.pair.amp().await.unwrap(), None);
+    let res = env
+        .pair
+        .stop_ramp_amp()
+        .with_params(|args| args.with_actor_id(admin))
+        .await;
+    assert!(res.is_err());
+}
@@ -15,6 +15,15 @@ pub struct TestEnv {
 
 impl TestEnv {
     pub async fn new(treasury_id: ActorId) -> Self {
+        Self::with_curve(treasury_id, None).await
+    }
+
+    /// Deploys a StableSwap pair with amplification coefficient `amp`.
+    pub async fn new_stable(treasury_id: ActorId, amp: u64) -> Self {
+        Self::with_curve(treasury_id, Some(amp)).await
+    }
+
+    async fn with_curve(treasury_id: ActorId, amp: Option<u64>) -> Self {
         let system = System::new();
         system.init_logger();
         system.mint_to(ACTOR_ID, 1_000_000_000_000_000);
@@ -22,7 +31,7 @@ impl TestEnv {
         let env = GtestEnv::new(system, ACTOR_ID.into());
 
         let (token_a_program, token_b_program, pair_program) =
-            TestEnv::setup_tokens_and_pair(&env, FEE_TO.into(), treasury_id).await;
+            TestEnv::setup_tokens_and_pair(&env, FEE_TO.into(), treasury_id, amp).await;
 
         let pair = pair_program.pair();
         let lp_vft = pair_program.vft();
@@ -42,6 +51,7 @@ impl TestEnv {
         env: &GtestEnv,
         fee_to: ActorId,
         treasury_id: ActorId,
+        amp: Option<u64>,
     ) -> (
         Actor<ExtendedVftClientProgram, GtestEnv>,
         Actor<ExtendedVftClientProgram, GtestEnv>,
@@ -83,20 +93,35 @@ impl TestEnv {
             gas_for_reply_deposit: 5_000_000_000,
             reply_timeout: 50,
             gas_for_full_tx: 100_000_000_000,
+            swap_fee_bps: 30,
         };
 
-        let pair = env
-            .deploy::<pair_client::PairProgram>(program_code_id, b"salt".to_vec())
-            .new(
-                config,
-                token_a.id(),
-                token_b.id(),
-                fee_to,
-                treasury_id,
-                ACTOR_ID.into(),
-            )
-            .await
-            .unwrap();
+        let deployment = env.deploy::<pair_client::PairProgram>(program_code_id, b"salt".to_vec());
+        let pair = match amp {
+            Some(amp) => deployment
+                .new_stable(
+                    config,
+                    token_a.id(),
+                    token_b.id(),
+                    fee_to,
+                    treasury_id,
+                    ACTOR_ID.into(),
+                    amp,
+                )
+                .await
+                .unwrap(),
+            None => deployment
+                .new(
+                    config,
+                    token_a.id(),
+                    token_b.id(),
+                    fee_to,
+                    treasury_id,
+                    ACTOR_ID.into(),
+                )
+                .await
+                .unwrap(),
+        };
 
         (token_a, token_b, pair)
     }
@@ -0,0 +1,37 @@
+#![no_std]
+
+pub mod services;
+use sails_rs::{cell::RefCell, prelude::*};
+use services::router::{self, Config, RouterService};
+
+pub struct RouterProgram {
+    state: RefCell<router::State>,
+}
+
+#[sails_rs::program]
+impl RouterProgram {
+    // Program's constructor
+    pub fn new(factory_id: ActorId, admin: ActorId, config: Config, wvara: ActorId) -> Self {
+        let state = router::State {
+            factory_id,
+            admin,
+            wvara,
+            config,
+            ..Default::default()
+        };
+        Self {
+            state: RefCell::new(state),
+        }
+    }
+
+    // Exposed service
+    pub fn router(&self) -> RouterService<'_> {
+        RouterService::new(&self.state)
+    }
+
+    #[allow(dead_code)]
+    #[handle_reply]
+    fn handle_reply(&self) {
+        self.router().on_reply();
+    }
+}
@@ -0,0 +1,313 @@
+use crate::services::router::{Config, RouterError, pending::PendingKind};
+use extended_vft_client::vft::io::{Approve, Transfer, TransferFrom};
+use factory_client::factory::io::GetPair;
+use gstd::errors::{Error, ErrorReplyReason, SimpleExecutionError};
+use pair_client::pair::io::{
+    AddLiquidityTo, GetAmountIn, GetAmountOut, GetTokens, RemoveLiquidity,
+    SwapExactTokensForTokens, SwapTokensForExactTokens,
+};
+use sails_rs::client::CallCodec;
+use sails_rs::{U256, prelude::*};
+
+/// Service of the wVARA program that exchanges VARA for wVARA and back.
+const NATIVE_EXCHANGE: &str = "VftNativeExchange";
+
+/// Errors the pair returns before it moves any tokens.
+const PAIR_REJECTIONS: &[&str] = &[
+    "PoolMigrated",
+    "NotEnoghAttachedGas",
+    "ZeroLiquidity",
+    "AnotherTxInProgress",
+    "DeadlineExpired",
+    "InsufficientAmount",
+    "ExcessiveInputAmount",
+    "InsufficientLiquidity",
+];
+
+async fn send_for_reply(
+    destination: ActorId,
+    payload: Vec<u8>,
+    gas_limit: u64,
+    config: &Config,
+) -> Result<Vec<u8>, RouterError> {
+    send_with_value_for_reply(destination, payload, gas_limit, 0, config).await
+}
+
+async fn send_with_value_for_reply(
+    destination: ActorId,
+    payload: Vec<u8>,
+    gas_limit: u64,
+    value: u128,
+    config: &Config,
+) -> Result<Vec<u8>, RouterError> {
+    send_bound_for_reply(destination, payload, gas_limit, value, config, |_| {}).await
+}
+
+/// Same as `send_with_value_for_reply`, but calls `bind_reply` with the id the reply
+/// will answer once the message is sent, so a late reply can be told apart.
+/// An error reply naming one of `PAIR_REJECTIONS` is `RouterError::PairRejected`.
+async fn send_bound_for_reply(
+    destination: ActorId,
+    payload: Vec<u8>,
+    gas_limit: u64,
+    value: u128,
+    config: &Config,
+    bind_reply: impl FnOnce(MessageId),
+) -> Result<Vec<u8>, RouterError> {
+    let fut = sails_rs::gstd::msg::send_bytes_with_gas_for_reply(
+        destination,
+        payload,
+        gas_limit,
+        value,
+        config.gas_for_reply_deposit,
+    )
+    .map_err(|_| RouterError::SendFailure)?;
+
+    bind_reply(fut.waiting_reply_to);
+
+    fut.up_to(Some(config.reply_timeout))
+        .map_err(|_| RouterError::ReplyTimeout)?
+        .await
+        .map_err(|e| match e {
+            Error::ErrorReply(payload, reason)
+                if is_pair_rejection(&reason, payload.as_bytes()) =>
+            {
+                RouterError::PairRejected
+            }
+            _ => RouterError::ReplyFailure,
+        })
+}
+
+/// Whether an error reply of a pair is one of `PAIR_REJECTIONS`, which the pair
+/// panics with through `unwrap_result`.
+pub fn is_pair_rejection(reason: &ErrorReplyReason, payload: &[u8]) -> bool {
+    if !matches!(
+        reason,
+        ErrorReplyReason::Execution(SimpleExecutionError::UserspacePanic)
+    ) {
+        return false;
+    }
+    let Ok(message) = core::str::from_utf8(payload) else {
+        return false;
+    };
+    message
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| PAIR_REJECTIONS.contains(&word))
+}
+
+/// Looks up the pair for `token_a`/`token_b` in the factory. Returns zero address if there is none.
+pub async fn get_pair(
+    factory_id: ActorId,
+    token_a: ActorId,
+    token_b: ActorId,
+    config: &Config,
+) -> Result<ActorId, RouterError> {
+    let bytes = GetPair::encode_params_with_prefix("Factory", token_a, token_b);
+    let reply = send_for_reply(factory_id, bytes, config.gas_for_token_ops, config).await?;
+    GetPair::decode_reply_with_prefix("Factory", &reply).map_err(|_| RouterError::UnableToDecode)
+}
+
+pub async fn get_tokens(pair: ActorId, config: &Config) -> Result<(ActorId, ActorId), RouterError> {
+    let bytes = GetTokens::encode_params_with_prefix("Pair");
+    let reply = send_for_reply(pair, bytes, config.gas_for_token_ops, config).await?;
+    GetTokens::decode_reply_with_prefix("Pair", &reply).map_err(|_| RouterError::UnableToDecode)
+}
+
+/// Quotes a single hop. The pair returns zero when the swap is not possible.
+pub async fn get_amount_out(
+    pair: ActorId,
+    amount_in: U256,
+    is_token0_to_token1: bool,
+    config: &Config,
+) -> Result<U256, RouterError> {
+    let bytes = GetAmountOut::encode_params_with_prefix("Pair", amount_in, is_token0_to_token1);
+    let reply = send_for_reply(pair, bytes, config.gas_for_token_ops, config).await?;
+    GetAmountOut::decode_reply_with_prefix("Pair", &reply).map_err(|_| RouterError::UnableToDecode)
+}
+
+/// Quotes a single hop. The pair returns zero when the swap is not possible.
+pub async fn get_amount_in(
+    pair: ActorId,
+    amount_out: U256,
+    is_token0_to_token1: bool,
+    config: &Config,
+) -> Result<U256, RouterError> {
+    let bytes = GetAmountIn::encode_params_with_prefix("Pair", amount_out, is_token0_to_token1);
+    let reply = send_for_reply(pair, bytes, config.gas_for_token_ops, config).await?;
+    GetAmountIn::decode_reply_with_prefix("Pair", &reply).map_err(|_| RouterError::UnableToDecode)
+}
+
+/// Returns `(amount_in, amount_out)` reported by the pair.
+pub async fn swap_exact_tokens_for_tokens(
+    pair: ActorId,
+    amount_in: U256,
+    amount_out_min: U256,
+    is_token0_to_token1: bool,
+    deadline: u64,
+    config: &Config,
+    bind_reply: impl FnOnce(MessageId),
+) -> Result<(U256, U256), RouterError> {
+    let bytes = SwapExactTokensForTokens::encode_params_with_prefix(
+        "Pair",
+        amount_in,
+        amount_out_min,
+        is_token0_to_token1,
+        deadline,
+    );
+    let reply =
+        send_bound_for_reply(pair, bytes, config.gas_for_swap, 0, config, bind_reply).await?;
+    SwapExactTokensForTokens::decode_reply_with_prefix("Pair", &reply)
+        .map_err(|_| RouterError::UnableToDecode)
+}
+
+/// Returns `(amount_in, amount_out)` reported by the pair.
+pub async fn swap_tokens_for_exact_tokens(
+    pair: ActorId,
+    amount_out: U256,
+    amount_in_max: U256,
+    is_token0_to_token1: bool,
+    deadline: u64,
+    config: &Config,
+    bind_reply: impl FnOnce(MessageId),
+) -> Result<(U256, U256), RouterError> {
+    let bytes = SwapTokensForExactTokens::encode_params_with_prefix(
+        "Pair",
+        amount_out,
+        amount_in_max,
+        is_token0_to_token1,
+        deadline,
+    );
+    let reply =
+        send_bound_for_reply(pair, bytes, config.gas_for_swap, 0, config, bind_reply).await?;
+    SwapTokensForExactTokens::decode_reply_with_prefix("Pair", &reply)
+        .map_err(|_| RouterError::UnableToDecode)
+}
+
+/// Returns `(amount_a, amount_b, liquidity)` reported by the pair.
+#[allow(clippy::too_many_arguments)]
+pub async fn add_liquidity_to(
+    pair: ActorId,
+    amount_a_desired: U256,
+    amount_b_desired: U256,
+    amount_a_min: U256,
+    amount_b_min: U256,
+    to: ActorId,
+    deadline: u64,
+    config: &Config,
+    bind_reply: impl FnOnce(MessageId),
+) -> Result<(U256, U256, U256), RouterError> {
+    let bytes = AddLiquidityTo::encode_params_with_prefix(
+        "Pair",
+        amount_a_desired,
+        amount_b_desired,
+        amount_a_min,
+        amount_b_min,
+        to,
+        deadline,
+    );
+    let reply =
+        send_bound_for_reply(pair, bytes, config.gas_for_swap, 0, config, bind_reply).await?;
+    AddLiquidityTo::decode_reply_with_prefix("Pair", &reply)
+        .map_err(|_| RouterError::UnableToDecode)
+}
+
+/// Returns `(amount_a, amount_b)` reported by the pair.
+pub async fn remove_liquidity(
+    pair: ActorId,
+    liquidity: U256,
+    amount_a_min: U256,
+    amount_b_min: U256,
+    deadline: u64,
+    config: &Config,
+    bind_reply: impl FnOnce(MessageId),
+) -> Result<(U256, U256), RouterError> {
+    let bytes = RemoveLiquidity::encode_params_with_prefix(
+        "Pair",
+        liquidity,
+        amount_a_min,
+        amount_b_min,
+        deadline,
+    );
+    let reply =
+        send_bound_for_reply(pair, bytes, config.gas_for_swap, 0, config, bind_reply).await?;
+    RemoveLiquidity::decode_reply_with_prefix("Pair", &reply)
+        .map_err(|_| RouterError::UnableToDecode)
+}
+
+/// Decodes the reply of a pair call into what it moved: `(amount_in, amount_out)` of a swap,
+/// `(amount_a, amount_b)` of a liquidity call.
+pub fn decode_pair_reply(kind: &PendingKind, bytes: &[u8]) -> Option<(U256, U256)> {
+    match kind {
+        PendingKind::Swap {
+            exact_output: false,
+            ..
+        } => SwapExactTokensForTokens::decode_reply_with_prefix("Pair", bytes).ok(),
+        PendingKind::Swap {
+            exact_output: true, ..
+        } => SwapTokensForExactTokens::decode_reply_with_prefix("Pair", bytes).ok(),
+        PendingKind::AddLiquidity { .. } => AddLiquidityTo::decode_reply_with_prefix("Pair", bytes)
+            .ok()
+            .map(|(amount_a, amount_b, _)| (amount_a, amount_b)),
+        PendingKind::RemoveLiquidity { .. } => {
+            RemoveLiquidity::decode_reply_with_prefix("Pair", bytes).ok()
+        }
+    }
+}
+
+/// Mints `value` of wVARA to the router for the attached value.
+pub async fn wrap_vara(wvara: ActorId, value: u128, config: &Config) -> Result<(), RouterError> {
+    let bytes = (NATIVE_EXCHANGE, "Mint").encode();
+    send_with_value_for_reply(wvara, bytes, config.gas_for_token_ops, value, config).await?;
+    Ok(())
+}
+
+/// Burns `amount` of the router's wVARA. The reply carries the same value in VARA.
+pub async fn unwrap_vara(wvara: ActorId, amount: U256, config: &Config) -> Result<(), RouterError> {
+    let bytes = (NATIVE_EXCHANGE, "Burn", amount).encode();
+    send_for_reply(wvara, bytes, config.gas_for_token_ops, config).await?;
+    Ok(())
+}
+
+pub async fn approve(
+    token_id: ActorId,
+    spender: ActorId,
+    amount: U256,
+    config: &Config,
+) -> Result<(), RouterError> {
+    let bytes = Approve::encode_params_with_prefix("Vft", spender, amount);
+    let reply = send_for_reply(token_id, bytes, config.gas_for_token_ops, config).await?;
+    // `false` only means the allowance was already set to this value
+    Approve::decode_reply_with_prefix("Vft", &reply).map_err(|_| RouterError::ApproveFailed)?;
+    Ok(())
+}
+
+pub async fn transfer(
+    token_id: ActorId,
+    receiver: ActorId,
+    amount: U256,
+    config: &Config,
+) -> Result<(), RouterError> {
+    let bytes = Transfer::encode_params_with_prefix("Vft", receiver, amount);
+    let reply = send_for_reply(token_id, bytes, config.gas_for_token_ops, config).await?;
+    if Transfer::decode_reply_with_prefix("Vft", &reply).unwrap_or(false) {
+        Ok(())
+    } else {
+        Err(RouterError::TokenTransferFailed)
+    }
+}
+
+pub async fn transfer_from(
+    token_id: ActorId,
+    sender: ActorId,
+    receiver: ActorId,
+    amount: U256,
+    config: &Config,
+) -> Result<(), RouterError> {
+    let bytes = TransferFrom::encode_params_with_prefix("Vft", sender, receiver, amount);
+    let reply = send_for_reply(token_id, bytes, config.gas_for_token_ops, config).await?;
+    if TransferFrom::decode_reply_with_prefix("Vft", &reply).unwrap_or(false) {
+        Ok(())
+    } else {
+        Err(RouterError::TokenTransferFailed)
+    }
+}
@@ -0,0 +1,696 @@
+use crate::services::router::{
+    Config, Custody, LiquidityOutcome, Native, RouterError, RouterService, SwapOutcome, calls,
+    pending::{PendingCall, PendingKind},
+};
+use sails_rs::{
+    cell::Cell,
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// A single hop of a route, resolved through the factory.
+struct Hop {
+    pair: ActorId,
+    token_in: ActorId,
+    token_out: ActorId,
+    is_token0_to_token1: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum HopSwap {
+    ExactInput {
+        amount_in: U256,
+        amount_out_min: U256,
+    },
+    ExactOutput {
+        amount_out: U256,
+        amount_in_max: U256,
+    },
+}
+
+impl HopSwap {
+    /// Largest amount of the input token the pair may pull for this hop.
+    fn max_input(&self) -> U256 {
+        match *self {
+            HopSwap::ExactInput { amount_in, .. } => amount_in,
+            HopSwap::ExactOutput { amount_in_max, .. } => amount_in_max,
+        }
+    }
+}
+
+/// Why a pair call of a route did not complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CallFailure {
+    /// The pair never got the call or rejected it, so the route can be refunded.
+    Rejected,
+    /// The pair got the call but its outcome is unknown, so the route is left pending.
+    Pending,
+}
+
+impl<'a> RouterService<'a> {
+    pub async fn swap_exact_input(
+        &self,
+        amount_in: U256,
+        amount_out_min: U256,
+        path: &[ActorId],
+        to: ActorId,
+        deadline: u64,
+        native: Native,
+    ) -> Result<SwapOutcome, RouterError> {
+        if amount_in.is_zero() {
+            return Err(RouterError::ZeroAmount);
+        }
+        check_deadline(deadline)?;
+        self.check_native_path(path, native)?;
+
+        let config = self.with_state(|st| st.config.clone());
+        let hops = self.resolve_hops(path, &config).await?;
+
+        // Quote the whole route before touching user funds
+        let mut quoted = amount_in;
+        for hop in &hops {
+            quoted =
+                calls::get_amount_out(hop.pair, quoted, hop.is_token0_to_token1, &config).await?;
+            if quoted.is_zero() {
+                return Err(RouterError::InsufficientLiquidity);
+            }
+        }
+        if quoted < amount_out_min {
+            return Err(RouterError::InsufficientOutputAmount);
+        }
+
+        let route_id = msg::id();
+        self.take_custody(route_id, msg::source(), path[0], amount_in, native, &config)
+            .await?;
+
+        // Intermediate hops accept any output, the route's limit is enforced on the last hop
+        let last = hops.len() - 1;
+        let mut amount = amount_in;
+        for (i, hop) in hops.iter().enumerate() {
+            let swap = HopSwap::ExactInput {
+                amount_in: amount,
+                amount_out_min: if i == last {
+                    amount_out_min
+                } else {
+                    U256::zero()
+                },
+            };
+            match self
+                .execute_hop(route_id, hop, swap, deadline, &config)
+                .await
+            {
+                Ok((_, amount_out)) => amount = amount_out,
+                Err(CallFailure::Rejected) => {
+                    return self.refund_route(route_id, i as u32, &config).await;
+                }
+                Err(CallFailure::Pending) => {
+                    return Ok(SwapOutcome::Pending {
+                        failed_hop: i as u32,
+                    });
+                }
+            }
+        }
+
+        let token_out = path[path.len() - 1];
+        self.release_custody(route_id, Some((token_out, to)), msg::source(), &config)
+            .await?;
+
+        Ok(SwapOutcome::Completed {
+            amount_in,
+            amount_out: amount,
+        })
+    }
+
+    pub async fn swap_exact_output(
+        &self,
+        amount_out: U256,
+        amount_in_max: U256,
+        path: &[ActorId],
+        to: ActorId,
+        deadline: u64,
+        native: Native,
+    ) -> Result<SwapOutcome, RouterError> {
+        if amount_out.is_zero() {
+            return Err(RouterError::ZeroAmount);
+        }
+        check_deadline(deadline)?;
+        self.check_native_path(path, native)?;
+
+        let config = self.with_state(|st| st.config.clone());
+        let hops = self.resolve_hops(path, &config).await?;
+
+        // amounts[i] is the input of hop i, amounts[hops.len()] is the route output
+        let mut amounts = vec![U256::zero(); hops.len() + 1];
+        amounts[hops.len()] = amount_out;
+        for i in (0..hops.len()).rev() {
+            let hop = &hops[i];
+            amounts[i] =
+                calls::get_amount_in(hop.pair, amounts[i + 1], hop.is_token0_to_token1, &config)
+                    .await?;
+            if amounts[i].is_zero() {
+                return Err(RouterError::InsufficientLiquidity);
+            }
+        }
+        if amounts[0] > amount_in_max {
+            return Err(RouterError::ExcessiveInputAmount);
+        }
+
+        let route_id = msg::id();
+        self.take_custody(
+            route_id,
+            msg::source(),
+            path[0],
+            amounts[0],
+            native,
+            &config,
+        )
+        .await?;
+        if native.input && amount_in_max > amounts[0] {
+            // Only the quoted input was wrapped, the rest of the value goes back
+            send_value(msg::source(), amount_in_max - amounts[0])?;
+        }
+
+        let mut held = amounts[0];
+        for (i, hop) in hops.iter().enumerate() {
+            let swap = HopSwap::ExactOutput {
+                amount_out: amounts[i + 1],
+                amount_in_max: held,
+            };
+            match self
+                .execute_hop(route_id, hop, swap, deadline, &config)
+                .await
+            {
+                Ok((_, hop_out)) => held = hop_out,
+                Err(CallFailure::Rejected) => {
+                    return self.refund_route(route_id, i as u32, &config).await;
+                }
+                Err(CallFailure::Pending) => {
+                    return Ok(SwapOutcome::Pending {
+                        failed_hop: i as u32,
+                    });
+                }
+            }
+        }
+
+        let token_out = path[path.len() - 1];
+        let amount_in = self.with_state(|st| {
+            let custody = st.routes.get(&route_id).ok_or(RouterError::RouteNotFound)?;
+            // Whatever is left of the first token was not needed by the first hop
+            Ok::<_, RouterError>(amounts[0] - custody.balance(path[0]).min(amounts[0]))
+        })?;
+        self.release_custody(route_id, Some((token_out, to)), msg::source(), &config)
+            .await?;
+
+        Ok(SwapOutcome::Completed {
+            amount_in,
+            amount_out: held,
+        })
+    }
+
+    /// Sends every balance held for the route to `user`, except `output`
+    /// (token, recipient) which goes to its own recipient.
+    /// Balances are debited one by one, so a failed transfer leaves the rest claimable.
+    pub async fn release_custody(
+        &self,
+        route_id: MessageId,
+        output: Option<(ActorId, ActorId)>,
+        user: ActorId,
+        config: &Config,
+    ) -> Result<Vec<(ActorId, U256)>, RouterError> {
+        let (balances, native, wvara) = self.with_state(|st| {
+            st.routes
+                .get(&route_id)
+                .map(|c| (c.balances.clone(), c.native, st.wvara))
+                .ok_or(RouterError::RouteNotFound)
+        })?;
+
+        for (token, amount) in balances.iter() {
+            let recipient = match output {
+                Some((token_out, to)) if token_out == *token => to,
+                _ => user,
+            };
+            if native && *token == wvara {
+                calls::unwrap_vara(wvara, *amount, config).await?;
+                send_value(recipient, *amount)?;
+            } else {
+                calls::transfer(*token, recipient, *amount, config).await?;
+            }
+            self.with_state_mut(|st| {
+                if let Some(custody) = st.routes.get_mut(&route_id) {
+                    custody.debit(*token, *amount)?;
+                }
+                Ok::<_, RouterError>(())
+            })?;
+        }
+
+        self.with_state_mut(|st| {
+            if st.routes.get(&route_id).is_some_and(Custody::is_empty) {
+                st.routes.remove(&route_id);
+            }
+        });
+        Ok(balances)
+    }
+
+    async fn refund_route(
+        &self,
+        route_id: MessageId,
+        failed_hop: u32,
+        config: &Config,
+    ) -> Result<SwapOutcome, RouterError> {
+        let refunded = self
+            .release_custody(route_id, None, msg::source(), config)
+            .await?;
+        Ok(SwapOutcome::Refunded {
+            failed_hop,
+            refunded,
+        })
+    }
+
+    /// Adds liquidity to the `token`/wVARA pair with the attached value as VARA.
+    /// Returns the pair and the outcome.
+    pub async fn add_liquidity_native(
+        &self,
+        token: ActorId,
+        amount_token_desired: U256,
+        amount_token_min: U256,
+        amount_vara_min: U256,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<(ActorId, LiquidityOutcome), RouterError> {
+        let amount_vara = U256::from(msg::value());
+        if amount_token_desired.is_zero() || amount_vara.is_zero() {
+            return Err(RouterError::ZeroAmount);
+        }
+        check_deadline(deadline)?;
+        let wvara = self.with_state(|st| st.wvara);
+        let path = [token, wvara];
+        // What the pair does not take of the wVARA is paid back as VARA
+        let unwrap = Native {
+            input: false,
+            output: true,
+        };
+        self.check_native_path(&path, unwrap)?;
+
+        let config = self.with_state(|st| st.config.clone());
+        let hop = self.resolve_pair(&path, &config).await?;
+        let pair = hop.pair;
+
+        let route_id = msg::id();
+        let user = msg::source();
+        let wrap = Native {
+            input: true,
+            output: false,
+        };
+        self.take_custody(route_id, user, wvara, amount_vara, wrap, &config)
+            .await?;
+        if self
+            .take_custody(
+                route_id,
+                user,
+                token,
+                amount_token_desired,
+                Native::default(),
+                &config,
+            )
+            .await
+            .is_err()
+        {
+            return Ok((pair, self.refund_liquidity(route_id, &config).await?));
+        }
+
+        let (tokens, desired, min) = if hop.is_token0_to_token1 {
+            (
+                (token, wvara),
+                (amount_token_desired, amount_vara),
+                (amount_token_min, amount_vara_min),
+            )
+        } else {
+            (
+                (wvara, token),
+                (amount_vara, amount_token_desired),
+                (amount_vara_min, amount_token_min),
+            )
+        };
+        if self
+            .grant_allowance(token, pair, amount_token_desired, &config)
+            .await
+            .is_err()
+        {
+            return Ok((pair, self.refund_liquidity(route_id, &config).await?));
+        }
+        if self
+            .grant_allowance(wvara, pair, amount_vara, &config)
+            .await
+            .is_err()
+        {
+            self.release_allowance(token, pair, amount_token_desired);
+            return Ok((pair, self.refund_liquidity(route_id, &config).await?));
+        }
+        let call = PendingCall {
+            route_id,
+            pair,
+            kind: PendingKind::AddLiquidity {
+                token0: tokens.0,
+                token1: tokens.1,
+                amount0_max: desired.0,
+                amount1_max: desired.1,
+            },
+        };
+        let reply_to = Cell::new(None);
+        let result = calls::add_liquidity_to(
+            pair,
+            desired.0,
+            desired.1,
+            min.0,
+            min.1,
+            to,
+            deadline,
+            &config,
+            |id| reply_to.set(Some(id)),
+        )
+        .await;
+        let liquidity = result.as_ref().map_or(U256::zero(), |(_, _, l)| *l);
+        let result = result.map(|(amount_a, amount_b, _)| (amount_a, amount_b));
+
+        let (amount_a, amount_b) = match self.finish_call(reply_to.get(), call, result) {
+            Ok(amounts) => amounts,
+            Err(CallFailure::Rejected) => {
+                return Ok((pair, self.refund_liquidity(route_id, &config).await?));
+            }
+            Err(CallFailure::Pending) => return Ok((pair, LiquidityOutcome::Pending)),
+        };
+        let (amount_token, amount_vara) = if hop.is_token0_to_token1 {
+            (amount_a, amount_b)
+        } else {
+            (amount_b, amount_a)
+        };
+        // Return what the pair did not take
+        self.release_custody(route_id, None, user, &config).await?;
+
+        Ok((
+            pair,
+            LiquidityOutcome::Added {
+                amount_token,
+                amount_vara,
+                liquidity,
+            },
+        ))
+    }
+
+    /// Removes `liquidity` from the `token`/wVARA pair and pays the wVARA share as VARA.
+    /// Returns the pair and the outcome.
+    pub async fn remove_liquidity_native(
+        &self,
+        token: ActorId,
+        liquidity: U256,
+        amount_token_min: U256,
+        amount_vara_min: U256,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<(ActorId, LiquidityOutcome), RouterError> {
+        if liquidity.is_zero() {
+            return Err(RouterError::ZeroAmount);
+        }
+        check_deadline(deadline)?;
+        let wvara = self.with_state(|st| st.wvara);
+        let path = [token, wvara];
+        let unwrap = Native {
+            input: false,
+            output: true,
+        };
+        self.check_native_path(&path, unwrap)?;
+
+        let config = self.with_state(|st| st.config.clone());
+        let hop = self.resolve_pair(&path, &config).await?;
+        let pair = hop.pair;
+
+        // The pair's LP token is held like any other token of a route
+        let route_id = msg::id();
+        self.take_custody(route_id, msg::source(), pair, liquidity, unwrap, &config)
+            .await?;
+
+        let ((token0, token1), (min_a, min_b)) = if hop.is_token0_to_token1 {
+            ((token, wvara), (amount_token_min, amount_vara_min))
+        } else {
+            ((wvara, token), (amount_vara_min, amount_token_min))
+        };
+        let call = PendingCall {
+            route_id,
+            pair,
+            kind: PendingKind::RemoveLiquidity {
+                token0,
+                token1,
+                liquidity,
+            },
+        };
+        let reply_to = Cell::new(None);
+        let result =
+            calls::remove_liquidity(pair, liquidity, min_a, min_b, deadline, &config, |id| {
+                reply_to.set(Some(id))
+            })
+            .await;
+        let (amount_a, amount_b) = match self.finish_call(reply_to.get(), call, result) {
+            Ok(amounts) => amounts,
+            Err(CallFailure::Rejected) => {
+                return Ok((pair, self.refund_liquidity(route_id, &config).await?));
+            }
+            Err(CallFailure::Pending) => return Ok((pair, LiquidityOutcome::Pending)),
+        };
+        let (amount_token, amount_vara) = if hop.is_token0_to_token1 {
+            (amount_a, amount_b)
+        } else {
+            (amount_b, amount_a)
+        };
+        self.release_custody(route_id, None, to, &config).await?;
+
+        Ok((
+            pair,
+            LiquidityOutcome::Removed {
+                amount_token,
+                amount_vara,
+                liquidity,
+            },
+        ))
+    }
+
+    async fn refund_liquidity(
+        &self,
+        route_id: MessageId,
+        config: &Config,
+    ) -> Result<LiquidityOutcome, RouterError> {
+        let refunded = self
+            .release_custody(route_id, None, msg::source(), config)
+            .await?;
+        Ok(LiquidityOutcome::Refunded { refunded })
+    }
+
+    /// Checks that the native ends of `path` are the wVARA token.
+    fn check_native_path(&self, path: &[ActorId], native: Native) -> Result<(), RouterError> {
+        if !native.input && !native.output {
+            return Ok(());
+        }
+        let wvara = self.with_state(|st| st.wvara);
+        let (Some(first), Some(last)) = (path.first(), path.last()) else {
+            return Err(RouterError::InvalidPath);
+        };
+        if wvara.is_zero() || (native.input && *first != wvara) || (native.output && *last != wvara)
+        {
+            return Err(RouterError::InvalidPath);
+        }
+        Ok(())
+    }
+
+    /// Resolves the single pair of a two-token `path`.
+    async fn resolve_pair(&self, path: &[ActorId], config: &Config) -> Result<Hop, RouterError> {
+        self.resolve_hops(path, config)
+            .await?
+            .pop()
+            .ok_or(RouterError::InvalidPath)
+    }
+
+    async fn resolve_hops(
+        &self,
+        path: &[ActorId],
+        config: &Config,
+    ) -> Result<Vec<Hop>, RouterError> {
+        if path.len() < 2 {
+            return Err(RouterError::InvalidPath);
+        }
+        // A token met twice would share the route's balance of it between two hops,
+        // e.g. the input left unused by the first hop of [A, B, A] with the output
+        if path.iter().enumerate().any(|(i, t)| path[..i].contains(t)) {
+            return Err(RouterError::InvalidPath);
+        }
+        let factory_id = self.with_state(|st| st.factory_id);
+
+        let mut hops = Vec::with_capacity(path.len() - 1);
+        for tokens in path.windows(2) {
+            let (token_in, token_out) = (tokens[0], tokens[1]);
+            let pair = calls::get_pair(factory_id, token_in, token_out, config).await?;
+            if pair.is_zero() {
+                return Err(RouterError::PairNotFound);
+            }
+            // Pairs registered by the admin are not guaranteed to be sorted
+            let (token0, _) = calls::get_tokens(pair, config).await?;
+            hops.push(Hop {
+                pair,
+                token_in,
+                token_out,
+                is_token0_to_token1: token_in == token0,
+            });
+        }
+        Ok(hops)
+    }
+
+    /// Takes `amount` of `token` from the user, or wraps it from the attached value
+    /// when the route's input is native.
+    async fn take_custody(
+        &self,
+        route_id: MessageId,
+        user: ActorId,
+        token: ActorId,
+        amount: U256,
+        native: Native,
+        config: &Config,
+    ) -> Result<(), RouterError> {
+        if native.input {
+            let value = u128::try_from(amount).map_err(|_| RouterError::Overflow)?;
+            calls::wrap_vara(token, value, config).await?;
+        } else {
+            calls::transfer_from(token, user, exec::program_id(), amount, config).await?;
+        }
+        self.with_state_mut(|st| {
+            st.routes
+                .entry(route_id)
+                .or_insert_with(|| Custody::new(user, native.input || native.output))
+                .credit(token, amount)
+        })
+    }
+
+    /// Adds `amount` to the allowance the router has granted to `spender` and approves it.
+    async fn grant_allowance(
+        &self,
+        token: ActorId,
+        spender: ActorId,
+        amount: U256,
+        config: &Config,
+    ) -> Result<(), RouterError> {
+        let allowance = self.with_state_mut(|st| {
+            let allowance = st.allowances.entry((token, spender)).or_default();
+            *allowance = allowance.saturating_add(amount);
+            *allowance
+        });
+        let result = calls::approve(token, spender, allowance, config).await;
+        if result.is_err() {
+            self.release_allowance(token, spender, amount);
+        }
+        result
+    }
+
+    fn release_allowance(&self, token: ActorId, spender: ActorId, amount: U256) {
+        self.with_state_mut(|st| st.release_allowance(token, spender, amount));
+    }
+
+    /// Applies the result of a pair call sent as `reply_to` to its route.
+    /// A call the pair rejected moved nothing. A call that reached the pair but has
+    /// no usable reply may still move tokens, so it stays pending together with its allowance.
+    fn finish_call(
+        &self,
+        reply_to: Option<MessageId>,
+        call: PendingCall,
+        result: Result<(U256, U256), RouterError>,
+    ) -> Result<(U256, U256), CallFailure> {
+        let reply_to = match reply_to {
+            Some(reply_to) if !matches!(result, Err(RouterError::PairRejected)) => reply_to,
+            // Never sent or rejected, the pair will not use the allowance
+            _ => {
+                for (token, amount) in call.allowances() {
+                    self.release_allowance(token, call.pair, amount);
+                }
+                return Err(CallFailure::Rejected);
+            }
+        };
+        self.mark_pending(reply_to, call)
+            .map_err(|_| CallFailure::Pending)?;
+        let moved = result.map_err(|_| CallFailure::Pending)?;
+        self.settle_pending(reply_to, Some(moved))
+            .map_err(|_| CallFailure::Pending)?;
+        Ok(moved)
+    }
+
+    async fn execute_hop(
+        &self,
+        route_id: MessageId,
+        hop: &Hop,
+        swap: HopSwap,
+        deadline: u64,
+        config: &Config,
+    ) -> Result<(U256, U256), CallFailure> {
+        let max_input = swap.max_input();
+        self.grant_allowance(hop.token_in, hop.pair, max_input, config)
+            .await
+            .map_err(|_| CallFailure::Rejected)?;
+
+        let call = PendingCall {
+            route_id,
+            pair: hop.pair,
+            kind: PendingKind::Swap {
+                token_in: hop.token_in,
+                token_out: hop.token_out,
+                max_input,
+                exact_output: matches!(swap, HopSwap::ExactOutput { .. }),
+            },
+        };
+        let reply_to = Cell::new(None);
+        let bind_reply = |id: MessageId| reply_to.set(Some(id));
+        let result = match swap {
+            HopSwap::ExactInput {
+                amount_in,
+                amount_out_min,
+            } => {
+                calls::swap_exact_tokens_for_tokens(
+                    hop.pair,
+                    amount_in,
+                    amount_out_min,
+                    hop.is_token0_to_token1,
+                    deadline,
+                    config,
+                    bind_reply,
+                )
+                .await
+            }
+            HopSwap::ExactOutput {
+                amount_out,
+                amount_in_max,
+            } => {
+                calls::swap_tokens_for_exact_tokens(
+                    hop.pair,
+                    amount_out,
+                    amount_in_max,
+                    hop.is_token0_to_token1,
+                    deadline,
+                    config,
+                    bind_reply,
+                )
+                .await
+            }
+        };
+        self.finish_call(reply_to.get(), call, result)
+    }
+}
+
+/// Sends `amount` of native VARA to `to` in a message with an empty payload.
+/// Nothing waits for a reply: a program that can't decode the message (any Sails program)
+/// fails it and the value bounces back to the router, which doesn't track it.
+fn send_value(to: ActorId, amount: U256) -> Result<(), RouterError> {
+    let value = u128::try_from(amount).map_err(|_| RouterError::Overflow)?;
+    msg::send_bytes(to, b"", value).map_err(|_| RouterError::ValueTransferFailed)?;
+    Ok(())
+}
+
+fn check_deadline(deadline: u64) -> Result<(), RouterError> {
+    if exec::block_timestamp() > deadline {
+        return Err(RouterError::DeadlineExpired);
+    }
+    Ok(())
+}
@@ -0,0 +1,680 @@
+use sails_rs::{cell::RefCell, collections::HashMap, gstd::msg, prelude::*};
+
+mod calls;
+mod funcs;
+pub mod pending;
+
+use pending::PendingCall;
+
+pub struct RouterService<'a> {
+    state: &'a RefCell<State>,
+}
+
+#[derive(Debug, Default)]
+pub struct State {
+    pub factory_id: ActorId,
+    pub admin: ActorId,
+    /// Wrapped VARA token, used by the routes that take or pay native VARA.
+    pub wvara: ActorId,
+    pub config: Config,
+    /// Tokens held by the router on behalf of in-flight (or failed) routes,
+    /// keyed by the id of the message that started the route.
+    pub routes: HashMap<MessageId, Custody>,
+    /// Allowance the router has granted to `(token, pair)` and not yet consumed.
+    pub allowances: HashMap<(ActorId, ActorId), U256>,
+    /// Pair calls that reached the pair but got no usable reply, keyed by the id
+    /// of the message sent to the pair.
+    pub pending: HashMap<MessageId, PendingCall>,
+}
+
+/// Config that will be used to send messages to the other programs.
+#[derive(Default, Debug, Decode, Encode, TypeInfo, Clone)]
+pub struct Config {
+    /// Gas limit for token operations and queries. Token operations include:
+    /// - Approve
+    /// - Transfer
+    /// - TransferFrom
+    gas_for_token_ops: u64,
+    /// Gas to reserve for reply processing.
+    gas_for_reply_deposit: u64,
+    /// Timeout in blocks that current program will wait for reply from
+    /// the other programs such as VFT
+    reply_timeout: u32,
+    /// Gas limit for a single swap or liquidity call sent to a pair.
+    /// Must be greater than the pair's `gas_for_full_tx`.
+    gas_for_swap: u64,
+}
+
+/// Tokens the router holds for a single route.
+#[derive(Debug, Default, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub struct Custody {
+    /// Account that started the route and receives refunds.
+    pub user: ActorId,
+    /// Non-zero balances per token.
+    pub balances: Vec<(ActorId, U256)>,
+    /// Whether wVARA held for the route is paid out as native VARA.
+    pub native: bool,
+    /// Pair call, by the id of the message sent to the pair, whose outcome is unknown.
+    /// The balances don't account for it until it is settled.
+    pub pending: Option<MessageId>,
+}
+
+impl Custody {
+    pub fn new(user: ActorId, native: bool) -> Self {
+        Self {
+            user,
+            balances: Vec::new(),
+            native,
+            pending: None,
+        }
+    }
+
+    pub fn credit(&mut self, token: ActorId, amount: U256) -> Result<(), RouterError> {
+        if amount.is_zero() {
+            return Ok(());
+        }
+        match self.balances.iter_mut().find(|(t, _)| *t == token) {
+            Some((_, balance)) => {
+                *balance = balance.checked_add(amount).ok_or(RouterError::Overflow)?;
+            }
+            None => self.balances.push((token, amount)),
+        }
+        Ok(())
+    }
+
+    pub fn debit(&mut self, token: ActorId, amount: U256) -> Result<(), RouterError> {
+        let pos = self
+            .balances
+            .iter()
+            .position(|(t, _)| *t == token)
+            .ok_or(RouterError::InsufficientCustody)?;
+        let balance = &mut self.balances[pos].1;
+        *balance = balance
+            .checked_sub(amount)
+            .ok_or(RouterError::InsufficientCustody)?;
+        if balance.is_zero() {
+            self.balances.swap_remove(pos);
+        }
+        Ok(())
+    }
+
+    pub fn balance(&self, token: ActorId) -> U256 {
+        self.balances
+            .iter()
+            .find(|(t, _)| *t == token)
+            .map(|(_, b)| *b)
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.balances.is_empty()
+    }
+}
+
+/// Result of a routed swap.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub enum SwapOutcome {
+    /// Every hop succeeded and the output was delivered to the recipient.
+    Completed { amount_in: U256, amount_out: U256 },
+    /// Hop `failed_hop` failed and the tokens held for the route were returned to the user.
+    Refunded {
+        failed_hop: u32,
+        refunded: Vec<(ActorId, U256)>,
+    },
+    /// The pair of hop `failed_hop` got the swap but its reply timed out or couldn't be decoded.
+    /// The route keeps its tokens until the hop is settled, then they can be claimed.
+    Pending { failed_hop: u32 },
+}
+
+/// Result of a liquidity call paired with native VARA.
+#[derive(Debug, Clone, Encode, Decode, TypeInfo, PartialEq, Eq)]
+pub enum LiquidityOutcome {
+    /// Liquidity was added; what the pair did not take was returned to the user.
+    Added {
+        amount_token: U256,
+        amount_vara: U256,
+        liquidity: U256,
+    },
+    /// Liquidity was removed and both amounts were delivered to the recipient.
+    Removed {
+        amount_token: U256,
+        amount_vara: U256,
+        liquidity: U256,
+    },
+    /// The pair call failed and the tokens held for it were returned to the user.
+    Refunded { refunded: Vec<(ActorId, U256)> },
+    /// The pair got the call but its reply timed out or couldn't be decoded, as `SwapOutcome::Pending`.
+    Pending,
+}
+
+/// Which ends of a route are native VARA instead of a token.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Native {
+    pub input: bool,
+    pub output: bool,
+}
+
+#[event]
+#[derive(Debug, Encode, Decode, TypeInfo)]
+pub enum RouterEvent {
+    Swap {
+        user_id: ActorId,
+        to: ActorId,
+        path: Vec<ActorId>,
+        amount_in: U256,
+        amount_out: U256,
+    },
+    RouteRefunded {
+        user_id: ActorId,
+        route_id: MessageId,
+        failed_hop: u32,
+        refunded: Vec<(ActorId, U256)>,
+    },
+    RoutePending {
+        user_id: ActorId,
+        route_id: MessageId,
+        failed_hop: u32,
+    },
+    RouteResolved {
+        route_id: MessageId,
+    },
+    CustodyClaimed {
+        user_id: ActorId,
+        route_id: MessageId,
+    },
+    LiquidityAdded {
+        user_id: ActorId,
+        to: ActorId,
+        pair: ActorId,
+        amount_token: U256,
+        amount_vara: U256,
+        liquidity: U256,
+    },
+    LiquidityRemoved {
+        user_id: ActorId,
+        to: ActorId,
+        pair: ActorId,
+        amount_token: U256,
+        amount_vara: U256,
+        liquidity: U256,
+    },
+}
+
+#[derive(Debug)]
+pub enum RouterError {
+    InvalidPath,
+    ZeroAmount,
+    DeadlineExpired,
+    PairNotFound,
+    InsufficientLiquidity,
+    InsufficientOutputAmount,
+    ExcessiveInputAmount,
+    SendFailure,
+    ReplyTimeout,
+    ReplyFailure,
+    PairRejected,
+    UnableToDecode,
+    TokenTransferFailed,
+    ApproveFailed,
+    InsufficientCustody,
+    RouteNotFound,
+    RoutePending,
+    RouteNotPending,
+    Overflow,
+    Unauthorized,
+    ValueTransferFailed,
+    EventError,
+}
+
+impl<'a> RouterService<'a> {
+    pub fn new(state: &'a RefCell<State>) -> Self {
+        Self { state }
+    }
+
+    #[inline]
+    pub fn with_state<R>(&self, f: impl FnOnce(&State) -> R) -> R {
+        let st = self.state.borrow();
+        f(&st)
+    }
+
+    #[inline]
+    pub fn with_state_mut<R>(&self, f: impl FnOnce(&mut State) -> R) -> R {
+        let mut st = self.state.borrow_mut();
+        f(&mut st)
+    }
+
+    fn ensure_admin(&self) -> Result<(), RouterError> {
+        if self.with_state(|st| st.admin == msg::source()) {
+            Ok(())
+        } else {
+            Err(RouterError::Unauthorized)
+        }
+    }
+}
+
+#[sails_rs::service(events = RouterEvent)]
+impl<'a> RouterService<'a> {
+    /// Swaps an exact amount of `path[0]` for as many `path[n-1]` tokens as possible,
+    /// hopping through every pair along the path.
+    ///
+    /// # Arguments
+    /// * `amount_in` - Exact amount of the first token to swap
+    /// * `amount_out_min` - Minimum amount of the last token to receive (slippage protection for the whole route)
+    /// * `path` - Distinct tokens to swap through; each adjacent couple must have a pair registered in the factory
+    /// * `to` - Recipient of the output tokens
+    /// * `deadline` - Unix timestamp after which the transaction will revert
+    ///
+    /// The caller must approve the router for `amount_in` of `path[0]`.
+    /// If the pair rejects a hop, the tokens the router holds for the route are returned to the caller.
+    /// If the pair's reply times out or can't be decoded, the route is left pending instead:
+    /// a late reply settles it, otherwise the admin does with `resolve_route`.
+    #[export(unwrap_result)]
+    pub async fn swap_exact_tokens_for_tokens(
+        &mut self,
+        amount_in: U256,
+        amount_out_min: U256,
+        path: Vec<ActorId>,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<SwapOutcome, RouterError> {
+        let outcome = self
+            .swap_exact_input(
+                amount_in,
+                amount_out_min,
+                &path,
+                to,
+                deadline,
+                Native::default(),
+            )
+            .await?;
+        self.emit_outcome(&outcome, path, to)?;
+        Ok(outcome)
+    }
+
+    /// Swaps as few `path[0]` tokens as possible for an exact amount of `path[n-1]`,
+    /// hopping through every pair along the path.
+    ///
+    /// # Arguments
+    /// * `amount_out` - Exact amount of the last token to receive
+    /// * `amount_in_max` - Maximum amount of the first token to pay (slippage protection for the whole route)
+    /// * `path` - Distinct tokens to swap through; each adjacent couple must have a pair registered in the factory
+    /// * `to` - Recipient of the output tokens
+    /// * `deadline` - Unix timestamp after which the transaction will revert
+    ///
+    /// The caller must approve the router for `amount_in_max` of `path[0]`.
+    /// Input left unused by a hop is returned to the caller together with the output.
+    #[export(unwrap_result)]
+    pub async fn swap_tokens_for_exact_tokens(
+        &mut self,
+        amount_out: U256,
+        amount_in_max: U256,
+        path: Vec<ActorId>,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<SwapOutcome, RouterError> {
+        let outcome = self
+            .swap_exact_output(
+                amount_out,
+                amount_in_max,
+                &path,
+                to,
+                deadline,
+                Native::default(),
+            )
+            .await?;
+        self.emit_outcome(&outcome, path, to)?;
+        Ok(outcome)
+    }
+
+    /// Same as `swap_exact_tokens_for_tokens`, but swaps the attached VARA.
+    /// The value is wrapped into wVARA, so `path` must start with the wVARA token.
+    /// If a hop fails, the refund of wVARA is paid in VARA.
+    #[export(unwrap_result)]
+    pub async fn swap_exact_vara_for_tokens(
+        &mut self,
+        amount_out_min: U256,
+        path: Vec<ActorId>,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<SwapOutcome, RouterError> {
+        let native = Native {
+            input: true,
+            output: false,
+        };
+        let outcome = self
+            .swap_exact_input(
+                U256::from(msg::value()),
+                amount_out_min,
+                &path,
+                to,
+                deadline,
+                native,
+            )
+            .await?;
+        self.emit_outcome(&outcome, path, to)?;
+        Ok(outcome)
+    }
+
+    /// Same as `swap_tokens_for_exact_tokens`, paid with the attached VARA.
+    /// The attached value is the maximum input; what the route does not need is sent back.
+    /// `path` must start with the wVARA token.
+    #[export(unwrap_result)]
+    pub async fn swap_vara_for_exact_tokens(
+        &mut self,
+        amount_out: U256,
+        path: Vec<ActorId>,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<SwapOutcome, RouterError> {
+        let native = Native {
+            input: true,
+            output: false,
+        };
+        let outcome = self
+            .swap_exact_output(
+                amount_out,
+                U256::from(msg::value()),
+                &path,
+                to,
+                deadline,
+                native,
+            )
+            .await?;
+        self.emit_outcome(&outcome, path, to)?;
+        Ok(outcome)
+    }
+
+    /// Same as `swap_exact_tokens_for_tokens`, but `path` ends with the wVARA token
+    /// and the output is unwrapped and sent to `to` as VARA.
+    /// VARA is sent as a plain value message, so `to` should be an account: a program
+    /// rejecting the message bounces the value to the router, where nobody can claim it.
+    #[export(unwrap_result)]
+    pub async fn swap_exact_tokens_for_vara(
+        &mut self,
+        amount_in: U256,
+        amount_out_min: U256,
+        path: Vec<ActorId>,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<SwapOutcome, RouterError> {
+        let native = Native {
+            input: false,
+            output: true,
+        };
+        let outcome = self
+            .swap_exact_input(amount_in, amount_out_min, &path, to, deadline, native)
+            .await?;
+        self.emit_outcome(&outcome, path, to)?;
+        Ok(outcome)
+    }
+
+    /// Same as `swap_tokens_for_exact_tokens`, but `path` ends with the wVARA token
+    /// and the output is unwrapped and sent to `to` as VARA, like `swap_exact_tokens_for_vara`.
+    #[export(unwrap_result)]
+    pub async fn swap_tokens_for_exact_vara(
+        &mut self,
+        amount_out: U256,
+        amount_in_max: U256,
+        path: Vec<ActorId>,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<SwapOutcome, RouterError> {
+        let native = Native {
+            input: false,
+            output: true,
+        };
+        let outcome = self
+            .swap_exact_output(amount_out, amount_in_max, &path, to, deadline, native)
+            .await?;
+        self.emit_outcome(&outcome, path, to)?;
+        Ok(outcome)
+    }
+
+    /// Adds liquidity to the `token`/wVARA pair with `token` and the attached VARA.
+    ///
+    /// # Arguments
+    /// * `token` - The other token of the pair
+    /// * `amount_token_desired` - Amount of `token` to add at most
+    /// * `amount_token_min` - Minimum amount of `token` to add (slippage protection)
+    /// * `amount_vara_min` - Minimum amount of VARA to add (slippage protection)
+    /// * `to` - Recipient of the LP tokens
+    /// * `deadline` - Unix timestamp after which the transaction will revert
+    ///
+    /// The caller must approve the router for `amount_token_desired` of `token`.
+    /// The attached value is the VARA to add at most; what the pair does not take
+    /// is returned to the caller, VARA as VARA.
+    #[export(unwrap_result)]
+    pub async fn add_liquidity_vara(
+        &mut self,
+        token: ActorId,
+        amount_token_desired: U256,
+        amount_token_min: U256,
+        amount_vara_min: U256,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<LiquidityOutcome, RouterError> {
+        let (pair, outcome) = self
+            .add_liquidity_native(
+                token,
+                amount_token_desired,
+                amount_token_min,
+                amount_vara_min,
+                to,
+                deadline,
+            )
+            .await?;
+        self.emit_liquidity_outcome(&outcome, pair, to)?;
+        Ok(outcome)
+    }
+
+    /// Removes `liquidity` from the `token`/wVARA pair and sends `token` and the
+    /// unwrapped VARA to `to`.
+    ///
+    /// The caller must approve the router for `liquidity` of the pair's LP token.
+    /// If the pair call fails, the LP tokens are returned to the caller.
+    /// `to` should be an account, see `swap_exact_tokens_for_vara`.
+    #[export(unwrap_result)]
+    pub async fn remove_liquidity_vara(
+        &mut self,
+        token: ActorId,
+        liquidity: U256,
+        amount_token_min: U256,
+        amount_vara_min: U256,
+        to: ActorId,
+        deadline: u64,
+    ) -> Result<LiquidityOutcome, RouterError> {
+        let (pair, outcome) = self
+            .remove_liquidity_native(
+                token,
+                liquidity,
+                amount_token_min,
+                amount_vara_min,
+                to,
+                deadline,
+            )
+            .await?;
+        self.emit_liquidity_outcome(&outcome, pair, to)?;
+        Ok(outcome)
+    }
+
+    /// Sends the tokens still held for a route back to the user that started it.
+    /// Used when the refund after a failed hop could not be delivered, or once a pending
+    /// route is settled. Callable by the route's user or the admin.
+    #[export(unwrap_result)]
+    pub async fn claim(&mut self, route_id: MessageId) -> Result<(), RouterError> {
+        let caller = msg::source();
+        let user = self.with_state(|st| {
+            let custody = st.routes.get(&route_id).ok_or(RouterError::RouteNotFound)?;
+            if caller != custody.user && caller != st.admin {
+                return Err(RouterError::Unauthorized);
+            }
+            if custody.pending.is_some() {
+                return Err(RouterError::RoutePending);
+            }
+            Ok(custody.user)
+        })?;
+
+        let config = self.with_state(|st| st.config.clone());
+        self.release_custody(route_id, None, user, &config).await?;
+
+        self.emit_event(RouterEvent::CustodyClaimed {
+            user_id: user,
+            route_id,
+        })
+        .map_err(|_| RouterError::EventError)
+    }
+
+    /// Settles a pending route whose pair call got no usable reply. Admin only.
+    ///
+    /// # Arguments
+    /// * `route_id` - The pending route
+    /// * `moved` - What the pair call moved, as checked on the pair: `(amount_in, amount_out)`
+    ///   of a swap hop, `(amount_a, amount_b)` of a liquidity call in the pair's token order,
+    ///   or `None` if the pair took nothing or has already returned it
+    ///
+    /// The allowance granted for the call is revoked first, so the pair can't use it afterwards.
+    /// The route's tokens can then be claimed.
+    #[export(unwrap_result)]
+    pub async fn resolve_route(
+        &mut self,
+        route_id: MessageId,
+        moved: Option<(U256, U256)>,
+    ) -> Result<(), RouterError> {
+        self.ensure_admin()?;
+        self.resolve_pending(route_id, moved).await?;
+        self.emit_event(RouterEvent::RouteResolved { route_id })
+            .map_err(|_| RouterError::EventError)
+    }
+
+    #[export(unwrap_result)]
+    pub fn update_config(&mut self, config: Config) -> Result<(), RouterError> {
+        self.ensure_admin()?;
+        self.with_state_mut(|st| {
+            st.config = config;
+        });
+        Ok(())
+    }
+
+    #[export(unwrap_result)]
+    pub fn set_admin(&mut self, admin: ActorId) -> Result<(), RouterError> {
+        self.ensure_admin()?;
+        self.with_state_mut(|st| {
+            st.admin = admin;
+        });
+        Ok(())
+    }
+
+    #[export]
+    pub fn factory_id(&self) -> ActorId {
+        self.with_state(|st| st.factory_id)
+    }
+
+    #[export]
+    pub fn admin(&self) -> ActorId {
+        self.with_state(|st| st.admin)
+    }
+
+    #[export]
+    pub fn wvara(&self) -> ActorId {
+        self.with_state(|st| st.wvara)
+    }
+
+    #[export]
+    pub fn config(&self) -> Config {
+        self.with_state(|st| st.config.clone())
+    }
+
+    /// Returns the routes that still hold tokens for `user`.
+    #[export]
+    pub fn custody_of(&self, user: ActorId) -> Vec<(MessageId, Custody)> {
+        self.with_state(|st| {
+            st.routes
+                .iter()
+                .filter(|(_, c)| c.user == user)
+                .map(|(id, c)| (*id, c.clone()))
+                .collect()
+        })
+    }
+
+    fn emit_outcome(
+        &self,
+        outcome: &SwapOutcome,
+        path: Vec<ActorId>,
+        to: ActorId,
+    ) -> Result<(), RouterError> {
+        let event = match outcome {
+            SwapOutcome::Completed {
+                amount_in,
+                amount_out,
+            } => RouterEvent::Swap {
+                user_id: msg::source(),
+                to,
+                path,
+                amount_in: *amount_in,
+                amount_out: *amount_out,
+            },
+            SwapOutcome::Refunded {
+                failed_hop,
+                refunded,
+            } => RouterEvent::RouteRefunded {
+                user_id: msg::source(),
+                route_id: msg::id(),
+                failed_hop: *failed_hop,
+                refunded: refunded.clone(),
+            },
+            SwapOutcome::Pending { failed_hop } => RouterEvent::RoutePending {
+                user_id: msg::source(),
+                route_id: msg::id(),
+                failed_hop: *failed_hop,
+            },
+        };
+        self.emit_event(event).map_err(|_| RouterError::EventError)
+    }
+
+    fn emit_liquidity_outcome(
+        &self,
+        outcome: &LiquidityOutcome,
+        pair: ActorId,
+        to: ActorId,
+    ) -> Result<(), RouterError> {
+        let event = match outcome {
+            LiquidityOutcome::Added {
+                amount_token,
+                amount_vara,
+                liquidity,
+            } => RouterEvent::LiquidityAdded {
+                user_id: msg::source(),
+                to,
+                pair,
+                amount_token: *amount_token,
+                amount_vara: *amount_vara,
+                liquidity: *liquidity,
+            },
+            LiquidityOutcome::Removed {
+                amount_token,
+                amount_vara,
+                liquidity,
+            } => RouterEvent::LiquidityRemoved {
+                user_id: msg::source(),
+                to,
+                pair,
+                amount_token: *amount_token,
+                amount_vara: *amount_vara,
+                liquidity: *liquidity,
+            },
+            // The pair is the only hop of a liquidity call
+            LiquidityOutcome::Refunded { refunded } => RouterEvent::RouteRefunded {
+                user_id: msg::source(),
+                route_id: msg::id(),
+                failed_hop: 0,
+                refunded: refunded.clone(),
+            },
+            LiquidityOutcome::Pending => RouterEvent::RoutePending {
+                user_id: msg::source(),
+                route_id: msg::id(),
+                failed_hop: 0,
+            },
+        };
+        self.emit_event(event).map_err(|_| RouterError::EventError)
+    }
+}
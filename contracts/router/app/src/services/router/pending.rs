@@ -0,0 +1,199 @@
+use crate::services::router::{Custody, RouterError, RouterService, State, calls};
+use gstd::errors::ReplyCode;
+use sails_rs::{gstd::msg, prelude::*};
+
+/// What a pair call of a route does, to settle it once its outcome is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum PendingKind {
+    /// Swap of at most `max_input` of `token_in` for `token_out`.
+    Swap {
+        token_in: ActorId,
+        token_out: ActorId,
+        max_input: U256,
+        exact_output: bool,
+    },
+    /// Liquidity added with at most `amount0_max`/`amount1_max` of the pair's tokens.
+    AddLiquidity {
+        token0: ActorId,
+        token1: ActorId,
+        amount0_max: U256,
+        amount1_max: U256,
+    },
+    /// `liquidity` of the pair's LP token burnt for the pair's tokens.
+    RemoveLiquidity {
+        token0: ActorId,
+        token1: ActorId,
+        liquidity: U256,
+    },
+}
+
+/// A pair call the pair got but whose outcome the route is still waiting for.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PendingCall {
+    pub route_id: MessageId,
+    pub pair: ActorId,
+    pub kind: PendingKind,
+}
+
+impl PendingCall {
+    /// Allowances granted to the pair for the call.
+    pub fn allowances(&self) -> Vec<(ActorId, U256)> {
+        match self.kind {
+            PendingKind::Swap {
+                token_in,
+                max_input,
+                ..
+            } => vec![(token_in, max_input)],
+            PendingKind::AddLiquidity {
+                token0,
+                token1,
+                amount0_max,
+                amount1_max,
+            } => vec![(token0, amount0_max), (token1, amount1_max)],
+            PendingKind::RemoveLiquidity { .. } => Vec::new(),
+        }
+    }
+
+    /// Moves the custody of the route by what the pair reported: `(amount_in, amount_out)`
+    /// of a swap, `(amount0, amount1)` of a liquidity call. `None` moves nothing.
+    fn apply(&self, custody: &mut Custody, moved: Option<(U256, U256)>) -> Result<(), RouterError> {
+        let Some((a, b)) = moved else {
+            return Ok(());
+        };
+        match self.kind {
+            PendingKind::Swap {
+                token_in,
+                token_out,
+                ..
+            } => {
+                custody.debit(token_in, a)?;
+                custody.credit(token_out, b)
+            }
+            PendingKind::AddLiquidity { token0, token1, .. } => {
+                custody.debit(token0, a)?;
+                custody.debit(token1, b)
+            }
+            PendingKind::RemoveLiquidity {
+                token0,
+                token1,
+                liquidity,
+            } => {
+                custody.debit(self.pair, liquidity)?;
+                custody.credit(token0, a)?;
+                custody.credit(token1, b)
+            }
+        }
+    }
+}
+
+impl State {
+    /// Forgets `amount` of the allowance granted to `spender` once the call using it is over.
+    pub fn release_allowance(&mut self, token: ActorId, spender: ActorId, amount: U256) {
+        if let Some(allowance) = self.allowances.get_mut(&(token, spender)) {
+            *allowance = allowance.saturating_sub(amount);
+            if allowance.is_zero() {
+                self.allowances.remove(&(token, spender));
+            }
+        }
+    }
+}
+
+impl<'a> RouterService<'a> {
+    /// Records that the route is waiting for the pair call sent as `reply_to`.
+    /// The route can't be claimed until the call is settled.
+    pub fn mark_pending(&self, reply_to: MessageId, call: PendingCall) -> Result<(), RouterError> {
+        self.with_state_mut(|st| {
+            st.routes
+                .get_mut(&call.route_id)
+                .ok_or(RouterError::RouteNotFound)?
+                .pending = Some(reply_to);
+            st.pending.insert(reply_to, call);
+            Ok(())
+        })
+    }
+
+    /// Applies the outcome of the pending call `reply_to` to its route and releases the
+    /// allowance granted for it. Returns the route.
+    pub fn settle_pending(
+        &self,
+        reply_to: MessageId,
+        moved: Option<(U256, U256)>,
+    ) -> Result<MessageId, RouterError> {
+        self.with_state_mut(|st| {
+            let call = st
+                .pending
+                .get(&reply_to)
+                .ok_or(RouterError::RouteNotPending)?
+                .clone();
+            let custody = st
+                .routes
+                .get_mut(&call.route_id)
+                .ok_or(RouterError::RouteNotFound)?;
+            // All or nothing, a call that can't be applied stays pending
+            let mut settled = custody.clone();
+            call.apply(&mut settled, moved)?;
+            settled.pending = None;
+            *custody = settled;
+
+            st.pending.remove(&reply_to);
+            for (token, amount) in call.allowances() {
+                st.release_allowance(token, call.pair, amount);
+            }
+            Ok(call.route_id)
+        })
+    }
+
+    /// Settles the pending route `route_id` with what the admin found the pair call moved,
+    /// once the pair can no longer use the allowance granted for it.
+    pub async fn resolve_pending(
+        &self,
+        route_id: MessageId,
+        moved: Option<(U256, U256)>,
+    ) -> Result<(), RouterError> {
+        let (reply_to, call, config) = self.with_state(|st| {
+            let reply_to = st
+                .routes
+                .get(&route_id)
+                .ok_or(RouterError::RouteNotFound)?
+                .pending
+                .ok_or(RouterError::RouteNotPending)?;
+            let call = st
+                .pending
+                .get(&reply_to)
+                .ok_or(RouterError::RouteNotPending)?
+                .clone();
+            Ok::<_, RouterError>((reply_to, call, st.config.clone()))
+        })?;
+
+        // Cut the allowance down to what the other calls to the pair still need
+        for (token, amount) in call.allowances() {
+            let remaining = self.with_state(|st| {
+                st.allowances
+                    .get(&(token, call.pair))
+                    .map_or(U256::zero(), |a| a.saturating_sub(amount))
+            });
+            calls::approve(token, call.pair, remaining, &config).await?;
+        }
+        self.settle_pending(reply_to, moved)?;
+        Ok(())
+    }
+
+    /// Settles a pending call with its late reply. A rejection by the pair moved nothing;
+    /// any other error reply leaves the outcome unknown, so the route stays pending for the admin.
+    pub fn on_reply(&self) {
+        let reply_to = msg::reply_to().expect("reply_to only in reply context");
+        let Some(kind) = self.with_state(|st| st.pending.get(&reply_to).map(|c| c.kind)) else {
+            return;
+        };
+        let bytes = msg::load_bytes().expect("Unable to load bytes");
+        let moved = match msg::reply_code() {
+            Ok(ReplyCode::Success(_)) => match calls::decode_pair_reply(&kind, &bytes) {
+                Some(moved) => Some(moved),
+                None => return,
+            },
+            Ok(ReplyCode::Error(reason)) if calls::is_pair_rejection(&reason, &bytes) => None,
+            _ => return,
+        };
+        let _ = self.settle_pending(reply_to, moved);
+    }
+}
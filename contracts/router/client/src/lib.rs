@@ -0,0 +1,3 @@
+#![no_std]
+#![allow(clippy::doc_lazy_continuation)]
+include!(concat!(env!("OUT_DIR"), "/router_client.rs"));
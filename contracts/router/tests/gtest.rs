@@ -0,0 +1,778 @@
+use extended_vft_client::vft::{Vft, VftImpl};
+use extended_vft_client::{ExtendedVftClient, ExtendedVftClientCtors, ExtendedVftClientProgram};
+use factory_app::ONE_VARA;
+use factory_client::{factory::Factory, FactoryClient, FactoryClientCtors};
+use pair_client::pair::{Pair, PairImpl};
+use pair_client::{vft::Vft as _, Pair as PairClient, PairProgram};
+use router_client::{
+    router::*, Config, LiquidityOutcome, Router as RouterClient, RouterCtors, SwapOutcome,
+};
+use sails_rs::gtest::System;
+use sails_rs::{client::*, prelude::*};
+use wvara_client::{
+    vft_native_exchange::VftNativeExchange as _, Wvara as WvaraClient, WvaraCtors, WvaraProgram,
+};
+
+const ADMIN_ID: u64 = 1;
+const USER_ID: u64 = 2;
+const LP_ID: u64 = 3;
+const RECIPIENT_ID: u64 = 4;
+/// wVARA the LP puts in the C/wVARA pair.
+const WVARA_LIQUIDITY: u128 = 100 * ONE_VARA;
+
+type Token = Service<VftImpl, GtestEnv>;
+
+struct Setup {
+    env: GtestEnv,
+    router: Service<RouterImpl, GtestEnv>,
+    tokens: Vec<Token>,
+    /// The wVARA token, through the `Vft` calls it shares with the other tokens.
+    wvara: Token,
+    pairs: Vec<Service<PairImpl, GtestEnv>>,
+}
+
+fn default_factory_config() -> factory_client::Config {
+    factory_client::Config {
+        gas_for_token_ops: 10_000_000_000,
+        gas_for_reply_deposit: 10_000_000_000,
+        reply_timeout: 100,
+        gas_for_full_tx: 100_000_000_000,
+        gas_for_pair_creation: 200_000_000_000,
+        gas_to_change_fee_to: 10_000_000_000,
+    }
+}
+
+fn default_router_config() -> Config {
+    Config {
+        gas_for_token_ops: 10_000_000_000,
+        gas_for_reply_deposit: 10_000_000_000,
+        reply_timeout: 100,
+        gas_for_swap: 150_000_000_000,
+    }
+}
+
+fn deadline(env: &GtestEnv) -> u64 {
+    env.system().block_timestamp() + 100_000_000
+}
+
+/// Deploys the factory, the router, three tokens A, B, C and wVARA,
+/// with pairs A/B and B/C funded with `liquidity` of each token
+/// and a C/wVARA pair funded with `liquidity` of C and `WVARA_LIQUIDITY`.
+async fn setup(liquidity: U256) -> Setup {
+    let system = System::new();
+    let admin = ActorId::from(ADMIN_ID);
+    let user = ActorId::from(USER_ID);
+    let lp = ActorId::from(LP_ID);
+    system.mint_to(admin, 1000 * ONE_VARA);
+    system.mint_to(user, 1000 * ONE_VARA);
+    system.mint_to(lp, 1000 * ONE_VARA);
+    let env = GtestEnv::new(system, admin);
+
+    let pair_code_id = env.system().submit_code(pair::WASM_BINARY);
+    let factory_code_id = env.system().submit_code(factory::WASM_BINARY);
+    let router_code_id = env.system().submit_code(router::WASM_BINARY);
+    let wvara_code_id = env.system().submit_code(wvara::WASM_BINARY);
+
+    let release_path = "../target/wasm32-gear/release/extended_vft.opt.wasm";
+    let debug_path = "../target/wasm32-gear/debug/extended_vft.opt.wasm";
+    let wasm_path = if std::path::Path::new(release_path).exists() {
+        release_path
+    } else {
+        debug_path
+    };
+    let token_code_id = env.system().submit_code_file(wasm_path);
+
+    let factory_program = env
+        .deploy::<factory_client::FactoryClientProgram>(factory_code_id, b"salt".to_vec())
+        .new(
+            pair_code_id,
+            admin,
+            ActorId::from(900u64),
+            default_factory_config(),
+            ActorId::from(901u64),
+        )
+        .await
+        .unwrap();
+    let mut factory = factory_program.factory();
+
+    let wvara_program = env
+        .deploy::<WvaraProgram>(wvara_code_id, b"salt".to_vec())
+        .new()
+        .await
+        .unwrap();
+    let wvara_id = wvara_program.id();
+
+    let router_program = env
+        .deploy::<router_client::RouterProgram>(router_code_id, b"salt".to_vec())
+        .new(
+            factory_program.id(),
+            admin,
+            default_router_config(),
+            wvara_id,
+        )
+        .await
+        .unwrap();
+
+    let mut tokens = Vec::new();
+    for name in ["TokenA", "TokenB", "TokenC"] {
+        let program = env
+            .deploy::<ExtendedVftClientProgram>(token_code_id, name.as_bytes().to_vec())
+            .new(name.to_string(), name.to_string(), 6)
+            .await
+            .unwrap();
+        let mut token = program.vft();
+        token.mint(lp, liquidity * 2).await.unwrap();
+        token.mint(user, liquidity).await.unwrap();
+        tokens.push(token);
+    }
+
+    let mut pairs = Vec::new();
+    for (a, b) in [(0, 1), (1, 2)] {
+        let (token_a, token_b) = (tokens[a].actor_id(), tokens[b].actor_id());
+        factory
+            .create_pair(token_a, token_b)
+            .with_params(|p| p.with_value(ONE_VARA))
+            .await
+            .unwrap();
+        let pair_id = factory.get_pair(token_a, token_b).await.unwrap();
+
+        for token in [a, b] {
+            tokens[token]
+                .approve(pair_id, liquidity)
+                .with_params(|p| p.with_actor_id(lp))
+                .await
+                .unwrap();
+        }
+        let mut pair = Actor::<PairProgram, GtestEnv>::new(env.clone(), pair_id).pair();
+        let (token0, _) = pair.get_tokens().await.unwrap();
+        assert!(token0 == token_a || token0 == token_b);
+        pair.add_liquidity(
+            liquidity,
+            liquidity,
+            U256::zero(),
+            U256::zero(),
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(lp))
+        .await
+        .unwrap();
+        pairs.push(pair);
+    }
+
+    // The C/wVARA pair of the `*Vara*` calls, with wVARA the LP wrapped
+    let token_c = tokens[2].actor_id();
+    factory
+        .create_pair(token_c, wvara_id)
+        .with_params(|p| p.with_value(ONE_VARA))
+        .await
+        .unwrap();
+    let pair_id = factory.get_pair(token_c, wvara_id).await.unwrap();
+    let mut wvara = Actor::<ExtendedVftClientProgram, GtestEnv>::new(env.clone(), wvara_id).vft();
+    wvara_program
+        .vft_native_exchange()
+        .mint()
+        .with_params(|p| p.with_actor_id(lp).with_value(WVARA_LIQUIDITY))
+        .await
+        .unwrap();
+    let wvara_liquidity = U256::from(WVARA_LIQUIDITY);
+    for (token, amount) in [(&mut tokens[2], liquidity), (&mut wvara, wvara_liquidity)] {
+        token
+            .approve(pair_id, amount)
+            .with_params(|p| p.with_actor_id(lp))
+            .await
+            .unwrap();
+    }
+    let mut pair = Actor::<PairProgram, GtestEnv>::new(env.clone(), pair_id).pair();
+    let (amount_a, amount_b) = if pair.get_tokens().await.unwrap().0 == token_c {
+        (liquidity, wvara_liquidity)
+    } else {
+        (wvara_liquidity, liquidity)
+    };
+    pair.add_liquidity(
+        amount_a,
+        amount_b,
+        U256::zero(),
+        U256::zero(),
+        deadline(&env),
+    )
+    .with_params(|p| p.with_actor_id(lp))
+    .await
+    .unwrap();
+    pairs.push(pair);
+
+    for token in tokens.iter_mut() {
+        token
+            .approve(router_program.id(), liquidity)
+            .with_params(|p| p.with_actor_id(user))
+            .await
+            .unwrap();
+    }
+
+    Setup {
+        env,
+        router: router_program.router(),
+        tokens,
+        wvara,
+        pairs,
+    }
+}
+
+#[tokio::test]
+async fn router_swaps_exact_input_through_two_pairs() {
+    let liquidity = U256::from(1_000_000_000u64);
+    let Setup {
+        env,
+        mut router,
+        tokens,
+        pairs,
+        ..
+    } = setup(liquidity).await;
+    let user = ActorId::from(USER_ID);
+    let path: Vec<ActorId> = tokens.iter().map(|t| t.actor_id()).collect();
+
+    let amount_in = U256::from(1_000_000u64);
+    let a_is_token0 = pairs[0].get_tokens().await.unwrap().0 == path[0];
+    let b_is_token0 = pairs[1].get_tokens().await.unwrap().0 == path[1];
+    let quote_b = pairs[0]
+        .get_amount_out(amount_in, a_is_token0)
+        .await
+        .unwrap();
+    let expected_out = pairs[1].get_amount_out(quote_b, b_is_token0).await.unwrap();
+
+    let outcome = router
+        .swap_exact_tokens_for_tokens(amount_in, expected_out, path.clone(), user, deadline(&env))
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        SwapOutcome::Completed {
+            amount_in,
+            amount_out: expected_out
+        }
+    );
+
+    assert_eq!(
+        tokens[0].balance_of(user).await.unwrap(),
+        liquidity - amount_in
+    );
+    // Nothing of the intermediate token is left behind
+    assert_eq!(tokens[1].balance_of(user).await.unwrap(), liquidity);
+    assert_eq!(
+        tokens[2].balance_of(user).await.unwrap(),
+        liquidity + expected_out
+    );
+    for token in tokens.iter() {
+        assert!(token.balance_of(router.actor_id()).await.unwrap().is_zero());
+    }
+    assert!(router.custody_of(user).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn router_swaps_for_exact_output_through_two_pairs() {
+    let liquidity = U256::from(1_000_000_000u64);
+    let Setup {
+        env,
+        mut router,
+        tokens,
+        pairs,
+        ..
+    } = setup(liquidity).await;
+    let user = ActorId::from(USER_ID);
+    let recipient = ActorId::from(77u64);
+    let path: Vec<ActorId> = tokens.iter().map(|t| t.actor_id()).collect();
+
+    let amount_out = U256::from(500_000u64);
+    let a_is_token0 = pairs[0].get_tokens().await.unwrap().0 == path[0];
+    let b_is_token0 = pairs[1].get_tokens().await.unwrap().0 == path[1];
+    let needed_b = pairs[1]
+        .get_amount_in(amount_out, b_is_token0)
+        .await
+        .unwrap();
+    let expected_in = pairs[0].get_amount_in(needed_b, a_is_token0).await.unwrap();
+
+    let outcome = router
+        .swap_tokens_for_exact_tokens(
+            amount_out,
+            expected_in * 2,
+            path.clone(),
+            recipient,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        SwapOutcome::Completed {
+            amount_in: expected_in,
+            amount_out
+        }
+    );
+
+    assert_eq!(
+        tokens[0].balance_of(user).await.unwrap(),
+        liquidity - expected_in
+    );
+    assert_eq!(tokens[2].balance_of(recipient).await.unwrap(), amount_out);
+    for token in tokens.iter() {
+        assert!(token.balance_of(router.actor_id()).await.unwrap().is_zero());
+    }
+}
+
+#[tokio::test]
+async fn router_rejects_route_before_taking_funds() {
+    let liquidity = U256::from(1_000_000_000u64);
+    let Setup {
+        env,
+        mut router,
+        tokens,
+        ..
+    } = setup(liquidity).await;
+    let user = ActorId::from(USER_ID);
+    let path: Vec<ActorId> = tokens.iter().map(|t| t.actor_id()).collect();
+
+    // Unreachable minimum output
+    let res = router
+        .swap_exact_tokens_for_tokens(
+            U256::from(1_000u64),
+            liquidity,
+            path.clone(),
+            user,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    // No pair for A/C
+    let res = router
+        .swap_exact_tokens_for_tokens(
+            U256::from(1_000u64),
+            U256::zero(),
+            vec![path[0], path[2]],
+            user,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    // Single-token path
+    let res = router
+        .swap_exact_tokens_for_tokens(
+            U256::from(1_000u64),
+            U256::zero(),
+            vec![path[0]],
+            user,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    // A token met twice, even though every hop has a pair
+    let res = router
+        .swap_tokens_for_exact_tokens(
+            U256::from(1_000u64),
+            U256::from(10_000u64),
+            vec![path[0], path[1], path[0]],
+            user,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    assert_eq!(tokens[0].balance_of(user).await.unwrap(), liquidity);
+    assert!(router.custody_of(user).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn router_refunds_route_rejected_by_pair() {
+    let liquidity = U256::from(1_000_000_000u64);
+    let Setup {
+        env,
+        mut router,
+        tokens,
+        ..
+    } = setup(liquidity).await;
+    let user = ActorId::from(USER_ID);
+    let path = vec![tokens[0].actor_id(), tokens[1].actor_id()];
+    let amount_in = U256::from(1_000_000u64);
+
+    // Not enough gas for the pair, which rejects the swap before moving anything
+    router
+        .update_config(Config {
+            gas_for_swap: 50_000_000_000,
+            ..default_router_config()
+        })
+        .await
+        .unwrap();
+    let outcome = router
+        .swap_exact_tokens_for_tokens(amount_in, U256::zero(), path.clone(), user, deadline(&env))
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        SwapOutcome::Refunded {
+            failed_hop: 0,
+            refunded: vec![(path[0], amount_in)],
+        }
+    );
+    assert_eq!(tokens[0].balance_of(user).await.unwrap(), liquidity);
+    assert!(router.custody_of(user).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn router_keeps_route_pending_until_failed_hop_is_resolved() {
+    let liquidity = U256::from(1_000_000_000u64);
+    let Setup {
+        env,
+        mut router,
+        tokens,
+        mut pairs,
+        ..
+    } = setup(liquidity).await;
+    let user = ActorId::from(USER_ID);
+    let path = vec![tokens[0].actor_id(), tokens[1].actor_id()];
+    let amount_in = U256::from(1_000_000u64);
+
+    // The pair's token call runs out of gas after the pair got the swap,
+    // so the router can't tell what the pair moved
+    pairs[0]
+        .update_config(pair_client::Config {
+            gas_for_token_ops: 1_000,
+            gas_for_reply_deposit: 10_000_000_000,
+            reply_timeout: 100,
+            gas_for_full_tx: 100_000_000_000,
+        })
+        .await
+        .unwrap();
+    let outcome = router
+        .swap_exact_tokens_for_tokens(amount_in, U256::zero(), path.clone(), user, deadline(&env))
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_eq!(outcome, SwapOutcome::Pending { failed_hop: 0 });
+
+    // The custody isn't refunded while the pair may still use it
+    let routes = router.custody_of(user).await.unwrap();
+    assert_eq!(routes.len(), 1);
+    let (route_id, custody) = routes[0].clone();
+    assert!(custody.pending.is_some());
+    assert_eq!(custody.balances, vec![(path[0], amount_in)]);
+    let res = router
+        .claim(route_id)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    // Only the admin settles it, the pair moved nothing
+    let res = router
+        .resolve_route(route_id, None)
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+    router.resolve_route(route_id, None).await.unwrap();
+
+    router
+        .claim(route_id)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_eq!(tokens[0].balance_of(user).await.unwrap(), liquidity);
+    assert!(router.custody_of(user).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn router_checks_native_ends_of_route() {
+    let liquidity = U256::from(1_000_000_000u64);
+    let Setup {
+        env,
+        mut router,
+        tokens,
+        wvara,
+        ..
+    } = setup(liquidity).await;
+    let user = ActorId::from(USER_ID);
+    let path: Vec<ActorId> = tokens.iter().map(|t| t.actor_id()).collect();
+    assert_eq!(router.wvara().await.unwrap(), wvara.actor_id());
+
+    // The route must start with wVARA to take VARA
+    let res = router
+        .swap_exact_vara_for_tokens(U256::zero(), path.clone(), user, deadline(&env))
+        .with_params(|p| p.with_actor_id(user).with_value(ONE_VARA))
+        .await;
+    assert!(res.is_err());
+
+    // and end with it to pay VARA
+    let res = router
+        .swap_exact_tokens_for_vara(
+            U256::from(1_000u64),
+            U256::zero(),
+            path.clone(),
+            user,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    // No value attached
+    let res = router
+        .add_liquidity_vara(
+            path[0],
+            U256::from(1_000u64),
+            U256::zero(),
+            U256::zero(),
+            user,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user))
+        .await;
+    assert!(res.is_err());
+
+    assert_eq!(tokens[0].balance_of(user).await.unwrap(), liquidity);
+    assert!(router.custody_of(user).await.unwrap().is_empty());
+    assert_eq!(env.system().balance_of(router.actor_id()), 0);
+}
+
+#[tokio::test]
+async fn router_wraps_and_unwraps_vara_in_swaps() {
+    let liquidity = U256::from(1_000_000_000u64);
+    let Setup {
+        env,
+        mut router,
+        tokens,
+        wvara,
+        pairs,
+    } = setup(liquidity).await;
+    let user = ActorId::from(USER_ID);
+    let recipient = ActorId::from(RECIPIENT_ID);
+    env.system().mint_to(recipient, 1000 * ONE_VARA);
+    let (token_c, wvara_id) = (tokens[2].actor_id(), wvara.actor_id());
+    let wvara_is_token0 = pairs[2].get_tokens().await.unwrap().0 == wvara_id;
+
+    // The attached value is wrapped and swapped
+    let value = 10 * ONE_VARA;
+    let expected_out = pairs[2]
+        .get_amount_out(U256::from(value), wvara_is_token0)
+        .await
+        .unwrap();
+    let outcome = router
+        .swap_exact_vara_for_tokens(expected_out, vec![wvara_id, token_c], user, deadline(&env))
+        .with_params(|p| p.with_actor_id(user).with_value(value))
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        SwapOutcome::Completed {
+            amount_in: U256::from(value),
+            amount_out: expected_out
+        }
+    );
+    assert_eq!(
+        tokens[2].balance_of(user).await.unwrap(),
+        liquidity + expected_out
+    );
+
+    // The output is unwrapped and paid to the recipient as VARA
+    let amount_in = expected_out;
+    let expected_vara = pairs[2]
+        .get_amount_out(amount_in, !wvara_is_token0)
+        .await
+        .unwrap();
+    let before = env.system().balance_of(recipient);
+    let outcome = router
+        .swap_exact_tokens_for_vara(
+            amount_in,
+            expected_vara,
+            vec![token_c, wvara_id],
+            recipient,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        SwapOutcome::Completed {
+            amount_in,
+            amount_out: expected_vara
+        }
+    );
+    assert_eq!(
+        env.system().balance_of(recipient),
+        before + expected_vara.as_u128()
+    );
+
+    // Only the quoted input is wrapped, the rest of the value goes back
+    let amount_out = U256::from(50_000_000u64);
+    let needed = pairs[2]
+        .get_amount_in(amount_out, wvara_is_token0)
+        .await
+        .unwrap();
+    let attached = needed.as_u128() + 10 * ONE_VARA;
+    let supply = wvara.total_supply().await.unwrap();
+    let before = env.system().balance_of(user);
+    let outcome = router
+        .swap_vara_for_exact_tokens(amount_out, vec![wvara_id, token_c], user, deadline(&env))
+        .with_params(|p| p.with_actor_id(user).with_value(attached))
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        SwapOutcome::Completed {
+            amount_in: needed,
+            amount_out
+        }
+    );
+    assert_eq!(wvara.total_supply().await.unwrap(), supply + needed);
+    assert!(before - env.system().balance_of(user) < attached);
+
+    assert!(wvara.balance_of(router.actor_id()).await.unwrap().is_zero());
+    assert_eq!(env.system().balance_of(router.actor_id()), 0);
+    assert!(router.custody_of(user).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn router_adds_and_removes_liquidity_with_vara() {
+    let liquidity = U256::from(1_000_000_000u64);
+    let Setup {
+        env,
+        mut router,
+        tokens,
+        wvara,
+        pairs,
+    } = setup(liquidity).await;
+    let user = ActorId::from(USER_ID);
+    let recipient = ActorId::from(RECIPIENT_ID);
+    env.system().mint_to(recipient, 1000 * ONE_VARA);
+    let (token_c, pair_id) = (tokens[2].actor_id(), pairs[2].actor_id());
+
+    // A tenth of the reserves, with twice the VARA needed attached
+    let amount_token = liquidity / 10;
+    let amount_vara = U256::from(WVARA_LIQUIDITY / 10);
+    let attached = 2 * WVARA_LIQUIDITY / 10;
+    let supply = wvara.total_supply().await.unwrap();
+    let before = env.system().balance_of(user);
+    let outcome = router
+        .add_liquidity_vara(
+            token_c,
+            amount_token,
+            amount_token,
+            amount_vara,
+            user,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user).with_value(attached))
+        .await
+        .unwrap();
+    let LiquidityOutcome::Added {
+        amount_token: added_token,
+        amount_vara: added_vara,
+        liquidity: minted,
+    } = outcome
+    else {
+        panic!("unexpected outcome {:?}", outcome);
+    };
+    assert_eq!((added_token, added_vara), (amount_token, amount_vara));
+    // What the pair didn't take went back as VARA
+    assert_eq!(wvara.total_supply().await.unwrap(), supply + amount_vara);
+    assert!(before - env.system().balance_of(user) < attached);
+    let mut lp = Actor::<PairProgram, GtestEnv>::new(env.clone(), pair_id).vft();
+    assert_eq!(lp.balance_of(user).await.unwrap(), minted);
+
+    // The wVARA share is unwrapped and paid to the recipient
+    lp.approve(router.actor_id(), minted)
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    let before = env.system().balance_of(recipient);
+    let outcome = router
+        .remove_liquidity_vara(
+            token_c,
+            minted,
+            U256::zero(),
+            U256::zero(),
+            recipient,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    let LiquidityOutcome::Removed {
+        amount_token: removed_token,
+        amount_vara: removed_vara,
+        liquidity: burnt,
+    } = outcome
+    else {
+        panic!("unexpected outcome {:?}", outcome);
+    };
+    assert_eq!(burnt, minted);
+    assert!(!removed_vara.is_zero());
+    assert_eq!(
+        tokens[2].balance_of(recipient).await.unwrap(),
+        removed_token
+    );
+    assert_eq!(
+        env.system().balance_of(recipient),
+        before + removed_vara.as_u128()
+    );
+    assert!(lp.balance_of(user).await.unwrap().is_zero());
+
+    assert!(wvara.balance_of(router.actor_id()).await.unwrap().is_zero());
+    assert_eq!(env.system().balance_of(router.actor_id()), 0);
+    assert!(router.custody_of(user).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn router_refunds_wrapped_vara_as_vara() {
+    let liquidity = U256::from(1_000_000_000u64);
+    let Setup {
+        env,
+        mut router,
+        mut tokens,
+        wvara,
+        ..
+    } = setup(liquidity).await;
+    let user = ActorId::from(USER_ID);
+    let (token_c, wvara_id) = (tokens[2].actor_id(), wvara.actor_id());
+
+    // The VARA is wrapped, then the router can't take the token
+    tokens[2]
+        .approve(router.actor_id(), U256::zero())
+        .with_params(|p| p.with_actor_id(user))
+        .await
+        .unwrap();
+    let value = 10 * ONE_VARA;
+    let supply = wvara.total_supply().await.unwrap();
+    let before = env.system().balance_of(user);
+    let outcome = router
+        .add_liquidity_vara(
+            token_c,
+            liquidity / 10,
+            U256::zero(),
+            U256::zero(),
+            user,
+            deadline(&env),
+        )
+        .with_params(|p| p.with_actor_id(user).with_value(value))
+        .await
+        .unwrap();
+    assert_eq!(
+        outcome,
+        LiquidityOutcome::Refunded {
+            refunded: vec![(wvara_id, U256::from(value))]
+        }
+    );
+
+    // The wVARA was unwrapped again and the value sent back
+    assert_eq!(wvara.total_supply().await.unwrap(), supply);
+    assert!(before - env.system().balance_of(user) < value);
+    assert_eq!(tokens[2].balance_of(user).await.unwrap(), liquidity);
+    assert_eq!(env.system().balance_of(router.actor_id()), 0);
+    assert!(router.custody_of(user).await.unwrap().is_empty());
+}
@@ -0,0 +1,88 @@
+#![no_std]
+
+//! Messages the pool programs send to VFT tokens.
+//!
+//! Every message is sent with a reply deposit, so the sender's `handle_reply`
+//! sees the outcome even after the sending message stopped waiting for it.
+//! `TokenCustody` builds the transfers of a program holding users' tokens on top.
+
+mod tracker;
+pub use tracker::{Direction, PendingTransfer, TokenCustody, TransferTracker};
+
+use extended_vft_client::vft::io::{BalanceOf, Transfer, TransferFrom};
+use sails_rs::client::CallCodec;
+use sails_rs::{U256, prelude::*};
+
+/// Gas and timeout of the messages sent to tokens.
+#[derive(Debug, Clone, Copy)]
+pub struct GasConfig {
+    pub gas_for_token_ops: u64,
+    pub gas_for_reply_deposit: u64,
+    /// Blocks to wait for the reply.
+    pub reply_timeout: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenOpError {
+    SendFailure,
+    ReplyTimeout,
+    ReplyFailure,
+    UnableToDecode,
+    /// The token replied that the transfer did not go through.
+    TransferFailed,
+}
+
+pub fn transfer_payload(receiver: ActorId, amount: U256) -> Vec<u8> {
+    Transfer::encode_params_with_prefix("Vft", receiver, amount)
+}
+
+pub fn transfer_from_payload(sender: ActorId, receiver: ActorId, amount: U256) -> Vec<u8> {
+    TransferFrom::encode_params_with_prefix("Vft", sender, receiver, amount)
+}
+
+/// Sends `payload` to `token` and waits for the reply.
+///
+/// `bind_reply` is called with the id the reply will answer before waiting starts,
+/// so the caller's reply hook can tell which message the reply belongs to.
+pub async fn send_with_reply(
+    token: ActorId,
+    payload: Vec<u8>,
+    config: &GasConfig,
+    bind_reply: impl FnOnce(MessageId),
+) -> Result<Vec<u8>, TokenOpError> {
+    let fut = sails_rs::gstd::msg::send_bytes_with_gas_for_reply(
+        token,
+        payload,
+        config.gas_for_token_ops,
+        0,
+        config.gas_for_reply_deposit,
+    )
+    .map_err(|_| TokenOpError::SendFailure)?;
+
+    bind_reply(fut.waiting_reply_to);
+
+    fut.up_to(Some(config.reply_timeout))
+        .map_err(|_| TokenOpError::ReplyTimeout)?
+        .await
+        .map_err(|_| TokenOpError::ReplyFailure)
+}
+
+pub async fn balance_of(
+    token: ActorId,
+    account: ActorId,
+    config: &GasConfig,
+) -> Result<U256, TokenOpError> {
+    let payload = BalanceOf::encode_params_with_prefix("Vft", account);
+    let reply = send_with_reply(token, payload, config, |_| {}).await?;
+    BalanceOf::decode_reply_with_prefix("Vft", &reply).map_err(|_| TokenOpError::UnableToDecode)
+}
+
+/// Decode reply received from the TransferFrom method.
+pub fn decode_transfer_from_reply(bytes: &[u8]) -> bool {
+    TransferFrom::decode_reply_with_prefix("Vft", bytes).unwrap_or(false)
+}
+
+/// Decode reply received from the Transfer method.
+pub fn decode_transfer_reply(bytes: &[u8]) -> bool {
+    Transfer::decode_reply_with_prefix("Vft", bytes).unwrap_or(false)
+}
@@ -0,0 +1,167 @@
+use crate::{
+    GasConfig, TokenOpError, decode_transfer_from_reply, decode_transfer_reply, send_with_reply,
+    transfer_from_payload, transfer_payload,
+};
+use sails_rs::{
+    U256,
+    cell::Cell,
+    collections::HashMap,
+    gstd::{exec, msg},
+    prelude::*,
+};
+
+/// Whether a transfer brings tokens into the program or sends them out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// A token transfer waiting for its reply.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PendingTransfer {
+    pub user: ActorId,
+    pub token: ActorId,
+    pub amount: U256,
+    pub direction: Direction,
+    /// Block at which the sending message stops waiting for the reply.
+    pub expires_at: u32,
+}
+
+/// Transfers sent to tokens, by the id of the message their reply answers.
+///
+/// The reply hook records the outcome for the message that sent the transfer.
+/// Once that message has stopped waiting, the hook settles the transfer itself:
+/// tokens that arrive late are credited back to the user.
+/// A transfer out that fails is always credited to its receiver, so the tokens
+/// can be claimed later whatever happens to the sending message.
+#[derive(Default, Debug)]
+pub struct TransferTracker {
+    pub pending: HashMap<MessageId, PendingTransfer>,
+    /// Outcomes the sending message has not picked up yet.
+    pub results: HashMap<MessageId, bool>,
+}
+
+impl TransferTracker {
+    pub fn track(&mut self, reply_to: MessageId, transfer: PendingTransfer) {
+        self.pending.insert(reply_to, transfer);
+    }
+
+    /// Outcome of the transfer if its reply was handled while the sender was still waiting.
+    pub fn take_result(&mut self, reply_to: &MessageId) -> Option<bool> {
+        self.results.remove(reply_to)
+    }
+}
+
+/// A program holding its users' tokens, whose transfers go through a `TransferTracker`.
+///
+/// The program provides the gas settings, the tracker and the ledger of tokens owed
+/// to users; `pull`, `push` and the reply hook `on_reply` come with the trait.
+// Programs handle one message at a time, so the futures need no `Send` bound
+#[allow(async_fn_in_trait)]
+pub trait TokenCustody {
+    fn gas_config(&self) -> GasConfig;
+
+    fn with_tracker_mut<R>(&self, f: impl FnOnce(&mut TransferTracker) -> R) -> R;
+
+    /// Records `amount` of `token` as owed to `user`.
+    fn credit(&self, user: ActorId, token: ActorId, amount: U256);
+
+    /// Takes `amount` of `token` from `user` into the program.
+    async fn pull(&self, token: ActorId, user: ActorId, amount: U256) -> Result<(), TokenOpError> {
+        if amount.is_zero() {
+            return Ok(());
+        }
+        let payload = transfer_from_payload(user, exec::program_id(), amount);
+        self.send_transfer(token, user, amount, Direction::In, payload)
+            .await
+    }
+
+    /// Sends `amount` of `token` to `user`. A transfer that fails is credited to
+    /// `user` instead, to be claimed later.
+    /// Returns whether the tokens were delivered.
+    async fn push(&self, token: ActorId, user: ActorId, amount: U256) -> bool {
+        if amount.is_zero() {
+            return true;
+        }
+        let payload = transfer_payload(user, amount);
+        match self
+            .send_transfer(token, user, amount, Direction::Out, payload)
+            .await
+        {
+            Ok(()) => true,
+            // Never reached the token, so the reply hook will not see it
+            Err(TokenOpError::SendFailure) => {
+                self.credit(user, token, amount);
+                false
+            }
+            // The reply hook credits failed transfers that reached the token
+            Err(_) => false,
+        }
+    }
+
+    /// Sends the transfer `payload` to `token` and tracks it until its reply.
+    async fn send_transfer(
+        &self,
+        token: ActorId,
+        user: ActorId,
+        amount: U256,
+        direction: Direction,
+        payload: Vec<u8>,
+    ) -> Result<(), TokenOpError> {
+        let config = self.gas_config();
+        let reply_to = Cell::new(None);
+        let sent = send_with_reply(token, payload, &config, |id| {
+            reply_to.set(Some(id));
+            self.with_tracker_mut(|tr| {
+                tr.track(
+                    id,
+                    PendingTransfer {
+                        user,
+                        token,
+                        amount,
+                        direction,
+                        expires_at: exec::block_height().saturating_add(config.reply_timeout),
+                    },
+                )
+            });
+        })
+        .await;
+
+        // The reply may have been handled even if this message woke up after the timeout
+        match reply_to
+            .get()
+            .and_then(|id| self.with_tracker_mut(|tr| tr.take_result(&id)))
+        {
+            Some(true) => Ok(()),
+            Some(false) => Err(TokenOpError::TransferFailed),
+            None => Err(sent.err().unwrap_or(TokenOpError::ReplyTimeout)),
+        }
+    }
+
+    /// Settles the tracked transfer the current reply answers, if any.
+    fn on_reply(&self) {
+        let reply_to = msg::reply_to().expect("reply_to only in reply context");
+        let Some(transfer) = self.with_tracker_mut(|tr| tr.pending.remove(&reply_to)) else {
+            return;
+        };
+        let bytes = msg::load_bytes().expect("Unable to load bytes");
+
+        let success = match transfer.direction {
+            Direction::In => decode_transfer_from_reply(&bytes),
+            Direction::Out => decode_transfer_reply(&bytes),
+        };
+        let waiting = exec::block_height() < transfer.expires_at;
+
+        let credit = match transfer.direction {
+            Direction::In => success && !waiting,
+            Direction::Out => !success,
+        };
+        if credit {
+            self.credit(transfer.user, transfer.token, transfer.amount);
+        }
+        if waiting {
+            self.with_tracker_mut(|tr| tr.results.insert(reply_to, success));
+        }
+    }
+}
@@ -0,0 +1,26 @@
+#![no_std]
+
+pub mod services;
+use sails_rs::{cell::RefCell, prelude::*};
+use services::wvara::{self, NativeExchangeService, VftService};
+
+#[derive(Default)]
+pub struct WvaraProgram {
+    state: RefCell<wvara::State>,
+}
+
+#[sails_rs::program]
+impl WvaraProgram {
+    // Program's constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vft(&self) -> VftService<'_> {
+        VftService::new(&self.state)
+    }
+
+    pub fn vft_native_exchange(&self) -> NativeExchangeService<'_> {
+        NativeExchangeService::new(&self.state)
+    }
+}
@@ -0,0 +1,160 @@
+use sails_rs::{
+    cell::RefCell,
+    collections::HashMap,
+    gstd::{CommandReply, msg},
+    prelude::*,
+};
+
+/// Balances and allowances of wrapped VARA, one token per VARA unit.
+#[derive(Debug, Default)]
+pub struct State {
+    pub balances: HashMap<ActorId, U256>,
+    /// Allowances by `(owner, spender)`.
+    pub allowances: HashMap<(ActorId, ActorId), U256>,
+    pub total_supply: U256,
+}
+
+impl State {
+    fn debit(&mut self, account: ActorId, value: U256) {
+        let balance = self.balances.entry(account).or_default();
+        *balance = balance
+            .checked_sub(value)
+            .unwrap_or_else(|| panic!("Insufficient balance"));
+        if balance.is_zero() {
+            self.balances.remove(&account);
+        }
+    }
+
+    fn credit(&mut self, account: ActorId, value: U256) {
+        let balance = self.balances.entry(account).or_default();
+        *balance = balance.saturating_add(value);
+    }
+
+    fn transfer(&mut self, from: ActorId, to: ActorId, value: U256) -> bool {
+        if value.is_zero() || from == to {
+            return false;
+        }
+        self.debit(from, value);
+        self.credit(to, value);
+        true
+    }
+}
+
+/// The `Vft` service of the token, with the calls and replies of the standard VFT.
+pub struct VftService<'a> {
+    state: &'a RefCell<State>,
+}
+
+impl<'a> VftService<'a> {
+    pub fn new(state: &'a RefCell<State>) -> Self {
+        Self { state }
+    }
+}
+
+#[sails_rs::service]
+impl<'a> VftService<'a> {
+    /// Returns `false` if the allowance was already `value`.
+    #[export]
+    pub fn approve(&mut self, spender: ActorId, value: U256) -> bool {
+        let owner = msg::source();
+        let mut st = self.state.borrow_mut();
+        if value.is_zero() {
+            return st.allowances.remove(&(owner, spender)).is_some();
+        }
+        st.allowances.insert((owner, spender), value) != Some(value)
+    }
+
+    /// Returns `false` if nothing moved, panics if the balance is too low.
+    #[export]
+    pub fn transfer(&mut self, to: ActorId, value: U256) -> bool {
+        self.state.borrow_mut().transfer(msg::source(), to, value)
+    }
+
+    /// Same as `transfer`, spending the allowance `from` gave the caller.
+    #[export]
+    pub fn transfer_from(&mut self, from: ActorId, to: ActorId, value: U256) -> bool {
+        let spender = msg::source();
+        let mut st = self.state.borrow_mut();
+        if spender != from && !value.is_zero() {
+            let allowance = st.allowances.entry((from, spender)).or_default();
+            *allowance = allowance
+                .checked_sub(value)
+                .unwrap_or_else(|| panic!("Insufficient allowance"));
+            if allowance.is_zero() {
+                st.allowances.remove(&(from, spender));
+            }
+        }
+        st.transfer(from, to, value)
+    }
+
+    #[export]
+    pub fn allowance(&self, owner: ActorId, spender: ActorId) -> U256 {
+        let st = self.state.borrow();
+        st.allowances
+            .get(&(owner, spender))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    #[export]
+    pub fn balance_of(&self, account: ActorId) -> U256 {
+        let st = self.state.borrow();
+        st.balances.get(&account).copied().unwrap_or_default()
+    }
+
+    #[export]
+    pub fn total_supply(&self) -> U256 {
+        self.state.borrow().total_supply
+    }
+
+    #[export]
+    pub fn decimals(&self) -> u8 {
+        12
+    }
+
+    #[export]
+    pub fn name(&self) -> String {
+        "Wrapped VARA".into()
+    }
+
+    #[export]
+    pub fn symbol(&self) -> String {
+        "WVARA".into()
+    }
+}
+
+/// Exchange of VARA for the token and back, as `VftNativeExchange` of the wVARA program.
+pub struct NativeExchangeService<'a> {
+    state: &'a RefCell<State>,
+}
+
+impl<'a> NativeExchangeService<'a> {
+    pub fn new(state: &'a RefCell<State>) -> Self {
+        Self { state }
+    }
+}
+
+#[sails_rs::service]
+impl<'a> NativeExchangeService<'a> {
+    /// Mints the attached value to the caller.
+    #[export]
+    pub fn mint(&mut self) {
+        let value = U256::from(msg::value());
+        if value.is_zero() {
+            panic!("No value attached");
+        }
+        let mut st = self.state.borrow_mut();
+        st.credit(msg::source(), value);
+        st.total_supply = st.total_supply.saturating_add(value);
+    }
+
+    /// Burns `value` of the caller's tokens. The reply carries the same value in VARA.
+    #[export]
+    pub fn burn(&mut self, value: U256) -> CommandReply<()> {
+        let amount = u128::try_from(value).unwrap_or_else(|_| panic!("Value overflow"));
+        let mut st = self.state.borrow_mut();
+        st.debit(msg::source(), value);
+        st.total_supply = st.total_supply.saturating_sub(value);
+        CommandReply::new(()).with_value(amount)
+    }
+}
@@ -0,0 +1,72 @@
+use sails_rs::gtest::System;
+use sails_rs::{client::*, prelude::*};
+use wvara_client::{
+    vft::Vft as _, vft_native_exchange::VftNativeExchange as _, Wvara as _, WvaraCtors,
+    WvaraProgram,
+};
+
+const USER_ID: u64 = 2;
+const SPENDER_ID: u64 = 3;
+const ONE_VARA: u128 = 1_000_000_000_000;
+
+#[tokio::test]
+async fn wvara_wraps_and_unwraps_value() {
+    let system = System::new();
+    let user = ActorId::from(USER_ID);
+    let spender = ActorId::from(SPENDER_ID);
+    system.mint_to(user, 1000 * ONE_VARA);
+    system.mint_to(spender, 1000 * ONE_VARA);
+    let env = GtestEnv::new(system, user);
+    let code_id = env.system().submit_code(wvara::WASM_BINARY);
+    let program = env
+        .deploy::<WvaraProgram>(code_id, b"salt".to_vec())
+        .new()
+        .await
+        .unwrap();
+    let (mut vft, mut exchange) = (program.vft(), program.vft_native_exchange());
+    let held = env.system().balance_of(program.id());
+
+    assert!(exchange.mint().await.is_err());
+    exchange
+        .mint()
+        .with_params(|p| p.with_value(10 * ONE_VARA))
+        .await
+        .unwrap();
+    let minted = U256::from(10 * ONE_VARA);
+    assert_eq!(vft.balance_of(user).await.unwrap(), minted);
+    assert_eq!(vft.total_supply().await.unwrap(), minted);
+    assert_eq!(env.system().balance_of(program.id()), held + 10 * ONE_VARA);
+
+    // Spending an allowance
+    assert!(vft.approve(spender, minted).await.unwrap());
+    assert!(!vft.approve(spender, minted).await.unwrap());
+    let res = vft
+        .transfer_from(user, spender, minted + 1)
+        .with_params(|p| p.with_actor_id(spender))
+        .await;
+    assert!(res.is_err());
+    let value = U256::from(4 * ONE_VARA);
+    assert!(vft
+        .transfer_from(user, spender, value)
+        .with_params(|p| p.with_actor_id(spender))
+        .await
+        .unwrap());
+    assert_eq!(vft.allowance(user, spender).await.unwrap(), minted - value);
+
+    // Burning pays the value back
+    assert!(exchange
+        .burn(value + 1)
+        .with_params(|p| p.with_actor_id(spender))
+        .await
+        .is_err());
+    let before = env.system().balance_of(spender);
+    exchange
+        .burn(value)
+        .with_params(|p| p.with_actor_id(spender))
+        .await
+        .unwrap();
+    assert!(env.system().balance_of(spender) > before);
+    assert_eq!(vft.balance_of(spender).await.unwrap(), U256::zero());
+    assert_eq!(vft.total_supply().await.unwrap(), minted - value);
+    assert_eq!(env.system().balance_of(program.id()), held + 6 * ONE_VARA);
+}